@@ -3,6 +3,8 @@ mod ui;
 
 use eframe::egui;
 use state::AppState;
+use std::path::PathBuf;
+use treesize_core::snapshot::SNAPSHOT_EXTENSION;
 
 struct MyApp {
     state: AppState,
@@ -10,9 +12,23 @@ struct MyApp {
 
 impl MyApp {
     fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        Self {
-            state: AppState::new(),
+        let mut state = AppState::new();
+        state.refresh_volumes();
+        let args: Vec<PathBuf> = std::env::args_os().skip(1).map(root_arg).collect();
+        match args.as_slice() {
+            // Opening a shared view or a saved scan, e.g. from a file
+            // manager or `treesize-cli --open-gui`, shows it
+            [path]
+                if path.extension().is_some_and(|ext| {
+                    ext == state::SHARED_VIEW_EXTENSION || ext == SNAPSHOT_EXTENSION
+                }) =>
+            {
+                state.open_saved_scan(path)
+            }
+            // Folders given on the command line are scanned right away
+            _ => state.start_scan_many(args),
         }
+        Self { state }
     }
 }
 
@@ -22,6 +38,18 @@ impl eframe::App for MyApp {
     }
 }
 
+/// `arg` as a path. Explorer quotes the background of a drive's root as
+/// `"C:\"`, whose backslash escapes the closing quote and leaves `C:"`;
+/// quotes can't appear in Windows paths, so a trailing one stands for
+/// that backslash.
+fn root_arg(arg: std::ffi::OsString) -> PathBuf {
+    #[cfg(windows)]
+    if let Some(root) = arg.to_str().and_then(|a| a.strip_suffix('"')) {
+        return PathBuf::from(format!("{root}\\"));
+    }
+    PathBuf::from(arg)
+}
+
 fn main() -> eframe::Result<()> {
     let options = eframe::NativeOptions::default();
     eframe::run_native(
@@ -1,9 +1,34 @@
+use chrono::{DateTime, Local};
 use crossbeam_channel::{unbounded, Receiver, Sender};
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use treesize_core::model::{NodeId, NodeKind, Tree};
-use treesize_core::scanner::{ScanMsg, Scanner};
+use treesize_core::advisor::{self, Advice, AdvisorOptions};
+use treesize_core::age::{self, AgeHistogram};
+use treesize_core::annotations::Annotations;
+use treesize_core::cloud;
+use treesize_core::diff::{self, ChangeKind, NodeChange, TreeDiff};
+use treesize_core::extstats::{self, ExtStat};
+use treesize_core::handle::ScanHandle;
+use treesize_core::history::{History, Trend};
+use treesize_core::human::{self, human_bytes, DateFormat};
+use treesize_core::inodes::{self, CountThresholds, HeavyDir, InodeQuota, InodeUsage};
+use treesize_core::integration;
+use treesize_core::interference::{self, InterferenceHint};
+use treesize_core::journal::{Journal, JournalEntry, Operation};
+use treesize_core::model::{IdRemap, NodeId, NodeKind, Tree, TreeNode, TreeView};
+use treesize_core::ops::{self, TransferOptions, TransferProgress};
+use treesize_core::overview::{self, Overview};
+use treesize_core::owner::{usage_by_owner, OwnerUsage};
+use treesize_core::profile::SlowDir;
+use treesize_core::progress::ProgressEvent;
+use treesize_core::scanner::{self, ScanMsg, ScanOptions, Scanner};
+use treesize_core::search::NameMatching;
+use treesize_core::share::{SharedView, ViewBundle};
+use treesize_core::streams::{self, AltStream};
+use treesize_core::volumes::{self, Volume};
+use treesize_core::watch::{self, TreeWatcher};
+use treesize_core::watchdog::{GrowthAlert, GrowthRate, Watchdog};
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum SortKey {
@@ -18,277 +43,2449 @@ pub enum ViewTab {
     Files,
 }
 
+/// How the central panel charts the current folder.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ChartKind {
+    Pie,
+    /// Rings for several levels below the folder at once.
+    Sunburst,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ExportFormat {
     Csv,
     Json,
     Pdf,
+    /// Nested JSON for d3; see
+    /// [`treesize_core::export::export_hierarchy_json`].
+    D3,
+    /// Folded stacks for flame graphs; see
+    /// [`treesize_core::export::export_folded`].
+    Folded,
 }
 
-pub struct SearchFilter {
-    pub direct_matches: Vec<bool>,
-    pub subtree_matches: Vec<bool>,
+/// Fresh scan of one directory, to be spliced into the loaded tree.
+pub struct Rescan {
+    pub path: PathBuf,
+    pub tree: Tree,
 }
 
-impl SearchFilter {
-    pub fn matches_node(&self, id: NodeId) -> bool {
-        self.direct_matches[id.0 as usize]
+/// A folder being scanned again to update it in place, such as one the
+/// depth limit left unlisted.
+pub struct Listing {
+    pub path: PathBuf,
+    progress: Receiver<ProgressEvent>,
+    /// Files and bytes counted so far.
+    pub files: u64,
+    pub bytes: u128,
+}
+
+/// What changed between the tree shown before a background refresh and the
+/// one swapped in.
+pub struct RefreshSummary {
+    pub added: u64,
+    pub removed: u64,
+    pub changed: u64,
+    pub bytes_before: u128,
+    pub bytes_after: u128,
+}
+
+impl RefreshSummary {
+    fn between(old: &Tree, new: &Tree, remap: &IdRemap) -> Self {
+        let mut summary = Self {
+            added: 0,
+            removed: 0,
+            changed: 0,
+            bytes_before: old.node(old.root).size,
+            bytes_after: new.node(new.root).size,
+        };
+        let mut kept = 0;
+        for node in old.nodes.iter().filter(|n| n.kind.is_file()) {
+            match remap.get(node.id).map(|id| new.node(id)) {
+                Some(now) if now.kind.is_file() => {
+                    kept += 1;
+                    if now.size != node.size || now.modified != node.modified {
+                        summary.changed += 1;
+                    }
+                }
+                _ => summary.removed += 1,
+            }
+        }
+        let new_files = new.nodes.iter().filter(|n| n.kind.is_file()).count() as u64;
+        summary.added = new_files - kept;
+        summary
     }
 
-    pub fn matches_subtree(&self, id: NodeId) -> bool {
-        self.subtree_matches[id.0 as usize]
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.removed == 0 && self.changed == 0
     }
+}
 
-    pub fn build(needle: &str, tree: &Tree) -> Self {
-        let needle_lc = needle.to_ascii_lowercase();
-        let n = tree.nodes.len();
-        let mut direct = vec![false; n];
+/// Most changes a [`Review`] steps through.
+pub const REVIEW_CHANGES: usize = 25;
 
-        for (i, node) in tree.nodes.iter().enumerate() {
-            let mut matched = node.name.to_ascii_lowercase().contains(&needle_lc);
-            if !matched {
-                let path_lc = node.path.to_string_lossy().to_ascii_lowercase();
-                matched = path_lc.contains(&needle_lc);
-            }
-            direct[i] = matched;
-        }
+/// What to do with the change under review.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewAction {
+    /// Add it to the cart, to clean up with everything else there.
+    Tag,
+    /// Delete it, once confirmed.
+    Delete,
+    Dismiss,
+}
+
+/// The largest new files and folders and the folders that grew most in a
+/// background refresh, stepped through one at a time so each can be
+/// tagged, deleted or dismissed.
+pub struct Review {
+    /// Largest first.
+    pub changes: Vec<NodeChange>,
+    /// Index of the change under review; past the end once all are.
+    pub position: usize,
+    pub tagged: usize,
+    pub deleted: usize,
+    pub dismissed: usize,
+    /// Tagged or deleted paths; changes inside them are skipped.
+    acted: Vec<PathBuf>,
+    /// Set while the deletion of the change under review awaits
+    /// confirmation.
+    deleting: bool,
+}
 
-        let mut subtree = vec![false; n];
-        fn dfs(tree: &Tree, direct: &[bool], subtree: &mut [bool], id: NodeId) -> bool {
-            let idx = id.0 as usize;
-            let node = &tree.nodes[idx];
-            let mut any = direct[idx];
-            for &child in &node.children {
-                if dfs(tree, direct, subtree, child) {
-                    any = true;
+impl Review {
+    fn new(diff: &TreeDiff) -> Self {
+        let grows = |c: &NodeChange| matches!(c.change, ChangeKind::Added | ChangeKind::Grown);
+        let folder_grew = |c: &NodeChange| c.is_dir && c.change == ChangeKind::Grown;
+        // Growth of each grown folder accounted for by the largest files and
+        // new folders below it; a folder they explain is left to them, since
+        // they tell more about it, while growth spread over many small files
+        // is best shown as the folder
+        let mut explained: HashMap<&Path, i128> = diff
+            .changes
+            .iter()
+            .filter(|c| folder_grew(c))
+            .map(|c| (c.path.as_path(), 0))
+            .collect();
+        let leaves = diff.changes.iter().filter(|c| grows(c) && !folder_grew(c));
+        for leaf in leaves.take(REVIEW_CHANGES) {
+            for dir in leaf.path.ancestors().skip(1) {
+                if let Some(sum) = explained.get_mut(dir) {
+                    *sum += leaf.delta();
                 }
             }
-            subtree[idx] = any;
-            any
         }
-        if !tree.nodes.is_empty() {
-            dfs(tree, &direct, &mut subtree, tree.root);
+        let changes = diff
+            .changes
+            .iter()
+            .filter(|c| grows(c))
+            .filter(|c| {
+                !folder_grew(c)
+                    || explained
+                        .get(c.path.as_path())
+                        .is_none_or(|&sum| sum * 10 < c.delta() * 9)
+            })
+            .take(REVIEW_CHANGES)
+            .cloned()
+            .collect();
+        Self {
+            changes,
+            position: 0,
+            tagged: 0,
+            deleted: 0,
+            dismissed: 0,
+            acted: Vec::new(),
+            deleting: false,
         }
+    }
+
+    fn restart(&mut self) {
+        self.position = 0;
+        self.tagged = 0;
+        self.deleted = 0;
+        self.dismissed = 0;
+        self.acted.clear();
+        self.deleting = false;
+    }
+
+    /// Whether a deletion asked for from the review awaits confirmation.
+    pub fn is_deleting(&self) -> bool {
+        self.deleting
+    }
+}
+
+/// The loaded tree compared with an earlier scan of it.
+pub struct Comparison {
+    pub baseline: PathBuf,
+    pub diff: TreeDiff,
+}
+
+/// Entries in the "Largest files" panel.
+pub const LARGEST_FILES: usize = 50;
+/// How often a running scan shows its tree so far.
+pub const PARTIAL_TREE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+/// Directories listed in the scan timing window.
+pub const SLOWEST_DIRS: usize = 50;
+
+/// Snapshots kept for [`AppState::open_root`] besides the loaded tree.
+const MAX_SNAPSHOTS: usize = 3;
+/// Finished scans remembered for [`AppState::growth`].
+const MAX_HISTORY: usize = 50;
+/// Extension of the files written by [`AppState::share_view`].
+pub const SHARED_VIEW_EXTENSION: &str = "tszview";
+/// Recorded scans a folder row's sparkline covers.
+pub const SPARKLINE_SCANS: usize = 12;
+/// Folders listed on the Computer overview.
+pub const OVERVIEW_FOLDERS: usize = 10;
 
-        SearchFilter {
-            direct_matches: direct,
-            subtree_matches: subtree,
+/// A finished scan set aside when another root was opened, so opening its
+/// root again with the same options can reuse it instead of rescanning.
+pub struct Snapshot {
+    pub root: PathBuf,
+    pub fingerprint: u64,
+    pub tree: Tree,
+}
+
+/// Total size of a root when one of its scans finished.
+pub struct ScanRecord {
+    pub root: PathBuf,
+    pub at: DateTime<Local>,
+    pub bytes: u128,
+}
+
+/// A "Move to Archive" awaiting confirmation, with the space it is expected
+/// to take at the destination.
+pub struct ArchivePlan {
+    pub id: NodeId,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub size: u128,
+    pub allocation_unit: u64,
+    pub estimated_size: u128,
+}
+
+/// A folder set beside the current one for comparison. It keeps its own
+/// copy of the branch, so it stays put while the loaded tree is rescanned,
+/// changed or replaced by another root.
+pub struct PinnedFolder {
+    /// Where the copy came from: the scan it was pinned from, or a saved
+    /// scan's file.
+    pub source: String,
+    pub tree: Tree,
+    /// Folder of `tree` shown; navigable like the main view.
+    pub current: NodeId,
+}
+
+/// Result of a background "Move to Archive" operation.
+pub struct ArchiveOutcome {
+    pub id: NodeId,
+    pub source: PathBuf,
+    pub result: Result<PathBuf, String>,
+}
+
+pub enum ArchiveMsg {
+    Progress(TransferProgress),
+    Done(ArchiveOutcome),
+}
+
+pub struct SearchFilter {
+    view: TreeView,
+}
+
+impl SearchFilter {
+    pub fn matches_node(&self, id: NodeId) -> bool {
+        self.view.matches(id)
+    }
+
+    pub fn matches_subtree(&self, id: NodeId) -> bool {
+        self.view.contains(id)
+    }
+
+    /// Whether `id` is listed under the filter: folders when something
+    /// below them matches, anything else when it matches itself.
+    pub fn shows(&self, tree: &Tree, id: NodeId) -> bool {
+        match tree.node(id).kind {
+            NodeKind::Dir | NodeKind::ArchiveEntry => self.matches_subtree(id),
+            NodeKind::File
+            | NodeKind::Archive
+            | NodeKind::MountPoint
+            | NodeKind::Symlink
+            | NodeKind::Other => self.matches_node(id),
         }
     }
+
+    /// Matches names and paths containing `needle`, if it isn't empty,
+    /// among what is tagged `tag`, if given.
+    pub fn build(
+        needle: &str,
+        tag: Option<&str>,
+        tree: &Tree,
+        matching: NameMatching,
+        annotations: &Annotations,
+    ) -> Self {
+        let needle = matching.key(needle);
+        let paths = tree.paths();
+        let tagged: Option<HashSet<NodeId>> =
+            tag.map(|tag| annotations.tagged(tree, tag).into_iter().collect());
+        let view = tree.filtered(|node| {
+            let named = needle.is_empty()
+                || matching.contains(&node.name, &needle)
+                || matching.contains(&paths[node.id.0 as usize].to_string_lossy(), &needle);
+            named
+                && tagged
+                    .as_ref()
+                    .is_none_or(|tagged| tagged.contains(&node.id))
+        });
+        SearchFilter { view }
+    }
 }
 
 pub struct AppState {
     pub root: Option<PathBuf>,
-    pub cancel: Arc<AtomicBool>,
-    pub paused: Arc<AtomicBool>,
+    /// Further roots scanned alongside `root` into one combined tree.
+    pub extra_roots: Vec<PathBuf>,
+    /// The most recent full scan; kept after it finishes.
+    scan: Option<ScanHandle>,
     pub scan_rx: Option<Receiver<ScanMsg>>,
+    /// Background rescan of the loaded root; the current tree stays usable
+    /// until its result is swapped in.
+    refresh: Option<ScanHandle>,
+    pub refresh_rx: Option<Receiver<ScanMsg>>,
+    pub refresh_summary: Option<RefreshSummary>,
+    /// The largest growth found by the last background refresh; see
+    /// [`AppState::start_review`].
+    pub review: Option<Review>,
+    pub show_review: bool,
+    rescan_tx: Sender<Rescan>,
+    pub rescan_rx: Receiver<Rescan>,
+    pub rescans_pending: usize,
+    /// Folders being scanned again; see [`AppState::refresh_folder`] and
+    /// [`AppState::list_cut_off`].
+    pub listings: Vec<Listing>,
+    /// Keep the tree in sync with the filesystem once a scan finishes.
+    pub watch_changes: bool,
+    watcher: Option<TreeWatcher>,
+    watch_rx: Option<Receiver<ScanMsg>>,
+    /// Raise an alarm when a watched folder grows faster than this.
+    pub growth_alarm: Option<GrowthRate>,
+    pub growth_alarm_input: String,
+    pub growth_alarm_error: Option<String>,
+    watchdog: Option<Watchdog>,
+    /// Alarms raised while watching, oldest first.
+    pub growth_alerts: Vec<(DateTime<Local>, GrowthAlert)>,
+    pub show_alerts: bool,
+    /// An alarm went off since the window last asked for attention.
+    pub unseen_alerts: bool,
     pub progress_bytes: u128,
     pub progress_files: u64,
     pub progress_discovered: u64,
+    /// Bytes per second and estimated time left of the running scan.
+    pub progress_rate: f64,
+    pub progress_eta: Option<std::time::Duration>,
+    /// Directory the running scan is in.
+    pub progress_path: Option<PathBuf>,
     pub sort: SortKey,
+    /// Leave folders smaller than `min_folder_percent` of the root out of
+    /// the folder tree, behind a row that reveals them.
+    pub hide_small_folders: bool,
+    pub min_folder_percent: f32,
+    /// Levels below a folder that "Expand all" opens, so a huge branch
+    /// doesn't unfold completely.
+    pub expand_depth: usize,
+    /// Show percentages as shares of `baseline`, or of the root until a
+    /// folder is chosen, instead of the folder they are in.
+    pub percent_of_baseline: bool,
+    pub baseline: Option<NodeId>,
     pub search: String,
+    /// Narrows the search to what is tagged this.
+    pub search_tag: Option<String>,
+    /// Tags and notes on files and folders, kept across scans; see
+    /// [`Annotations`].
+    pub annotations: Annotations,
+    /// Tag being typed in the properties panel.
+    pub tag_input: String,
+    /// Note being edited in the properties panel.
+    pub note_input: String,
+    /// How names are compared when searching, and when the advisor looks
+    /// for duplicates.
+    pub search_matching: NameMatching,
+    pub dedupe_matching: NameMatching,
+    /// How modification times are shown and exported.
+    pub date_format: DateFormat,
+    /// Show and export paths from the scanned folder down instead of in
+    /// full; see [`Tree::relative_path`].
+    pub relative_paths: bool,
+    /// Locale of exported reports; see [`ExportOptions::locale`].
+    ///
+    /// [`ExportOptions::locale`]: treesize_core::ExportOptions::locale
+    pub export_locale: Option<chrono::Locale>,
     pub tree: Option<Tree>,
     pub current_dir: Option<NodeId>,
     pub selected: Option<NodeId>,
     pub pending_delete: Option<NodeId>,
     pub pending_properties: Option<NodeId>,
+    /// Alternate data streams of the node in the Properties dialog, read
+    /// when it was opened.
+    pub properties_streams: Vec<AltStream>,
+    /// Space per owner below the node in the Properties dialog, with names
+    /// looked up when it was opened.
+    pub properties_owners: Vec<OwnerUsage>,
+    /// Links and bind mounts leading to the node in the Properties dialog,
+    /// found when it was opened; see [`Tree::aliases_of`].
+    pub properties_aliases: Vec<PathBuf>,
     pub search_filter: Option<SearchFilter>,
     pub view_tab: ViewTab,
     pub file_nodes: Vec<NodeId>,
     pub filtered_file_nodes: Vec<NodeId>,
+    /// Total size of `filtered_file_nodes`, for the file list's footer.
+    pub filtered_file_bytes: u128,
     pub export_format: ExportFormat,
     pub export_status: Option<String>,
+    pub scan_options: ScanOptions,
+    pub show_settings: bool,
+    /// Whether the file manager offers to open folders here; looked up
+    /// when Settings first shows it, since on Windows that runs `reg`.
+    pub context_menu: Option<bool>,
+    pub exclude_input: String,
+    /// Comma-separated extensions being edited into
+    /// [`ScanOptions::include_extensions`].
+    pub include_ext_input: String,
+    /// Comma-separated extensions being edited into
+    /// [`ScanOptions::exclude_extensions`].
+    pub exclude_ext_input: String,
+    pub archive_dir: String,
+    pub pending_archive: Option<ArchivePlan>,
+    pub archive_rx: Option<Receiver<ArchiveMsg>>,
+    pub archive_progress: Option<TransferProgress>,
+    pub transfer_options: TransferOptions,
+    pub operation_status: Option<String>,
+    pub journal: Journal,
+    pub show_activity: bool,
+    pub activity: Vec<JournalEntry>,
+    /// Nodes queued for removal together.
+    pub cart: Vec<NodeId>,
+    pub show_cart: bool,
+    pub show_advisor: bool,
+    pub advisor_target: String,
+    pub advice: Option<Advice>,
+    /// Extension breakdown of `current_dir`, recomputed when it changes.
+    pub ext_stats: Option<(NodeId, Vec<ExtStat>)>,
+    /// Largest files in the tree, refreshed whenever the tree changes.
+    pub largest_files: Vec<NodeId>,
+    pub chart: ChartKind,
+    /// Colour the chart by how long ago files were modified.
+    pub age_overlay: bool,
+    /// Age histogram of every node, indexed by id; refreshed with the tree.
+    pub age_histograms: Vec<AgeHistogram>,
+    /// Bytes in online-only cloud placeholders per node, by node id.
+    pub cloud_bytes: Vec<u128>,
+    pub comparison: Option<Comparison>,
+    pub show_compare: bool,
+    pub pinned: Option<PinnedFolder>,
+    /// Directories that took longest to scan, from the tree's profile.
+    pub slowest_dirs: Vec<SlowDir>,
+    pub show_timing: bool,
+    /// Set when the last scan looked slowed down by an antivirus scanner.
+    pub interference: Option<InterferenceHint>,
+    /// Set when `tree` is what a cancelled scan had found so far.
+    pub scan_cancelled: bool,
+    exclusion_rx: Option<Receiver<std::io::Result<()>>>,
+    /// Drives offered on the start screen; see [`AppState::refresh_volumes`].
+    pub volumes: Vec<Volume>,
+    /// [`ScanOptions::fingerprint`] of the options `tree` was scanned with.
+    tree_fingerprint: u64,
+    snapshots: Vec<Snapshot>,
+    scan_history: Vec<ScanRecord>,
+    /// The Computer page, built from the snapshots and the loaded tree
+    /// when first shown after they change.
+    overview: Option<Overview>,
+    /// Reuse a snapshot of the same root and options at most this old;
+    /// 0 always rescans.
+    pub reuse_window_mins: u32,
+    /// Scan time of the snapshot currently shown, when it was reused rather
+    /// than freshly scanned.
+    pub reused_at: Option<DateTime<Local>>,
+    /// Folder sizes of past scans; `None` if the database couldn't be
+    /// opened, e.g. because another instance has it open.
+    history: Option<History>,
+    /// Add the sizes of every finished scan to `history`.
+    pub record_history: bool,
+    pub show_history: bool,
+    /// The folder the History window shows, with its trend and those of the
+    /// recorded folders below it; see [`AppState::refresh_history`].
+    pub history_trends: Option<(PathBuf, Vec<Trend>)>,
+    /// Sizes in the last [`SPARKLINE_SCANS`] recorded scans of each folder
+    /// below the loaded roots that has at least two; see
+    /// [`AppState::refresh_size_history`].
+    size_history: HashMap<PathBuf, Vec<u128>>,
+    pub show_file_counts: bool,
+    pub count_thresholds: CountThresholds,
+    /// Folders over `count_thresholds`, most files first, with the
+    /// thresholds they were found with; see [`AppState::refresh_file_counts`].
+    pub heavy_dirs: Option<(CountThresholds, Vec<HeavyDir>)>,
+    /// Inode use of the filesystem the scan is on, if it has a fixed number.
+    pub inode_usage: Option<InodeUsage>,
+    /// The user's inode quota there, if quotas are enabled.
+    pub inode_quota: Option<InodeQuota>,
 }
 
 impl AppState {
     pub fn new() -> Self {
+        let (rescan_tx, rescan_rx) = unbounded();
         Self {
             root: None,
-            cancel: Arc::new(AtomicBool::new(false)),
-            paused: Arc::new(AtomicBool::new(false)),
+            extra_roots: Vec::new(),
+            scan: None,
             scan_rx: None,
+            refresh_rx: None,
+            refresh: None,
+            refresh_summary: None,
+            review: None,
+            show_review: false,
+            rescan_tx,
+            rescan_rx,
+            rescans_pending: 0,
+            listings: Vec::new(),
+            watch_changes: true,
+            watcher: None,
+            watch_rx: None,
+            growth_alarm: None,
+            growth_alarm_input: String::new(),
+            growth_alarm_error: None,
+            watchdog: None,
+            growth_alerts: Vec::new(),
+            show_alerts: false,
+            unseen_alerts: false,
             progress_bytes: 0,
             progress_files: 0,
+            progress_rate: 0.0,
+            progress_eta: None,
+            progress_path: None,
             progress_discovered: 0,
             sort: SortKey::Size,
+            hide_small_folders: false,
+            min_folder_percent: 1.0,
+            expand_depth: 5,
+            percent_of_baseline: false,
+            baseline: None,
             search: String::new(),
+            search_tag: None,
+            annotations: Annotations::load(&Annotations::default_path()).unwrap_or_default(),
+            tag_input: String::new(),
+            note_input: String::new(),
+            search_matching: NameMatching {
+                normalize: true,
+                ignore_case: true,
+            },
+            dedupe_matching: NameMatching::native(),
+            date_format: DateFormat::default(),
+            relative_paths: false,
+            export_locale: None,
             tree: None,
             current_dir: None,
             selected: None,
             pending_delete: None,
             pending_properties: None,
+            properties_streams: Vec::new(),
+            properties_owners: Vec::new(),
+            properties_aliases: Vec::new(),
             search_filter: None,
             view_tab: ViewTab::Tree,
             file_nodes: Vec::new(),
             filtered_file_nodes: Vec::new(),
+            filtered_file_bytes: 0,
             export_format: ExportFormat::Csv,
             export_status: None,
+            scan_options: ScanOptions::default(),
+            show_settings: false,
+            context_menu: None,
+            exclude_input: String::new(),
+            include_ext_input: String::new(),
+            exclude_ext_input: String::new(),
+            archive_dir: String::new(),
+            pending_archive: None,
+            archive_rx: None,
+            archive_progress: None,
+            transfer_options: TransferOptions { verify: true },
+            operation_status: None,
+            journal: Journal::default_location(),
+            show_activity: false,
+            activity: Vec::new(),
+            cart: Vec::new(),
+            show_cart: false,
+            show_advisor: false,
+            advisor_target: String::new(),
+            advice: None,
+            ext_stats: None,
+            largest_files: Vec::new(),
+            chart: ChartKind::Pie,
+            age_overlay: false,
+            age_histograms: Vec::new(),
+            cloud_bytes: Vec::new(),
+            comparison: None,
+            pinned: None,
+            show_compare: false,
+            slowest_dirs: Vec::new(),
+            show_timing: false,
+            interference: None,
+            scan_cancelled: false,
+            exclusion_rx: None,
+            volumes: Vec::new(),
+            tree_fingerprint: 0,
+            snapshots: Vec::new(),
+            scan_history: Vec::new(),
+            overview: None,
+            reuse_window_mins: 15,
+            reused_at: None,
+            history: History::open(&History::default_path()).ok(),
+            record_history: true,
+            show_history: false,
+            history_trends: None,
+            size_history: HashMap::new(),
+            show_file_counts: false,
+            count_thresholds: CountThresholds::default(),
+            heavy_dirs: None,
+            inode_usage: None,
+            inode_quota: None,
         }
     }
 
-    pub fn start_scan(&mut self, root: PathBuf) {
-        self.root = Some(root.clone());
+    pub fn refresh_volumes(&mut self) {
+        match volumes::list_volumes() {
+            Ok(volumes) => self.volumes = volumes,
+            Err(err) => self.operation_status = Some(format!("Could not list drives: {err}")),
+        }
+        self.overview = None;
+    }
+
+    /// Whether there are enough scans for the Computer page to be the
+    /// start screen.
+    pub fn has_overview(&self) -> bool {
+        self.snapshots.len() + usize::from(self.tree.is_some()) >= 2
+    }
+
+    /// Every finished scan at hand side by side, rebuilt when they change.
+    pub fn overview(&mut self) -> &Overview {
+        let snapshots = self.snapshots.iter().map(|s| &s.tree);
+        let trees = snapshots.chain(self.tree.as_ref().filter(|_| self.scan_rx.is_none()));
+        let volumes = &self.volumes;
+        let options = AdvisorOptions {
+            name_matching: self.dedupe_matching,
+            ..AdvisorOptions::default()
+        };
+        self.overview
+            .get_or_insert_with(|| overview::overview(trees, volumes, &options, OVERVIEW_FOLDERS))
+    }
+
+    /// Change in size of `root` between its last two scans, and the time
+    /// between them.
+    pub fn growth(&self, root: &Path) -> Option<(i128, chrono::Duration)> {
+        let mut scans = self.scan_history.iter().rev().filter(|r| r.root == root);
+        let (last, previous) = (scans.next()?, scans.next()?);
+        Some((
+            last.bytes as i128 - previous.bytes as i128,
+            last.at - previous.at,
+        ))
+    }
+
+    /// Remembers the size of the loaded tree once its scan has finished.
+    pub fn record_scan(&mut self) {
+        self.overview = None;
+        self.history_trends = None;
+        if let (true, Some(history), Some(tree)) = (self.record_history, &self.history, &self.tree)
+        {
+            if let Err(err) = history.record(tree) {
+                self.operation_status = Some(format!("Could not record scan history: {err}"));
+            }
+        }
+        self.refresh_size_history();
+        let Some(tree) = self.tree.as_ref().filter(|t| !t.is_combined()) else {
+            return;
+        };
+        let root = tree.node(tree.root);
+        self.scan_history.push(ScanRecord {
+            root: tree.path(tree.root),
+            at: tree.meta.scanned_at.unwrap_or_else(Local::now),
+            bytes: root.size,
+        });
+        if self.scan_history.len() > MAX_HISTORY {
+            self.scan_history.remove(0);
+        }
+    }
+
+    /// Sets the loaded tree aside and returns to the start screen, which is
+    /// the Computer page once several scans exist.
+    pub fn go_home(&mut self) {
+        self.stash_tree();
+        self.reset_to_initial();
+        self.root = None;
+        self.extra_roots.clear();
+    }
+
+    /// Shows `root`, reusing a recent snapshot scanned with the current
+    /// options if there is one, and scanning it otherwise.
+    pub fn open_root(&mut self, root: PathBuf) {
+        self.stash_tree();
+        let fingerprint = self.scan_options.fingerprint();
+        let window = chrono::Duration::minutes(self.reuse_window_mins as i64);
+        let now = Local::now();
+        let reusable = self.snapshots.iter().position(|snap| {
+            snap.root == root
+                && snap.fingerprint == fingerprint
+                && snap
+                    .tree
+                    .meta
+                    .scanned_at
+                    .is_some_and(|at| now - at <= window)
+        });
+        match reusable {
+            Some(index) if self.reuse_window_mins > 0 => {
+                let snap = self.snapshots.remove(index);
+                self.overview = None;
+                self.clear_loaded();
+                self.root = Some(snap.root);
+                self.extra_roots.clear();
+                self.tree_fingerprint = snap.fingerprint;
+                self.reused_at = snap.tree.meta.scanned_at;
+                self.progress_files = snap.tree.node(snap.tree.root).file_count;
+                self.progress_discovered = self.progress_files;
+                self.progress_bytes = snap.tree.node(snap.tree.root).size;
+                self.tree = Some(snap.tree);
+                self.rebuild_file_cache();
+                self.start_watching();
+            }
+            _ => self.start_scan(root),
+        }
+    }
+
+    /// Sets the loaded tree aside as a snapshot, if a scan of it finished.
+    fn stash_tree(&mut self) {
+        if self.scan_rx.is_some() || self.scan_cancelled || !self.extra_roots.is_empty() {
+            return;
+        }
+        let (Some(root), Some(tree)) = (self.root.clone(), self.tree.take()) else {
+            return;
+        };
+        let fingerprint = self.tree_fingerprint;
+        self.snapshots
+            .retain(|s| !(s.root == root && s.fingerprint == fingerprint));
+        self.snapshots.push(Snapshot {
+            root,
+            fingerprint,
+            tree,
+        });
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.remove(0);
+        }
+        self.overview = None;
+    }
+
+    /// Resets everything derived from the loaded tree.
+    fn clear_loaded(&mut self) {
         self.progress_bytes = 0;
         self.progress_files = 0;
         self.progress_discovered = 0;
+        self.progress_rate = 0.0;
+        self.progress_eta = None;
+        self.progress_path = None;
         self.tree = None;
         self.current_dir = None;
         self.selected = None;
         self.pending_delete = None;
         self.pending_properties = None;
+        self.baseline = None;
         self.search_filter = None;
         self.file_nodes.clear();
         self.filtered_file_nodes.clear();
+        self.filtered_file_bytes = 0;
         self.view_tab = ViewTab::Tree;
         self.export_status = None;
-        self.cancel.store(false, Ordering::Relaxed);
-        self.paused.store(false, Ordering::Relaxed);
+        self.cart.clear();
+        self.advice = None;
+        self.reused_at = None;
+        self.refresh_summary = None;
+        self.review = None;
+        self.comparison = None;
+        self.interference = None;
+        self.scan_cancelled = false;
+        self.cancel_refresh();
+        self.stop_watching();
+    }
 
-        let (tx, rx): (Sender<ScanMsg>, Receiver<ScanMsg>) = unbounded();
-        self.scan_rx = Some(rx);
-        let cancel = self.cancel.clone();
-        let paused = self.paused.clone();
+    /// Scans `root` from scratch, discarding any snapshot of it taken with
+    /// the current options.
+    pub fn start_scan(&mut self, root: PathBuf) {
+        self.stash_tree();
+        let fingerprint = self.scan_options.fingerprint();
+        self.snapshots
+            .retain(|s| !(s.root == root && s.fingerprint == fingerprint));
+        self.root = Some(root);
+        self.extra_roots.clear();
+        self.rescan();
+    }
 
-        std::thread::spawn(move || {
-            let scanner = Scanner::new(cancel, paused);
-            scanner.scan(root, tx);
-        });
+    /// Scans `roots` into one combined tree, as if the first were opened
+    /// and the rest added with [`AppState::add_root`].
+    pub fn start_scan_many(&mut self, mut roots: Vec<PathBuf>) {
+        if roots.is_empty() {
+            return;
+        }
+        let first = roots.remove(0);
+        self.start_scan(first);
+        if !roots.is_empty() {
+            self.extra_roots = roots;
+            self.rescan();
+        }
     }
 
-    pub fn cancel_scan(&self) {
-        self.cancel.store(true, Ordering::Relaxed);
+    /// Adds `root` to the roots being shown and rescans them all into one
+    /// combined tree.
+    pub fn add_root(&mut self, root: PathBuf) {
+        if self.root.is_none() {
+            return self.start_scan(root);
+        }
+        if self.scan_roots().any(|r| *r == root) {
+            return;
+        }
+        self.extra_roots.push(root);
+        self.rescan();
     }
 
-    pub fn pause_or_resume(&self) {
-        let was = self.paused.load(Ordering::Relaxed);
-        self.paused.store(!was, Ordering::Relaxed);
+    /// Every root the loaded tree covers, `root` first.
+    pub fn scan_roots(&self) -> impl Iterator<Item = &PathBuf> {
+        self.root.iter().chain(&self.extra_roots)
     }
 
-    pub fn reset_to_initial(&mut self) {
+    /// Scans all current roots from scratch in the foreground.
+    pub fn rescan(&mut self) {
+        self.clear_loaded();
+        self.tree_fingerprint = self.scan_options.fingerprint();
+        self.cancel_scan();
         self.scan_rx = None;
-        self.tree = None;
-        self.current_dir = None;
-        self.selected = None;
-        self.progress_bytes = 0;
-        self.progress_discovered = 0;
-        self.progress_files = 0;
-        self.search_filter = None;
-        self.file_nodes.clear();
-        self.filtered_file_nodes.clear();
-        self.view_tab = ViewTab::Tree;
-        self.export_status = None;
+        let roots: Vec<PathBuf> = self.scan_roots().cloned().collect();
+        if roots.is_empty() {
+            return;
+        }
+        let (tx, rx) = scanner::scan_channel();
+        self.scan_rx = Some(rx);
+        let scanner = Scanner::new()
+            .with_options(self.scan_options.clone())
+            .with_partial_trees(PARTIAL_TREE_INTERVAL);
+        self.scan = Some(scanner.spawn_many(roots, tx));
     }
 
-    pub fn navigate_up(&mut self) {
-        if let (Some(tree), Some(cur)) = (&self.tree, self.current_dir) {
-            if let Some(parent) = tree.nodes[cur.0 as usize].parent {
-                self.current_dir = Some(parent);
-                self.selected = None;
-            }
+    /// Whether `tree` is a partial tree of a scan still running. It must not
+    /// be changed, since the next update replaces it.
+    pub fn is_provisional(&self) -> bool {
+        self.scan_rx.is_some() && self.tree.is_some()
+    }
+
+    /// Shows the tree of a running scan so far. Derived views that are cheap
+    /// to keep current are refreshed; the rest wait for the finished tree.
+    pub fn show_partial_tree(&mut self, tree: Tree) {
+        self.tree = Some(tree);
+        self.ext_stats = None;
+        if self.search_filter.is_some() {
+            self.apply_search();
         }
     }
 
-    pub fn set_current_root(&mut self) {
-        if let Some(tree) = &self.tree {
-            self.current_dir = Some(tree.root);
+    /// Refuses an operation that changes the tree while it is provisional.
+    fn busy_scanning(&mut self) -> bool {
+        let busy = self.is_provisional();
+        if busy {
+            self.operation_status = Some("Wait for the scan to finish first".into());
         }
+        busy
     }
 
-    pub fn request_delete(&mut self, id: NodeId) {
-        self.selected = Some(id);
-        self.pending_delete = Some(id);
-        self.pending_properties = None;
+    /// Refuses an operation on an entry inside an archive, which isn't a
+    /// file of its own on disk.
+    fn inside_archive(&mut self, id: NodeId) -> bool {
+        let inside = self
+            .tree
+            .as_ref()
+            .and_then(|t| t.nodes.get(id.0 as usize))
+            .is_some_and(|n| matches!(n.kind, NodeKind::ArchiveEntry));
+        if inside {
+            self.operation_status = Some("Entries inside an archive can't be changed".into());
+        }
+        inside
     }
 
-    pub fn request_properties(&mut self, id: NodeId) {
-        self.selected = Some(id);
-        self.pending_properties = Some(id);
-        self.pending_delete = None;
+    /// Refuses an operation on a mount point, which would reach into the
+    /// other filesystem the scan didn't enter.
+    fn mount_point(&mut self, id: NodeId) -> bool {
+        let mount = self
+            .tree
+            .as_ref()
+            .and_then(|t| t.nodes.get(id.0 as usize))
+            .is_some_and(|n| matches!(n.kind, NodeKind::MountPoint));
+        if mount {
+            self.operation_status =
+                Some("Mount points lead to another filesystem and can't be changed here".into());
+        }
+        mount
     }
 
-    pub fn delete_selected_and_rescan(&mut self) {
-        if let (Some(tree), Some(id)) = (&self.tree, self.selected) {
-            let path = &tree.nodes[id.0 as usize].path;
-            if trash::delete(path).is_err() {
-                let _ = if path.is_dir() {
-                    std::fs::remove_dir_all(path)
-                } else {
-                    std::fs::remove_file(path)
-                };
+    /// Rescans the loaded root on a worker thread while the current tree
+    /// stays interactive; [`AppState::poll_refresh`] swaps the result in.
+    pub fn start_background_refresh(&mut self) {
+        if self.scan_rx.is_some() || self.refresh_rx.is_some() || self.tree.is_none() {
+            return;
+        }
+        let roots: Vec<PathBuf> = self.scan_roots().cloned().collect();
+        if roots.is_empty() {
+            return;
+        }
+        self.refresh_summary = None;
+        let (tx, rx) = scanner::scan_channel();
+        self.refresh_rx = Some(rx);
+        let scanner = Scanner::new().with_options(self.scan_options.clone());
+        self.refresh = Some(scanner.spawn_many(roots, tx));
+    }
+
+    pub fn is_refreshing(&self) -> bool {
+        self.refresh_rx.is_some()
+    }
+
+    pub fn cancel_refresh(&mut self) {
+        if let Some(refresh) = self.refresh.take() {
+            refresh.cancel();
+        }
+        self.refresh_rx = None;
+    }
+
+    /// Swaps in the background refresh once it finishes. Returns whether
+    /// the tree was replaced.
+    pub fn poll_refresh(&mut self) -> bool {
+        let Some(rx) = &self.refresh_rx else {
+            return false;
+        };
+        let mut finished = None;
+        loop {
+            match rx.try_recv() {
+                Ok(ScanMsg::Done(tree)) => {
+                    finished = Some(Arc::unwrap_or_clone(tree));
+                    break;
+                }
+                Ok(_) => {}
+                Err(crossbeam_channel::TryRecvError::Empty) => return false,
+                Err(crossbeam_channel::TryRecvError::Disconnected) => break,
             }
-            if let Some(root) = &self.root {
-                self.start_scan(root.clone());
+        }
+        self.refresh_rx = None;
+        self.refresh = None;
+        match finished {
+            Some(tree) => {
+                self.swap_tree(tree);
+                true
             }
+            None => false,
         }
     }
 
-    pub fn rebuild_file_cache(&mut self) {
-        self.file_nodes.clear();
-        if let Some(tree) = &self.tree {
-            for (idx, node) in tree.nodes.iter().enumerate() {
-                if matches!(node.kind, NodeKind::File) {
-                    self.file_nodes.push(NodeId(idx as u64));
+    /// Replaces the loaded tree with a rescan of the same root, carrying
+    /// navigation, selection and the cart over to the nodes at the same
+    /// paths.
+    fn swap_tree(&mut self, tree: Tree) {
+        let Some(old) = self.tree.take() else {
+            return;
+        };
+        let remap = old.remap_to(&tree);
+        self.refresh_summary = Some(RefreshSummary::between(&old, &tree, &remap));
+        self.review = Some(Review::new(&diff::diff(&old, &tree)));
+        self.show_review = false;
+        self.scan_cancelled = false;
+        self.progress_files = tree.node(tree.root).file_count;
+        self.progress_discovered = self.progress_files;
+        self.progress_bytes = tree.node(tree.root).size;
+        let root = tree.root;
+        self.tree = Some(tree);
+        self.apply_remap(&remap, None);
+        self.current_dir = self.current_dir.or(Some(root));
+        self.tree_fingerprint = self.scan_options.fingerprint();
+        self.reused_at = None;
+        self.rebuild_file_cache();
+        self.record_scan();
+    }
+
+    /// Steps through the largest growth the last background refresh found,
+    /// from the start, showing each change in the main view.
+    pub fn start_review(&mut self) {
+        let Some(review) = &mut self.review else {
+            return;
+        };
+        review.restart();
+        self.show_review = true;
+        self.settle_review();
+        self.reveal_reviewed();
+    }
+
+    /// The change under review and its node.
+    pub fn reviewed_change(&self) -> Option<(&NodeChange, NodeId)> {
+        let review = self.review.as_ref()?;
+        let change = review.changes.get(review.position)?;
+        Some((change, self.tree.as_ref()?.find(&change.path)?))
+    }
+
+    /// Applies `action` to the change under review and moves on to the
+    /// next, or for a deletion, waits until it is confirmed or cancelled.
+    pub fn decide_review(&mut self, action: ReviewAction) {
+        let Some((change, id)) = self.reviewed_change() else {
+            return;
+        };
+        let path = change.path.clone();
+        match action {
+            ReviewAction::Tag => self.add_to_cart(id),
+            ReviewAction::Delete => self.request_delete(id),
+            ReviewAction::Dismiss => {}
+        }
+        let pending_delete = self.pending_delete;
+        let Some(review) = &mut self.review else {
+            return;
+        };
+        match action {
+            ReviewAction::Tag => {
+                review.tagged += 1;
+                review.acted.push(path);
+            }
+            ReviewAction::Delete => {
+                review.deleting = pending_delete == Some(id);
+                return;
+            }
+            ReviewAction::Dismiss => review.dismissed += 1,
+        }
+        review.position += 1;
+        self.settle_review();
+        self.reveal_reviewed();
+    }
+
+    /// Moves the review past changes no longer in the tree, or inside one
+    /// already tagged or deleted, counting a deletion once it is confirmed.
+    /// Returns whether the change under review moved.
+    pub fn settle_review(&mut self) -> bool {
+        let (Some(review), Some(tree)) = (&mut self.review, &self.tree) else {
+            return false;
+        };
+        if review.deleting && self.pending_delete.is_none() {
+            review.deleting = false;
+            if let Some(change) = review.changes.get(review.position) {
+                if tree.find(&change.path).is_none() {
+                    review.deleted += 1;
+                    review.acted.push(change.path.clone());
                 }
             }
         }
-        self.apply_search();
+        let start = review.position;
+        while let Some(change) = review.changes.get(review.position) {
+            let settled = review.acted.iter().any(|p| change.path.starts_with(p));
+            if !settled && tree.find(&change.path).is_some() {
+                break;
+            }
+            review.position += 1;
+        }
+        review.position != start
     }
 
-    pub fn sort_file_lists(&mut self) {
+    /// Selects the change under review in its folder.
+    pub fn reveal_reviewed(&mut self) {
+        let Some((_, id)) = self.reviewed_change() else {
+            return;
+        };
+        let parent = self.tree.as_ref().and_then(|t| t.node(id).parent);
+        self.current_dir = parent.or(Some(id));
+        self.selected = Some(id);
+    }
+
+    /// Shows a tree saved with [`Tree::save`] in place of a scan. It is not
+    /// watched, since its root may no longer be reachable.
+    pub fn open_saved_scan(&mut self, path: &std::path::Path) {
+        if path
+            .extension()
+            .is_some_and(|ext| ext == SHARED_VIEW_EXTENSION)
+        {
+            self.open_shared_view(path);
+            return;
+        }
+        match Tree::open(path) {
+            Ok(tree) => self.show_saved_tree(tree),
+            Err(err) => {
+                self.operation_status = Some(format!("Could not open {}: {err}", path.display()))
+            }
+        }
+    }
+
+    /// Shows `tree`, read from a file rather than scanned.
+    fn show_saved_tree(&mut self, tree: Tree) {
+        self.stash_tree();
+        self.cancel_scan();
+        self.scan_rx = None;
+        self.clear_loaded();
+        let root = tree.node(tree.root);
+        if tree.is_combined() {
+            let mut roots = root.children.iter().map(|c| tree.path(*c));
+            self.root = roots.next();
+            self.extra_roots = roots.collect();
+        } else {
+            self.root = Some(tree.path(tree.root));
+            self.extra_roots.clear();
+        }
+        self.progress_files = root.file_count;
+        self.progress_discovered = root.file_count;
+        self.progress_bytes = root.size;
+        // Saved options aren't known, so never reuse this as a snapshot
+        self.tree_fingerprint = 0;
+        self.reused_at = tree.meta.scanned_at;
+        self.tree = Some(tree);
+        self.rebuild_file_cache();
+    }
+
+    /// Compares the loaded tree with an earlier scan saved at `path` (or a
+    /// JSON export of one).
+    pub fn compare_with(&mut self, path: PathBuf) {
         let Some(tree) = &self.tree else {
             return;
         };
-        let mut sort_ids = |ids: &mut Vec<NodeId>| match self.sort {
-            SortKey::Size => ids.sort_by(|a, b| {
-                tree.nodes[b.0 as usize]
-                    .size
-                    .cmp(&tree.nodes[a.0 as usize].size)
-            }),
-            SortKey::Name => ids.sort_by(|a, b| {
-                tree.nodes[a.0 as usize]
-                    .name
-                    .cmp(&tree.nodes[b.0 as usize].name)
-            }),
-            SortKey::Count => ids.sort_by(|a, b| {
-                tree.nodes[b.0 as usize]
-                    .file_count
-                    .cmp(&tree.nodes[a.0 as usize].file_count)
-            }),
+        match Tree::open(&path) {
+            Ok(baseline) => {
+                self.comparison = Some(Comparison {
+                    diff: diff::diff(&baseline, tree),
+                    baseline: path,
+                });
+                self.show_compare = true;
+            }
+            Err(err) => {
+                self.operation_status = Some(format!("Could not open {}: {err}", path.display()));
+            }
+        }
+    }
+
+    /// Pins the current folder beside the main view.
+    pub fn pin_current(&mut self) {
+        let (Some(tree), Some(id)) = (&self.tree, self.current_dir) else {
+            return;
         };
-        sort_ids(&mut self.file_nodes);
-        sort_ids(&mut self.filtered_file_nodes);
+        let Some(branch) = tree.subtree(id) else {
+            return;
+        };
+        let source = match tree.meta.scanned_at {
+            Some(at) => format!("Scan of {}", at.format("%Y-%m-%d %H:%M")),
+            None => "Loaded scan".to_string(),
+        };
+        self.pinned = Some(PinnedFolder {
+            source,
+            current: branch.root,
+            tree: branch,
+        });
     }
 
-    pub fn refresh_filtered_files(&mut self) {
-        if let Some(tree) = &self.tree {
-            if let Some(filter) = &self.search_filter {
-                self.filtered_file_nodes = self
-                    .file_nodes
-                    .iter()
-                    .copied()
-                    .filter(|id| filter.matches_node(*id))
-                    .collect();
-            } else {
-                self.filtered_file_nodes = self.file_nodes.clone();
+    /// Pins a scan saved at `path` (or a JSON export of one), opened at the
+    /// current folder if the saved scan has it.
+    pub fn pin_saved_scan(&mut self, path: &std::path::Path) {
+        match Tree::open(path) {
+            Ok(tree) => {
+                let current = self
+                    .current_path()
+                    .and_then(|p| tree.find(&p))
+                    .unwrap_or(tree.root);
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                self.pinned = Some(PinnedFolder {
+                    source: name.to_string_lossy().into_owned(),
+                    tree,
+                    current,
+                });
+            }
+            Err(err) => {
+                self.operation_status = Some(format!("Could not open {}: {err}", path.display()));
             }
-            self.sort_file_lists();
-        } else {
-            self.filtered_file_nodes.clear();
         }
     }
 
-    pub fn apply_search(&mut self) {
-        if let Some(tree) = &self.tree {
-            let trimmed = self.search.trim();
-            if trimmed.is_empty() {
-                self.search_filter = None;
-            } else {
-                self.search_filter = Some(SearchFilter::build(trimmed, tree));
+    fn current_path(&self) -> Option<PathBuf> {
+        let tree = self.tree.as_ref()?;
+        let id = self.current_dir?;
+        tree.nodes.get(id.0 as usize)?;
+        Some(tree.path(id))
+    }
+
+    /// Saves the loaded tree together with the folder being looked at, the
+    /// selection, the search and the view settings, for
+    /// [`AppState::open_shared_view`] to restore elsewhere.
+    pub fn share_view(&mut self, path: &Path) {
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        let path_of = |id: Option<NodeId>| {
+            let id = id?;
+            tree.nodes.get(id.0 as usize)?;
+            Some(tree.path(id))
+        };
+        let settings = [
+            (
+                "sort",
+                match self.sort {
+                    SortKey::Size => "size",
+                    SortKey::Name => "name",
+                    SortKey::Count => "files",
+                },
+            ),
+            (
+                "tab",
+                match self.view_tab {
+                    ViewTab::Tree => "tree",
+                    ViewTab::Files => "files",
+                },
+            ),
+            (
+                "chart",
+                match self.chart {
+                    ChartKind::Pie => "pie",
+                    ChartKind::Sunburst => "sunburst",
+                },
+            ),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .chain([
+            ("age_overlay".to_string(), self.age_overlay.to_string()),
+            (
+                "percent_of_baseline".to_string(),
+                self.percent_of_baseline.to_string(),
+            ),
+            (
+                "hide_small_folders".to_string(),
+                self.hide_small_folders.to_string(),
+            ),
+            (
+                "min_folder_percent".to_string(),
+                self.min_folder_percent.to_string(),
+            ),
+        ])
+        .collect();
+        let bundle = ViewBundle {
+            view: SharedView {
+                current: path_of(self.current_dir),
+                selected: path_of(self.selected),
+                baseline: path_of(self.baseline),
+                search: self.search.trim().to_string(),
+                matching: self.search_matching,
+                settings,
+            },
+            tree: tree.clone(),
+        };
+        self.export_status = Some(match bundle.save(path) {
+            Ok(()) => format!("Shared view saved to {}", path.display()),
+            Err(err) => format!("Sharing failed: {err}"),
+        });
+    }
+
+    /// Opens a file written by [`AppState::share_view`] and shows its tree
+    /// exactly as it was shared. Folders no longer in it are skipped.
+    pub fn open_shared_view(&mut self, path: &Path) {
+        let ViewBundle { view, tree } = match ViewBundle::load(path) {
+            Ok(bundle) => bundle,
+            Err(err) => {
+                self.operation_status = Some(format!("Could not open {}: {err}", path.display()));
+                return;
             }
-            self.refresh_filtered_files();
-        } else {
-            self.search_filter = None;
-            self.filtered_file_nodes.clear();
+        };
+        self.show_saved_tree(tree);
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        let find = |path: &Option<PathBuf>| tree.find(path.as_ref()?);
+        self.current_dir = find(&view.current).or(self.current_dir);
+        self.selected = find(&view.selected);
+        self.baseline = find(&view.baseline);
+        let setting = |name: &str| view.settings.get(name).map(String::as_str);
+        let flag = |name: &str| setting(name).and_then(|v| v.parse::<bool>().ok());
+        match setting("sort") {
+            Some("size") => self.sort = SortKey::Size,
+            Some("name") => self.sort = SortKey::Name,
+            Some("files") => self.sort = SortKey::Count,
+            _ => {}
+        }
+        match setting("tab") {
+            Some("tree") => self.view_tab = ViewTab::Tree,
+            Some("files") => self.view_tab = ViewTab::Files,
+            _ => {}
+        }
+        match setting("chart") {
+            Some("pie") => self.chart = ChartKind::Pie,
+            Some("sunburst") => self.chart = ChartKind::Sunburst,
+            _ => {}
         }
+        self.age_overlay = flag("age_overlay").unwrap_or(self.age_overlay);
+        self.percent_of_baseline = flag("percent_of_baseline").unwrap_or(self.percent_of_baseline);
+        self.hide_small_folders = flag("hide_small_folders").unwrap_or(self.hide_small_folders);
+        if let Some(percent) = setting("min_folder_percent").and_then(|v| v.parse().ok()) {
+            self.min_folder_percent = percent;
+        }
+        self.search = view.search;
+        self.search_matching = view.matching;
+        self.apply_search();
+        self.operation_status = Some(format!("Opened shared view {}", path.display()));
     }
+
+    pub fn save_scan(&mut self, path: &std::path::Path) {
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        self.export_status = Some(match tree.save(path) {
+            Ok(()) => format!("Saved scan to {}", path.display()),
+            Err(err) => format!("Saving failed: {err}"),
+        });
+    }
+
+    /// A `treesize-cli` command that scans the current roots with the
+    /// current options, saves the scan and opens it in the app, or says how
+    /// to open it later where it can't; for running the same scan headless,
+    /// e.g. over SSH on a server.
+    pub fn cli_command(&self) -> Option<String> {
+        self.root.as_ref()?;
+        let options = &self.scan_options;
+        let mut args: Vec<String> = vec!["treesize-cli".into()];
+        args.extend(self.scan_roots().map(|root| root.display().to_string()));
+        let mut flag = |name: &str, value: Option<String>| {
+            args.push(name.into());
+            args.extend(value);
+        };
+        for pattern in &options.excludes {
+            flag("--exclude", Some(pattern.clone()));
+        }
+        if options.one_file_system {
+            flag("--one-file-system", None);
+        }
+        if options.follow_links {
+            flag("--follow-links", None);
+        }
+        if options.alternate_streams {
+            flag("--alternate-streams", None);
+        }
+        if options.owners {
+            flag("--owners", None);
+        }
+        if options.archives {
+            flag("--archives", None);
+        }
+        if options.min_file_size > 0 {
+            flag("--min-size", Some(options.min_file_size.to_string()));
+        }
+        if !options.include_extensions.is_empty() {
+            flag("--include-ext", Some(options.include_extensions.join(",")));
+        }
+        if !options.exclude_extensions.is_empty() {
+            flag("--exclude-ext", Some(options.exclude_extensions.join(",")));
+        }
+        if let Some(depth) = options.max_depth {
+            flag("--max-depth", Some(depth.to_string()));
+        }
+        if let Some(per_mille) = options.sample_per_mille {
+            flag("--sample", Some((f64::from(per_mille) / 10.0).to_string()));
+        }
+        if let Some(threads) = options.tuning.threads {
+            flag("--threads", Some(threads.to_string()));
+        }
+        if let Some(batch) = options.tuning.batch_size {
+            flag("--batch-size", Some(batch.to_string()));
+        }
+        if let Some(dirs) = options.tuning.max_open_dirs {
+            flag("--max-open-dirs", Some(dirs.to_string()));
+        }
+        if let Some(delay) = options.tuning.entry_delay {
+            flag("--entry-delay", Some(delay.as_micros().to_string()));
+        }
+        if options.tuning.background {
+            flag("--low-priority", None);
+        }
+        if let Some(storage) = options.storage {
+            flag("--storage", Some(storage.label().to_lowercase()));
+        }
+        if let Some(dir) = &options.spill {
+            flag("--spill", Some(dir.display().to_string()));
+        }
+        flag("--open-gui", None);
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| integration::shell_quote(arg))
+            .collect();
+        Some(args.join(" "))
+    }
+
+    /// Stops the running scan. Unless the scan's channel is dropped too,
+    /// what it found so far still arrives as [`ScanMsg::Cancelled`].
+    pub fn cancel_scan(&mut self) {
+        if let Some(scan) = self.scan.take() {
+            scan.cancel();
+        }
+    }
+
+    /// Whether the running scan or background refresh is paused.
+    pub fn is_paused(&self) -> bool {
+        self.scan
+            .iter()
+            .chain(&self.refresh)
+            .any(ScanHandle::is_paused)
+    }
+
+    /// Pauses or resumes the running scan and background refresh together.
+    pub fn pause_or_resume(&self) {
+        let pause = !self.is_paused();
+        for handle in self.scan.iter().chain(&self.refresh) {
+            if pause {
+                handle.pause();
+            } else {
+                handle.resume();
+            }
+        }
+    }
+
+    pub fn reset_to_initial(&mut self) {
+        self.scan_rx = None;
+        self.cancel_refresh();
+        self.refresh_summary = None;
+        self.review = None;
+        self.stop_watching();
+        self.cart.clear();
+        self.advice = None;
+        self.tree = None;
+        self.current_dir = None;
+        self.selected = None;
+        self.progress_bytes = 0;
+        self.progress_discovered = 0;
+        self.progress_files = 0;
+        self.progress_rate = 0.0;
+        self.progress_eta = None;
+        self.progress_path = None;
+        self.search_filter = None;
+        self.file_nodes.clear();
+        self.filtered_file_nodes.clear();
+        self.filtered_file_bytes = 0;
+        self.view_tab = ViewTab::Tree;
+        self.export_status = None;
+        self.scan_cancelled = false;
+    }
+
+    /// Starts watching the scanned root, if enabled, so later changes are
+    /// applied by [`AppState::apply_watch_events`].
+    pub fn start_watching(&mut self) {
+        self.stop_watching();
+        let Some(root) = &self.root else {
+            return;
+        };
+        if !self.watch_changes {
+            return;
+        }
+        let (tx, rx) = scanner::scan_channel();
+        let watching = watch::watch(root, &self.scan_options, tx).and_then(|mut watcher| {
+            for extra in &self.extra_roots {
+                watcher.add(extra)?;
+            }
+            Ok(watcher)
+        });
+        match watching {
+            Ok(watcher) => {
+                self.watcher = Some(watcher);
+                self.watch_rx = Some(rx);
+                self.watchdog = self.growth_alarm.map(|limit| {
+                    let mut roots = vec![root.clone()];
+                    roots.extend(self.extra_roots.iter().cloned());
+                    Watchdog::new(roots, limit)
+                });
+            }
+            Err(err) => {
+                self.operation_status = Some(format!("Not watching for changes: {err}"));
+            }
+        }
+    }
+
+    pub fn stop_watching(&mut self) {
+        self.watcher = None;
+        self.watch_rx = None;
+        self.watchdog = None;
+    }
+
+    /// Applies [`AppState::growth_alarm_input`]: empty turns the alarm off.
+    /// Returns an error for a rate that doesn't parse.
+    pub fn set_growth_alarm(&mut self) -> Result<(), String> {
+        let input = self.growth_alarm_input.trim();
+        let alarm = if input.is_empty() {
+            None
+        } else {
+            Some(input.parse::<GrowthRate>()?)
+        };
+        if alarm != self.growth_alarm {
+            self.growth_alarm = alarm;
+            if self.is_watching() {
+                self.start_watching();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    /// Applies pending filesystem changes to the tree. Returns whether
+    /// anything changed.
+    pub fn apply_watch_events(&mut self) -> bool {
+        let (Some(rx), Some(tree)) = (&self.watch_rx, self.tree.as_mut()) else {
+            return false;
+        };
+        let mut changed = false;
+        let mut remaps = Vec::new();
+        let size_at =
+            |tree: &Tree, path: &Path| tree.find(path).map_or(0, |id| tree.node(id).size as i128);
+        while let Ok(msg) = rx.try_recv() {
+            let (path, delta) = match msg {
+                ScanMsg::NodeUpdated {
+                    path,
+                    bytes,
+                    modified,
+                } => {
+                    let delta = bytes as i128 - size_at(tree, &path);
+                    changed |= tree.upsert_file(&path, bytes as u128, modified).is_some();
+                    (path, delta)
+                }
+                ScanMsg::NodeRemoved { path } => {
+                    let delta = -size_at(tree, &path);
+                    let parent = path.parent().and_then(|p| tree.find(p));
+                    if let Some(remap) = tree.remove_path(&path) {
+                        remaps.push((remap, parent));
+                        changed = true;
+                    }
+                    (path, delta)
+                }
+                _ => continue,
+            };
+            let alert = self
+                .watchdog
+                .as_mut()
+                .and_then(|dog| dog.record(&path, delta, std::time::Instant::now()));
+            if let Some(alert) = alert {
+                self.operation_status = Some(format!("Growth alarm: {alert}"));
+                self.growth_alerts.push((Local::now(), alert));
+                self.show_alerts = true;
+                self.unseen_alerts = true;
+            }
+        }
+        for (remap, parent) in remaps {
+            self.apply_remap(&remap, parent);
+        }
+        if changed {
+            self.rebuild_file_cache();
+        }
+        changed
+    }
+
+    pub fn add_exclude(&mut self) {
+        let pattern = self.exclude_input.trim();
+        if !pattern.is_empty() && !self.scan_options.excludes.iter().any(|p| p == pattern) {
+            self.scan_options.excludes.push(pattern.to_string());
+        }
+        self.exclude_input.clear();
+    }
+
+    /// Copies the extension lists being edited into the scan options.
+    pub fn apply_extension_filters(&mut self) {
+        let split = |input: &str| -> Vec<String> {
+            input
+                .split([',', ' '])
+                .map(|ext| ext.trim().trim_start_matches('.'))
+                .filter(|ext| !ext.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        self.scan_options.include_extensions = split(&self.include_ext_input);
+        self.scan_options.exclude_extensions = split(&self.exclude_ext_input);
+    }
+
+    /// The node percentages are relative to, while they are shown as shares
+    /// of a baseline rather than of their own folder.
+    pub fn percent_baseline(&self) -> Option<&TreeNode> {
+        if !self.percent_of_baseline {
+            return None;
+        }
+        let tree = self.tree.as_ref()?;
+        tree.nodes
+            .get(self.baseline.unwrap_or(tree.root).0 as usize)
+    }
+
+    /// `path` as it is shown, following [`Self::relative_paths`].
+    pub fn shown_path<'a>(&self, path: &'a Path) -> &'a Path {
+        match &self.tree {
+            Some(tree) if self.relative_paths => tree.relative_path(path),
+            _ => path,
+        }
+    }
+
+    pub fn set_baseline(&mut self, id: NodeId) {
+        self.baseline = Some(id);
+        self.percent_of_baseline = true;
+    }
+
+    pub fn navigate_up(&mut self) {
+        if let (Some(tree), Some(cur)) = (&self.tree, self.current_dir) {
+            if let Some(parent) = tree.node(cur).parent {
+                self.current_dir = Some(parent);
+                self.selected = None;
+            }
+        }
+    }
+
+    pub fn set_current_root(&mut self) {
+        if let Some(tree) = &self.tree {
+            self.current_dir = Some(tree.root);
+        }
+    }
+
+    pub fn request_delete(&mut self, id: NodeId) {
+        if self.busy_scanning() || self.inside_archive(id) || self.mount_point(id) {
+            return;
+        }
+        self.selected = Some(id);
+        self.pending_delete = Some(id);
+        self.pending_properties = None;
+    }
+
+    pub fn request_properties(&mut self, id: NodeId) {
+        self.selected = Some(id);
+        self.pending_properties = Some(id);
+        self.pending_delete = None;
+        self.tag_input.clear();
+        self.note_input = self
+            .tree
+            .as_ref()
+            .filter(|t| t.nodes.get(id.0 as usize).is_some())
+            .and_then(|t| self.annotations.get(&t.path(id)))
+            .map(|a| a.note.clone())
+            .unwrap_or_default();
+        self.properties_streams = self
+            .tree
+            .as_ref()
+            .filter(|t| t.nodes.get(id.0 as usize).is_some())
+            .and_then(|t| streams::alternate_streams(&t.path(id)).ok())
+            .unwrap_or_default();
+        self.properties_owners = self
+            .tree
+            .as_ref()
+            .map(|t| usage_by_owner(t, id))
+            .unwrap_or_default();
+        self.properties_aliases = self
+            .tree
+            .as_ref()
+            .map(|t| {
+                t.aliases_of(id)
+                    .into_iter()
+                    .map(|alias| t.path(alias))
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    /// Deletes the selected node, drops it from the loaded tree and rescans
+    /// its parent directory in the background to pick up anything else that
+    /// changed there.
+    pub fn delete_selected_and_rescan(&mut self) {
+        let (Some(tree), Some(id)) = (&self.tree, self.selected) else {
+            return;
+        };
+        let node = tree.node(id);
+        let path = tree.path(id);
+        // The synthetic root of a combined scan is not on disk
+        if path.as_os_str().is_empty() {
+            return;
+        }
+        let parent = node
+            .parent
+            .map(|p| tree.path(p))
+            .filter(|p| !p.as_os_str().is_empty());
+        let result = trash_or_remove(&path, &node.kind);
+        self.record(JournalEntry::new(Operation::Delete, &path, node.size, "app").outcome(&result));
+        match (result, parent) {
+            (Ok(()), Some(parent)) => {
+                self.remove_node_in_place(id);
+                self.rescan_dir(parent);
+            }
+            (Err(err), Some(parent)) => {
+                self.operation_status = Some(format!("Deleting {} failed: {err}", path.display()));
+                // Part of it may be gone already
+                self.rescan_dir(parent);
+            }
+            (_, None) => self.rescan(),
+        }
+    }
+
+    /// Queues `id` for removal, unless it or an ancestor already is.
+    /// Queued descendants of `id` are dropped since it covers them.
+    pub fn add_to_cart(&mut self, id: NodeId) {
+        if self.inside_archive(id) || self.mount_point(id) {
+            return;
+        }
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        if self.cart.iter().any(|&c| tree.is_within(id, c)) {
+            return;
+        }
+        self.cart.retain(|&c| !tree.is_within(c, id));
+        self.cart.push(id);
+    }
+
+    pub fn cart_total(&self) -> u128 {
+        let Some(tree) = &self.tree else {
+            return 0;
+        };
+        self.cart.iter().map(|id| tree.node(*id).size).sum()
+    }
+
+    /// Deletes everything in the cart, journaling each item as a clean.
+    pub fn clean_cart(&mut self) {
+        if self.busy_scanning() {
+            return;
+        }
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        let items: Vec<_> = self
+            .cart
+            .drain(..)
+            .map(|id| {
+                let node = tree.node(id);
+                (tree.path(id), node.size, node.kind.clone())
+            })
+            .collect();
+        let mut freed = 0u128;
+        let mut failed = 0usize;
+        for (path, size, kind) in items {
+            let result = trash_or_remove(&path, &kind);
+            self.record(JournalEntry::new(Operation::Clean, &path, size, "app").outcome(&result));
+            if result.is_err() {
+                failed += 1;
+                continue;
+            }
+            freed += size;
+            // Ids shift with every removal, so look each one up again
+            if let Some(id) = self.tree.as_ref().and_then(|t| t.find(&path)) {
+                self.remove_node_in_place(id);
+            }
+        }
+        self.operation_status = Some(if failed == 0 {
+            format!("Freed {}", human_bytes(freed))
+        } else {
+            format!("Freed {}; {failed} item(s) failed", human_bytes(freed))
+        });
+    }
+
+    /// Proposes what to remove to free the amount in `advisor_target`.
+    pub fn run_advisor(&mut self) {
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        match human::parse_bytes(&self.advisor_target) {
+            Some(target) => {
+                let options = AdvisorOptions {
+                    name_matching: self.dedupe_matching,
+                    ..AdvisorOptions::default()
+                };
+                self.advice = Some(advisor::advise(tree, target, &options));
+            }
+            None => {
+                self.advice = None;
+                self.operation_status =
+                    Some(format!("Not a size: {:?}", self.advisor_target.trim()));
+            }
+        }
+    }
+
+    pub fn add_advice_to_cart(&mut self) {
+        let Some(advice) = self.advice.take() else {
+            return;
+        };
+        for candidate in &advice.candidates {
+            self.add_to_cart(candidate.id);
+        }
+        self.show_cart = true;
+    }
+
+    /// Puts every empty folder and zero-byte file in the cart; see
+    /// [`Tree::empty_dirs`] and [`Tree::empty_files`].
+    pub fn add_empty_to_cart(&mut self) {
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        let dirs = tree.empty_dirs();
+        let files = tree.empty_files();
+        self.operation_status = Some(format!(
+            "Found {} empty folder(s) and {} empty file(s)",
+            dirs.len(),
+            files.len()
+        ));
+        for id in dirs.into_iter().chain(files) {
+            self.add_to_cart(id);
+        }
+        self.show_cart = true;
+    }
+
+    /// Rescans one directory on a worker thread; the result is applied by
+    /// [`AppState::apply_rescan`].
+    pub fn rescan_dir(&mut self, path: PathBuf) {
+        let scanner = Scanner::new().with_options(self.scan_options.clone());
+        self.spawn_rescan(path, scanner);
+    }
+
+    /// Lists a folder whose contents the depth limit left out, by scanning
+    /// it as many levels deep as the loaded scan went. Does nothing for
+    /// other folders, or if it is already being listed.
+    pub fn list_cut_off(&mut self, id: NodeId) {
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        let (Some(_), Some(depth)) = (tree.nodes.get(id.0 as usize), tree.meta.max_depth) else {
+            return;
+        };
+        if !tree.is_cut_off(id) {
+            return;
+        }
+        let path = tree.path(id);
+        let options = ScanOptions {
+            max_depth: Some(depth.max(1)),
+            ..self.scan_options.clone()
+        };
+        self.rescan_in_place(path, options);
+    }
+
+    /// Rescans the folder `id` in the background and swaps the result in,
+    /// showing its progress beside the folder meanwhile.
+    pub fn refresh_folder(&mut self, id: NodeId) {
+        let Some(tree) = self
+            .tree
+            .as_ref()
+            .filter(|t| t.nodes.get(id.0 as usize).is_some())
+        else {
+            return;
+        };
+        let path = tree.path(id);
+        self.rescan_in_place(path, self.scan_options.clone());
+    }
+
+    fn rescan_in_place(&mut self, path: PathBuf, options: ScanOptions) {
+        if self.listings.iter().any(|l| l.path == path) {
+            return;
+        }
+        let scanner = Scanner::new().with_options(options);
+        let progress = scanner.progress().subscribe();
+        if self.spawn_rescan(path.clone(), scanner) {
+            self.listings.push(Listing {
+                path,
+                progress,
+                files: 0,
+                bytes: 0,
+            });
+        }
+    }
+
+    /// The rescan under way for the folder at `path`, if any.
+    pub fn listing(&self, path: &Path) -> Option<&Listing> {
+        self.listings.iter().find(|l| l.path == path)
+    }
+
+    /// Takes in the progress of folders being scanned again.
+    pub fn poll_listings(&mut self) {
+        for listing in &mut self.listings {
+            while let Ok(event) = listing.progress.try_recv() {
+                if let ProgressEvent::Progress(progress)
+                | ProgressEvent::Finished { progress, .. } = event
+                {
+                    listing.files = progress.files;
+                    listing.bytes = progress.bytes;
+                }
+            }
+        }
+    }
+
+    /// Runs `scanner` on `path` on a worker thread; false if refused
+    /// because the tree is still provisional.
+    fn spawn_rescan(&mut self, path: PathBuf, scanner: Scanner) -> bool {
+        if self.busy_scanning() {
+            return false;
+        }
+        let tx = self.rescan_tx.clone();
+        self.rescans_pending += 1;
+        std::thread::spawn(move || {
+            let tree = scanner.scan_subtree(path.clone());
+            let _ = tx.send(Rescan { path, tree });
+        });
+        true
+    }
+
+    pub fn apply_rescan(&mut self, rescan: Rescan) {
+        self.rescans_pending = self.rescans_pending.saturating_sub(1);
+        self.listings.retain(|l| l.path != rescan.path);
+        let Some(tree) = self.tree.as_mut() else {
+            return;
+        };
+        // Ids may have shifted since the rescan started, so look it up again.
+        let Some(id) = tree
+            .find(&rescan.path)
+            .filter(|id| matches!(tree.node(*id).kind, NodeKind::Dir))
+        else {
+            return;
+        };
+        if let Some(remap) = tree.splice_subtree(id, rescan.tree) {
+            self.apply_remap(&remap, Some(id));
+            self.rebuild_file_cache();
+        }
+    }
+
+    /// Tags `id` with `tag` and saves the tags right away.
+    pub fn add_tag(&mut self, id: NodeId, tag: &str) {
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        if self.annotations.add_tag(&tree.path(id), tag) {
+            self.annotations_changed();
+        }
+    }
+
+    pub fn remove_tag(&mut self, id: NodeId, tag: &str) {
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        if self.annotations.remove_tag(&tree.path(id), tag) {
+            self.annotations_changed();
+        }
+    }
+
+    /// Sets the note of `id`; a blank one removes it.
+    pub fn set_note(&mut self, id: NodeId, note: &str) {
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        self.annotations.set_note(&tree.path(id), note);
+        self.annotations_changed();
+    }
+
+    /// Saves the tags and notes and searches again, dropping the tag
+    /// searched for once nothing carries it; failures to save are surfaced
+    /// in the status line.
+    fn annotations_changed(&mut self) {
+        if let Err(err) = self.annotations.save(&Annotations::default_path()) {
+            self.operation_status = Some(format!("Could not save tags: {err}"));
+        }
+        let labels = self.annotations.labels();
+        if let Some(tag) = self.search_tag.as_deref() {
+            if !labels.contains(tag) {
+                self.search_tag = None;
+            }
+        }
+        if self.search_tag.is_some() || self.search_filter.is_some() {
+            self.apply_search();
+        }
+    }
+
+    /// Appends to the operation journal; failures to write it are surfaced
+    /// in the status line rather than aborting the operation.
+    pub fn record(&mut self, entry: JournalEntry) {
+        if let Err(err) = self.journal.append(&entry) {
+            self.operation_status = Some(format!("Could not write activity log: {err}"));
+        }
+        if self.show_activity {
+            self.activity.push(entry);
+        }
+    }
+
+    pub fn toggle_activity(&mut self) {
+        self.show_activity = !self.show_activity;
+        if self.show_activity {
+            self.reload_activity();
+        }
+    }
+
+    /// Whether past scans can be recorded and shown.
+    pub fn has_history(&self) -> bool {
+        self.history.is_some()
+    }
+
+    /// Loads the trends of the current folder and the recorded folders
+    /// below it for the History window, unless they are already loaded.
+    pub fn refresh_history(&mut self) {
+        let (Some(history), Some(tree)) = (&self.history, &self.tree) else {
+            self.history_trends = None;
+            return;
+        };
+        let id = self.current_dir.unwrap_or(tree.root);
+        let path = tree.path(id);
+        if self
+            .history_trends
+            .as_ref()
+            .is_some_and(|(shown, _)| *shown == path)
+        {
+            return;
+        }
+        // The synthetic root of a combined scan has no history of its own
+        if path.as_os_str().is_empty() {
+            self.history_trends = Some((path, Vec::new()));
+            return;
+        }
+        match history.trends(&path) {
+            Ok(trends) => self.history_trends = Some((path, trends)),
+            Err(err) => {
+                self.history_trends = Some((path, Vec::new()));
+                self.operation_status = Some(format!("Could not read scan history: {err}"));
+            }
+        }
+    }
+
+    pub fn reload_activity(&mut self) {
+        match self.journal.entries() {
+            Ok(entries) => self.activity = entries,
+            Err(err) => {
+                self.activity.clear();
+                self.operation_status = Some(format!("Could not read activity log: {err}"));
+            }
+        }
+    }
+
+    /// The scanned root `path` lies under; `None` for the synthetic root of
+    /// a combined scan.
+    fn scan_root_of(&self, path: &Path) -> Option<&PathBuf> {
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+        self.scan_roots().find(|r| path.starts_with(r))
+    }
+
+    /// Asks for a Defender exclusion for this program on a worker thread,
+    /// since it waits on a UAC prompt.
+    pub fn request_defender_exclusion(&mut self) {
+        if self.exclusion_rx.is_some() {
+            return;
+        }
+        let (tx, rx) = unbounded();
+        self.exclusion_rx = Some(rx);
+        self.operation_status = Some("Waiting for administrator approval...".into());
+        std::thread::spawn(move || {
+            let _ = tx.send(interference::request_defender_exclusion());
+        });
+    }
+
+    /// Starts another instance of the app with administrator rights,
+    /// through a UAC prompt, scanning the current roots, so folders this
+    /// one was denied are included.
+    pub fn retry_elevated(&mut self) {
+        let roots: Vec<PathBuf> = self.scan_roots().cloned().collect();
+        self.operation_status = Some(match relaunch_elevated(&roots) {
+            Ok(()) => "Rescanning in a new window with administrator rights".into(),
+            Err(err) => format!("Could not restart as administrator: {err}"),
+        });
+    }
+
+    pub fn poll_defender_exclusion(&mut self) {
+        let Some(result) = self.exclusion_rx.as_ref().and_then(|rx| rx.try_recv().ok()) else {
+            return;
+        };
+        self.exclusion_rx = None;
+        self.operation_status = Some(match result {
+            Ok(()) => {
+                self.interference = None;
+                "Excluded from Defender real-time scanning; rescan to see the difference".into()
+            }
+            Err(err) => format!("Could not add the Defender exclusion: {err}"),
+        });
+    }
+
+    /// Adds or removes the "Analyze with TreeSize RS" entry of the file
+    /// manager's folder menu, pointing it at this executable.
+    pub fn set_context_menu(&mut self, on: bool) {
+        let result = if on {
+            std::env::current_exe().and_then(|exe| integration::install(&exe))
+        } else {
+            integration::uninstall()
+        };
+        self.operation_status = Some(match result {
+            Ok(_) if on => "Right-click a folder in the file manager to analyze it here".into(),
+            Ok(_) => "Removed from the file manager's folder menu".into(),
+            Err(err) => format!("Could not change the file manager's folder menu: {err}"),
+        });
+        self.context_menu = Some(integration::is_installed());
+    }
+
+    pub fn archive_destination(&self) -> Option<PathBuf> {
+        let dir = self.archive_dir.trim();
+        (!dir.is_empty()).then(|| PathBuf::from(dir))
+    }
+
+    /// Plans moving `id` to the archive and asks for confirmation; the move
+    /// itself starts from [`AppState::archive_node`].
+    pub fn request_archive(&mut self, id: NodeId) {
+        if self.busy_scanning() || self.inside_archive(id) || self.mount_point(id) {
+            return;
+        }
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        let node = tree.node(id);
+        let path = tree.path(id);
+        let Some(root) = self.scan_root_of(&path) else {
+            return;
+        };
+        let Some(archive_root) = self.archive_destination() else {
+            self.operation_status = Some("Set an archive destination in Settings first".into());
+            self.show_settings = true;
+            return;
+        };
+        let destination = match ops::archive_destination(&path, root, &archive_root) {
+            Ok(dest) => dest,
+            Err(err) => {
+                self.operation_status = Some(err.to_string());
+                return;
+            }
+        };
+        let allocation_unit = ops::allocation_unit(&destination);
+        self.selected = Some(id);
+        self.pending_archive = Some(ArchivePlan {
+            id,
+            source: path,
+            size: node.size,
+            estimated_size: ops::estimate_allocated(tree, id, allocation_unit),
+            allocation_unit,
+            destination,
+        });
+    }
+
+    pub fn archive_node(&mut self, id: NodeId) {
+        if self.archive_rx.is_some() {
+            self.operation_status = Some("Another archive operation is still running".into());
+            return;
+        }
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        let source = tree.path(id);
+        let Some(scan_root) = self.scan_root_of(&source).cloned() else {
+            return;
+        };
+        let Some(archive_root) = self.archive_destination() else {
+            self.operation_status = Some("Set an archive destination in Settings first".into());
+            self.show_settings = true;
+            return;
+        };
+        let options = self.transfer_options;
+        let (tx, rx) = unbounded();
+        self.archive_rx = Some(rx);
+        self.archive_progress = None;
+        self.operation_status = Some(format!("Archiving {}...", source.display()));
+        std::thread::spawn(move || {
+            let result = ops::move_to_archive(&source, &scan_root, &archive_root, options, |p| {
+                let _ = tx.send(ArchiveMsg::Progress(p.clone()));
+            })
+            .map_err(|e| e.to_string());
+            let _ = tx.send(ArchiveMsg::Done(ArchiveOutcome { id, source, result }));
+        });
+    }
+
+    pub fn finish_archive(&mut self, outcome: ArchiveOutcome) {
+        self.archive_progress = None;
+        let bytes = self
+            .tree
+            .as_ref()
+            .filter(|t| t.nodes.get(outcome.id.0 as usize).is_some())
+            .filter(|t| t.path(outcome.id) == outcome.source)
+            .map_or(0, |t| t.node(outcome.id).size);
+        let mut entry = JournalEntry::new(Operation::Archive, &outcome.source, bytes, "app")
+            .outcome(&outcome.result);
+        if let Ok(dest) = &outcome.result {
+            entry = entry.destination(dest);
+        }
+        self.record(entry);
+        match outcome.result {
+            Ok(dest) => {
+                self.operation_status = Some(format!(
+                    "Moved {} to {}",
+                    outcome.source.display(),
+                    dest.display()
+                ));
+                let still_present = self
+                    .tree
+                    .as_ref()
+                    .filter(|t| t.nodes.get(outcome.id.0 as usize).is_some())
+                    .is_some_and(|t| t.path(outcome.id) == outcome.source);
+                if still_present {
+                    self.remove_node_in_place(outcome.id);
+                }
+            }
+            Err(err) => {
+                self.operation_status = Some(format!(
+                    "Archiving {} failed: {err}",
+                    outcome.source.display()
+                ));
+            }
+        }
+    }
+
+    /// Drops a node from the loaded tree after it was moved or deleted on
+    /// disk, keeping navigation state pointing at surviving nodes.
+    pub fn remove_node_in_place(&mut self, id: NodeId) {
+        let Some(tree) = self.tree.as_mut() else {
+            return;
+        };
+        let parent = tree.node(id).parent;
+        let Some(remap) = tree.remove_subtree(id) else {
+            return;
+        };
+        self.apply_remap(&remap, parent);
+        self.rebuild_file_cache();
+    }
+
+    /// Translates navigation state after the tree's ids were renumbered,
+    /// moving the current directory to `fallback` (an old id) if it is gone.
+    /// The file list caches are left for the caller to rebuild.
+    fn apply_remap(&mut self, remap: &IdRemap, fallback: Option<NodeId>) {
+        self.cart.retain_mut(|id| match remap.get(*id) {
+            Some(new) => {
+                *id = new;
+                true
+            }
+            None => false,
+        });
+        // Candidate ids can't be translated reliably; propose again instead
+        self.advice = None;
+        let fallback = fallback.and_then(|f| remap.get(f));
+        self.current_dir = self.current_dir.and_then(|c| remap.get(c)).or(fallback);
+        self.selected = self.selected.and_then(|s| remap.get(s));
+        self.baseline = self.baseline.and_then(|b| remap.get(b));
+        self.pending_delete = self.pending_delete.and_then(|d| remap.get(d));
+        self.pending_properties = self.pending_properties.and_then(|p| remap.get(p));
+        self.pending_archive = self.pending_archive.take().and_then(|mut plan| {
+            plan.id = remap.get(plan.id)?;
+            Some(plan)
+        });
+    }
+
+    pub fn refresh_ext_stats(&mut self) {
+        let (Some(tree), Some(cur)) = (&self.tree, self.current_dir) else {
+            self.ext_stats = None;
+            return;
+        };
+        if self.ext_stats.as_ref().is_none_or(|(id, _)| *id != cur) {
+            self.ext_stats = Some((cur, extstats::ext_stats(tree, cur)));
+        }
+    }
+
+    pub fn rebuild_file_cache(&mut self) {
+        self.ext_stats = None;
+        self.heavy_dirs = None;
+        self.largest_files = self
+            .tree
+            .as_ref()
+            .map(|t| t.top_files(LARGEST_FILES))
+            .unwrap_or_default();
+        self.age_histograms = self
+            .tree
+            .as_ref()
+            .map(|t| age::age_histograms(t, std::time::SystemTime::now()))
+            .unwrap_or_default();
+        self.cloud_bytes = self
+            .tree
+            .as_ref()
+            .map(cloud::cloud_bytes)
+            .unwrap_or_default();
+        self.slowest_dirs = self
+            .tree
+            .as_ref()
+            .and_then(|t| t.meta.profile.as_ref())
+            .map(|p| p.slowest(SLOWEST_DIRS))
+            .unwrap_or_default();
+        self.file_nodes.clear();
+        if let Some(tree) = &self.tree {
+            for (idx, node) in tree.nodes.iter().enumerate() {
+                if node.kind.is_file() {
+                    self.file_nodes.push(NodeId(idx as u32));
+                }
+            }
+        }
+        self.refresh_size_history();
+        self.apply_search();
+    }
+
+    /// Finds the folders over the file count thresholds, and reads the
+    /// inode use and quota where the scan is, unless already done for the
+    /// current tree and thresholds.
+    pub fn refresh_file_counts(&mut self) {
+        let Some(tree) = &self.tree else {
+            self.heavy_dirs = None;
+            return;
+        };
+        match &self.heavy_dirs {
+            Some((thresholds, _)) if *thresholds == self.count_thresholds => return,
+            Some(_) => {}
+            None => {
+                let root = tree.path(tree.root);
+                self.inode_usage = inodes::filesystem_inodes(&root)
+                    .ok()
+                    .filter(|usage| usage.total > 0);
+                self.inode_quota = inodes::user_quota(&root).ok().flatten();
+            }
+        }
+        self.heavy_dirs = Some((
+            self.count_thresholds,
+            inodes::heavy_dirs(tree, self.count_thresholds),
+        ));
+    }
+
+    /// Recent sizes of a folder for its sparkline, oldest first; `None`
+    /// unless it was recorded in at least two scans.
+    pub fn size_history(&self, path: &Path) -> Option<&[u128]> {
+        self.size_history.get(path).map(Vec::as_slice)
+    }
+
+    /// Reloads the recent sizes of the folders below the loaded roots from
+    /// the scan history.
+    fn refresh_size_history(&mut self) {
+        self.size_history.clear();
+        let (Some(history), Some(tree)) = (&self.history, &self.tree) else {
+            return;
+        };
+        let root = tree.node(tree.root);
+        let roots: Vec<PathBuf> = if tree.is_combined() {
+            root.children.iter().map(|c| tree.path(*c)).collect()
+        } else {
+            vec![tree.path(tree.root)]
+        };
+        for root in roots {
+            // Without history the rows simply have no sparklines
+            for trend in history.trends(&root).unwrap_or_default() {
+                if trend.points.len() < 2 {
+                    continue;
+                }
+                let recent = trend.points.len().saturating_sub(SPARKLINE_SCANS);
+                let sizes = trend.points[recent..].iter().map(|p| p.bytes).collect();
+                self.size_history.insert(trend.path, sizes);
+            }
+        }
+    }
+
+    pub fn sort_file_lists(&mut self) {
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        let sort_ids = |ids: &mut Vec<NodeId>| match self.sort {
+            SortKey::Size => ids.sort_by_key(|&n| std::cmp::Reverse(tree.node(n).size)),
+            SortKey::Name => ids.sort_by(|a, b| tree.node(*a).name.cmp(&tree.node(*b).name)),
+            SortKey::Count => ids.sort_by_key(|&n| std::cmp::Reverse(tree.node(n).file_count)),
+        };
+        sort_ids(&mut self.file_nodes);
+        sort_ids(&mut self.filtered_file_nodes);
+    }
+
+    pub fn refresh_filtered_files(&mut self) {
+        if self.tree.is_some() {
+            if let Some(filter) = &self.search_filter {
+                self.filtered_file_nodes = self
+                    .file_nodes
+                    .iter()
+                    .copied()
+                    .filter(|id| filter.matches_node(*id))
+                    .collect();
+            } else {
+                self.filtered_file_nodes = self.file_nodes.clone();
+            }
+            self.sort_file_lists();
+        } else {
+            self.filtered_file_nodes.clear();
+        }
+        self.filtered_file_bytes = self.tree.as_ref().map_or(0, |tree| {
+            self.filtered_file_nodes
+                .iter()
+                .map(|id| tree.node(*id).size)
+                .sum()
+        });
+    }
+
+    pub fn apply_search(&mut self) {
+        if let Some(tree) = &self.tree {
+            let trimmed = self.search.trim();
+            if trimmed.is_empty() && self.search_tag.is_none() {
+                self.search_filter = None;
+            } else {
+                self.search_filter = Some(SearchFilter::build(
+                    trimmed,
+                    self.search_tag.as_deref(),
+                    tree,
+                    self.search_matching,
+                    &self.annotations,
+                ));
+            }
+            self.refresh_filtered_files();
+        } else {
+            self.search_filter = None;
+            self.filtered_file_nodes.clear();
+            self.filtered_file_bytes = 0;
+        }
+    }
+}
+
+/// Moves `path` to the trash, deleting it outright where no trash is
+/// available. `kind` is what the scan found there; mount points are
+/// refused.
+fn trash_or_remove(path: &std::path::Path, kind: &NodeKind) -> Result<(), String> {
+    if matches!(kind, NodeKind::MountPoint) {
+        return Err("it is a mount point of another filesystem".into());
+    }
+    let is_dir = matches!(kind, NodeKind::Dir);
+    trash::delete(path)
+        .map_err(|e| e.to_string())
+        .or_else(|_| ops::remove_entry(path, is_dir).map_err(|e| e.to_string()))
+}
+
+/// Runs this executable again elevated with `roots` as arguments, without
+/// waiting for it.
+#[cfg(windows)]
+fn relaunch_elevated(roots: &[PathBuf]) -> std::io::Result<()> {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+    let exe = std::env::current_exe()?;
+    let mut command = format!(
+        "Start-Process -FilePath {} -Verb RunAs",
+        quote(&exe.display().to_string())
+    );
+    if !roots.is_empty() {
+        // Start-Process joins the list with spaces, so each root carries
+        // its own double quotes
+        let args: Vec<String> = roots
+            .iter()
+            .map(|root| quote(&argv_quote(&root.display().to_string())))
+            .collect();
+        command.push_str(" -ArgumentList ");
+        command.push_str(&args.join(","));
+    }
+    let status = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-Command", &command])
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "the prompt was declined ({status})"
+        )))
+    }
+}
+
+/// `path` in double quotes as `CommandLineToArgvW` reads them back.
+/// Backslashes before the closing quote would escape it, so trailing ones
+/// are doubled: a drive root `C:\` becomes `"C:\\"`, not `"C:\"`, which
+/// reads as `C:"`. Paths can't hold quotes, so nothing else needs escaping.
+#[cfg(windows)]
+fn argv_quote(path: &str) -> String {
+    let trailing = path.len() - path.trim_end_matches('\\').len();
+    format!("\"{path}{}\"", "\\".repeat(trailing))
+}
+
+#[cfg(not(windows))]
+fn relaunch_elevated(_roots: &[PathBuf]) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "run TreeSize with sudo to read these folders",
+    ))
 }
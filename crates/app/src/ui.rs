@@ -1,20 +1,45 @@
-use chrono::{DateTime, Local};
+use chrono::Local;
 use eframe::egui::{
-    self, collapsing_header::CollapsingState, Align2, Color32, Id, Pos2, ScrollArea, Sense,
-    TextStyle, Ui,
+    self,
+    collapsing_header::{self, CollapsingState},
+    Align2, Color32, Id, Pos2, ScrollArea, Sense, TextStyle, Ui,
 };
-use std::path::PathBuf;
-use std::sync::atomic::Ordering;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::SystemTime;
-use treesize_core::human::human_bytes;
-use treesize_core::model::{NodeId, NodeKind, Tree, TreeNode};
+use treesize_core::advisor::Reason;
+use treesize_core::age::{AgeBucket, AgeHistogram};
+use treesize_core::annotations::Annotation;
+use treesize_core::diff::ChangeKind;
+use treesize_core::export::{self, ExportOptions};
+use treesize_core::extstats::ExtStat;
+use treesize_core::history::{self, Trend};
+use treesize_core::human::{
+    human_bytes, human_duration, human_percent, parse_bytes, system_locale, DateFormat,
+};
+use treesize_core::i18n::Language;
+use treesize_core::integration;
+use treesize_core::interference;
+use treesize_core::journal::Outcome;
+use treesize_core::model::{share, NodeId, NodeKind, ScanErrorKind, Tree, TreeNode};
 use treesize_core::scanner::ScanMsg;
+use treesize_core::search::NameMatching;
+use treesize_core::tuning::StorageKind;
+use treesize_core::volumes::Volume;
 
-use crate::state::{AppState, SearchFilter, SortKey, ViewTab};
+use crate::state::{
+    AppState, ArchiveMsg, ChartKind, ExportFormat, PinnedFolder, ReviewAction, SearchFilter,
+    SortKey, ViewTab, SHARED_VIEW_EXTENSION,
+};
 
 const GB_FACTOR: f64 = 1024.0 * 1024.0 * 1024.0;
 const MIN_SLICE_RATIO: f64 = 0.04;
 const MAX_PRIMARY_SLICES: usize = 6;
+/// Levels a sunburst shows around its centre.
+const SUNBURST_RINGS: usize = 4;
+/// Narrowest sunburst segment drawn, in radians.
+const MIN_SEGMENT_SWEEP: f32 = 0.01;
+const ERROR_COLOR: Color32 = Color32::from_rgb(220, 150, 40);
 
 #[derive(Default)]
 struct FolderTreeActions {
@@ -22,6 +47,11 @@ struct FolderTreeActions {
     open: Option<NodeId>,
     delete: Option<NodeId>,
     properties: Option<NodeId>,
+    archive: Option<NodeId>,
+    add_to_cart: Option<NodeId>,
+    baseline: Option<NodeId>,
+    list_contents: Option<NodeId>,
+    refresh: Option<NodeId>,
 }
 
 #[derive(Default)]
@@ -30,6 +60,16 @@ struct PieActions {
     open: Option<NodeId>,
     delete: Option<NodeId>,
     properties: Option<NodeId>,
+    archive: Option<NodeId>,
+    add_to_cart: Option<NodeId>,
+}
+
+#[derive(Default)]
+struct PinnedActions {
+    pin_current: bool,
+    pin_saved: Option<PathBuf>,
+    open: Option<NodeId>,
+    unpin: bool,
 }
 
 struct PieSlice {
@@ -38,18 +78,50 @@ struct PieSlice {
     kind: NodeKind,
     bytes: u128,
     ratio: f64,
+    /// Share of the folder the chart shows, from [`Tree::share_of_parent`].
+    share: f64,
     color: Color32,
     path: PathBuf,
+    /// `path` as it is shown; see [`AppState::relative_paths`].
+    shown_path: PathBuf,
     modified: Option<SystemTime>,
     file_count: u64,
 }
 
 pub fn draw(app: &mut AppState, ctx: &egui::Context) {
     poll_scan(app, ctx);
+    poll_archive(app);
+    app.poll_defender_exclusion();
+    if app.poll_refresh() {
+        ctx.request_repaint();
+    }
+    app.poll_listings();
+    while let Ok(rescan) = app.rescan_rx.try_recv() {
+        app.apply_rescan(rescan);
+    }
+    if app.apply_watch_events() {
+        ctx.request_repaint();
+    }
+    if std::mem::take(&mut app.unseen_alerts) {
+        ctx.send_viewport_cmd(egui::ViewportCommand::RequestUserAttention(
+            egui::UserAttentionType::Informational,
+        ));
+    }
+    if app.is_watching() {
+        // The watcher can't wake the UI itself, so check back periodically
+        ctx.request_repaint_after(std::time::Duration::from_secs(1));
+    }
+
+    if app.archive_rx.is_some() || app.rescans_pending > 0 {
+        ctx.request_repaint_after(std::time::Duration::from_millis(200));
+    }
 
     if app.scan_rx.is_some() {
         ctx.request_repaint();
     }
+    if app.is_refreshing() {
+        ctx.request_repaint_after(std::time::Duration::from_millis(200));
+    }
 
     egui::TopBottomPanel::top("top").show(ctx, |ui| {
         top_bar(ui, app);
@@ -60,21 +132,50 @@ pub fn draw(app: &mut AppState, ctx: &egui::Context) {
         .default_width(280.0)
         .show(ctx, |ui| {
             ui.heading("Folders");
-            if let Some(root) = &app.root {
-                ui.label(root.display().to_string());
-            } else {
+            if app.root.is_none() {
                 ui.label("Choose a folder to start");
             }
-            ui.separator();
-            if ui.button("Up").clicked() {
-                app.navigate_up();
+            for root in app.scan_roots() {
+                ui.label(root.display().to_string());
             }
             ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Up").clicked() {
+                    app.navigate_up();
+                }
+                if let Some(tree) = &app.tree {
+                    if ui
+                        .button("Collapse all")
+                        .on_hover_text("Closes every folder in the tree below the root")
+                        .clicked()
+                    {
+                        let root = tree.node(tree.root);
+                        for &child in &root.children {
+                            collapse_branch(ui.ctx(), tree, child);
+                        }
+                    }
+                }
+            });
+            ui.separator();
             ui.horizontal(|ui| {
                 ui.selectable_value(&mut app.view_tab, ViewTab::Tree, "Tree View");
                 ui.selectable_value(&mut app.view_tab, ViewTab::Files, "File View");
             });
             ui.separator();
+            if app.view_tab == ViewTab::Tree && app.tree.is_some() {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut app.hide_small_folders, "Only folders ≥");
+                    ui.add_enabled(
+                        app.hide_small_folders,
+                        egui::DragValue::new(&mut app.min_folder_percent)
+                            .range(0.01..=50.0)
+                            .speed(0.1)
+                            .suffix("% of root"),
+                    );
+                })
+                .response
+                .on_hover_text("Keeps the tree of a huge volume short; ignored while searching");
+            }
             if let Some(tree) = app.tree.as_ref() {
                 match app.view_tab {
                     ViewTab::Tree => {
@@ -93,9 +194,137 @@ pub fn draw(app: &mut AppState, ctx: &egui::Context) {
         });
 
     egui::CentralPanel::default().show(ctx, |ui| {
+        if app.tree.is_none() && app.scan_rx.is_none() {
+            if app.has_overview() {
+                draw_computer_overview(ui, app);
+            } else {
+                draw_volume_picker(ui, app);
+            }
+            return;
+        }
         ui.heading("Overview");
         ui.separator();
+        if let Some(at) = app.reused_at {
+            let mut rescan = false;
+            ui.horizontal(|ui| {
+                ui.colored_label(
+                    ERROR_COLOR,
+                    format!("Data from {}", format_age(Local::now() - at)),
+                );
+                rescan = ui.button("Rescan").clicked();
+            });
+            if rescan {
+                app.rescan();
+            }
+        }
+        if let Some(hint) = &app.interference {
+            let mut exclude = false;
+            let mut dismiss = false;
+            ui.colored_label(ERROR_COLOR, hint.message());
+            ui.horizontal(|ui| {
+                if cfg!(windows) {
+                    exclude = ui
+                        .button("Exclude from Defender...")
+                        .on_hover_text("Needs administrator approval; policy may not allow it")
+                        .clicked();
+                }
+                dismiss = ui.small_button("Dismiss").clicked();
+            });
+            if exclude {
+                app.request_defender_exclusion();
+            }
+            if dismiss {
+                app.interference = None;
+            }
+        }
+        if let Some(tree) = &app.tree {
+            if let (Some(estimate), Some(margin)) =
+                (&tree.meta.estimate, tree.estimate_margin(tree.root))
+            {
+                ui.colored_label(
+                    Color32::from_rgb(200, 120, 40),
+                    format!(
+                        "Estimate: sizes extrapolated from a {:.1}% sample of files, \
+                         total ± {} at 95% confidence",
+                        estimate.fraction * 100.0,
+                        human_bytes(margin)
+                    ),
+                )
+                .on_hover_text(format!(
+                    "{} files were sized; file sizes are exact, folder sizes estimated",
+                    estimate.sampled
+                ));
+            }
+        }
+        let denied: Vec<PathBuf> = app
+            .tree
+            .as_ref()
+            .map(|tree| tree.access_denied().map(|e| e.path.clone()).collect())
+            .unwrap_or_default();
+        if !denied.is_empty() {
+            ui.colored_label(
+                ERROR_COLOR,
+                format!(
+                    "{} folders could not be read (access denied); their contents are not counted",
+                    denied.len()
+                ),
+            );
+            egui::CollapsingHeader::new("Show folders")
+                .id_source("access_denied")
+                .show(ui, |ui| {
+                    for path in &denied {
+                        ui.label(app.shown_path(path).display().to_string());
+                    }
+                });
+            if cfg!(windows) {
+                if ui
+                    .button("Retry as administrator...")
+                    .on_hover_text("Scans again in a new window with administrator rights")
+                    .clicked()
+                {
+                    app.retry_elevated();
+                }
+            } else {
+                ui.label("Run TreeSize with sudo to include them.");
+            }
+        }
+        if let Some(summary) = &app.refresh_summary {
+            let text = if summary.is_empty() {
+                "Refreshed: no changes".to_string()
+            } else {
+                format!(
+                    "Refreshed: {} added, {} removed, {} changed ({} → {})",
+                    summary.added,
+                    summary.removed,
+                    summary.changed,
+                    human_bytes(summary.bytes_before),
+                    human_bytes(summary.bytes_after)
+                )
+            };
+            let reviewable = app.review.as_ref().is_some_and(|r| !r.changes.is_empty());
+            let mut dismiss = false;
+            let mut review = false;
+            ui.horizontal(|ui| {
+                ui.label(text);
+                if reviewable {
+                    review = ui
+                        .small_button("Review changes")
+                        .on_hover_text("Step through the largest new files and growth")
+                        .clicked();
+                }
+                dismiss = ui.small_button("✕").clicked();
+            });
+            if review {
+                app.start_review();
+            }
+            if dismiss {
+                app.refresh_summary = None;
+            }
+        }
         ui.label(format!("Files: {}", app.progress_files));
+        if let Some(tree) = &app.tree {
+            ui.label(format!("Storage: {}", tree.meta.storage.label()));
+        }
         ui.label(format!("Bytes: {}", human_bytes(app.progress_bytes)));
         let progress = if app.progress_discovered > 0 {
             (app.progress_files as f32 / app.progress_discovered as f32).clamp(0.0, 1.0)
@@ -103,72 +332,235 @@ pub fn draw(app: &mut AppState, ctx: &egui::Context) {
             0.0
         };
         let progress_label = if app.scan_rx.is_some() {
-            "Scanning..."
+            let mut label = format!("Scanning... {}/s", human_bytes(app.progress_rate as u128));
+            if let Some(eta) = app.progress_eta.filter(|eta| eta.as_secs() > 0) {
+                label += &format!(", ~{} remaining", human_duration(eta));
+            }
+            label
+        } else if app.scan_cancelled {
+            "Scan cancelled; sizes are incomplete".to_string()
         } else {
-            "Scan complete"
+            "Scan complete".to_string()
         };
         ui.add(
             egui::ProgressBar::new(progress)
                 .show_percentage()
                 .text(progress_label),
         );
+        if let Some(path) = app.progress_path.as_ref().filter(|_| app.scan_rx.is_some()) {
+            ui.add(egui::Label::new(format!("Scanning {}", path.display())).truncate())
+                .on_hover_text(path.display().to_string());
+        }
+        if let Some(status) = &app.operation_status {
+            ui.label(status);
+        }
+        if let Some(tree) = app.tree.as_ref().filter(|t| !t.errors.is_empty()) {
+            let vanished = tree
+                .errors
+                .iter()
+                .filter(|e| e.kind == ScanErrorKind::Vanished)
+                .count();
+            let unreadable = tree.errors.len() - vanished;
+            let summary = match (unreadable, vanished) {
+                (0, vanished) => egui::RichText::new(format!(
+                    "{vanished} path(s) were deleted or renamed while scanning"
+                )),
+                (unreadable, 0) => egui::RichText::new(format!(
+                    "⚠ {unreadable} path(s) could not be read; sizes may be incomplete"
+                ))
+                .color(ERROR_COLOR),
+                (unreadable, vanished) => egui::RichText::new(format!(
+                    "⚠ {unreadable} path(s) could not be read and {vanished} vanished \
+                     while scanning; sizes may be incomplete"
+                ))
+                .color(ERROR_COLOR),
+            };
+            egui::CollapsingHeader::new(summary)
+                .id_source("scan_errors")
+                .show(ui, |ui| {
+                    ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+                        for error in &tree.errors {
+                            let path = app.shown_path(&error.path);
+                            ui.label(format!("{}: {}", path.display(), error.kind.label()))
+                                .on_hover_text(&error.message);
+                        }
+                    });
+                });
+        }
+        if let Some(p) = &app.archive_progress {
+            let fraction = if p.bytes_total > 0 {
+                p.bytes_done as f32 / p.bytes_total as f32
+            } else {
+                0.0
+            };
+            ui.add(egui::ProgressBar::new(fraction).text(format!(
+                "{} / {} files, {} / {}",
+                p.files_done,
+                p.files_total,
+                human_bytes(p.bytes_done),
+                human_bytes(p.bytes_total)
+            )));
+            ui.small(p.current.display().to_string());
+        }
 
         ui.separator();
 
         if app.current_dir.is_none() {
-            if let Some(root_id) = app.tree.as_ref().map(|t| t.root) {
-                app.current_dir = Some(root_id);
-            }
+            app.set_current_root();
         }
 
+        app.refresh_ext_stats();
+        let mut pie_actions = None;
+        let mut pinned_actions = PinnedActions::default();
+        let mut largest_clicked = None;
         if let Some(tree) = &app.tree {
             if let Some(cur) = app.current_dir {
-                let node = &tree.nodes[cur.0 as usize];
+                let node = tree.node(cur);
                 ui.horizontal(|ui| {
                     ui.strong("Dir:");
-                    ui.label(node.path.display().to_string());
+                    ui.label(app.shown_path(&tree.path(cur)).display().to_string());
+                    pinned_actions.pin_current = ui
+                        .small_button("Pin")
+                        .on_hover_text(
+                            "Keep this folder beside the view to compare another with it",
+                        )
+                        .clicked();
+                    if ui
+                        .small_button("Pin Saved Scan...")
+                        .on_hover_text("Compare this folder with how it looked in a saved scan")
+                        .clicked()
+                    {
+                        pinned_actions.pin_saved = rfd::FileDialog::new()
+                            .add_filter("Saved scan", &["tsz", "json"])
+                            .pick_file();
+                    }
                 });
 
                 let mut children = node.children.clone();
                 if let Some(filter) = app.search_filter.as_ref() {
-                    children.retain(|cid| {
-                        let child_node = &tree.nodes[cid.0 as usize];
-                        match child_node.kind {
-                            NodeKind::Dir => filter.matches_subtree(*cid),
-                            NodeKind::File => filter.matches_node(*cid),
-                        }
-                    });
+                    children.retain(|cid| filter.shows(tree, *cid));
                 }
 
                 match app.sort {
-                    SortKey::Size => children.sort_by(|a, b| {
-                        tree.nodes[b.0 as usize]
-                            .size
-                            .cmp(&tree.nodes[a.0 as usize].size)
-                    }),
-                    SortKey::Name => children.sort_by(|a, b| {
-                        tree.nodes[a.0 as usize]
-                            .name
-                            .cmp(&tree.nodes[b.0 as usize].name)
-                    }),
-                    SortKey::Count => children.sort_by(|a, b| {
-                        tree.nodes[b.0 as usize]
-                            .file_count
-                            .cmp(&tree.nodes[a.0 as usize].file_count)
-                    }),
+                    SortKey::Size => {
+                        children.sort_by_key(|&c| std::cmp::Reverse(tree.node(c).size))
+                    }
+                    SortKey::Name => {
+                        children.sort_by(|a, b| tree.node(*a).name.cmp(&tree.node(*b).name))
+                    }
+                    SortKey::Count => {
+                        children.sort_by_key(|&c| std::cmp::Reverse(tree.node(c).file_count))
+                    }
                 }
 
-                let slices = collect_pie_slices(tree, &children);
-                if slices.is_empty() {
-                    ui.label("Nothing to display for this folder yet.");
+                let ages = app.age_overlay.then_some(app.age_histograms.as_slice());
+                let slices = collect_pie_slices(tree, &children, ages, app.relative_paths);
+                let segments = match app.chart {
+                    ChartKind::Pie => Vec::new(),
+                    ChartKind::Sunburst => collect_sunburst(
+                        tree,
+                        cur,
+                        &children,
+                        ages,
+                        app.search_filter.as_ref(),
+                        app.relative_paths,
+                    ),
+                };
+                let draw_current = |ui: &mut Ui| {
+                    if slices.is_empty() {
+                        ui.label("Nothing to display for this folder yet.");
+                        None
+                    } else if app.chart == ChartKind::Sunburst {
+                        Some(draw_sunburst(
+                            ui,
+                            tree,
+                            cur,
+                            &segments,
+                            app.selected,
+                            &app.date_format,
+                            app.percent_baseline(),
+                        ))
+                    } else {
+                        Some(draw_pie_chart(
+                            ui,
+                            &slices,
+                            app.selected,
+                            app.current_dir,
+                            &app.date_format,
+                            app.percent_baseline(),
+                            true,
+                        ))
+                    }
+                };
+                if let Some(pinned) = &app.pinned {
+                    ui.columns(2, |columns| {
+                        columns[0].label(format!("{} · {}", node.name, human_bytes(node.size)));
+                        pie_actions = draw_current(&mut columns[0]);
+                        draw_pinned_folder(
+                            &mut columns[1],
+                            pinned,
+                            &app.date_format,
+                            app.relative_paths,
+                            &mut pinned_actions,
+                        );
+                    });
+                    draw_pinned_comparison(ui, tree, cur, pinned);
                 } else {
-                    let actions = draw_pie_chart(ui, &slices, app.selected, app.current_dir);
-                    apply_pie_actions(app, actions);
+                    pie_actions = draw_current(ui);
                 }
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut app.chart, ChartKind::Pie, "Pie");
+                    ui.selectable_value(&mut app.chart, ChartKind::Sunburst, "Sunburst")
+                        .on_hover_text(
+                            "Rings for several levels at once; click a ring to zoom in, \
+                             the centre to zoom out",
+                        );
+                    ui.separator();
+                    ui.checkbox(&mut app.age_overlay, "Age")
+                        .on_hover_text("Colour by how long ago files were last modified");
+                    if app.age_overlay {
+                        for bucket in AgeBucket::ALL {
+                            let (rect, _) =
+                                ui.allocate_exact_size(egui::vec2(12.0, 12.0), Sense::hover());
+                            ui.painter().rect_filled(rect, 2.0, age_color(bucket));
+                            ui.label(bucket.label());
+                        }
+                    }
+                });
             } else {
                 ui.label("No folder selected.");
             }
 
+            if let Some((_, stats)) = &app.ext_stats {
+                let base = app.percent_baseline().map(|b| b.size);
+                egui::CollapsingHeader::new("By file type")
+                    .id_source("ext_stats")
+                    .show(ui, |ui| draw_ext_stats(ui, stats, base));
+            }
+            egui::CollapsingHeader::new("Largest files")
+                .id_source("largest_files")
+                .show(ui, |ui| {
+                    ScrollArea::vertical()
+                        .id_source("largest_files_scroll")
+                        .max_height(240.0)
+                        .show(ui, |ui| {
+                            for &id in &app.largest_files {
+                                let node = tree.node(id);
+                                let label = format!(
+                                    "{:>10}  {}",
+                                    human_bytes(node.size),
+                                    app.shown_path(&tree.path(id)).display()
+                                );
+                                if ui
+                                    .selectable_label(app.selected == Some(id), label)
+                                    .clicked()
+                                {
+                                    largest_clicked = Some(id);
+                                }
+                            }
+                        });
+                });
+
             ui.separator();
             ui.horizontal(|ui| {
                 ui.label("Export as:");
@@ -177,21 +569,39 @@ pub fn draw(app: &mut AppState, ctx: &egui::Context) {
                         ExportFormat::Csv => "CSV",
                         ExportFormat::Json => "JSON",
                         ExportFormat::Pdf => "PDF",
+                        ExportFormat::D3 => "D3 JSON",
+                        ExportFormat::Folded => "Flame graph",
                     })
                     .show_ui(ui, |ui| {
                         ui.selectable_value(&mut app.export_format, ExportFormat::Csv, "CSV");
                         ui.selectable_value(&mut app.export_format, ExportFormat::Json, "JSON");
                         ui.selectable_value(&mut app.export_format, ExportFormat::Pdf, "PDF");
+                        ui.selectable_value(&mut app.export_format, ExportFormat::D3, "D3 JSON")
+                            .on_hover_text("Nested name/value/children JSON for d3 charts");
+                        ui.selectable_value(
+                            &mut app.export_format,
+                            ExportFormat::Folded,
+                            "Flame graph",
+                        )
+                        .on_hover_text("Folded stacks for flamegraph.pl or inferno");
                     });
                 if ui.button("Export").clicked() {
                     if let Some(path) = rfd::FileDialog::new()
                         .set_file_name(export_default_name(&app.root, app.export_format))
                         .save_file()
                     {
+                        let options = ExportOptions {
+                            date_format: app.date_format.clone(),
+                            locale: app.export_locale,
+                            relative_paths: app.relative_paths,
+                            annotations: app.annotations.clone(),
+                        };
                         let result = match app.export_format {
-                            ExportFormat::Csv => treesize_core::export::export_csv(tree, &path),
-                            ExportFormat::Json => treesize_core::export::export_json(tree, &path),
-                            ExportFormat::Pdf => treesize_core::export::export_pdf(tree, &path),
+                            ExportFormat::Csv => export::export_csv(tree, &path, &options),
+                            ExportFormat::Json => export::export_json(tree, &path, &options),
+                            ExportFormat::Pdf => export::export_pdf(tree, &path, &options),
+                            ExportFormat::D3 => export::export_hierarchy_json(tree, &path),
+                            ExportFormat::Folded => export::export_folded(tree, &path),
                         };
                         app.export_status = Some(match result {
                             Ok(_) => format!("Exported to {}", path.display()),
@@ -200,16 +610,79 @@ pub fn draw(app: &mut AppState, ctx: &egui::Context) {
                     }
                 }
             });
+            let mut save_to = None;
+            if ui
+                .button("Save Scan...")
+                .on_hover_text("Save this scan to reopen later without rescanning")
+                .clicked()
+            {
+                save_to = rfd::FileDialog::new()
+                    .add_filter("Saved scan", &["tsz"])
+                    .set_file_name(snapshot_default_name(&app.root))
+                    .save_file();
+            }
+            if let Some(path) = save_to {
+                app.save_scan(&path);
+            }
+            if ui
+                .button("Copy CLI Command")
+                .on_hover_text(
+                    "Copy a treesize-cli command that runs this scan and opens it here, \
+                     or saves it to open later when run over SSH",
+                )
+                .clicked()
+            {
+                if let Some(command) = app.cli_command() {
+                    ui.ctx().copy_text(command);
+                    app.export_status = Some("Copied the command to the clipboard".to_string());
+                }
+            }
+            let mut share_to = None;
+            if ui
+                .button("Share View...")
+                .on_hover_text(
+                    "Save this scan with the folder, selection and search shown now, \
+                     so someone else can open exactly this view",
+                )
+                .clicked()
+            {
+                share_to = rfd::FileDialog::new()
+                    .add_filter("Shared view", &[SHARED_VIEW_EXTENSION])
+                    .set_file_name(format!("view.{SHARED_VIEW_EXTENSION}"))
+                    .save_file();
+            }
+            if let Some(path) = share_to {
+                app.share_view(&path);
+            }
             if let Some(status) = &app.export_status {
                 ui.label(status);
             }
         } else {
             ui.label("Scan a directory to see details.");
         }
+        if let Some(actions) = pie_actions {
+            apply_pie_actions(app, actions);
+        }
+        apply_pinned_actions(app, pinned_actions);
+        if let Some(id) = largest_clicked {
+            app.selected = Some(id);
+            app.request_properties(id);
+        }
     });
 
     show_delete_confirmation(ctx, app);
+    show_archive_plan(ctx, app);
     show_properties_panel(ctx, app);
+    show_settings_window(ctx, app);
+    show_advisor_window(ctx, app);
+    show_cart_window(ctx, app);
+    show_compare_window(ctx, app);
+    show_review_window(ctx, app);
+    show_timing_window(ctx, app);
+    show_history_window(ctx, app);
+    show_file_counts_window(ctx, app);
+    show_activity_window(ctx, app);
+    show_alerts_window(ctx, app);
 }
 
 fn top_bar(ui: &mut Ui, app: &mut AppState) {
@@ -219,14 +692,60 @@ fn top_bar(ui: &mut Ui, app: &mut AppState) {
     ui.horizontal(|ui| {
         if ui.button("Choose Folder").clicked() {
             if let Some(path) = rfd::FileDialog::new().pick_folder() {
-                app.start_scan(path);
+                app.open_root(path);
+            }
+        }
+        if ui
+            .add_enabled(app.root.is_some(), egui::Button::new("Add Folder..."))
+            .on_hover_text("Scan another folder into the same tree")
+            .clicked()
+        {
+            if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                app.add_root(path);
+            }
+        }
+        if ui.button("Open Scan...").clicked() {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("Saved scan", &["tsz", "json", SHARED_VIEW_EXTENSION])
+                .pick_file()
+            {
+                app.open_saved_scan(&path);
+            }
+        }
+        if ui
+            .add_enabled(app.tree.is_some(), egui::Button::new("Compare..."))
+            .on_hover_text("Compare with an earlier saved scan or JSON export")
+            .clicked()
+        {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("Saved scan", &["tsz", "json"])
+                .pick_file()
+            {
+                app.compare_with(path);
             }
         }
-        if ui.button("Cancel").clicked() {
-            app.cancel_scan();
+        if ui
+            .add_enabled(
+                app.tree.is_some() && app.scan_rx.is_none(),
+                egui::Button::new("Computer"),
+            )
+            .on_hover_text("All drives and recent scans at a glance")
+            .clicked()
+        {
+            app.go_home();
+        }
+        if app.scan_rx.is_some() {
+            if ui
+                .button("Cancel")
+                .on_hover_text("Stop scanning and show what was found so far")
+                .clicked()
+            {
+                app.cancel_scan();
+            }
+        } else if ui.button("Cancel").clicked() {
             app.reset_to_initial();
         }
-        let paused_now = app.paused.load(Ordering::Relaxed);
+        let paused_now = app.is_paused();
         let toggle_label = if paused_now { "Resume" } else { "Pause" };
         if ui
             .button(toggle_label)
@@ -235,6 +754,57 @@ fn top_bar(ui: &mut Ui, app: &mut AppState) {
         {
             app.pause_or_resume();
         }
+        if app.is_refreshing() {
+            ui.spinner();
+            if ui.button("Stop Refresh").clicked() {
+                app.cancel_refresh();
+            }
+        } else if ui
+            .add_enabled(
+                app.tree.is_some() && app.scan_rx.is_none(),
+                egui::Button::new("Refresh"),
+            )
+            .on_hover_text("Rescan in the background and swap in the result")
+            .clicked()
+        {
+            app.start_background_refresh();
+        }
+        if ui.button("Settings").clicked() {
+            app.show_settings = !app.show_settings;
+        }
+        if ui.button("Activity").clicked() {
+            app.toggle_activity();
+        }
+        if ui
+            .add_enabled(
+                !app.slowest_dirs.is_empty(),
+                egui::Button::new("Scan Timing"),
+            )
+            .on_hover_text("Which directories took longest to scan")
+            .clicked()
+        {
+            app.show_timing = !app.show_timing;
+        }
+        if ui
+            .add_enabled(app.has_history(), egui::Button::new("History"))
+            .on_hover_text("How this folder and those in it grew over past scans")
+            .clicked()
+        {
+            app.show_history = !app.show_history;
+        }
+        if ui
+            .add_enabled(app.tree.is_some(), egui::Button::new("File Counts"))
+            .on_hover_text("Folders holding the most files, and how many inodes are left")
+            .clicked()
+        {
+            app.show_file_counts = !app.show_file_counts;
+        }
+        if ui.button("Free Space...").clicked() {
+            app.show_advisor = !app.show_advisor;
+        }
+        if ui.button(format!("Cart ({})", app.cart.len())).clicked() {
+            app.show_cart = !app.show_cart;
+        }
         ui.separator();
         ui.label("Sort by:");
         egui::ComboBox::from_label("")
@@ -244,20 +814,23 @@ fn top_bar(ui: &mut Ui, app: &mut AppState) {
                 SortKey::Count => "Files",
             })
             .show_ui(ui, |ui| {
-                if ui
-                    .selectable_value(&mut app.sort, SortKey::Size, "Size")
-                    .clicked()
-                {}
-                if ui
-                    .selectable_value(&mut app.sort, SortKey::Name, "Name")
-                    .clicked()
-                {}
-                if ui
-                    .selectable_value(&mut app.sort, SortKey::Count, "Files")
-                    .clicked()
-                {}
+                ui.selectable_value(&mut app.sort, SortKey::Size, "Size");
+                ui.selectable_value(&mut app.sort, SortKey::Name, "Name");
+                ui.selectable_value(&mut app.sort, SortKey::Count, "Files");
             });
         ui.separator();
+        let baseline = app.percent_baseline().map(|b| b.name.clone());
+        ui.add_enabled(
+            app.tree.is_some(),
+            egui::Checkbox::new(&mut app.percent_of_baseline, "% of baseline"),
+        )
+        .on_hover_text(match baseline {
+            Some(name) => format!("Percentages are shares of {name}"),
+            None => "Show percentages as shares of the root, or of a folder chosen with \
+                     \"Use as % Baseline\", instead of the folder they are in"
+                .to_string(),
+        });
+        ui.separator();
         ui.label("Search:");
         let resp = ui.text_edit_singleline(&mut app.search);
         if ui.button("Search").clicked() {
@@ -266,6 +839,21 @@ fn top_bar(ui: &mut Ui, app: &mut AppState) {
         if resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
             trigger_search = true;
         }
+        let labels = app.annotations.labels();
+        if !labels.is_empty() || app.search_tag.is_some() {
+            let previous_tag = app.search_tag.clone();
+            egui::ComboBox::from_id_source("search_tag")
+                .selected_text(app.search_tag.as_deref().unwrap_or("Any tag"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut app.search_tag, None, "Any tag");
+                    for label in labels {
+                        ui.selectable_value(&mut app.search_tag, Some(label.to_string()), label);
+                    }
+                })
+                .response
+                .on_hover_text("Search only what is tagged this");
+            trigger_search |= app.search_tag != previous_tag;
+        }
     });
 
     if previous_sort != app.sort {
@@ -278,6 +866,193 @@ fn top_bar(ui: &mut Ui, app: &mut AppState) {
     }
 }
 
+fn format_age(age: chrono::Duration) -> String {
+    match age.num_minutes() {
+        m if m < 1 => "less than a minute ago".to_string(),
+        1 => "1 minute ago".to_string(),
+        m if m < 120 => format!("{m} minutes ago"),
+        _ => format!("{} hours ago", age.num_hours()),
+    }
+}
+
+/// Start screen: one usage gauge per drive, each a click away from a scan.
+fn draw_volume_picker(ui: &mut Ui, app: &mut AppState) {
+    ui.horizontal(|ui| {
+        ui.heading("Select a drive");
+        if ui
+            .small_button("⟳")
+            .on_hover_text("Reload drives")
+            .clicked()
+        {
+            app.refresh_volumes();
+        }
+    });
+    ui.separator();
+    if let Some(status) = &app.operation_status {
+        ui.label(status);
+    }
+    if app.volumes.is_empty() {
+        ui.label("No drives found. Use Choose Folder to pick a folder instead.");
+        return;
+    }
+    let mut chosen = None;
+    ScrollArea::vertical().show(ui, |ui| {
+        chosen = volume_rows(ui, &app.volumes);
+    });
+    if let Some(path) = chosen {
+        app.open_root(path);
+    }
+}
+
+/// One usage gauge per drive; returns the mount point clicked.
+fn volume_rows(ui: &mut Ui, volumes: &[Volume]) -> Option<PathBuf> {
+    let mut chosen = None;
+    egui::Grid::new("volumes")
+        .num_columns(3)
+        .spacing([12.0, 8.0])
+        .striped(true)
+        .show(ui, |ui| {
+            for volume in volumes {
+                let title = volume.mount_point.display().to_string();
+                let response = ui
+                    .button(&title)
+                    .on_hover_text(format!("{} ({})", volume.name, volume.fs_type));
+                if response.clicked() {
+                    chosen = Some(volume.mount_point.clone());
+                }
+                ui.add(
+                    egui::ProgressBar::new(volume.used_fraction())
+                        .desired_width(240.0)
+                        .text(format!("{:.0}% used", volume.used_fraction() * 100.0)),
+                );
+                ui.label(format!(
+                    "{} free of {}",
+                    human_bytes(volume.free_bytes),
+                    human_bytes(volume.total_bytes)
+                ));
+                ui.end_row();
+            }
+        });
+    chosen
+}
+
+/// Start screen once several scans exist: drives, the scans themselves,
+/// the largest folders across them and what could be freed.
+fn draw_computer_overview(ui: &mut Ui, app: &mut AppState) {
+    ui.horizontal(|ui| {
+        ui.heading("Computer");
+        if ui
+            .small_button("⟳")
+            .on_hover_text("Reload drives")
+            .clicked()
+        {
+            app.refresh_volumes();
+        }
+    });
+    ui.separator();
+    if let Some(status) = &app.operation_status {
+        ui.label(status);
+    }
+    let mut chosen = None;
+    let mut reveal = None;
+    let overview = app.overview().clone();
+    ScrollArea::vertical().show(ui, |ui| {
+        ui.strong("Drives");
+        chosen = volume_rows(ui, &app.volumes);
+
+        ui.separator();
+        ui.strong("Scans");
+        egui::Grid::new("overview_scans")
+            .num_columns(5)
+            .spacing([12.0, 6.0])
+            .striped(true)
+            .show(ui, |ui| {
+                for scan in &overview.scans {
+                    if ui.button(scan.root.display().to_string()).clicked() {
+                        chosen = Some(scan.root.clone());
+                    }
+                    let label = ui.label(format!(
+                        "{} in {} files",
+                        human_bytes(scan.bytes),
+                        scan.files
+                    ));
+                    if let Some(volume) = &scan.volume {
+                        label.on_hover_text(format!(
+                            "{:.0}% of {}",
+                            scan.bytes as f64 / volume.total_bytes.max(1) as f64 * 100.0,
+                            volume.mount_point.display()
+                        ));
+                    }
+                    ui.label(
+                        scan.scanned_at
+                            .map(|at| format!("scanned {}", format_age(Local::now() - at)))
+                            .unwrap_or_default(),
+                    );
+                    ui.label(match app.growth(&scan.root) {
+                        Some((delta, over)) if delta >= 0 => {
+                            format!("+{} since {}", human_bytes(delta as u128), format_age(over))
+                        }
+                        Some((delta, over)) => {
+                            format!(
+                                "-{} since {}",
+                                human_bytes(delta.unsigned_abs()),
+                                format_age(over)
+                            )
+                        }
+                        None => String::new(),
+                    })
+                    .on_hover_text("Change since the scan before");
+                    ui.label(format!("{} reclaimable", human_bytes(scan.reclaimable)));
+                    ui.end_row();
+                }
+            });
+        ui.label(format!(
+            "About {} could be freed in total: caches, duplicates and old large files",
+            human_bytes(overview.reclaimable)
+        ));
+
+        ui.separator();
+        ui.strong("Biggest folders");
+        egui::Grid::new("overview_folders")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for folder in &overview.biggest {
+                    ui.label(human_bytes(folder.bytes));
+                    if ui.link(folder.path.display().to_string()).clicked() {
+                        reveal = Some(folder.path.clone());
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+    if let Some(path) = reveal {
+        let root = overview
+            .scans
+            .iter()
+            .map(|s| &s.root)
+            .find(|root| path.starts_with(root))
+            .cloned();
+        if let Some(root) = root {
+            app.open_root(root);
+            if let Some(id) = app.tree.as_ref().and_then(|t| t.find(&path)) {
+                app.current_dir = Some(id);
+            }
+        }
+    } else if let Some(path) = chosen {
+        app.open_root(path);
+    }
+}
+
+fn snapshot_default_name(root: &Option<PathBuf>) -> String {
+    let base = root
+        .as_ref()
+        .and_then(|p| p.file_name().map(|s| s.to_string_lossy().to_string()))
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "treesize_scan".to_string());
+    format!("{}_{}.tsz", base, Local::now().format("%Y%m%d_%H%M%S"))
+}
+
 fn export_default_name(root: &Option<PathBuf>, format: ExportFormat) -> String {
     let base = root
         .as_ref()
@@ -289,6 +1064,8 @@ fn export_default_name(root: &Option<PathBuf>, format: ExportFormat) -> String {
         ExportFormat::Csv => "csv",
         ExportFormat::Json => "json",
         ExportFormat::Pdf => "pdf",
+        ExportFormat::D3 => "d3.json",
+        ExportFormat::Folded => "folded",
     };
     format!("{}_{}.{}", base, timestamp, ext)
 }
@@ -306,21 +1083,45 @@ fn poll_scan(app: &mut AppState, ctx: &egui::Context) {
                 scanned,
                 discovered,
                 bytes,
+                bytes_per_sec,
+                eta,
+                current,
+                ..
             } => {
                 app.progress_files = scanned;
                 app.progress_discovered = discovered;
                 app.progress_bytes = bytes;
+                app.progress_rate = bytes_per_sec;
+                app.progress_eta = eta;
+                app.progress_path = current;
             }
             ScanMsg::File { .. } => {}
             ScanMsg::DirDone { .. } => {}
+            // Ids are stable across partial trees, so selection and the
+            // current folder carry over
+            ScanMsg::PartialTree(tree) => app.show_partial_tree(Arc::unwrap_or_clone(tree)),
             ScanMsg::Done(tree) => {
-                app.tree = Some(tree);
+                app.interference = interference::detect(&tree);
+                app.tree = Some(Arc::unwrap_or_clone(tree));
+                app.rebuild_file_cache();
+                app.record_scan();
+                app.export_status = None;
+                app.start_watching();
+                finished = true;
+                break;
+            }
+            // Not watched: changes in folders never walked can't be applied
+            ScanMsg::Cancelled(tree) => {
+                app.tree = Some(Arc::unwrap_or_clone(tree));
+                app.scan_cancelled = true;
+                app.progress_path = None;
                 app.rebuild_file_cache();
                 app.export_status = None;
                 finished = true;
                 break;
             }
             ScanMsg::Error(_e) => {}
+            _ => {}
         }
     }
     if !finished {
@@ -331,6 +1132,54 @@ fn poll_scan(app: &mut AppState, ctx: &egui::Context) {
     }
 }
 
+fn poll_archive(app: &mut AppState) {
+    let Some(rx) = app.archive_rx.as_ref() else {
+        return;
+    };
+    let mut done = None;
+    while let Ok(msg) = rx.try_recv() {
+        match msg {
+            ArchiveMsg::Progress(progress) => app.archive_progress = Some(progress),
+            ArchiveMsg::Done(outcome) => {
+                done = Some(outcome);
+                break;
+            }
+        }
+    }
+    if let Some(outcome) = done {
+        app.archive_rx = None;
+        app.finish_archive(outcome);
+    }
+}
+
+/// One line of the folder tree as laid out for the current frame.
+enum FolderRow {
+    Dir {
+        id: NodeId,
+        depth: usize,
+        /// Whether it has children to expand into.
+        expandable: bool,
+    },
+    File {
+        id: NodeId,
+        depth: usize,
+    },
+    /// Folders under `parent` left out for being below the size threshold.
+    Hidden {
+        parent: NodeId,
+        depth: usize,
+        count: usize,
+    },
+}
+
+/// What decides which rows of the folder tree are shown.
+struct FolderView<'a> {
+    sort: SortKey,
+    search: Option<&'a SearchFilter>,
+    /// Folders smaller than this are hidden until revealed; 0 for none.
+    min_bytes: u128,
+}
+
 fn draw_folder_tree(
     ui: &mut Ui,
     app: &AppState,
@@ -338,136 +1187,384 @@ fn draw_folder_tree(
     filter: Option<&SearchFilter>,
 ) -> FolderTreeActions {
     let mut actions = FolderTreeActions::default();
+    let min_bytes = match (app.hide_small_folders, filter) {
+        (true, None) => {
+            let root = tree.node(tree.root).size as f64;
+            (root * f64::from(app.min_folder_percent) / 100.0) as u128
+        }
+        _ => 0,
+    };
+    let view = FolderView {
+        sort: app.sort,
+        search: filter,
+        min_bytes,
+    };
+    // Only expanded branches are listed and sorted, and only the rows
+    // scrolled into view get widgets
+    let mut rows = Vec::new();
+    if !push_folder_rows(ui.ctx(), tree, tree.root, 0, &view, &mut rows) {
+        ui.label("No matches in tree");
+        return actions;
+    }
+    let row_height = ui.spacing().interact_size.y;
     ScrollArea::vertical()
         .id_source("folder_tree_scroll")
         .auto_shrink([false; 2])
-        .show(ui, |ui| {
-            if !render_folder_node(
-                ui,
-                tree,
-                tree.root,
-                app.selected,
-                app.current_dir,
-                app.sort,
-                filter,
-                &mut actions,
-            ) {
-                ui.label("No matches in tree");
+        .show_rows(ui, row_height, rows.len(), |ui, range| {
+            for row in &rows[range] {
+                let size = egui::vec2(ui.available_width(), row_height);
+                let layout = egui::Layout::left_to_right(egui::Align::Center);
+                ui.allocate_ui_with_layout(size, layout, |ui| {
+                    render_folder_row(ui, app, tree, row, &mut actions);
+                });
             }
         });
     actions
 }
 
-fn render_folder_node(
-    ui: &mut Ui,
-    tree: &Tree,
-    node_id: NodeId,
-    selected: Option<NodeId>,
-    current: Option<NodeId>,
-    sort: SortKey,
-    search: Option<&SearchFilter>,
-    actions: &mut FolderTreeActions,
-) -> bool {
-    let mut rendered = false;
-    ui.push_id(node_id.0, |ui| {
-        rendered = render_folder_node_contents(
-            ui, tree, node_id, selected, current, sort, search, actions,
+/// Expansion state of the folder `id`, keyed by its path so it survives a
+/// refresh renumbering the nodes.
+fn folder_state(ctx: &egui::Context, tree: &Tree, id: NodeId) -> CollapsingState {
+    let node = tree.node(id);
+    let key = Id::new(("folder_node", tree.path(id)));
+    CollapsingState::load_with_default_open(ctx, key, node.parent.is_none())
+}
+
+/// Opens `id` and the folders below it, down to `depth` levels in all.
+fn expand_branch(ctx: &egui::Context, tree: &Tree, id: NodeId, depth: usize) {
+    let mut stack = vec![(id, 1)];
+    while let Some((id, level)) = stack.pop() {
+        let node = tree.node(id);
+        if node.children.is_empty() || level > depth {
+            continue;
+        }
+        let mut state = folder_state(ctx, tree, id);
+        state.set_open(true);
+        state.store(ctx);
+        stack.extend(
+            node.children
+                .iter()
+                .filter(|&&c| is_container(tree.node(c)))
+                .map(|&c| (c, level + 1)),
         );
-    });
-    rendered
+    }
 }
 
-fn render_folder_node_contents(
-    ui: &mut Ui,
+/// Closes `id` and every open folder below it. Closed folders aren't
+/// descended into, so this stays cheap however big the branch is.
+fn collapse_branch(ctx: &egui::Context, tree: &Tree, id: NodeId) {
+    let mut stack = vec![id];
+    while let Some(id) = stack.pop() {
+        let mut state = folder_state(ctx, tree, id);
+        if !state.is_open() {
+            continue;
+        }
+        state.set_open(false);
+        state.store(ctx);
+        stack.extend(
+            tree.node(id)
+                .children
+                .iter()
+                .filter(|&&c| is_container(tree.node(c))),
+        );
+    }
+}
+
+/// Whether the small folders under the folder at `path` were revealed.
+fn small_folders_id(path: &std::path::Path) -> Id {
+    Id::new(("small_folders", path))
+}
+
+/// Whether `node` has children to list in the sidebar: a folder, or an
+/// archive (or folder inside one) listed with
+/// [`ScanOptions::archives`](treesize_core::scanner::ScanOptions::archives).
+fn is_container(node: &TreeNode) -> bool {
+    matches!(node.kind, NodeKind::Dir) || !node.children.is_empty()
+}
+
+/// Appends the rows for the folder `node_id` and, if it is expanded, its
+/// contents. Returns false if a search leaves nothing of it to show.
+fn push_folder_rows(
+    ctx: &egui::Context,
     tree: &Tree,
     node_id: NodeId,
-    selected: Option<NodeId>,
-    current: Option<NodeId>,
-    sort: SortKey,
-    search: Option<&SearchFilter>,
-    actions: &mut FolderTreeActions,
+    depth: usize,
+    view: &FolderView,
+    rows: &mut Vec<FolderRow>,
 ) -> bool {
-    let node = &tree.nodes[node_id.0 as usize];
-    if !matches!(node.kind, NodeKind::Dir) {
+    let node = tree.node(node_id);
+    if !is_container(node) {
+        return false;
+    }
+    let search = view.search;
+    let visible = |child: &NodeId| {
+        if is_container(tree.node(*child)) {
+            search.is_none_or(|f| f.matches_subtree(*child))
+        } else {
+            search.is_none_or(|f| f.matches_node(*child))
+        }
+    };
+    if search.is_some_and(|f| !f.matches_node(node_id)) && !node.children.iter().any(visible) {
         return false;
     }
+    rows.push(FolderRow::Dir {
+        id: node_id,
+        depth,
+        // Opening a cut-off folder lists it
+        expandable: !node.children.is_empty() || tree.is_cut_off(node_id),
+    });
+    if !folder_state(ctx, tree, node_id).is_open() {
+        return true;
+    }
 
     let mut dir_children = Vec::new();
     let mut file_children = Vec::new();
-    for &child in &node.children {
-        let child_node = &tree.nodes[child.0 as usize];
-        match child_node.kind {
-            NodeKind::Dir => {
-                if search.map_or(true, |f| f.matches_subtree(child)) {
-                    dir_children.push(child);
-                }
-            }
-            NodeKind::File => {
-                if search.map_or(true, |f| f.matches_node(child)) {
-                    file_children.push(child);
-                }
-            }
+    for child in node.children.iter().filter(|c| visible(c)) {
+        if is_container(tree.node(*child)) {
+            dir_children.push(*child);
+        } else {
+            file_children.push(*child);
         }
     }
+    sort_node_ids(&mut dir_children, tree, view.sort);
+    sort_node_ids(&mut file_children, tree, view.sort);
 
-    sort_node_ids(&mut dir_children, tree, sort);
-    sort_node_ids(&mut file_children, tree, sort);
+    // Folders below the size threshold stay out until asked for
+    let revealed = ctx.data(|d| d.get_temp::<bool>(small_folders_id(&tree.path(node_id))));
+    let mut hidden = 0;
+    if view.min_bytes > 0 && !revealed.unwrap_or(false) {
+        let before = dir_children.len();
+        dir_children.retain(|c| {
+            let child = tree.node(*c);
+            !matches!(child.kind, NodeKind::Dir) || child.size >= view.min_bytes
+        });
+        hidden = before - dir_children.len();
+    }
 
-    let matches_self = search.map_or(true, |f| f.matches_node(node.id));
-    let has_visible_children = !dir_children.is_empty() || !file_children.is_empty();
-    if search.is_some() && !matches_self && !has_visible_children {
-        return false;
+    for child in dir_children {
+        push_folder_rows(ctx, tree, child, depth + 1, view, rows);
+    }
+    if hidden > 0 {
+        rows.push(FolderRow::Hidden {
+            parent: node_id,
+            depth: depth + 1,
+            count: hidden,
+        });
     }
+    rows.extend(file_children.into_iter().map(|id| FolderRow::File {
+        id,
+        depth: depth + 1,
+    }));
+    true
+}
 
-    let id = ui.make_persistent_id(("folder_node", node_id.0));
-    let state = CollapsingState::load_with_default_open(ui.ctx(), id, node.parent.is_none());
-    let is_selected = selected == Some(node_id) || current == Some(node_id);
-    let mut delete_clicked = false;
-    let mut header_label_response = None;
-    let label_text = format!("{} ({})", node.name, human_bytes(node.size));
-    let header = state.show_header(ui, |ui| {
-        ui.horizontal(|ui| {
-            let response = ui.selectable_label(is_selected, label_text.clone());
-            header_label_response = Some(response.clone());
-            ui.add_space(6.0);
-            if ui
-                .small_button("Del")
-                .on_hover_text("Delete this directory")
-                .clicked()
-            {
-                delete_clicked = true;
+fn render_folder_row(
+    ui: &mut Ui,
+    app: &AppState,
+    tree: &Tree,
+    row: &FolderRow,
+    actions: &mut FolderTreeActions,
+) {
+    let indent = ui.spacing().indent;
+    match *row {
+        FolderRow::Dir {
+            id,
+            depth,
+            expandable,
+        } => {
+            ui.add_space(depth as f32 * indent);
+            if expandable {
+                let mut state = folder_state(ui.ctx(), tree, id);
+                let toggle = state.show_toggle_button(ui, collapsing_header::paint_default_icon);
+                if toggle.clicked() && state.is_open() && tree.is_cut_off(id) {
+                    actions.list_contents = Some(id);
+                }
+                state.store(ui.ctx());
+            } else {
+                ui.add_space(indent);
             }
-            response
-        })
-        .inner
-    });
-    let (_toggle, header_inner, _) = header.body(|ui| {
-        for &child in &dir_children {
-            render_folder_node(ui, tree, child, selected, current, sort, search, actions);
+            render_folder_label(ui, app, tree, id, actions);
         }
-
-        for &child in &file_children {
-            render_file_entry(ui, tree, child, selected, actions);
+        FolderRow::File { id, depth } => {
+            ui.add_space(depth as f32 * indent + indent);
+            let annotation = app.annotations.get(&tree.path(id));
+            render_file_entry(ui, app, tree, id, annotation, actions);
         }
-    });
+        FolderRow::Hidden {
+            parent,
+            depth,
+            count,
+        } => {
+            ui.add_space(depth as f32 * indent + indent);
+            let text = match count {
+                1 => "1 hidden smaller folder".to_string(),
+                n => format!("{n} hidden smaller folders"),
+            };
+            let row = ui.add(
+                egui::Label::new(egui::RichText::new(text).small().weak()).sense(Sense::click()),
+            );
+            if row.on_hover_text("Click to show them").clicked() {
+                let path = &tree.path(parent);
+                ui.data_mut(|d| d.insert_temp(small_folders_id(path), true));
+            }
+        }
+    }
+}
+
+/// A tiny line of a folder's size in its last few recorded scans, drawn
+/// in the colour of whether it grew or shrank overall.
+fn draw_sparkline(ui: &mut Ui, sizes: &[u128]) {
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(48.0, 14.0), Sense::hover());
+    let (Some(&first), Some(&last)) = (sizes.first(), sizes.last()) else {
+        return;
+    };
+    let low = sizes.iter().copied().min().unwrap_or(0);
+    let range = (sizes.iter().copied().max().unwrap_or(0) - low).max(1) as f32;
+    let step = rect.width() / (sizes.len().max(2) - 1) as f32;
+    let points: Vec<Pos2> = sizes
+        .iter()
+        .enumerate()
+        .map(|(i, &bytes)| {
+            Pos2::new(
+                rect.left() + i as f32 * step,
+                rect.bottom() - (bytes - low) as f32 / range * rect.height(),
+            )
+        })
+        .collect();
+    let color = match last.cmp(&first) {
+        std::cmp::Ordering::Greater => Color32::from_rgb(0xE7, 0x84, 0x3C),
+        std::cmp::Ordering::Less => Color32::from_rgb(0x7F, 0xC8, 0xA9),
+        std::cmp::Ordering::Equal => ui.visuals().weak_text_color(),
+    };
+    ui.painter()
+        .add(egui::Shape::line(points, egui::Stroke::new(1.2, color)));
+    response.on_hover_text(format!(
+        "Last {} scans: {} to {}",
+        sizes.len(),
+        human_bytes(first),
+        human_bytes(last)
+    ));
+}
 
-    if delete_clicked {
+fn render_folder_label(
+    ui: &mut Ui,
+    app: &AppState,
+    tree: &Tree,
+    node_id: NodeId,
+    actions: &mut FolderTreeActions,
+) {
+    let node = tree.node(node_id);
+    let is_selected = app.selected == Some(node_id) || app.current_dir == Some(node_id);
+    let error_count = tree.errors_at(node_id).count();
+    let base = app.percent_baseline().map(|b| b.size);
+    let warning = if error_count > 0 { "⚠ " } else { "" };
+    let icon = kind_icon(&node.kind);
+    let path = tree.path(node_id);
+    let label_text = format!(
+        "{warning}{icon} {} ({}){}",
+        node.name,
+        size_text(node.size, base),
+        tags_text(app.annotations.get(&path))
+    );
+    let response = ui.selectable_label(is_selected, label_text);
+    if let Some(sizes) = app.size_history(&path) {
+        draw_sparkline(ui, sizes);
+    }
+    if let Some(listing) = app.listing(&path) {
+        ui.spinner();
+        ui.label(
+            egui::RichText::new(format!(
+                "Scanning… {} files, {}",
+                listing.files,
+                human_bytes(listing.bytes)
+            ))
+            .small()
+            .weak(),
+        );
+    }
+    let on_disk = !matches!(node.kind, NodeKind::ArchiveEntry);
+    ui.add_space(6.0);
+    if on_disk
+        && ui
+            .small_button("Del")
+            .on_hover_text("Delete this directory")
+            .clicked()
+    {
         actions.select = Some(node_id);
         actions.delete = Some(node_id);
     }
 
-    let response = header_inner.response;
-
-    if let Some(resp) = &header_label_response {
-        resp.clone().on_hover_ui(|ui| show_node_metadata(ui, node));
-        if resp.clicked() {
+    let response = response.on_hover_ui(|ui| {
+        show_node_metadata(ui, node, &path, app.shown_path(&path), &app.date_format);
+        if let Some(baseline) = app.percent_baseline() {
+            ui.label(share_text(node.size, baseline));
+        }
+        if let Some(margin) = tree.estimate_margin(node_id) {
+            ui.label(format!(
+                "Estimated, ± {} at 95% confidence",
+                human_bytes(margin)
+            ));
+        }
+        if tree.is_cut_off(node_id) {
+            ui.label("Contents counted but not listed (scan depth limit); open it to list them");
+        }
+        if error_count > 0 {
+            ui.colored_label(
+                ERROR_COLOR,
+                format!("{error_count} path(s) here could not be read"),
+            );
+        }
+    });
+    if response.clicked() {
+        actions.select = Some(node_id);
+        actions.open = Some(node_id);
+    }
+    response.context_menu(|ui| {
+        if ui.button("Open").clicked() {
             actions.select = Some(node_id);
             actions.open = Some(node_id);
+            ui.close_menu();
         }
-        resp.context_menu(|ui| {
-            if ui.button("Open").clicked() {
-                actions.select = Some(node_id);
-                actions.open = Some(node_id);
+        if ui
+            .button("Use as % Baseline")
+            .on_hover_text("Show every percentage as a share of this folder")
+            .clicked()
+        {
+            actions.baseline = Some(node_id);
+            ui.close_menu();
+        }
+        if tree.is_cut_off(node_id)
+            && ui
+                .button("List Contents")
+                .on_hover_text("Scan this folder to list what is below the depth limit")
+                .clicked()
+        {
+            actions.list_contents = Some(node_id);
+            ui.close_menu();
+        }
+        if !node.children.is_empty() {
+            let levels = match app.expand_depth {
+                1 => "Opens this folder".to_string(),
+                2 => "Opens this folder and the folders in it".to_string(),
+                n => format!("Opens this folder and {} levels below it", n - 1),
+            };
+            if ui.button("Expand all").on_hover_text(levels).clicked() {
+                expand_branch(ui.ctx(), tree, node_id, app.expand_depth);
+                ui.close_menu();
+            }
+            if ui.button("Collapse all").clicked() {
+                collapse_branch(ui.ctx(), tree, node_id);
+                ui.close_menu();
+            }
+        }
+        if on_disk {
+            if matches!(node.kind, NodeKind::Dir)
+                && ui
+                    .button("Refresh this folder")
+                    .on_hover_text("Rescan just this folder and update its sizes")
+                    .clicked()
+            {
+                actions.refresh = Some(node_id);
                 ui.close_menu();
             }
             if ui.button("Delete").clicked() {
@@ -475,32 +1572,15 @@ fn render_folder_node_contents(
                 actions.delete = Some(node_id);
                 ui.close_menu();
             }
-            if ui.button("Properties").clicked() {
+            if ui.button("Move to Archive").clicked() {
                 actions.select = Some(node_id);
-                actions.properties = Some(node_id);
+                actions.archive = Some(node_id);
+                ui.close_menu();
+            }
+            if ui.button("Add to Cleanup Cart").clicked() {
+                actions.add_to_cart = Some(node_id);
                 ui.close_menu();
             }
-        });
-    }
-
-    let body_hover = response.clone();
-    body_hover.on_hover_ui(|ui| show_node_metadata(ui, node));
-
-    if response.clicked() {
-        actions.select = Some(node_id);
-        actions.open = Some(node_id);
-    }
-
-    response.context_menu(|ui| {
-        if ui.button("Open").clicked() {
-            actions.select = Some(node_id);
-            actions.open = Some(node_id);
-            ui.close_menu();
-        }
-        if ui.button("Delete").clicked() {
-            actions.select = Some(node_id);
-            actions.delete = Some(node_id);
-            ui.close_menu();
         }
         if ui.button("Properties").clicked() {
             actions.select = Some(node_id);
@@ -508,52 +1588,114 @@ fn render_folder_node_contents(
             ui.close_menu();
         }
     });
-
-    true
 }
 
 fn render_file_tab(ui: &mut Ui, app: &AppState, tree: &Tree) -> FolderTreeActions {
     let mut actions = FolderTreeActions::default();
+    let footer_height = ui.text_style_height(&TextStyle::Body) + ui.spacing().item_spacing.y * 3.0;
     ScrollArea::vertical()
         .id_source("file_list_scroll")
         .auto_shrink([false; 2])
+        .max_height((ui.available_height() - footer_height).max(0.0))
         .show(ui, |ui| {
             if app.filtered_file_nodes.is_empty() {
                 ui.label("No files to show");
             } else {
-                for &id in &app.filtered_file_nodes {
-                    render_file_entry(ui, tree, id, app.selected, &mut actions);
-                }
+                let annotated = app.annotations.in_tree(tree);
+                egui::Grid::new("file_list").striped(true).show(ui, |ui| {
+                    ui.strong("File");
+                    ui.strong("% of folder");
+                    ui.strong("% of scan");
+                    ui.end_row();
+                    for &id in &app.filtered_file_nodes {
+                        let annotation = annotated.get(&id).copied();
+                        render_file_entry(ui, app, tree, id, annotation, &mut actions);
+                        ui.label(human_percent(tree.share_of_parent(id)));
+                        ui.label(human_percent(tree.share_of_root(id)));
+                        ui.end_row();
+                    }
+                });
             }
         });
+    ui.separator();
+    draw_file_totals(ui, app, tree);
     actions
 }
+
+/// Footer of the file list: how much of the scanned folder the listed
+/// files add up to.
+fn draw_file_totals(ui: &mut Ui, app: &AppState, tree: &Tree) {
+    let folder = tree.node(tree.root);
+    let shown = app.filtered_file_nodes.len();
+    let count = if shown == app.file_nodes.len() {
+        format!("All {shown} files")
+    } else {
+        format!("{shown} of {} files", app.file_nodes.len())
+    };
+    ui.strong(format!(
+        "{count} · {} of {} ({})",
+        human_bytes(app.filtered_file_bytes),
+        human_bytes(folder.size),
+        human_percent(share(app.filtered_file_bytes, folder.size))
+    ))
+    .on_hover_text(format!(
+        "Total size of the files listed, as a share of {}",
+        app.shown_path(&tree.path(tree.root)).display()
+    ));
+}
 fn render_file_entry(
     ui: &mut Ui,
+    app: &AppState,
     tree: &Tree,
     node_id: NodeId,
-    selected: Option<NodeId>,
+    annotation: Option<&Annotation>,
     actions: &mut FolderTreeActions,
 ) {
-    let node = &tree.nodes[node_id.0 as usize];
-    let label = format!("{} ({})", node.name, human_bytes(node.size));
-    let response = ui.selectable_label(selected == Some(node_id), label);
+    let node = tree.node(node_id);
+    let baseline = app.percent_baseline();
+    let cloud = if node.online_only { "☁ " } else { "" };
+    let size = size_text(node.size, baseline.map(|b| b.size));
+    let icon = kind_icon(&node.kind);
+    let label = format!(
+        "{icon} {cloud}{} ({size}){}",
+        node.name,
+        tags_text(annotation)
+    );
+    let response = ui.selectable_label(app.selected == Some(node_id), label);
     let hover_response = response.clone();
-    hover_response.on_hover_ui(|ui| show_node_metadata(ui, node));
+    hover_response.on_hover_ui(|ui| {
+        let path = tree.path(node_id);
+        show_node_metadata(ui, node, &path, app.shown_path(&path), &app.date_format);
+        if let Some(baseline) = baseline {
+            ui.label(share_text(node.size, baseline));
+        }
+    });
 
     if response.clicked() {
         actions.select = Some(node_id);
     }
 
     response.context_menu(|ui| {
-        if ui.button("Open").clicked() {
-            let _ = open::that(&node.path);
-            ui.close_menu();
-        }
-        if ui.button("Delete").clicked() {
-            actions.select = Some(node_id);
-            actions.delete = Some(node_id);
-            ui.close_menu();
+        // Entries inside an archive aren't on disk to open or remove
+        if !matches!(node.kind, NodeKind::ArchiveEntry) {
+            if ui.button("Open").clicked() {
+                let _ = open::that(tree.path(node_id));
+                ui.close_menu();
+            }
+            if ui.button("Delete").clicked() {
+                actions.select = Some(node_id);
+                actions.delete = Some(node_id);
+                ui.close_menu();
+            }
+            if ui.button("Move to Archive").clicked() {
+                actions.select = Some(node_id);
+                actions.archive = Some(node_id);
+                ui.close_menu();
+            }
+            if ui.button("Add to Cleanup Cart").clicked() {
+                actions.add_to_cart = Some(node_id);
+                ui.close_menu();
+            }
         }
         if ui.button("Properties").clicked() {
             actions.select = Some(node_id);
@@ -563,34 +1705,103 @@ fn render_file_entry(
     });
 }
 
-fn show_node_metadata(ui: &mut Ui, node: &TreeNode) {
-    ui.label(format!("Path: {}", node.path.display()));
+/// `bytes`, followed by its share of `base` when percentages are relative
+/// to a baseline.
+fn size_text(bytes: u128, base: Option<u128>) -> String {
+    match base {
+        Some(base) => format!(
+            "{}, {}",
+            human_bytes(bytes),
+            human_percent(share(bytes, base))
+        ),
+        None => human_bytes(bytes),
+    }
+}
+
+/// The tags of a node, as listed after its name and size.
+fn tags_text(annotation: Option<&Annotation>) -> String {
+    match annotation.filter(|a| !a.tags.is_empty()) {
+        Some(annotation) => format!(" 🏷 {}", annotation.tags_text()),
+        None => String::new(),
+    }
+}
+
+fn share_text(bytes: u128, baseline: &TreeNode) -> String {
+    format!(
+        "Share: {} of {}",
+        human_percent(share(bytes, baseline.size)),
+        baseline.name
+    )
+}
+
+/// Symbol shown before a node's name in the folder tree.
+fn kind_icon(kind: &NodeKind) -> &'static str {
+    match kind {
+        NodeKind::Dir => "📁",
+        NodeKind::File => "🗋",
+        NodeKind::MountPoint => "🖴",
+        NodeKind::Symlink => "🔗",
+        NodeKind::ArchiveEntry => "🗐",
+        NodeKind::Archive => "📦",
+        NodeKind::Other => "⚙",
+    }
+}
+
+/// `shown` is `path` as the user chose to see paths.
+fn show_node_metadata(ui: &mut Ui, node: &TreeNode, path: &Path, shown: &Path, dates: &DateFormat) {
+    ui.label(format!("Path: {}", shown.display()));
     ui.label(match node.kind {
-        NodeKind::Dir => format!("Kind: Directory"),
-        NodeKind::File => format!("Kind: File"),
+        NodeKind::Dir => "Kind: Directory".to_string(),
+        NodeKind::File => "Kind: File".to_string(),
+        NodeKind::MountPoint => "Kind: Mount point (other filesystem, not scanned)".to_string(),
+        NodeKind::Symlink => match &node.link_target {
+            Some(target) => format!("Kind: Link -> {}", target.display()),
+            None => "Kind: Link".to_string(),
+        },
+        NodeKind::ArchiveEntry => "Kind: Inside an archive (uncompressed size)".to_string(),
+        NodeKind::Archive => "Kind: Archive (contents listed, size as stored)".to_string(),
+        NodeKind::Other => "Kind: Socket, pipe or device (takes no space)".to_string(),
     });
     ui.label(format!("Size: {}", human_bytes(node.size)));
+    if node.online_only {
+        ui.label("Online-only: stored in the cloud, not on this disk");
+    }
     if matches!(node.kind, NodeKind::Dir) {
         ui.label(format!("Files: {}", node.file_count));
     }
     ui.label(format!(
         "Modified: {}",
-        format_modified(node.modified, Some(&node.path))
+        format_modified(node.modified, Some(path), dates)
     ));
+    let attributes = [
+        (node.attributes.readonly, "read-only"),
+        (node.attributes.hidden, "hidden"),
+        (node.attributes.system, "system"),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| *name)
+    .collect::<Vec<_>>();
+    if !attributes.is_empty() {
+        ui.label(format!("Attributes: {}", attributes.join(", ")));
+    }
+    if let (Some(mode), Some(bits)) = (node.attributes.mode_string(), node.attributes.mode) {
+        ui.label(format!("Permissions: {mode} ({bits:o})"));
+    }
 }
 
-fn show_slice_metadata(ui: &mut Ui, slice: &PieSlice) {
+fn show_slice_metadata(ui: &mut Ui, slice: &PieSlice, dates: &DateFormat) {
     ui.label(format!("Name: {}", slice.name));
     ui.label(format!("Size: {}", human_bytes(slice.bytes)));
     match slice.id {
         Some(_) => {
-            ui.label(format!("Path: {}", slice.path.display()));
+            ui.label(format!("Path: {}", slice.shown_path.display()));
             if matches!(slice.kind, NodeKind::Dir) {
                 ui.label(format!("Files: {}", slice.file_count));
             }
             ui.label(format!(
                 "Modified: {}",
-                format_modified(slice.modified, Some(&slice.path))
+                format_modified(slice.modified, Some(&slice.path), dates)
             ));
         }
         None => {
@@ -602,13 +1813,15 @@ fn show_slice_metadata(ui: &mut Ui, slice: &PieSlice) {
     }
 }
 
-fn format_modified(modified: Option<SystemTime>, path: Option<&std::path::Path>) -> String {
+fn format_modified(
+    modified: Option<SystemTime>,
+    path: Option<&std::path::Path>,
+    dates: &DateFormat,
+) -> String {
     let time = modified.or_else(|| path.and_then(|p| std::fs::metadata(p).ok()?.modified().ok()));
-    if let Some(time) = time {
-        let datetime = DateTime::<Local>::from(time);
-        datetime.format("%Y-%m-%d %H:%M:%S").to_string()
-    } else {
-        "Unknown".to_string()
+    match time {
+        Some(time) => dates.format(time),
+        None => "Unknown".to_string(),
     }
 }
 
@@ -629,23 +1842,11 @@ fn truncate_middle(value: &str, max_len: usize) -> String {
     format!("{}...{}", start, end)
 }
 
-fn sort_node_ids(nodes: &mut Vec<NodeId>, tree: &Tree, sort: SortKey) {
+fn sort_node_ids(nodes: &mut [NodeId], tree: &Tree, sort: SortKey) {
     match sort {
-        SortKey::Size => nodes.sort_by(|a, b| {
-            tree.nodes[b.0 as usize]
-                .size
-                .cmp(&tree.nodes[a.0 as usize].size)
-        }),
-        SortKey::Name => nodes.sort_by(|a, b| {
-            tree.nodes[a.0 as usize]
-                .name
-                .cmp(&tree.nodes[b.0 as usize].name)
-        }),
-        SortKey::Count => nodes.sort_by(|a, b| {
-            tree.nodes[b.0 as usize]
-                .file_count
-                .cmp(&tree.nodes[a.0 as usize].file_count)
-        }),
+        SortKey::Size => nodes.sort_by_key(|&n| std::cmp::Reverse(tree.node(n).size)),
+        SortKey::Name => nodes.sort_by(|a, b| tree.node(*a).name.cmp(&tree.node(*b).name)),
+        SortKey::Count => nodes.sort_by_key(|&n| std::cmp::Reverse(tree.node(n).file_count)),
     }
 }
 
@@ -658,11 +1859,11 @@ fn show_delete_confirmation(ctx: &egui::Context, app: &mut AppState) {
     let (path_display, item_label, item_kind, size_label) = app
         .tree
         .as_ref()
-        .and_then(|tree| tree.nodes.get(delete_id.0 as usize))
-        .map(|node| {
+        .and_then(|tree| Some((tree, tree.nodes.get(delete_id.0 as usize)?)))
+        .map(|(tree, node)| {
             (
-                node.path.display().to_string(),
-                node.name.clone(),
+                app.shown_path(&tree.path(delete_id)).display().to_string(),
+                node.name.to_string(),
                 node.kind.clone(),
                 human_bytes(node.size),
             )
@@ -685,83 +1886,1399 @@ fn show_delete_confirmation(ctx: &egui::Context, app: &mut AppState) {
         .resizable(false)
         .open(&mut open)
         .show(ctx, |ui| {
-            let kind_text = match item_kind {
-                NodeKind::Dir => "folder",
-                NodeKind::File => "file",
+            let kind_text = match item_kind {
+                NodeKind::Dir => "folder",
+                NodeKind::File => "file",
+                NodeKind::MountPoint => "mount point",
+                NodeKind::Symlink => "symlink",
+                NodeKind::ArchiveEntry => "archive entry",
+                NodeKind::Archive => "archive",
+                NodeKind::Other => "special file",
+            };
+            ui.heading(format!("Delete {kind_text}?"));
+            ui.label(format!("Name: {item_label}"));
+            ui.label(format!("Path: {path_display}"));
+            if !size_label.is_empty() {
+                ui.label(format!("Size: {size_label}"));
+            }
+            ui.separator();
+            ui.label("This action cannot be undone.");
+            ui.horizontal(|ui| {
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+                if ui
+                    .add(egui::Button::new("Delete").fill(Color32::from_rgb(170, 50, 50)))
+                    .clicked()
+                {
+                    confirm = true;
+                }
+            });
+        });
+
+    if confirm {
+        app.delete_selected_and_rescan();
+        app.pending_delete = None;
+        ctx.request_repaint();
+    } else if cancel || !open {
+        app.pending_delete = None;
+    }
+}
+
+fn show_archive_plan(ctx: &egui::Context, app: &mut AppState) {
+    let Some(plan) = &app.pending_archive else {
+        return;
+    };
+
+    let mut confirm = false;
+    let mut cancel = false;
+    let mut open = true;
+    egui::Window::new("Move to Archive")
+        .anchor(Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .collapsible(false)
+        .resizable(false)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.label(format!("From: {}", plan.source.display()));
+            ui.label(format!("To: {}", plan.destination.display()));
+            ui.separator();
+            ui.label(format!("Size: {}", human_bytes(plan.size)));
+            ui.label(format!(
+                "Estimated space at destination: {} ({} allocation unit)",
+                human_bytes(plan.estimated_size),
+                human_bytes(plan.allocation_unit as u128)
+            ))
+            .on_hover_text("Compression at the destination is not taken into account");
+            ui.horizontal(|ui| {
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+                if ui.button("Move").clicked() {
+                    confirm = true;
+                }
+            });
+        });
+
+    if confirm {
+        let id = plan.id;
+        app.pending_archive = None;
+        app.archive_node(id);
+    } else if cancel || !open {
+        app.pending_archive = None;
+    }
+}
+
+fn show_properties_panel(ctx: &egui::Context, app: &mut AppState) {
+    let properties_id = match app.pending_properties {
+        Some(id) => id,
+        None => return,
+    };
+
+    let Some(tree) = app.tree.as_ref() else {
+        app.pending_properties = None;
+        return;
+    };
+    let Some(node) = tree.nodes.get(properties_id.0 as usize) else {
+        app.pending_properties = None;
+        return;
+    };
+
+    // Edited here and handed back below, as the tree is borrowed meanwhile
+    let mut tag_input = std::mem::take(&mut app.tag_input);
+    let mut note_input = std::mem::take(&mut app.note_input);
+    let mut add_tag = None;
+    let mut remove_tag = None;
+    let mut save_note = false;
+    let mut open = true;
+    egui::Window::new("Properties")
+        .anchor(Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .collapsible(false)
+        .resizable(false)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.heading(node.name.as_str());
+            let path = tree.path(properties_id);
+            show_node_metadata(ui, node, &path, app.shown_path(&path), &app.date_format);
+            if matches!(node.kind, NodeKind::Dir) {
+                ui.label(format!("Contains: {} files", node.file_count));
+                let cloud = app.cloud_bytes.get(properties_id.0 as usize).copied();
+                if let Some(cloud) = cloud.filter(|b| *b > 0) {
+                    ui.label(format!(
+                        "Stored locally: {}, online-only: {}",
+                        human_bytes(node.size.saturating_sub(cloud)),
+                        human_bytes(cloud)
+                    ));
+                }
+            }
+            if let Some(ages) = app
+                .age_histograms
+                .get(properties_id.0 as usize)
+                .filter(|h| h.total_bytes() > 0)
+            {
+                ui.separator();
+                ui.strong("Last modified");
+                egui::Grid::new("properties_age").show(ui, |ui| {
+                    for bucket in AgeBucket::ALL {
+                        ui.colored_label(age_color(bucket), bucket.label());
+                        ui.label(human_bytes(ages.bytes_in(bucket)));
+                        ui.label(format!("{} files", ages.files_in(bucket)));
+                        ui.end_row();
+                    }
+                });
+            }
+            match (&node.kind, app.properties_owners.as_slice()) {
+                (NodeKind::File | NodeKind::Archive, [usage]) => {
+                    ui.label(format!("Owner: {}", usage.owner));
+                }
+                (NodeKind::Dir, owners) if !owners.is_empty() => {
+                    ui.separator();
+                    ui.strong("Space by owner");
+                    egui::Grid::new("properties_owners").show(ui, |ui| {
+                        for usage in owners {
+                            ui.label(usage.owner.to_string());
+                            ui.label(human_bytes(usage.bytes));
+                            ui.label(format!("{} files", usage.files));
+                            ui.end_row();
+                        }
+                    });
+                }
+                _ => {}
+            }
+            if !app.properties_streams.is_empty() {
+                ui.separator();
+                ui.strong("Alternate data streams");
+                egui::Grid::new("properties_streams").show(ui, |ui| {
+                    for stream in &app.properties_streams {
+                        ui.label(&stream.name);
+                        ui.label(human_bytes(stream.size));
+                        ui.end_row();
+                    }
+                });
+            }
+            if !app.properties_aliases.is_empty() {
+                ui.separator();
+                ui.strong("Also reachable at");
+                for path in &app.properties_aliases {
+                    ui.label(app.shown_path(path).display().to_string());
+                }
+                ui.small("Through links or bind mounts; its size is counted here only");
+            }
+            if let Some(target) = tree.link_target_of(properties_id) {
+                let target = &tree.path(target);
+                ui.label(format!("Counted at {}", app.shown_path(target).display()));
+            }
+            if matches!(node.kind, NodeKind::Dir) {
+                let streams = tree.meta.alternate_stream_bytes(&tree.path(properties_id));
+                if streams > 0 {
+                    ui.label(format!(
+                        "In alternate data streams: {}",
+                        human_bytes(streams)
+                    ));
+                }
+            }
+            for error in tree.errors_at(properties_id) {
+                ui.colored_label(
+                    ERROR_COLOR,
+                    format!(
+                        "⚠ {}: {}",
+                        app.shown_path(&error.path).display(),
+                        error.kind.label()
+                    ),
+                )
+                .on_hover_text(&error.message);
+            }
+            // The synthetic root of a combined scan has no path to tag
+            if !path.as_os_str().is_empty() {
+                ui.separator();
+                let annotation = app.annotations.get(&path);
+                ui.horizontal_wrapped(|ui| {
+                    ui.strong("Tags:");
+                    for tag in annotation.iter().flat_map(|a| &a.tags) {
+                        if ui
+                            .small_button(format!("{tag} ✕"))
+                            .on_hover_text("Remove this tag")
+                            .clicked()
+                        {
+                            remove_tag = Some(tag.clone());
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let input = ui.add(
+                        egui::TextEdit::singleline(&mut tag_input)
+                            .hint_text("e.g. safe to delete")
+                            .desired_width(160.0),
+                    );
+                    let entered =
+                        input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if (ui.button("Add Tag").clicked() || entered) && !tag_input.trim().is_empty() {
+                        add_tag = Some(std::mem::take(&mut tag_input));
+                    }
+                });
+                ui.label("Note:");
+                ui.add(
+                    egui::TextEdit::multiline(&mut note_input)
+                        .desired_rows(2)
+                        .desired_width(260.0),
+                );
+                let saved_note = annotation.map_or("", |a| a.note.as_str());
+                save_note = ui
+                    .add_enabled(
+                        note_input.trim() != saved_note,
+                        egui::Button::new("Save Note"),
+                    )
+                    .clicked();
+            }
+            ui.separator();
+            if ui.button("Open Externally").clicked() {
+                let _ = open::that(tree.path(properties_id));
+            }
+        });
+
+    app.tag_input = tag_input;
+    app.note_input = note_input;
+    if let Some(tag) = add_tag {
+        app.add_tag(properties_id, &tag);
+    }
+    if let Some(tag) = remove_tag {
+        app.remove_tag(properties_id, &tag);
+    }
+    if save_note {
+        let note = app.note_input.clone();
+        app.set_note(properties_id, &note);
+    }
+    if !open {
+        app.pending_properties = None;
+    }
+}
+
+fn name_matching_row(ui: &mut Ui, label: &str, matching: &mut NameMatching) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.checkbox(&mut matching.ignore_case, "Ignore case");
+        ui.checkbox(&mut matching.normalize, "Normalize Unicode")
+            .on_hover_text(
+                "Treat names like \"Résumé\" as equal however their accents are encoded",
+            );
+    });
+}
+
+/// Locales offered for exports besides the system's.
+const REPORT_LOCALES: [chrono::Locale; 8] = [
+    chrono::Locale::en_US,
+    chrono::Locale::en_GB,
+    chrono::Locale::de_DE,
+    chrono::Locale::de_CH,
+    chrono::Locale::fr_FR,
+    chrono::Locale::fr_CA,
+    chrono::Locale::es_ES,
+    chrono::Locale::es_MX,
+];
+
+fn report_locale_row(ui: &mut Ui, locale: &mut Option<chrono::Locale>) {
+    let name = |l: chrono::Locale| format!("{} ({l})", Language::of(l).label());
+    ui.horizontal(|ui| {
+        ui.label("Language and number format:");
+        egui::ComboBox::from_id_source("report_locale")
+            .selected_text(locale.map_or("English, plain numbers".to_string(), name))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(locale, None, "English, plain numbers");
+                let system = system_locale();
+                let mut choices = vec![system];
+                choices.extend(REPORT_LOCALES.into_iter().filter(|l| *l != system));
+                for choice in choices {
+                    ui.selectable_value(locale, Some(choice), name(choice));
+                }
+            });
+    })
+    .response
+    .on_hover_text("Used by PDF exports, and by locale dates in every export");
+}
+
+fn date_format_row(ui: &mut Ui, format: &mut DateFormat) {
+    ui.horizontal(|ui| {
+        let pattern = match format {
+            DateFormat::Custom(pattern) => pattern.clone(),
+            _ => "%d/%m/%Y %H:%M".to_string(),
+        };
+        egui::ComboBox::from_id_source("date_format")
+            .selected_text(format.label())
+            .show_ui(ui, |ui| {
+                for choice in [
+                    DateFormat::Iso8601,
+                    DateFormat::Locale,
+                    DateFormat::Relative,
+                    DateFormat::Custom(pattern),
+                ] {
+                    let label = choice.label();
+                    ui.selectable_value(format, choice, label);
+                }
+            });
+        if let DateFormat::Custom(pattern) = format {
+            ui.text_edit_singleline(pattern)
+                .on_hover_text("strftime pattern, e.g. %d.%m.%Y %H:%M");
+            if pattern.parse::<DateFormat>().is_err() {
+                ui.colored_label(ERROR_COLOR, "Invalid pattern");
+            }
+        }
+    });
+    ui.label(format!(
+        "e.g. {}",
+        format.format(SystemTime::now() - std::time::Duration::from_secs(3 * 86_400))
+    ));
+}
+
+fn show_settings_window(ctx: &egui::Context, app: &mut AppState) {
+    if !app.show_settings {
+        return;
+    }
+
+    let mut open = true;
+    let mut remove = None;
+    egui::Window::new("Settings")
+        .collapsible(false)
+        .resizable(true)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.strong("Exclude patterns");
+            ui.label("Matching files and folders are skipped during the next scan.");
+            ui.add_space(4.0);
+            for (index, pattern) in app.scan_options.excludes.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.monospace(pattern);
+                    if ui.small_button("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                });
+            }
+            if app.scan_options.excludes.is_empty() {
+                ui.label("No exclusions");
+            }
+            ui.horizontal(|ui| {
+                let resp = ui.text_edit_singleline(&mut app.exclude_input);
+                let submitted = resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if ui.button("Add").clicked() || submitted {
+                    app.add_exclude();
+                }
+            });
+            ui.label("Glob syntax, e.g. node_modules, target/, **/.cache");
+            ui.checkbox(
+                &mut app.scan_options.one_file_system,
+                "Stay on one filesystem (skip mounted volumes)",
+            );
+            ui.checkbox(
+                &mut app.scan_options.follow_links,
+                "Follow symbolic links that lead outside the scanned folder",
+            );
+            if cfg!(windows) {
+                ui.checkbox(
+                    &mut app.scan_options.alternate_streams,
+                    "Count alternate data streams",
+                )
+                .on_hover_text("Includes the hidden NTFS streams of files in their size");
+            }
+            ui.checkbox(&mut app.scan_options.owners, "Record file owners")
+                .on_hover_text("Shows the space each user takes up in Properties");
+            ui.add_enabled(
+                app.has_history(),
+                egui::Checkbox::new(&mut app.record_history, "Keep a history of folder sizes"),
+            )
+            .on_hover_text(
+                "Records the folders near the top of every finished scan, so the History \
+                 window can show how fast they grow",
+            );
+            ui.checkbox(&mut app.scan_options.archives, "List archive contents")
+                .on_hover_text(
+                    "Shows what zip, tar.gz and 7z files contain, without extracting them",
+                );
+            ui.add_space(8.0);
+            ui.strong("File filters");
+            ui.label("Files left out here don't count toward any folder in the next scan.");
+            ui.horizontal(|ui| {
+                ui.label("Skip files smaller than:");
+                ui.add(
+                    egui::DragValue::new(&mut app.scan_options.min_file_size)
+                        .speed(1024.0)
+                        .custom_formatter(|bytes, _| human_bytes(bytes as u128))
+                        .custom_parser(|text| parse_bytes(text).map(|bytes| bytes as f64)),
+                );
+            })
+            .response
+            .on_hover_text("Type a size such as 1MB; 0 keeps every file");
+            let mut edited = false;
+            egui::Grid::new("extension_filters")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Only extensions:");
+                    edited |= ui
+                        .text_edit_singleline(&mut app.include_ext_input)
+                        .on_hover_text("e.g. mp4, mkv, iso; empty keeps every type")
+                        .changed();
+                    ui.end_row();
+                    ui.label("Skip extensions:");
+                    edited |= ui
+                        .text_edit_singleline(&mut app.exclude_ext_input)
+                        .on_hover_text("e.g. o, tmp, log")
+                        .changed();
+                    ui.end_row();
+                });
+            if edited {
+                app.apply_extension_filters();
+            }
+            ui.horizontal(|ui| {
+                let mut limited = app.scan_options.max_depth.is_some();
+                ui.checkbox(&mut limited, "List only");
+                let mut depth = app.scan_options.max_depth.unwrap_or(3);
+                ui.add_enabled(
+                    limited,
+                    egui::DragValue::new(&mut depth)
+                        .range(0..=64)
+                        .suffix(" levels"),
+                );
+                app.scan_options.max_depth = limited.then_some(depth);
+            })
+            .response
+            .on_hover_text(
+                "Deeper files still count toward the folder at that depth; \
+                 quick for an overview of a huge drive",
+            );
+            ui.horizontal(|ui| {
+                let mut sampling = app.scan_options.sample_per_mille.is_some();
+                ui.checkbox(&mut sampling, "Estimate from");
+                let mut percent = f64::from(app.scan_options.sample_per_mille.unwrap_or(50)) / 10.0;
+                ui.add_enabled(
+                    sampling,
+                    egui::DragValue::new(&mut percent)
+                        .range(0.1..=100.0)
+                        .speed(0.1)
+                        .max_decimals(1)
+                        .suffix("% of files"),
+                );
+                app.scan_options.sample_per_mille =
+                    sampling.then(|| ((percent * 10.0).round() as u16).max(1));
+            })
+            .response
+            .on_hover_text(
+                "Sizes only a random sample of files and extrapolates the rest; \
+                 results are labeled as estimates",
+            );
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                ui.label("Storage type:");
+                let selected = app
+                    .scan_options
+                    .storage
+                    .map_or("Auto-detect", StorageKind::label);
+                egui::ComboBox::from_id_source("storage_kind")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut app.scan_options.storage, None, "Auto-detect");
+                        for kind in [StorageKind::Ssd, StorageKind::Hdd, StorageKind::Network] {
+                            ui.selectable_value(
+                                &mut app.scan_options.storage,
+                                Some(kind),
+                                kind.label(),
+                            );
+                        }
+                    });
+            })
+            .response
+            .on_hover_text("Picks how many folders are read in parallel");
+            ui.checkbox(
+                &mut app.scan_options.tuning.background,
+                "Scan at low priority",
+            )
+            .on_hover_text("Lets other programs use the disk and CPU first");
+            ui.horizontal(|ui| {
+                ui.label("Pause after each entry:");
+                let tuning = &mut app.scan_options.tuning;
+                let mut micros = tuning.entry_delay.map_or(0, |d| d.as_micros() as u64);
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut micros)
+                            .range(0..=100_000)
+                            .suffix(" µs"),
+                    )
+                    .changed()
+                {
+                    tuning.entry_delay =
+                        (micros > 0).then(|| std::time::Duration::from_micros(micros));
+                }
+            })
+            .response
+            .on_hover_text("Slows the scan down to leave the disk to other programs; 0 for none");
+            if ui
+                .checkbox(
+                    &mut app.watch_changes,
+                    "Update the tree live as files change",
+                )
+                .changed()
+            {
+                if app.watch_changes && app.tree.is_some() {
+                    app.start_watching();
+                } else {
+                    app.stop_watching();
+                }
+            }
+            ui.horizontal(|ui| {
+                ui.label("Alarm when a folder grows faster than:");
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut app.growth_alarm_input)
+                        .hint_text("e.g. 1 GB/hour")
+                        .desired_width(100.0),
+                );
+                if response.changed() {
+                    app.growth_alarm_error = app.set_growth_alarm().err();
+                }
+                if let Some(err) = &app.growth_alarm_error {
+                    ui.colored_label(ERROR_COLOR, err);
+                }
+            })
+            .response
+            .on_hover_text(
+                "Checked against live updates while the tree is watched; empty for none",
+            );
+
+            ui.horizontal(|ui| {
+                ui.label("Reuse results when reopening a folder within");
+                ui.add(
+                    egui::DragValue::new(&mut app.reuse_window_mins)
+                        .range(0..=1440)
+                        .suffix(" min"),
+                );
+            })
+            .response
+            .on_hover_text("Only with unchanged settings; 0 always rescans");
+
+            ui.separator();
+            ui.strong("Name matching");
+            let search_matching = app.search_matching;
+            name_matching_row(ui, "Search:", &mut app.search_matching);
+            if app.search_matching != search_matching && app.search_filter.is_some() {
+                app.apply_search();
+            }
+            name_matching_row(ui, "Duplicates:", &mut app.dedupe_matching);
+
+            ui.separator();
+            ui.strong("Folder tree");
+            ui.horizontal(|ui| {
+                ui.label("\"Expand all\" opens up to");
+                ui.add(
+                    egui::DragValue::new(&mut app.expand_depth)
+                        .range(1..=64)
+                        .suffix(" levels"),
+                );
+            })
+            .response
+            .on_hover_text("Keeps expanding a huge branch from listing all of it");
+
+            ui.separator();
+            ui.strong("Paths");
+            ui.checkbox(
+                &mut app.relative_paths,
+                "Show paths relative to the scanned folder",
+            )
+            .on_hover_text("Also used for exports; keeps reports short and private");
+
+            ui.separator();
+            ui.strong("Dates");
+            date_format_row(ui, &mut app.date_format);
+
+            ui.separator();
+            ui.strong("Reports");
+            report_locale_row(ui, &mut app.export_locale);
+
+            ui.separator();
+            ui.strong("Archive destination");
+            ui.label("\"Move to Archive\" moves items here, keeping their folder structure.");
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut app.archive_dir)
+                    .on_hover_text("Local folder or network share path");
+                if ui.button("Browse...").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                        app.archive_dir = path.display().to_string();
+                    }
+                }
+            });
+            ui.checkbox(
+                &mut app.transfer_options.verify,
+                "Verify copies with checksums before removing the source",
+            );
+
+            ui.separator();
+            ui.strong("File manager");
+            let installed = *app
+                .context_menu
+                .get_or_insert_with(integration::is_installed);
+            let mut on = installed;
+            ui.checkbox(
+                &mut on,
+                format!("Offer \"{}\" on folders", integration::MENU_LABEL),
+            )
+            .on_hover_text(
+                "Right-click a folder in Explorer, Finder, Dolphin or Nemo, or pick it \
+                 from Scripts in Files, to scan it here",
+            );
+            if on != installed {
+                app.set_context_menu(on);
+            }
+        });
+
+    if let Some(index) = remove {
+        app.scan_options.excludes.remove(index);
+    }
+    if !open {
+        app.show_settings = false;
+    }
+}
+
+fn show_advisor_window(ctx: &egui::Context, app: &mut AppState) {
+    if !app.show_advisor {
+        return;
+    }
+
+    let mut open = true;
+    let mut propose = false;
+    let mut add_all = false;
+    let mut add_empty = false;
+    egui::Window::new("Free Space")
+        .collapsible(false)
+        .resizable(true)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Space needed:");
+                let resp = ui.text_edit_singleline(&mut app.advisor_target);
+                let submitted = resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if ui.button("Propose").clicked() || submitted {
+                    propose = true;
+                }
+            });
+            ui.label("e.g. 20 GB. Caches come first, then duplicates, then old large files.");
+            if ui
+                .button("Add Empty Files and Folders to Cart")
+                .on_hover_text("Zero-byte files, and folders with no files anywhere in them")
+                .clicked()
+            {
+                add_empty = true;
+            }
+            let (Some(advice), Some(tree)) = (&app.advice, &app.tree) else {
+                return;
+            };
+            ui.separator();
+            if advice.candidates.is_empty() {
+                ui.label("Nothing to propose.");
+                return;
+            }
+            ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                egui::Grid::new("advice_grid").striped(true).show(ui, |ui| {
+                    for candidate in &advice.candidates {
+                        ui.label(human_bytes(candidate.bytes));
+                        ui.label(match candidate.reason {
+                            Reason::OldLargeFile { age_days } => {
+                                format!("not modified in {age_days} days")
+                            }
+                            Reason::Duplicate { original } => format!(
+                                "copy of {}",
+                                app.shown_path(&tree.path(original)).display()
+                            ),
+                            Reason::Cache => candidate.reason.label().to_string(),
+                        });
+                        ui.label(
+                            app.shown_path(&tree.path(candidate.id))
+                                .display()
+                                .to_string(),
+                        );
+                        ui.end_row();
+                    }
+                });
+            });
+            ui.separator();
+            let summary = format!(
+                "{} of {} requested",
+                human_bytes(advice.total),
+                human_bytes(advice.target)
+            );
+            if advice.meets_target() {
+                ui.label(summary);
+            } else {
+                ui.colored_label(Color32::from_rgb(200, 120, 40), summary + " (not enough)");
+            }
+            if ui.button("Add All to Cleanup Cart").clicked() {
+                add_all = true;
+            }
+        });
+
+    if propose {
+        app.run_advisor();
+    }
+    if add_all {
+        app.add_advice_to_cart();
+    }
+    if add_empty {
+        app.add_empty_to_cart();
+    }
+    if !open {
+        app.show_advisor = false;
+    }
+}
+
+fn show_cart_window(ctx: &egui::Context, app: &mut AppState) {
+    if !app.show_cart {
+        return;
+    }
+
+    let mut open = true;
+    let mut remove = None;
+    let mut clean = false;
+    egui::Window::new("Cleanup Cart")
+        .collapsible(false)
+        .resizable(true)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            let Some(tree) = &app.tree else {
+                ui.label("Scan a directory first.");
+                return;
+            };
+            if app.cart.is_empty() {
+                ui.label("The cart is empty. Add items from their context menu or Free Space.");
+                return;
+            }
+            ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                for (index, id) in app.cart.iter().enumerate() {
+                    let node = tree.node(*id);
+                    ui.horizontal(|ui| {
+                        if ui.small_button("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                        ui.label(human_bytes(node.size));
+                        ui.label(app.shown_path(&tree.path(*id)).display().to_string());
+                    });
+                }
+            });
+            ui.separator();
+            ui.label(format!(
+                "{} item(s), {}",
+                app.cart.len(),
+                human_bytes(app.cart_total())
+            ));
+            if ui
+                .add(egui::Button::new("Delete All").fill(Color32::from_rgb(170, 50, 50)))
+                .clicked()
+            {
+                clean = true;
+            }
+        });
+
+    if let Some(index) = remove {
+        app.cart.remove(index);
+    }
+    if clean {
+        app.clean_cart();
+    }
+    if !open {
+        app.show_cart = false;
+    }
+}
+
+fn show_compare_window(ctx: &egui::Context, app: &mut AppState) {
+    const SHOWN: usize = 500;
+    if !app.show_compare {
+        return;
+    }
+    let Some(comparison) = &app.comparison else {
+        app.show_compare = false;
+        return;
+    };
+
+    let mut open = true;
+    let mut reveal = None;
+    egui::Window::new("Compare")
+        .collapsible(false)
+        .resizable(true)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            let diff = &comparison.diff;
+            ui.label(format!("Against {}", comparison.baseline.display()));
+            ui.label(format!(
+                "Total {} → {} ({})",
+                human_bytes(diff.old_total),
+                human_bytes(diff.new_total),
+                signed_bytes(diff.delta())
+            ));
+            ui.separator();
+            if diff.changes.is_empty() {
+                ui.label("No differences.");
+                return;
+            }
+            ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                egui::Grid::new("compare_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for change in diff.changes.iter().take(SHOWN) {
+                            let color = match change.change {
+                                ChangeKind::Added | ChangeKind::Grown => {
+                                    Color32::from_rgb(200, 110, 80)
+                                }
+                                ChangeKind::Removed | ChangeKind::Shrunk => {
+                                    Color32::from_rgb(100, 170, 110)
+                                }
+                            };
+                            ui.colored_label(color, signed_bytes(change.delta()));
+                            ui.label(change.change.label());
+                            let path = app.shown_path(&change.path).display().to_string();
+                            if change.change == ChangeKind::Removed {
+                                ui.label(path);
+                            } else if ui.link(path).clicked() {
+                                reveal = Some(change.path.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+            if diff.changes.len() > SHOWN {
+                ui.label(format!(
+                    "{} more changes not shown",
+                    diff.changes.len() - SHOWN
+                ));
+            }
+        });
+
+    if let Some(id) = reveal.and_then(|p| app.tree.as_ref()?.find(&p)) {
+        app.selected = Some(id);
+        app.request_properties(id);
+    }
+    if !open {
+        app.show_compare = false;
+    }
+}
+
+fn show_review_window(ctx: &egui::Context, app: &mut AppState) {
+    if !app.show_review {
+        return;
+    }
+    if app.settle_review() {
+        app.reveal_reviewed();
+    }
+    let Some(review) = &app.review else {
+        app.show_review = false;
+        return;
+    };
+
+    let mut open = true;
+    let mut action = None;
+    let mut reveal = false;
+    let mut open_cart = false;
+    egui::Window::new("Review Changes")
+        .collapsible(false)
+        .resizable(false)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            let Some((change, _)) = app.reviewed_change() else {
+                ui.label(format!(
+                    "Reviewed {} changes: {} tagged, {} deleted, {} dismissed.",
+                    review.changes.len(),
+                    review.tagged,
+                    review.deleted,
+                    review.dismissed
+                ));
+                if review.tagged > 0 && ui.button("Open Cart").clicked() {
+                    open_cart = true;
+                }
+                return;
+            };
+            ui.label(format!(
+                "Change {} of {}",
+                review.position + 1,
+                review.changes.len()
+            ));
+            ui.separator();
+            let what = match (change.change, change.is_dir) {
+                (ChangeKind::Added, true) => "New folder",
+                (ChangeKind::Added, false) => "New file",
+                (_, true) => "Folder grew",
+                (_, false) => "File grew",
+            };
+            ui.horizontal(|ui| {
+                ui.colored_label(
+                    Color32::from_rgb(200, 110, 80),
+                    signed_bytes(change.delta()),
+                );
+                ui.label(what);
+            });
+            let path = app.shown_path(&change.path).display().to_string();
+            if ui.link(path).on_hover_text("Show it again").clicked() {
+                reveal = true;
+            }
+            if change.change == ChangeKind::Grown {
+                ui.label(format!(
+                    "{} → {}",
+                    human_bytes(change.old_bytes),
+                    human_bytes(change.new_bytes)
+                ));
+            }
+            ui.separator();
+            // Selection must stay put until a deletion is confirmed
+            ui.add_enabled_ui(!review.is_deleting(), |ui| {
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Tag")
+                        .on_hover_text("Add it to the cart")
+                        .clicked()
+                    {
+                        action = Some(ReviewAction::Tag);
+                    }
+                    if ui.button("Delete…").clicked() {
+                        action = Some(ReviewAction::Delete);
+                    }
+                    if ui.button("Dismiss").clicked() {
+                        action = Some(ReviewAction::Dismiss);
+                    }
+                });
+            });
+        });
+
+    if let Some(action) = action {
+        app.decide_review(action);
+    }
+    if reveal {
+        app.reveal_reviewed();
+    }
+    if open_cart {
+        app.show_cart = true;
+        app.show_review = false;
+    }
+    if !open {
+        app.show_review = false;
+    }
+}
+
+fn signed_bytes(delta: i128) -> String {
+    let sign = if delta < 0 { '-' } else { '+' };
+    format!("{sign}{}", human_bytes(delta.unsigned_abs()))
+}
+
+fn show_timing_window(ctx: &egui::Context, app: &mut AppState) {
+    if !app.show_timing {
+        return;
+    }
+
+    let mut open = true;
+    let mut export_to = None;
+    let elapsed = app
+        .tree
+        .as_ref()
+        .and_then(|t| t.meta.profile.as_ref())
+        .map(|p| p.elapsed);
+    egui::Window::new("Slowest directories")
+        .default_width(640.0)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if let Some(elapsed) = elapsed {
+                    ui.label(format!("Scan took {elapsed:.1?}"));
+                }
+                if ui.button("Export CSV...").clicked() {
+                    export_to = rfd::FileDialog::new()
+                        .set_file_name("treesize_timing.csv")
+                        .save_file();
+                }
+            });
+            ui.small(
+                "A high time per entry suggests slow storage or an on-access scanner; \
+                 a high entry count, a directory that is simply huge.",
+            );
+            ui.separator();
+            ScrollArea::vertical()
+                .id_source("timing_scroll")
+                .auto_shrink([false; 2])
+                .show(ui, |ui| {
+                    egui::Grid::new("timing_grid")
+                        .striped(true)
+                        .num_columns(4)
+                        .show(ui, |ui| {
+                            ui.strong("Time");
+                            ui.strong("Entries");
+                            ui.strong("Per entry");
+                            ui.strong("Directory");
+                            ui.end_row();
+                            for dir in &app.slowest_dirs {
+                                ui.label(format!("{:.1?}", dir.subtree));
+                                ui.label(dir.subtree_entries.to_string());
+                                ui.label(format!("{:.1?}", dir.per_entry()));
+                                ui.label(app.shown_path(&dir.path).display().to_string())
+                                    .on_hover_text(format!(
+                                        "{} entries directly inside, {:.1?}",
+                                        dir.entries, dir.own
+                                    ));
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+
+    if let Some(path) = export_to {
+        app.operation_status = Some(
+            match treesize_core::export::export_slowest_csv(&app.slowest_dirs, &path) {
+                Ok(()) => format!("Timing exported to {}", path.display()),
+                Err(err) => format!("Timing export failed: {err}"),
+            },
+        );
+    }
+    if !open {
+        app.show_timing = false;
+    }
+}
+
+fn show_history_window(ctx: &egui::Context, app: &mut AppState) {
+    const SHOWN: usize = 30;
+    if !app.show_history {
+        return;
+    }
+    app.refresh_history();
+
+    let mut open = true;
+    egui::Window::new("History")
+        .default_width(640.0)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            let Some((path, trends)) = &app.history_trends else {
+                ui.label("No scan loaded.");
+                return;
+            };
+            let Some(trend) = trends.iter().find(|t| t.path == *path) else {
+                ui.label(format!(
+                    "No scans of {} recorded yet.",
+                    app.shown_path(path).display()
+                ));
+                return;
+            };
+            ui.heading(trend.to_string());
+            ui.label(format!(
+                "{} scans since {}",
+                trend.points.len(),
+                trend.points[0].at.format("%Y-%m-%d")
+            ));
+            draw_size_history(ui, trend);
+            ui.separator();
+            ui.strong("Fastest growing below it");
+            ScrollArea::vertical()
+                .id_source("history_scroll")
+                .auto_shrink([false; 2])
+                .show(ui, |ui| {
+                    egui::Grid::new("history_grid")
+                        .striped(true)
+                        .num_columns(4)
+                        .show(ui, |ui| {
+                            ui.strong("Per week");
+                            ui.strong("Size");
+                            ui.strong("Scans");
+                            ui.strong("Folder");
+                            ui.end_row();
+                            for trend in trends.iter().filter(|t| t.path != *path).take(SHOWN) {
+                                ui.label(match trend.per_week() {
+                                    Some(rate) if rate < 0.0 => {
+                                        format!("-{}", history::rate_text(-rate))
+                                    }
+                                    Some(rate) => format!("+{}", history::rate_text(rate)),
+                                    None => "-".to_string(),
+                                });
+                                ui.label(human_bytes(trend.latest().map_or(0, |p| p.bytes)));
+                                ui.label(trend.points.len().to_string());
+                                ui.label(app.shown_path(&trend.path).display().to_string());
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+    if !open {
+        app.show_history = false;
+    }
+}
+
+fn show_file_counts_window(ctx: &egui::Context, app: &mut AppState) {
+    const SHOWN: usize = 200;
+    if !app.show_file_counts {
+        return;
+    }
+    app.refresh_file_counts();
+
+    let mut open = true;
+    let mut clicked = None;
+    egui::Window::new("File Counts")
+        .default_width(560.0)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            let (Some(tree), Some((_, heavy))) = (&app.tree, &app.heavy_dirs) else {
+                ui.label("No scan loaded.");
+                return;
             };
-            ui.heading(format!("Delete {kind_text}?"));
-            ui.label(format!("Name: {item_label}"));
-            ui.label(format!("Path: {path_display}"));
-            if !size_label.is_empty() {
-                ui.label(format!("Size: {size_label}"));
-            }
-            ui.separator();
-            ui.label("This action cannot be undone.");
-            ui.horizontal(|ui| {
-                if ui.button("Cancel").clicked() {
-                    cancel = true;
+            match app.inode_usage {
+                Some(usage) => {
+                    ui.add(egui::ProgressBar::new(usage.used_fraction()).text(format!(
+                        "{} of {} inodes used, {} free",
+                        usage.used(),
+                        usage.total,
+                        usage.free
+                    )));
                 }
-                if ui
-                    .add(egui::Button::new("Delete").fill(Color32::from_rgb(170, 50, 50)))
-                    .clicked()
-                {
-                    confirm = true;
+                None => {
+                    ui.label("This filesystem has no fixed number of inodes.");
                 }
+            }
+            if let Some(quota) = app.inode_quota {
+                let limit =
+                    |limit: Option<u64>| limit.map_or("none".to_string(), |n| n.to_string());
+                ui.label(format!(
+                    "Your quota: {} files used, soft limit {}, hard limit {}",
+                    quota.used,
+                    limit(quota.soft),
+                    limit(quota.hard)
+                ));
+            }
+            ui.horizontal(|ui| {
+                ui.label("Flag folders with at least");
+                ui.add(
+                    egui::DragValue::new(&mut app.count_thresholds.files)
+                        .speed(100.0)
+                        .suffix(" files"),
+                );
+                ui.label("or");
+                ui.add(
+                    egui::DragValue::new(&mut app.count_thresholds.entries)
+                        .speed(100.0)
+                        .suffix(" entries"),
+                )
+                .on_hover_text("Entries directly inside the folder, which slow down listing it");
             });
+            ui.separator();
+            if heavy.is_empty() {
+                ui.label("No folder is over either limit.");
+                return;
+            }
+            ScrollArea::vertical()
+                .id_source("file_counts_scroll")
+                .auto_shrink([false; 2])
+                .show(ui, |ui| {
+                    egui::Grid::new("file_counts_grid")
+                        .striped(true)
+                        .num_columns(3)
+                        .show(ui, |ui| {
+                            ui.strong("Files");
+                            ui.strong("Entries");
+                            ui.strong("Folder");
+                            ui.end_row();
+                            for dir in heavy.iter().take(SHOWN) {
+                                let count = |count: u64, over: bool| {
+                                    let text = egui::RichText::new(count.to_string());
+                                    if over {
+                                        text.color(ERROR_COLOR)
+                                    } else {
+                                        text
+                                    }
+                                };
+                                ui.label(count(dir.files, dir.over_files));
+                                ui.label(count(dir.entries, dir.over_entries));
+                                let path = &tree.path(dir.id);
+                                if ui
+                                    .link(app.shown_path(path).display().to_string())
+                                    .clicked()
+                                {
+                                    clicked = Some(dir.id);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    if heavy.len() > SHOWN {
+                        ui.label(format!("… {} more", heavy.len() - SHOWN));
+                    }
+                });
         });
-
-    if confirm {
-        app.delete_selected_and_rescan();
-        app.pending_delete = None;
-        ctx.request_repaint();
-    } else if cancel || !open {
-        app.pending_delete = None;
+    if let Some(id) = clicked {
+        app.selected = Some(id);
+        app.current_dir = Some(id);
+    }
+    if !open {
+        app.show_file_counts = false;
     }
 }
 
-fn show_properties_panel(ctx: &egui::Context, app: &mut AppState) {
-    let properties_id = match app.pending_properties {
-        Some(id) => id,
-        None => return,
-    };
-
-    let Some(tree) = app.tree.as_ref() else {
-        app.pending_properties = None;
+/// Line chart of a folder's size in each recorded scan.
+fn draw_size_history(ui: &mut Ui, trend: &Trend) {
+    let (rect, response) =
+        ui.allocate_exact_size(egui::vec2(ui.available_width(), 160.0), Sense::hover());
+    let painter = ui.painter().with_clip_rect(rect);
+    painter.rect_filled(rect, 4.0, ui.visuals().extreme_bg_color);
+    let plot = rect.shrink2(egui::vec2(12.0, 18.0));
+    let (Some(first), Some(last)) = (trend.points.first(), trend.points.last()) else {
         return;
     };
-    let Some(node) = tree.nodes.get(properties_id.0 as usize) else {
-        app.pending_properties = None;
+    let span = (last.at - first.at).num_seconds().max(1) as f32;
+    let low = trend.points.iter().map(|p| p.bytes).min().unwrap_or(0);
+    let high = trend.points.iter().map(|p| p.bytes).max().unwrap_or(0);
+    let range = (high - low).max(1) as f32;
+    let points: Vec<Pos2> = trend
+        .points
+        .iter()
+        .map(|p| {
+            let x = if trend.points.len() > 1 {
+                (p.at - first.at).num_seconds() as f32 / span
+            } else {
+                0.5
+            };
+            let y = (p.bytes - low) as f32 / range;
+            Pos2::new(
+                plot.left() + x * plot.width(),
+                plot.bottom() - y * plot.height(),
+            )
+        })
+        .collect();
+    let color = palette_color(0);
+    painter.add(egui::Shape::line(
+        points.clone(),
+        egui::Stroke::new(2.0, color),
+    ));
+    for &point in &points {
+        painter.circle_filled(point, 3.0, color);
+    }
+    let small = TextStyle::Small.resolve(ui.style());
+    let text = ui.visuals().weak_text_color();
+    painter.text(
+        rect.left_top() + egui::vec2(4.0, 2.0),
+        Align2::LEFT_TOP,
+        human_bytes(high),
+        small.clone(),
+        text,
+    );
+    painter.text(
+        rect.left_bottom() + egui::vec2(4.0, -2.0),
+        Align2::LEFT_BOTTOM,
+        human_bytes(low),
+        small.clone(),
+        text,
+    );
+    painter.text(
+        rect.right_bottom() + egui::vec2(-4.0, -2.0),
+        Align2::RIGHT_BOTTOM,
+        last.at.format("%Y-%m-%d").to_string(),
+        small,
+        text,
+    );
+
+    let nearest = response.hover_pos().and_then(|pos| {
+        points
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (a.x - pos.x).abs().total_cmp(&(b.x - pos.x).abs()))
+            .map(|(i, _)| i)
+    });
+    if let Some(index) = nearest {
+        let point = &trend.points[index];
+        painter.circle_stroke(points[index], 5.0, egui::Stroke::new(1.5, Color32::WHITE));
+        egui::show_tooltip(
+            ui.ctx(),
+            ui.layer_id(),
+            Id::new("size_history_tooltip"),
+            |ui| {
+                ui.label(point.at.format("%Y-%m-%d %H:%M").to_string());
+                ui.label(format!(
+                    "{}, {} files",
+                    human_bytes(point.bytes),
+                    point.files
+                ));
+            },
+        );
+    }
+}
+
+fn show_activity_window(ctx: &egui::Context, app: &mut AppState) {
+    if !app.show_activity {
         return;
-    };
+    }
 
     let mut open = true;
-    egui::Window::new("Properties")
-        .anchor(Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
-        .collapsible(false)
-        .resizable(false)
+    let mut reload = false;
+    let mut export_to = None;
+    egui::Window::new("Activity")
+        .default_width(640.0)
         .open(&mut open)
         .show(ctx, |ui| {
-            ui.heading(&node.name);
-            show_node_metadata(ui, node);
-            if matches!(node.kind, NodeKind::Dir) {
-                ui.label(format!("Contains: {} files", node.file_count));
-            }
+            ui.horizontal(|ui| {
+                ui.label(format!("Log: {}", app.journal.path().display()));
+                if ui.button("Reload").clicked() {
+                    reload = true;
+                }
+                if ui.button("Export CSV...").clicked() {
+                    export_to = rfd::FileDialog::new()
+                        .set_file_name("treesize_activity.csv")
+                        .save_file();
+                }
+            });
             ui.separator();
-            if ui.button("Open Externally").clicked() {
-                let _ = open::that(&node.path);
+            if app.activity.is_empty() {
+                ui.label("No operations recorded yet.");
+                return;
             }
+            ScrollArea::vertical()
+                .id_source("activity_scroll")
+                .auto_shrink([false; 2])
+                .show(ui, |ui| {
+                    egui::Grid::new("activity_grid")
+                        .striped(true)
+                        .num_columns(5)
+                        .show(ui, |ui| {
+                            ui.strong("Time");
+                            ui.strong("Operation");
+                            ui.strong("Path");
+                            ui.strong("Size");
+                            ui.strong("Outcome");
+                            ui.end_row();
+                            for entry in app.activity.iter().rev() {
+                                ui.label(app.date_format.format(entry.timestamp.into()));
+                                ui.label(entry.operation.label());
+                                let path = ui.label(entry.source.display().to_string());
+                                if let Some(dest) = &entry.destination {
+                                    path.on_hover_text(format!("-> {}", dest.display()));
+                                }
+                                ui.label(human_bytes(entry.bytes));
+                                match &entry.outcome {
+                                    Outcome::Ok => ui.label("ok"),
+                                    Outcome::Failed(msg) => ui
+                                        .colored_label(Color32::from_rgb(200, 80, 80), "failed")
+                                        .on_hover_text(msg),
+                                };
+                                ui.end_row();
+                            }
+                        });
+                });
         });
 
+    if reload {
+        app.reload_activity();
+    }
+    if let Some(path) = export_to {
+        app.operation_status = Some(match app.journal.export_csv(&path) {
+            Ok(()) => format!("Activity exported to {}", path.display()),
+            Err(err) => format!("Activity export failed: {err}"),
+        });
+    }
     if !open {
-        app.pending_properties = None;
+        app.show_activity = false;
     }
 }
 
-fn collect_pie_slices(tree: &Tree, children: &[NodeId]) -> Vec<PieSlice> {
+/// Slices for `children`; with `ages`, each is coloured by the age bucket
+/// holding most of its bytes instead of by position.
+fn collect_pie_slices(
+    tree: &Tree,
+    children: &[NodeId],
+    ages: Option<&[AgeHistogram]>,
+    relative: bool,
+) -> Vec<PieSlice> {
     let mut items: Vec<_> = children
         .iter()
         .map(|cid| {
-            let node = &tree.nodes[cid.0 as usize];
+            let node = tree.node(*cid);
             (*cid, node)
         })
         .filter(|(_, node)| node.size > 0)
@@ -771,7 +3288,7 @@ fn collect_pie_slices(tree: &Tree, children: &[NodeId]) -> Vec<PieSlice> {
         return Vec::new();
     }
 
-    items.sort_by(|a, b| b.1.size.cmp(&a.1.size));
+    items.sort_by_key(|(_, node)| std::cmp::Reverse(node.size));
 
     let total: f64 = items.iter().map(|(_, node)| node.size as f64).sum();
     if total == 0.0 {
@@ -781,6 +3298,7 @@ fn collect_pie_slices(tree: &Tree, children: &[NodeId]) -> Vec<PieSlice> {
     let mut slices = Vec::new();
     let mut other_bytes: u128 = 0;
     let mut other_ratio = 0.0;
+    let mut other_share = 0.0;
     let mut other_files: u64 = 0;
 
     for (index, (id, node)) in items.iter().enumerate() {
@@ -793,21 +3311,36 @@ fn collect_pie_slices(tree: &Tree, children: &[NodeId]) -> Vec<PieSlice> {
         if slices.len() < MAX_PRIMARY_SLICES
             && (index < MAX_PRIMARY_SLICES || ratio >= MIN_SLICE_RATIO)
         {
-            let color = palette_color(slices.len());
+            let color = match ages {
+                Some(ages) => ages
+                    .get(id.0 as usize)
+                    .and_then(|h| h.dominant())
+                    .map(age_color)
+                    .unwrap_or(Color32::from_gray(140)),
+                None => palette_color(slices.len()),
+            };
+            let path = tree.path(*id);
             slices.push(PieSlice {
                 id: Some(*id),
-                name: node.name.clone(),
+                name: node.name.to_string(),
                 kind: node.kind.clone(),
                 bytes: node.size,
                 ratio,
+                share: tree.share_of_parent(*id),
                 color,
-                path: node.path.clone(),
+                shown_path: if relative {
+                    tree.relative_path(&path).to_path_buf()
+                } else {
+                    path.clone()
+                },
+                path,
                 modified: node.modified,
                 file_count,
             });
         } else {
             other_bytes += node.size;
             other_ratio += ratio;
+            other_share += tree.share_of_parent(*id);
             other_files += file_count;
         }
     }
@@ -819,8 +3352,10 @@ fn collect_pie_slices(tree: &Tree, children: &[NodeId]) -> Vec<PieSlice> {
             kind: NodeKind::Dir,
             bytes: other_bytes,
             ratio: other_ratio,
+            share: other_share,
             color: Color32::from_gray(110),
             path: PathBuf::new(),
+            shown_path: PathBuf::new(),
             modified: None,
             file_count: other_files,
         });
@@ -829,16 +3364,50 @@ fn collect_pie_slices(tree: &Tree, children: &[NodeId]) -> Vec<PieSlice> {
     slices
 }
 
+/// Size per file type, as fractions of `base` if given, else of all of them.
+fn draw_ext_stats(ui: &mut Ui, stats: &[ExtStat], base: Option<u128>) {
+    const SHOWN: usize = 12;
+    let total: u128 = base.unwrap_or_else(|| stats.iter().map(|s| s.bytes).sum());
+    if total == 0 {
+        ui.label("No files here.");
+        return;
+    }
+    egui::Grid::new("ext_stats_grid").show(ui, |ui| {
+        for stat in stats.iter().take(SHOWN) {
+            let fraction = share(stat.bytes, total);
+            ui.monospace(stat.label());
+            ui.add(
+                egui::ProgressBar::new(fraction as f32)
+                    .desired_width(160.0)
+                    .text(human_percent(fraction)),
+            );
+            ui.label(human_bytes(stat.bytes));
+            ui.label(format!("{} files", stat.files));
+            ui.end_row();
+        }
+    });
+    if stats.len() > SHOWN {
+        let rest = &stats[SHOWN..];
+        ui.label(format!(
+            "{} other types: {}",
+            rest.len(),
+            human_bytes(rest.iter().map(|s| s.bytes).sum::<u128>())
+        ));
+    }
+}
+
 fn draw_pie_chart(
     ui: &mut Ui,
     slices: &[PieSlice],
     selected: Option<NodeId>,
     current: Option<NodeId>,
+    dates: &DateFormat,
+    baseline: Option<&TreeNode>,
+    menu: bool,
 ) -> PieActions {
     let mut actions = PieActions::default();
 
     let legend_width = 220.0;
-    let tooltip_id = Id::new("pie_slice_tooltip");
     ui.horizontal(|ui| {
         let available_width = ui.available_width();
         let available_height = ui.available_height();
@@ -878,9 +3447,15 @@ fn draw_pie_chart(
         }
 
         if let Some(idx) = hovered_index {
-            egui::show_tooltip(ui.ctx(), ui.layer_id(), tooltip_id, |ui| {
-                show_slice_metadata(ui, &slices[idx]);
-            });
+            show_slice_tooltip(ui, &slices[idx], dates, baseline);
+        }
+        if menu {
+            slice_context_menu(
+                ui,
+                &response,
+                hovered_index.map(|i| &slices[i]),
+                &mut actions,
+            );
         }
 
         let mut start_angle = 0.0f32;
@@ -938,20 +3513,26 @@ fn draw_pie_chart(
         ui.add_space(12.0);
         ui.vertical(|ui| {
             ui.set_min_width(legend_width);
-            ui.strong("Breakdown");
+            ui.strong(match baseline {
+                Some(baseline) => format!("Breakdown, % of {}", baseline.name),
+                None => "Breakdown".to_string(),
+            });
             ui.add_space(6.0);
             for slice in slices {
-                let percentage = slice.ratio * 100.0;
+                let percentage = match baseline {
+                    Some(baseline) => share(slice.bytes, baseline.size),
+                    None => slice.share,
+                };
                 ui.horizontal(|ui| {
                     let (color_rect, _color_resp) =
                         ui.allocate_exact_size(egui::vec2(12.0, 12.0), Sense::hover());
                     ui.painter().rect_filled(color_rect, 2.0, slice.color);
                     ui.add_space(4.0);
                     ui.label(format!(
-                        "{name} - {size} - {percent:.1}%",
+                        "{name} - {size} - {percent}",
                         name = &slice.name,
                         size = format_gb(slice.bytes),
-                        percent = percentage
+                        percent = human_percent(percentage)
                     ));
                 });
             }
@@ -961,6 +3542,71 @@ fn draw_pie_chart(
     actions
 }
 
+/// The details of a hovered chart slice, with its share of `baseline`.
+fn show_slice_tooltip(ui: &Ui, slice: &PieSlice, dates: &DateFormat, baseline: Option<&TreeNode>) {
+    egui::show_tooltip(
+        ui.ctx(),
+        ui.layer_id(),
+        Id::new("pie_slice_tooltip"),
+        |ui| {
+            show_slice_metadata(ui, slice, dates);
+            if let Some(baseline) = baseline {
+                ui.label(share_text(slice.bytes, baseline));
+            }
+        },
+    );
+}
+
+/// Right-click menu of a chart, for the slice that was under the pointer
+/// when it opened. "Other" has none.
+fn slice_context_menu(
+    ui: &Ui,
+    response: &egui::Response,
+    hovered: Option<&PieSlice>,
+    actions: &mut PieActions,
+) {
+    let target_id = response.id.with("menu_target");
+    if response.secondary_clicked() {
+        let target = hovered.and_then(|slice| Some((slice.id?, slice.kind.clone())));
+        ui.data_mut(|data| data.insert_temp(target_id, target));
+    }
+    let Some((node_id, kind)) = ui
+        .data(|data| data.get_temp::<Option<(NodeId, NodeKind)>>(target_id))
+        .flatten()
+    else {
+        return;
+    };
+    response.context_menu(|ui| {
+        if matches!(kind, NodeKind::Dir) && ui.button("Open").clicked() {
+            actions.select = Some(node_id);
+            actions.open = Some(node_id);
+            ui.close_menu();
+        }
+        // Entries inside an archive aren't on disk to remove
+        if !matches!(kind, NodeKind::ArchiveEntry) {
+            if ui.button("Delete").clicked() {
+                actions.select = Some(node_id);
+                actions.delete = Some(node_id);
+                ui.close_menu();
+            }
+            if ui.button("Move to Archive").clicked() {
+                actions.select = Some(node_id);
+                actions.archive = Some(node_id);
+                ui.close_menu();
+            }
+            if ui.button("Add to Cleanup Cart").clicked() {
+                actions.add_to_cart = Some(node_id);
+                ui.close_menu();
+            }
+        }
+        if ui.button("Properties").clicked() {
+            actions.select = Some(node_id);
+            actions.properties = Some(node_id);
+            ui.close_menu();
+        }
+    });
+}
+
 fn slice_at_pos(
     slices: &[PieSlice],
     pos: Pos2,
@@ -1009,6 +3655,236 @@ fn wedge_points(center: Pos2, radius: f32, start_angle: f32, sweep: f32) -> Vec<
     points
 }
 
+/// One segment of a sunburst ring.
+struct SunburstSegment {
+    slice: PieSlice,
+    /// 0 for the children of the centre, 1 for theirs, and so on.
+    ring: usize,
+    start: f32,
+    sweep: f32,
+}
+
+/// Segments for the [`SUNBURST_RINGS`] levels below `root`, whose first
+/// ring is `children`. Each ring is split like the pie of its parent, over
+/// the share of the parent its listed entries make up.
+fn collect_sunburst(
+    tree: &Tree,
+    root: NodeId,
+    children: &[NodeId],
+    ages: Option<&[AgeHistogram]>,
+    filter: Option<&SearchFilter>,
+    relative: bool,
+) -> Vec<SunburstSegment> {
+    let mut segments = Vec::new();
+    let mut pending = vec![(
+        root,
+        children.to_vec(),
+        0,
+        0.0f32,
+        std::f32::consts::TAU,
+        None,
+    )];
+    while let Some((parent, children, ring, start, sweep, parent_color)) = pending.pop() {
+        let slices = collect_pie_slices(tree, &children, ages, relative);
+        let listed: u128 = slices.iter().map(|s| s.bytes).sum();
+        let size = tree.node(parent).size.max(listed);
+        let span = sweep * (listed as f64 / size.max(1) as f64) as f32;
+        let mut angle = start;
+        for mut slice in slices {
+            let slice_sweep = slice.ratio as f32 * span;
+            if slice_sweep < MIN_SEGMENT_SWEEP {
+                angle += slice_sweep;
+                continue;
+            }
+            if let (Some(color), None, Some(_)) = (parent_color, ages, slice.id) {
+                slice.color = lighten(color, 22);
+            }
+            if let Some(id) = slice.id.filter(|_| ring + 1 < SUNBURST_RINGS) {
+                let mut below = tree.node(id).children.clone();
+                if let Some(filter) = filter {
+                    below.retain(|c| filter.shows(tree, *c));
+                }
+                if !below.is_empty() {
+                    pending.push((id, below, ring + 1, angle, slice_sweep, Some(slice.color)));
+                }
+            }
+            segments.push(SunburstSegment {
+                slice,
+                ring,
+                start: angle,
+                sweep: slice_sweep,
+            });
+            angle += slice_sweep;
+        }
+    }
+    segments
+}
+
+/// Draws `segments` around `root`. Clicking a folder's segment zooms in
+/// to it; clicking the centre zooms out to the parent.
+fn draw_sunburst(
+    ui: &mut Ui,
+    tree: &Tree,
+    root: NodeId,
+    segments: &[SunburstSegment],
+    selected: Option<NodeId>,
+    dates: &DateFormat,
+    baseline: Option<&TreeNode>,
+) -> PieActions {
+    let mut actions = PieActions::default();
+    let side = ui
+        .available_width()
+        .min(ui.available_height())
+        .max(220.0)
+        .min(ui.available_width() - 20.0)
+        .max(200.0);
+    let (chart_rect, response) = ui.allocate_exact_size(egui::vec2(side, side), Sense::click());
+    let painter = ui.painter().with_clip_rect(chart_rect);
+    let center = chart_rect.center();
+    let radius = (side / 2.0 - 14.0).max(0.0);
+    let hole = radius * 0.22;
+    let ring_width = (radius - hole) / SUNBURST_RINGS as f32;
+
+    let hovered_pos = response.hover_pos();
+    let hovered_index =
+        hovered_pos.and_then(|pos| segment_at_pos(segments, pos, center, hole, ring_width));
+    let on_centre = hovered_pos.is_some_and(|pos| pos.distance(center) <= hole);
+    let root_node = tree.node(root);
+
+    if response.clicked() {
+        if let Some(idx) = hovered_index {
+            let slice = &segments[idx].slice;
+            if let Some(id) = slice.id {
+                actions.select = Some(id);
+                if matches!(slice.kind, NodeKind::Dir) {
+                    actions.open = Some(id);
+                }
+            }
+        } else if on_centre {
+            actions.open = root_node.parent;
+        }
+    }
+    if let Some(idx) = hovered_index {
+        show_slice_tooltip(ui, &segments[idx].slice, dates, baseline);
+    } else if on_centre && root_node.parent.is_some() {
+        egui::show_tooltip(
+            ui.ctx(),
+            ui.layer_id(),
+            Id::new("pie_slice_tooltip"),
+            |ui| {
+                ui.label("Click to go up a level");
+            },
+        );
+    }
+    slice_context_menu(
+        ui,
+        &response,
+        hovered_index.map(|i| &segments[i].slice),
+        &mut actions,
+    );
+
+    let stroke = egui::Stroke::new(1.0, Color32::BLACK);
+    for (index, segment) in segments.iter().enumerate() {
+        let mut color = segment.slice.color;
+        if Some(index) == hovered_index {
+            color = lighten(color, 35);
+        }
+        if segment.slice.id.is_some() && selected == segment.slice.id {
+            color = lighten(color, 20);
+        }
+        let inner = hole + ring_width * segment.ring as f32;
+        let outer = inner + ring_width;
+        let outline = annulus_points(center, inner, outer, segment.start, segment.sweep);
+        let half = outline.len() / 2;
+        let mut mesh = egui::Mesh::default();
+        for &point in &outline {
+            mesh.colored_vertex(point, color);
+        }
+        // The outer arc runs forwards and the inner one back, so point i
+        // on the outer arc faces point len - 1 - i on the inner one
+        let last = outline.len() as u32 - 1;
+        for i in 0..half as u32 - 1 {
+            mesh.add_triangle(i, i + 1, last - i);
+            mesh.add_triangle(i + 1, last - i - 1, last - i);
+        }
+        painter.add(egui::Shape::mesh(mesh));
+        painter.add(egui::Shape::closed_line(outline, stroke));
+
+        let mid_radius = (inner + outer) / 2.0;
+        if segment.sweep * mid_radius > 60.0 {
+            let mid = segment.start + segment.sweep / 2.0;
+            painter.text(
+                Pos2::new(
+                    center.x + mid_radius * mid.cos(),
+                    center.y + mid_radius * mid.sin(),
+                ),
+                Align2::CENTER_CENTER,
+                truncate_middle(&segment.slice.name, 16),
+                TextStyle::Small.resolve(ui.style()),
+                Color32::WHITE,
+            );
+        }
+    }
+
+    let centre_color = if on_centre && root_node.parent.is_some() {
+        Color32::from_gray(70)
+    } else {
+        Color32::from_gray(50)
+    };
+    painter.circle(center, hole, centre_color, stroke);
+    painter.text(
+        center,
+        Align2::CENTER_CENTER,
+        format!(
+            "{}\n{}",
+            truncate_middle(&root_node.name, 14),
+            format_gb(root_node.size)
+        ),
+        TextStyle::Small.resolve(ui.style()),
+        Color32::WHITE,
+    );
+
+    actions
+}
+
+fn segment_at_pos(
+    segments: &[SunburstSegment],
+    pos: Pos2,
+    center: Pos2,
+    hole: f32,
+    ring_width: f32,
+) -> Option<usize> {
+    let dist = pos.distance(center);
+    if dist <= hole || ring_width <= 0.0 {
+        return None;
+    }
+    let ring = ((dist - hole) / ring_width) as usize;
+    let offset = pos - center;
+    let mut angle = offset.y.atan2(offset.x);
+    if angle < 0.0 {
+        angle += std::f32::consts::TAU;
+    }
+    segments.iter().position(|segment| {
+        segment.ring == ring && angle >= segment.start && angle <= segment.start + segment.sweep
+    })
+}
+
+/// Outline of a ring segment: the outer arc forwards, then the inner one
+/// back, with the same number of points on each.
+fn annulus_points(center: Pos2, inner: f32, outer: f32, start_angle: f32, sweep: f32) -> Vec<Pos2> {
+    let steps = ((sweep.abs() * outer) / 12.0).ceil().max(1.0) as usize;
+    let arc = |radius: f32, i: usize| {
+        let angle = start_angle + sweep * i as f32 / steps as f32;
+        Pos2::new(
+            center.x + radius * angle.cos(),
+            center.y + radius * angle.sin(),
+        )
+    };
+    let mut points: Vec<Pos2> = (0..=steps).map(|i| arc(outer, i)).collect();
+    points.extend((0..=steps).rev().map(|i| arc(inner, i)));
+    points
+}
+
 fn format_gb(bytes: u128) -> String {
     let gb = bytes as f64 / GB_FACTOR;
     if gb >= 100.0 {
@@ -1029,6 +3905,15 @@ fn lighten(color: Color32, amount: u8) -> Color32 {
     )
 }
 
+fn age_color(bucket: AgeBucket) -> Color32 {
+    match bucket {
+        AgeBucket::Recent => Color32::from_rgb(0x7F, 0xC8, 0xA9),
+        AgeBucket::Months => Color32::from_rgb(0xED, 0xC9, 0x79),
+        AgeBucket::Years => Color32::from_rgb(0xE7, 0x84, 0x3C),
+        AgeBucket::Stale => Color32::from_rgb(0xBC, 0x45, 0x45),
+    }
+}
+
 fn palette_color(index: usize) -> Color32 {
     const PALETTE: [Color32; 10] = [
         Color32::from_rgb(0x5B, 0x8C, 0xCB),
@@ -1051,6 +3936,7 @@ fn apply_pie_actions(app: &mut AppState, actions: PieActions) {
     }
     if let Some(id) = actions.open {
         app.current_dir = Some(id);
+        app.list_cut_off(id);
     }
     if let Some(id) = actions.delete {
         app.request_delete(id);
@@ -1058,14 +3944,138 @@ fn apply_pie_actions(app: &mut AppState, actions: PieActions) {
     if let Some(id) = actions.properties {
         app.request_properties(id);
     }
+    if let Some(id) = actions.archive {
+        app.request_archive(id);
+    }
+    if let Some(id) = actions.add_to_cart {
+        app.add_to_cart(id);
+    }
+}
+
+/// Draws the breakdown of the pinned folder, whose slices open its
+/// subfolders within the pinned copy.
+fn draw_pinned_folder(
+    ui: &mut Ui,
+    pinned: &PinnedFolder,
+    dates: &DateFormat,
+    relative: bool,
+    actions: &mut PinnedActions,
+) {
+    let node = pinned.tree.node(pinned.current);
+    let full_path = pinned.tree.path(pinned.current);
+    let path = if relative {
+        pinned.tree.relative_path(&full_path)
+    } else {
+        &full_path
+    };
+    ui.horizontal(|ui| {
+        ui.label(format!("{} · {}", node.name, human_bytes(node.size)))
+            .on_hover_text(path.display().to_string());
+        if let Some(parent) = node.parent {
+            if ui.small_button("Up").clicked() {
+                actions.open = Some(parent);
+            }
+        }
+        actions.unpin = ui.small_button("Unpin").clicked();
+    });
+    ui.small(format!("Pinned from {}", pinned.source));
+    let slices = collect_pie_slices(&pinned.tree, &node.children, None, relative);
+    if slices.is_empty() {
+        ui.label("Nothing to display for this folder.");
+    } else {
+        let pie = draw_pie_chart(ui, &slices, None, None, dates, None, false);
+        actions.open = pie.open.or(actions.open);
+    }
+}
+
+/// Lists the entries of the current and the pinned folder side by side,
+/// matched by name, with the largest differences first.
+fn draw_pinned_comparison(ui: &mut Ui, tree: &Tree, current: NodeId, pinned: &PinnedFolder) {
+    const SHOWN: usize = 30;
+    let mut sizes: std::collections::BTreeMap<&str, (Option<u128>, Option<u128>)> =
+        std::collections::BTreeMap::new();
+    for &child in &tree.node(current).children {
+        let node = tree.node(child);
+        sizes.entry(&node.name).or_default().0 = Some(node.size);
+    }
+    for &child in &pinned.tree.node(pinned.current).children {
+        let node = pinned.tree.node(child);
+        sizes.entry(&node.name).or_default().1 = Some(node.size);
+    }
+    let mut rows: Vec<_> = sizes.into_iter().collect();
+    let delta = |(this, pinned): (Option<u128>, Option<u128>)| {
+        this.unwrap_or(0) as i128 - pinned.unwrap_or(0) as i128
+    };
+    rows.sort_by_key(|(_, s)| std::cmp::Reverse(delta(*s).unsigned_abs()));
+    let total = delta((
+        Some(tree.node(current).size),
+        Some(pinned.tree.node(pinned.current).size),
+    ));
+    egui::CollapsingHeader::new(format!("Side by side ({} overall)", signed_bytes(total)))
+        .id_source("pinned_comparison")
+        .default_open(true)
+        .show(ui, |ui| {
+            ScrollArea::vertical()
+                .id_source("pinned_comparison_scroll")
+                .max_height(240.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("pinned_comparison_grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Name");
+                            ui.strong("This folder");
+                            ui.strong("Pinned");
+                            ui.strong("Difference");
+                            ui.end_row();
+                            let or_dash = |size: Option<u128>| size.map_or("—".into(), human_bytes);
+                            for (name, size) in rows.iter().take(SHOWN) {
+                                ui.label(*name);
+                                ui.label(or_dash(size.0));
+                                ui.label(or_dash(size.1));
+                                ui.label(signed_bytes(delta(*size)));
+                                ui.end_row();
+                            }
+                        });
+                    if rows.len() > SHOWN {
+                        ui.label(format!("{} more entries not shown", rows.len() - SHOWN));
+                    }
+                });
+        });
+}
+
+fn apply_pinned_actions(app: &mut AppState, actions: PinnedActions) {
+    if actions.pin_current {
+        app.pin_current();
+    }
+    if let Some(path) = actions.pin_saved {
+        app.pin_saved_scan(&path);
+    }
+    if let Some(pinned) = &mut app.pinned {
+        if let Some(id) = actions.open {
+            pinned.current = id;
+        }
+        if actions.unpin {
+            app.pinned = None;
+        }
+    }
 }
 
 fn apply_folder_actions(app: &mut AppState, actions: FolderTreeActions) {
     if let Some(id) = actions.select {
         app.selected = Some(id);
     }
+    if let Some(id) = actions.baseline {
+        app.set_baseline(id);
+    }
+    if let Some(id) = actions.list_contents {
+        app.list_cut_off(id);
+    }
+    if let Some(id) = actions.refresh {
+        app.refresh_folder(id);
+    }
     if let Some(id) = actions.open {
         app.current_dir = Some(id);
+        app.list_cut_off(id);
     }
     if let Some(id) = actions.delete {
         app.request_delete(id);
@@ -1073,4 +4083,70 @@ fn apply_folder_actions(app: &mut AppState, actions: FolderTreeActions) {
     if let Some(id) = actions.properties {
         app.request_properties(id);
     }
+    if let Some(id) = actions.archive {
+        app.request_archive(id);
+    }
+    if let Some(id) = actions.add_to_cart {
+        app.add_to_cart(id);
+    }
+}
+
+fn show_alerts_window(ctx: &egui::Context, app: &mut AppState) {
+    if !app.show_alerts {
+        return;
+    }
+
+    let mut open = true;
+    let mut clear = false;
+    let mut reveal = None;
+    egui::Window::new("Growth alarms")
+        .default_width(520.0)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            if let Some(limit) = app.growth_alarm {
+                ui.label(format!("Folders growing faster than {limit} while watched"));
+            }
+            if app.growth_alerts.is_empty() {
+                ui.label("No alarms.");
+                return;
+            }
+            ScrollArea::vertical()
+                .id_source("alerts_scroll")
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("alerts_grid")
+                        .striped(true)
+                        .num_columns(3)
+                        .show(ui, |ui| {
+                            for (at, alert) in app.growth_alerts.iter().rev() {
+                                ui.label(app.date_format.format((*at).into()));
+                                if ui
+                                    .link(app.shown_path(&alert.path).display().to_string())
+                                    .clicked()
+                                {
+                                    reveal = Some(alert.path.clone());
+                                }
+                                ui.label(format!("+{}", human_bytes(alert.grown)));
+                                ui.end_row();
+                            }
+                        });
+                });
+            ui.separator();
+            if ui.button("Clear").clicked() {
+                clear = true;
+            }
+        });
+
+    if let Some(path) = reveal {
+        if let Some(id) = app.tree.as_ref().and_then(|t| t.find(&path)) {
+            app.current_dir = Some(id);
+            app.selected = None;
+        }
+    }
+    if clear {
+        app.growth_alerts.clear();
+    }
+    if !open {
+        app.show_alerts = false;
+    }
 }
@@ -0,0 +1,95 @@
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use std::path::PathBuf;
+use treesize_core::budget::{compare_reports, Budget, Violation, BUDGET_FILE};
+use treesize_core::human::human_bytes;
+
+#[derive(Args, Debug)]
+pub struct CiCheckArgs {
+    /// Baseline: a file saved with --save, or a --json report
+    old: PathBuf,
+    /// Report to check, of the same root
+    new: PathBuf,
+    /// Budget rules: a treesize-budgets.toml file, or a .json file mapping
+    /// paths relative to the root to their largest allowed size
+    #[arg(long, value_name = "FILE", default_value = BUDGET_FILE)]
+    budget: PathBuf,
+    /// How to report violations
+    #[arg(long, value_enum, default_value_t)]
+    format: CheckFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum CheckFormat {
+    #[default]
+    Human,
+    Json,
+    /// GitHub Actions workflow commands, shown as annotations on the run
+    Github,
+}
+
+#[derive(Serialize)]
+struct CheckOutput<'a> {
+    old_total: u128,
+    new_total: u128,
+    violations: &'a [Violation],
+}
+
+/// Exits with 1 if any budget is exceeded, 2 if an input can't be read.
+pub fn run(args: CiCheckArgs) -> i32 {
+    let budget = match Budget::load(&args.budget) {
+        Ok(budget) => budget,
+        Err(e) => {
+            eprintln!("error: could not read {}: {e}", args.budget.display());
+            return 2;
+        }
+    };
+    let report = match compare_reports(&args.old, &args.new) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("error: could not read reports: {e}");
+            return 2;
+        }
+    };
+    let violations = report.check(&budget);
+    let (old_total, new_total) = (report.diff.old_total, report.diff.new_total);
+    match args.format {
+        CheckFormat::Json => {
+            let output = CheckOutput {
+                old_total,
+                new_total,
+                violations: &violations,
+            };
+            match serde_json::to_string_pretty(&output) {
+                Ok(json) => println!("{json}"),
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    return 2;
+                }
+            }
+        }
+        CheckFormat::Github => {
+            for violation in &violations {
+                println!("::error title=Size budget exceeded::{violation}");
+            }
+            println!(
+                "::notice title=Total size::{} -> {}",
+                human_bytes(old_total),
+                human_bytes(new_total)
+            );
+        }
+        CheckFormat::Human => {
+            for violation in &violations {
+                println!("error: {violation}");
+            }
+            println!(
+                "Total {} -> {}; {} limits exceeded across {} budgets",
+                human_bytes(old_total),
+                human_bytes(new_total),
+                violations.len(),
+                budget.rules().len()
+            );
+        }
+    }
+    i32::from(!violations.is_empty())
+}
@@ -0,0 +1,68 @@
+use clap::Args;
+use std::path::PathBuf;
+use treesize_core::diff::{self, TreeDiff};
+use treesize_core::human::human_bytes;
+use treesize_core::model::Tree;
+
+use crate::mutate::OutputFormat;
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// Earlier scan: a file saved with --save, or a --json report
+    old: PathBuf,
+    /// Later scan of the same root
+    new: PathBuf,
+    /// Most changes to list; 0 lists all
+    #[arg(short = 'n', long, default_value_t = 50)]
+    limit: usize,
+    /// How to print the changes
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+pub fn run(args: DiffArgs) -> i32 {
+    let open = |path: &PathBuf| {
+        Tree::open(path).map_err(|e| eprintln!("error: could not read {}: {e}", path.display()))
+    };
+    let (Ok(old), Ok(new)) = (open(&args.old), open(&args.new)) else {
+        return 2;
+    };
+    let mut result = diff::diff(&old, &new);
+    if args.limit > 0 {
+        result.changes.truncate(args.limit);
+    }
+    match args.format {
+        OutputFormat::Json => match serde_json::to_string_pretty(&result) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("error: {e}");
+                return 1;
+            }
+        },
+        OutputFormat::Human => print_human(&result),
+    }
+    0
+}
+
+pub fn signed_bytes(delta: i128) -> String {
+    let sign = if delta < 0 { '-' } else { '+' };
+    format!("{sign}{}", human_bytes(delta.unsigned_abs()))
+}
+
+fn print_human(result: &TreeDiff) {
+    for change in &result.changes {
+        println!(
+            "{:>13}  {:<8} {}{}",
+            signed_bytes(change.delta()),
+            change.change.label(),
+            change.path.display(),
+            if change.is_dir { "/" } else { "" }
+        );
+    }
+    println!(
+        "Total {} -> {} ({})",
+        human_bytes(result.old_total),
+        human_bytes(result.new_total),
+        signed_bytes(result.delta())
+    );
+}
@@ -0,0 +1,85 @@
+use clap::Args;
+use std::path::PathBuf;
+use treesize_core::docker;
+use treesize_core::export::ExportOptions;
+use treesize_core::human::human_bytes;
+use treesize_core::model::{NodeId, Tree};
+
+#[derive(Args, Debug)]
+pub struct DockerArgs {
+    /// Image archive written by `docker save` to break down file by file;
+    /// without it the local daemon is asked for its disk usage
+    image: Option<PathBuf>,
+    /// Levels of the tree to print
+    #[arg(short, long, default_value_t = 3)]
+    depth: usize,
+    /// Most entries to print per folder, largest first; 0 prints all
+    #[arg(short = 'n', long, default_value_t = 10)]
+    limit: usize,
+    /// Output JSON report path
+    #[arg(short, long)]
+    json: Option<PathBuf>,
+    /// Save the tree so the app can open it
+    #[arg(long, value_name = "PATH")]
+    save: Option<PathBuf>,
+}
+
+pub fn run(args: DockerArgs) -> i32 {
+    let result = match &args.image {
+        Some(path) => docker::image_archive_tree(path),
+        None => docker::daemon_tree(),
+    };
+    let tree = match result {
+        Ok(tree) => tree,
+        Err(e) => {
+            match &args.image {
+                Some(path) => eprintln!("error: could not read {}: {e}", path.display()),
+                None => eprintln!("error: could not query the Docker daemon: {e}"),
+            }
+            return 1;
+        }
+    };
+    if let Some(path) = &args.json {
+        if let Err(e) = treesize_core::export::export_json(&tree, path, &ExportOptions::default()) {
+            eprintln!("failed to write {}: {e}", path.display());
+        }
+    }
+    if let Some(path) = &args.save {
+        if let Err(e) = tree.save(path) {
+            eprintln!("failed to write {}: {e}", path.display());
+        }
+    }
+    print_node(&tree, tree.root, 0, &args);
+    0
+}
+
+fn print_node(tree: &Tree, id: NodeId, depth: usize, args: &DockerArgs) {
+    let node = tree.node(id);
+    println!(
+        "{:>12}  {}{}",
+        human_bytes(node.size),
+        "  ".repeat(depth),
+        node.name
+    );
+    if depth + 1 >= args.depth {
+        return;
+    }
+    let mut children = node.children.clone();
+    children.sort_by_key(|c| std::cmp::Reverse(tree.node(*c).size));
+    let shown = match args.limit {
+        0 => children.len(),
+        n => n.min(children.len()),
+    };
+    for &child in &children[..shown] {
+        print_node(tree, child, depth + 1, args);
+    }
+    if shown < children.len() {
+        let rest: u128 = children[shown..].iter().map(|c| tree.node(*c).size).sum();
+        println!(
+            "{:>12}  {}… {} more",
+            human_bytes(rest),
+            "  ".repeat(depth + 1),
+            children.len() - shown
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use chrono::Local;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use treesize_core::integration::shell_quote;
+use treesize_core::model::Tree;
+use treesize_core::snapshot::{self, SNAPSHOT_EXTENSION};
+
+use crate::integrate::default_app;
+
+/// A new file in the per-user scans folder to save `tree` to for
+/// `--open-gui`, named after the scanned folder and the time.
+pub fn new_snapshot_path(tree: &Tree) -> Option<PathBuf> {
+    let dir = snapshot::default_dir();
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        eprintln!("error: could not create {}: {e}", dir.display());
+        return None;
+    }
+    let root = tree.path(tree.root);
+    let name = root
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "treesize_scan".to_string());
+    let stamp = Local::now().format("%Y%m%d_%H%M%S");
+    Some(dir.join(format!("{name}_{stamp}.{SNAPSHOT_EXTENSION}")))
+}
+
+/// Opens the scan saved at `path` in the app. Where no window can be
+/// opened, as over SSH, prints how to open it later instead, so a scan run
+/// on a headless server can be looked through on a desktop.
+pub fn open(path: &Path) -> i32 {
+    println!("Saved the scan to {}", path.display());
+    let later = format!("treesize-app {}", shell_quote(&path.display().to_string()));
+    if !can_open_windows() {
+        println!("No display to open the app on; open the scan later with");
+        println!("    {later}");
+        println!("here, or on a computer with a display once it is copied there");
+        return 0;
+    }
+    let app = match default_app() {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("error: could not find the app: {e}");
+            println!("Open the scan with: {later}");
+            return 1;
+        }
+    };
+    let started = Command::new(&app)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match started {
+        Ok(_) => {
+            println!("Opened it in {}", app.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: could not start {}: {e}", app.display());
+            println!("Open the scan with: {later}");
+            1
+        }
+    }
+}
+
+/// Whether the app's window can be shown from here: not over SSH, and on
+/// Linux and other Unix only with an X11 or Wayland display, which SSH can
+/// forward.
+fn can_open_windows() -> bool {
+    let set = |var: &str| std::env::var_os(var).is_some_and(|value| !value.is_empty());
+    if cfg!(any(windows, target_os = "macos")) {
+        !set("SSH_CONNECTION")
+    } else {
+        set("DISPLAY") || set("WAYLAND_DISPLAY")
+    }
+}
@@ -0,0 +1,133 @@
+use clap::Args;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use treesize_core::history::{self, History, HistoryError, Trend};
+use treesize_core::human::human_bytes;
+
+use crate::diff::signed_bytes;
+use crate::mutate::OutputFormat;
+
+#[derive(Args, Debug)]
+pub struct HistoryArgs {
+    /// Folder to report on, along with the recorded folders below it
+    root: PathBuf,
+    /// History database (default: the per-user one `--history` records to)
+    #[arg(long, value_name = "PATH")]
+    db: Option<PathBuf>,
+    /// Most folders to list, fastest growing first; 0 lists all
+    #[arg(short = 'n', long, default_value_t = 20)]
+    limit: usize,
+    /// Forget everything recorded for the folder and below instead
+    #[arg(long)]
+    forget: bool,
+    /// How to print the report
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+/// One folder of the report, as printed with `--format json`.
+#[derive(Serialize)]
+struct TrendRow<'a> {
+    path: &'a Path,
+    bytes: u128,
+    change: i128,
+    /// Bytes per week; absent with fewer than two scans.
+    per_week: Option<f64>,
+    scans: usize,
+}
+
+pub fn run(args: HistoryArgs) -> i32 {
+    let path = args.db.clone().unwrap_or_else(History::default_path);
+    let history = match History::open(&path) {
+        Ok(history) => history,
+        Err(e) => {
+            eprintln!("error: could not open {}: {e}", path.display());
+            return 1;
+        }
+    };
+    if args.forget {
+        return match history.forget(&args.root) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("error: {e}");
+                1
+            }
+        };
+    }
+    let mut trends = match trends(&history, &args.root) {
+        Ok(trends) => trends,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return 1;
+        }
+    };
+    if trends.is_empty() {
+        eprintln!(
+            "no history for {}; scan it with --history first",
+            args.root.display()
+        );
+        return 1;
+    }
+    if args.limit > 0 {
+        trends.truncate(args.limit);
+    }
+    match args.format {
+        OutputFormat::Json => {
+            let rows: Vec<TrendRow> = trends
+                .iter()
+                .map(|trend| TrendRow {
+                    path: &trend.path,
+                    bytes: trend.latest().map_or(0, |p| p.bytes),
+                    change: trend.change(),
+                    per_week: trend.per_week(),
+                    scans: trend.points.len(),
+                })
+                .collect();
+            match serde_json::to_string_pretty(&rows) {
+                Ok(json) => println!("{json}"),
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    return 1;
+                }
+            }
+        }
+        OutputFormat::Human => print_human(&trends, &args.root),
+    }
+    0
+}
+
+/// Trends under `root` as given, or else as its canonical path, which is
+/// how a scan of a relative path or a link may have recorded it.
+fn trends(history: &History, root: &Path) -> Result<Vec<Trend>, HistoryError> {
+    let trends = history.trends(root)?;
+    match std::fs::canonicalize(root) {
+        Ok(canonical) if trends.is_empty() && canonical != root => history.trends(&canonical),
+        _ => Ok(trends),
+    }
+}
+
+fn print_human(trends: &[Trend], root: &Path) {
+    if let Some(trend) = trends.iter().find(|t| t.path == root) {
+        println!("{trend}");
+        println!();
+    }
+    println!(
+        "{:>14}  {:>10}  {:>11}  {:>5}  Folder",
+        "Per week", "Size", "Change", "Scans"
+    );
+    for trend in trends {
+        let rate = match trend.per_week() {
+            Some(rate) if rate < 0.0 => format!("-{}", history::rate_text(-rate)),
+            Some(rate) => format!("+{}", history::rate_text(rate)),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:>14}  {:>10}  {:>11}  {:>5}  {}",
+            rate.trim_end_matches("/week"),
+            human_bytes(trend.latest().map_or(0, |p| p.bytes)),
+            signed_bytes(trend.change()),
+            trend.points.len(),
+            trend.path.display()
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use clap::{Args, Subcommand};
+use std::io;
+use std::path::PathBuf;
+use treesize_core::integration::{self, MENU_LABEL};
+
+#[derive(Args, Debug)]
+pub struct IntegrateArgs {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// Add the entry for the current user, replacing one added before
+    Install {
+        /// Program the entry starts with the folder (default: treesize-app
+        /// next to this program)
+        #[arg(long, value_name = "PATH")]
+        app: Option<PathBuf>,
+    },
+    /// Remove the entry
+    Uninstall,
+    /// Tell whether the entry is there; exits with 1 if not
+    Status,
+}
+
+/// Adds or removes the "Analyze with TreeSize RS" entry of the file
+/// manager's folder menu: Explorer's on Windows, a Finder Quick Action on
+/// macOS, and Nautilus, Dolphin and Nemo entries elsewhere.
+pub fn run(args: IntegrateArgs) -> i32 {
+    match args.action {
+        Action::Install { app } => {
+            let app = match app.map_or_else(default_app, std::fs::canonicalize) {
+                Ok(app) => app,
+                Err(e) => {
+                    eprintln!("error: could not find the app to start: {e}; pass --app");
+                    return 2;
+                }
+            };
+            match integration::install(&app) {
+                Ok(written) => {
+                    for location in written {
+                        println!("Added {location}");
+                    }
+                    println!(
+                        "Right-click a folder and pick \"{MENU_LABEL}\" to open it in the app"
+                    );
+                    0
+                }
+                Err(e) => {
+                    eprintln!("error: could not add the entry: {e}");
+                    1
+                }
+            }
+        }
+        Action::Uninstall => match integration::uninstall() {
+            Ok(removed) if removed.is_empty() => {
+                println!("Nothing to remove");
+                0
+            }
+            Ok(removed) => {
+                for location in removed {
+                    println!("Removed {location}");
+                }
+                0
+            }
+            Err(e) => {
+                eprintln!("error: could not remove the entry: {e}");
+                1
+            }
+        },
+        Action::Status => {
+            if integration::is_installed() {
+                println!("Installed");
+                0
+            } else {
+                println!("Not installed");
+                1
+            }
+        }
+    }
+}
+
+/// `treesize-app` in the folder this program runs from, where both are
+/// installed and built.
+pub(crate) fn default_app() -> io::Result<PathBuf> {
+    let name = format!("treesize-app{}", std::env::consts::EXE_SUFFIX);
+    let app = std::env::current_exe()?.with_file_name(name);
+    if app.is_file() {
+        Ok(app)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", app.display()),
+        ))
+    }
+}
@@ -1,57 +1,926 @@
-use clap::Parser;
-use std::path::PathBuf;
-use std::sync::atomic::AtomicBool;
+mod ci;
+mod diff;
+mod docker;
+mod gui;
+mod history;
+mod integrate;
+mod mutate;
+mod query;
+mod script;
+mod serve;
+mod tag;
+mod watch;
+
+use clap::{Parser, Subcommand};
+use crossbeam_channel::Receiver;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use treesize_core::scanner::{ScanMsg, Scanner};
+use std::time::{Duration, SystemTime};
+use treesize_core::age::{self, AgeBucket};
+use treesize_core::archives::{self, ArchiveBackend};
+use treesize_core::checkpoint::{Checkpoint, CheckpointError};
+use treesize_core::cloud::cloud_bytes;
+use treesize_core::export::ExportOptions;
+use treesize_core::extstats::ext_stats;
+use treesize_core::history::History;
+use treesize_core::human::{human_bytes, human_duration, parse_bytes, parse_locale, DateFormat};
+use treesize_core::inodes::{self, CountThresholds};
+use treesize_core::interference;
+use treesize_core::mapped::NodeTable;
+use treesize_core::model::{NodeId, NodeSource, Tree};
+use treesize_core::owner::usage_by_owner;
+use treesize_core::profile::ScanProfile;
+use treesize_core::progress::{Progress, ProgressEvent, ProgressThrottle};
+use treesize_core::s3;
+use treesize_core::scanner::{scan_channel, ScanMsg, ScanOptions, Scanner};
+use treesize_core::script::ScriptLimits;
+use treesize_core::tuning::{ScanTuning, StorageKind};
 
 #[derive(Parser, Debug)]
-#[command(name = "treesize-cli", about = "TreeSize report generator")]
-struct Args {
-    /// Root directory to scan
-    root: PathBuf,
+#[command(
+    name = "treesize-cli",
+    about = "TreeSize report generator",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    report: ReportArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Permanently delete files or directories
+    Delete(mutate::DeleteArgs),
+    /// Move files or directories into an archive location
+    Archive(mutate::ArchiveArgs),
+    /// Delete everything under a root that matches the given patterns
+    Clean(mutate::CleanArgs),
+    /// Show what grew, shrank, appeared or disappeared between two scans
+    Diff(diff::DiffArgs),
+    /// Check a report against size budgets and a baseline, for CI pipelines
+    CiCheck(ci::CiCheckArgs),
+    /// Watch directories after scanning them and report any growing faster
+    /// than a given rate
+    Watch(watch::WatchArgs),
+    /// Break down the disk used by Docker images, layers, containers and
+    /// volumes
+    Docker(docker::DockerArgs),
+    /// Report how fast folders grew across the scans recorded with
+    /// --history
+    History(history::HistoryArgs),
+    /// Answer questions about a scan saved with --save, without touching
+    /// the filesystem
+    Query(query::QueryArgs),
+    /// Rescan directories on a schedule and answer HTTP requests about the
+    /// last few scans
+    Serve(serve::ServeArgs),
+    /// Add or remove "Analyze with TreeSize RS" in the file manager's
+    /// folder menu
+    Integrate(integrate::IntegrateArgs),
+    /// Tag files and folders, or leave notes on them, for reports and the
+    /// app to show
+    Tag(tag::TagArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct ReportArgs {
+    /// Root directories to scan; several are reported as one combined tree.
+    /// A single `s3://bucket/prefix` lists that bucket instead, using the
+    /// usual AWS_* environment variables for credentials, region and
+    /// endpoint, and a single zip, tar or 7z file lists what is inside it
+    #[arg(required = true)]
+    roots: Vec<PathBuf>,
     /// Output JSON report path
     #[arg(short, long)]
     json: Option<PathBuf>,
+    /// Write the tree as nested {name, value, children} JSON for d3
+    /// treemaps, sunbursts and flame graphs
+    #[arg(long, value_name = "PATH")]
+    d3: Option<PathBuf>,
+    /// Write the tree as folded stacks for flamegraph.pl or inferno
+    #[arg(long, value_name = "PATH")]
+    folded: Option<PathBuf>,
+    /// Write only what lies in or below entries named like GLOB
+    /// (repeatable), e.g. `*.log` or `node_modules`, to --json, --d3 and
+    /// --folded, with the folders above them sized by what is written
+    #[arg(long = "only", value_name = "GLOB", conflicts_with = "spill")]
+    only: Vec<String>,
+    /// How modification times are written to the report: iso, locale,
+    /// relative or a strftime pattern such as %d/%m/%Y
+    #[arg(long, value_name = "FORMAT", default_value = "iso")]
+    date_format: DateFormat,
+    /// Locale for `--date-format locale`, e.g. de_DE (default: the system's)
+    #[arg(long, value_name = "LOCALE", value_parser = parse_locale_arg)]
+    locale: Option<chrono::Locale>,
+    /// Save the scanned tree so the app can open it later without rescanning
+    #[arg(long, value_name = "PATH")]
+    save: Option<PathBuf>,
+    /// Save the scan and open it in the app once done; saved to --save, or
+    /// else to the per-user scans folder. Where no window can be opened,
+    /// as over SSH, prints how to open it later instead
+    #[arg(long)]
+    open_gui: bool,
+    /// Record the sizes of the folders near the top of the tree, so the
+    /// `history` command can report how fast they grow
+    #[arg(long)]
+    history: bool,
+    /// History database for --history (default: the per-user one, shared
+    /// with the app)
+    #[arg(long, value_name = "PATH", requires = "history")]
+    history_db: Option<PathBuf>,
+    /// Print size and file count per file extension
+    #[arg(long)]
+    ext_summary: bool,
+    /// Write paths from the scanned folder down instead of in full, in the
+    /// report and the printed lists
+    #[arg(long)]
+    relative_paths: bool,
+    /// Print size and file count per file owner; implies --owners
+    #[arg(long)]
+    owner_summary: bool,
+    /// Print the N largest files
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+    /// Print how much data was last modified 0-30 days, 30-365 days, 1-3
+    /// years and over 3 years ago
+    #[arg(long)]
+    age_summary: bool,
+    /// Print files not modified for at least DAYS days, largest first
+    #[arg(long, value_name = "DAYS")]
+    older_than: Option<u64>,
+    /// Print empty folders and zero-byte files, which are usually safe to
+    /// clean up
+    #[arg(long)]
+    empty: bool,
+    /// Print the folders holding the most files, the inode use of the
+    /// filesystem and your inode quota, for filesystems that run out of
+    /// inodes before space
+    #[arg(long)]
+    file_counts: bool,
+    /// Files below a folder for --file-counts to flag it
+    #[arg(long, value_name = "N", default_value_t = CountThresholds::default().files)]
+    files_threshold: u64,
+    /// Entries directly inside a folder for --file-counts to flag it
+    #[arg(long, value_name = "N", default_value_t = CountThresholds::default().entries)]
+    entries_threshold: u64,
+    /// Print the N directories that took longest to scan, to tell slow
+    /// storage apart from directories with huge numbers of entries, and
+    /// the memory the scanned tree takes
+    #[arg(long, value_name = "N")]
+    slowest: Option<usize>,
+    /// Run a Rhai script on the scanned tree once the report is printed,
+    /// for policies such as tagging every folder over 100 GB and mailing
+    /// the list; scripts can look through the tree, tag, export and notify
+    #[arg(long, value_name = "PATH", conflicts_with = "spill")]
+    script: Option<PathBuf>,
+    /// Operations a --script may run before it is stopped, so a loop that
+    /// never ends fails instead of hanging the report
+    #[arg(long, value_name = "N", requires = "script", default_value_t = ScriptLimits::default().max_operations)]
+    script_max_operations: u64,
+    /// Shell command given the script's notify() messages on its standard
+    /// input, e.g. `mail -s "Disk report" ops@example.com`; without it
+    /// they are printed
+    #[arg(long, value_name = "COMMAND", requires = "script")]
+    notify_command: Option<String>,
+    #[command(flatten)]
+    walk: WalkArgs,
 }
 
-fn main() {
-    let args = Args::parse();
-    let cancel = Arc::new(AtomicBool::new(false));
-    let (tx, rx) = crossbeam_channel::unbounded::<ScanMsg>();
-    let scanner = Scanner::new(cancel);
-    std::thread::spawn({
-        let root = args.root.clone();
-        move || scanner.scan(root, tx)
+/// Options shared by every command that walks a directory tree.
+#[derive(clap::Args, Debug, Clone)]
+pub struct WalkArgs {
+    /// Glob pattern of entries to skip entirely (repeatable), e.g. `node_modules`
+    #[arg(long = "exclude", value_name = "GLOB")]
+    excludes: Vec<String>,
+    /// Do not descend into directories on other filesystems
+    #[arg(short = 'x', long)]
+    one_file_system: bool,
+    /// Follow symbolic links that lead outside the scanned tree
+    #[arg(short = 'L', long)]
+    follow_links: bool,
+    /// Include NTFS alternate data streams in file sizes (Windows only)
+    #[arg(long)]
+    alternate_streams: bool,
+    /// Record who owns each file, e.g. for trees saved with --save
+    #[arg(long)]
+    owners: bool,
+    /// List what zip, tar, tar.gz and 7z files contain, without extracting
+    #[arg(long)]
+    archives: bool,
+    /// Leave out files smaller than SIZE, e.g. 1MB, so trees of countless
+    /// tiny files take less time and memory
+    #[arg(long, value_name = "SIZE", value_parser = parse_size_arg)]
+    min_size: Option<u64>,
+    /// Only count files with these extensions (repeatable or
+    /// comma-separated), e.g. `mp4,mkv`
+    #[arg(long = "include-ext", value_name = "EXT", value_delimiter = ',')]
+    include_extensions: Vec<String>,
+    /// Leave out files with these extensions (repeatable or
+    /// comma-separated), e.g. `o,tmp`
+    #[arg(long = "exclude-ext", value_name = "EXT", value_delimiter = ',')]
+    exclude_extensions: Vec<String>,
+    /// List only N levels below the root, like `du --max-depth`; deeper
+    /// files are counted in the folder at that depth
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+    /// Size only PERCENT of the files, picked at random, and estimate
+    /// folder sizes from them; much faster on huge network shares
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent_arg)]
+    sample: Option<u16>,
+    /// Walker threads (default: chosen for the storage type)
+    #[arg(long, value_name = "N")]
+    threads: Option<usize>,
+    /// Files each thread sizes before reporting them (default: chosen for
+    /// the storage type)
+    #[arg(long, value_name = "N")]
+    batch_size: Option<usize>,
+    /// Most directories read at once; caps --threads
+    #[arg(long, value_name = "N")]
+    max_open_dirs: Option<usize>,
+    /// Pause each thread for MICROS microseconds after every entry, to
+    /// leave the disk to other programs
+    #[arg(long, value_name = "MICROS")]
+    entry_delay: Option<u64>,
+    /// Scan at background disk and CPU priority
+    #[arg(long)]
+    low_priority: bool,
+    /// Storage type to tune for instead of detecting it: ssd, hdd or network
+    #[arg(long, value_name = "TYPE")]
+    storage: Option<StorageKind>,
+    /// Scan cache database; directories unchanged since the last scan that
+    /// used it are not stat'ed again
+    #[arg(long, value_name = "PATH")]
+    cache: Option<PathBuf>,
+    /// Save progress to PATH every half minute, and resume from it if it
+    /// exists, so an interrupted scan doesn't start over
+    #[arg(long, value_name = "PATH", conflicts_with = "cache")]
+    checkpoint: Option<PathBuf>,
+    /// Keep the files found in a memory-mapped node table in DIR instead of
+    /// in memory, for scans too large for RAM; reports then list folders
+    /// only, and --top, --ext-summary, --owner-summary, --age-summary,
+    /// --older-than and --empty read the files from the table
+    #[arg(long, value_name = "DIR")]
+    spill: Option<PathBuf>,
+    /// Show a running file and byte count on stderr while scanning
+    #[arg(long)]
+    progress: bool,
+    /// Least time between progress reports while scanning, in
+    /// milliseconds (default: 50); raise it if reporting slows down scans
+    /// of fast disks
+    #[arg(long, value_name = "MS")]
+    progress_interval: Option<u64>,
+    /// Also report progress once N more files are counted, however soon
+    #[arg(long, value_name = "N")]
+    progress_files: Option<u64>,
+}
+
+impl WalkArgs {
+    pub fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            excludes: self.excludes.clone(),
+            one_file_system: self.one_file_system,
+            follow_links: self.follow_links,
+            tuning: ScanTuning {
+                threads: self.threads,
+                batch_size: self.batch_size,
+                max_open_dirs: self.max_open_dirs,
+                entry_delay: self.entry_delay.map(Duration::from_micros),
+                background: self.low_priority,
+            },
+            storage: self.storage,
+            alternate_streams: self.alternate_streams,
+            owners: self.owners,
+            archives: self.archives,
+            min_file_size: self.min_size.unwrap_or(0),
+            include_extensions: self.include_extensions.clone(),
+            exclude_extensions: self.exclude_extensions.clone(),
+            max_depth: self.max_depth,
+            sample_per_mille: self.sample,
+            spill: self.spill.clone(),
+        }
+    }
+
+    pub fn progress_throttle(&self) -> ProgressThrottle {
+        let default = ProgressThrottle::default();
+        ProgressThrottle {
+            interval: self
+                .progress_interval
+                .map_or(default.interval, Duration::from_millis),
+            files: self.progress_files,
+        }
+    }
+}
+
+/// How often `--checkpoint` saves progress.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Progress counters from the last `Progress` message of a scan.
+#[derive(Default)]
+pub struct ScanSummary {
+    pub files: u64,
+    pub discovered: u64,
+    pub bytes: u128,
+    pub vanished: u64,
+}
+
+/// Runs a scan to completion on a worker thread, printing walk errors as
+/// warnings. Several roots are combined into one tree.
+pub fn scan_tree(roots: &[PathBuf], walk: &WalkArgs) -> (Option<Tree>, ScanSummary) {
+    let (tx, rx) = scan_channel();
+    let scanner = Scanner::new()
+        .with_options(walk.scan_options())
+        .with_progress_throttle(walk.progress_throttle());
+    let handle = match (walk.cache.clone(), walk.checkpoint.clone(), roots) {
+        (_, _, [root]) if s3::is_s3_url(&root.to_string_lossy()) => {
+            match s3::S3Config::from_url(&root.to_string_lossy()) {
+                Ok(config) => {
+                    let url = PathBuf::from(config.url());
+                    scanner.spawn_with(Box::new(config), url, tx)
+                }
+                Err(e) => {
+                    eprintln!("error: could not list {}: {e}", root.display());
+                    return (None, ScanSummary::default());
+                }
+            }
+        }
+        (_, _, [root]) if root.is_file() && archives::is_archive(root) => {
+            scanner.spawn_with(Box::new(ArchiveBackend), root.clone(), tx)
+        }
+        (Some(cache), _, [root]) => scanner.spawn_with_cache(root.clone(), cache, tx),
+        (_, Some(path), [root]) => {
+            let scanner = scanner.with_checkpoints(path.clone(), CHECKPOINT_INTERVAL);
+            match Checkpoint::load(&path) {
+                Ok(checkpoint) if checkpoint.root() == root => {
+                    eprintln!(
+                        "Resuming from {} with {} files already sized",
+                        path.display(),
+                        checkpoint.files()
+                    );
+                    scanner.spawn_resume(checkpoint, tx)
+                }
+                Ok(_) => {
+                    eprintln!(
+                        "warning: {} is a checkpoint of another root",
+                        path.display()
+                    );
+                    return (None, ScanSummary::default());
+                }
+                Err(CheckpointError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                    scanner.spawn(root.clone(), tx)
+                }
+                Err(e) => {
+                    eprintln!("warning: could not read {}: {e}", path.display());
+                    return (None, ScanSummary::default());
+                }
+            }
+        }
+        (cache, checkpoint, _) => {
+            if cache.is_some() {
+                eprintln!("warning: --cache is only used when scanning a single root");
+            }
+            if checkpoint.is_some() {
+                eprintln!("warning: --checkpoint is only used when scanning a single root");
+            }
+            scanner.spawn_many(roots.to_vec(), tx)
+        }
+    };
+    let printer = walk.progress.then(|| {
+        let events = handle.subscribe();
+        std::thread::spawn(move || print_progress(events))
     });
+    // Only the scan thread keeps the progress hub alive from here on
+    drop(handle);
 
-    let mut files = 0u64;
-    let mut discovered = 0u64;
-    let mut bytes = 0u128;
+    let mut summary = ScanSummary::default();
     while let Ok(msg) = rx.recv() {
         match msg {
             ScanMsg::Progress {
                 scanned,
-                discovered: d,
-                bytes: b,
+                discovered,
+                bytes,
+                vanished,
+                ..
             } => {
-                files = scanned;
-                discovered = d;
-                bytes = b;
+                summary.files = scanned;
+                summary.discovered = discovered;
+                summary.bytes = bytes;
+                summary.vanished = vanished;
             }
             ScanMsg::Done(tree) => {
-                if let Some(path) = args.json {
-                    let json = treesize_core::export::to_json(&tree);
-                    std::fs::write(path, serde_json::to_string_pretty(&json).unwrap()).ok();
+                if let Some(printer) = printer {
+                    let _ = printer.join();
                 }
-                break;
+                return (Some(Arc::unwrap_or_clone(tree)), summary);
             }
+            ScanMsg::Error(e) => eprintln!("warning: {e}"),
             _ => {}
         }
     }
+    (None, summary)
+}
+
+/// Keeps one status line on stderr up to date until the scan finishes.
+fn print_progress(events: Receiver<ProgressEvent>) {
+    let line = |p: &Progress| {
+        let eta = p
+            .eta()
+            .filter(|eta| eta.as_secs() > 0)
+            .map(|eta| format!(", ~{} remaining", human_duration(eta)))
+            .unwrap_or_default();
+        eprint!(
+            "\r{} / {} files, {}, {}/s{eta}   ",
+            p.files,
+            p.discovered.max(p.files),
+            human_bytes(p.bytes),
+            human_bytes(p.bytes_per_sec() as u128)
+        );
+    };
+    for event in events {
+        match event {
+            ProgressEvent::Progress(p) => line(&p),
+            ProgressEvent::Finished { progress, .. } => {
+                line(&progress);
+                eprintln!();
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let code = match cli.command {
+        Some(Command::Delete(args)) => mutate::delete(args),
+        Some(Command::Archive(args)) => mutate::archive(args),
+        Some(Command::Clean(args)) => mutate::clean(args),
+        Some(Command::Diff(args)) => diff::run(args),
+        Some(Command::CiCheck(args)) => ci::run(args),
+        Some(Command::Watch(args)) => watch::run(args),
+        Some(Command::Docker(args)) => docker::run(args),
+        Some(Command::History(args)) => history::run(args),
+        Some(Command::Query(args)) => query::run(args),
+        Some(Command::Serve(args)) => serve::run(args),
+        Some(Command::Integrate(args)) => integrate::run(args),
+        Some(Command::Tag(args)) => tag::run(args),
+        None => report(cli.report),
+    };
+    std::process::exit(code);
+}
+
+fn report(args: ReportArgs) -> i32 {
+    if args.roots.is_empty() {
+        return 2;
+    }
+    let only = match only_matcher(&args.only) {
+        Ok(only) => only,
+        Err(e) => {
+            eprintln!("error: bad --only pattern: {e}");
+            return 2;
+        }
+    };
+    let script = match &args.script {
+        Some(path) => match script::load(path, args.script_max_operations) {
+            Some(script) => Some(script),
+            None => return 2,
+        },
+        None => None,
+    };
+    let mut walk = args.walk.clone();
+    walk.owners |= args.owner_summary;
+    let (tree, summary) = scan_tree(&args.roots, &walk);
+    let exported = match (&tree, &only) {
+        (Some(tree), Some(only)) => only_matching(tree, only).map(Cow::Owned),
+        (tree, _) => tree.as_ref().map(Cow::Borrowed),
+    };
+    if let (Some(tree), Some(path)) = (&exported, &args.json) {
+        let options = ExportOptions {
+            date_format: args.date_format.clone(),
+            locale: args.locale,
+            relative_paths: args.relative_paths,
+            annotations: tag::load_default(),
+        };
+        if let Err(e) = treesize_core::export::export_json(tree, path, &options) {
+            eprintln!("failed to write {}: {e}", path.display());
+        }
+    }
+    if let (Some(tree), Some(path)) = (&exported, &args.d3) {
+        if let Err(e) = treesize_core::export::export_hierarchy_json(tree, path) {
+            eprintln!("failed to write {}: {e}", path.display());
+        }
+    }
+    if let (Some(tree), Some(path)) = (&exported, &args.folded) {
+        if let Err(e) = treesize_core::export::export_folded(tree, path) {
+            eprintln!("failed to write {}: {e}", path.display());
+        }
+    }
+    let save = match (&tree, &args.save) {
+        (Some(tree), None) if args.open_gui => gui::new_snapshot_path(tree),
+        (_, save) => save.clone(),
+    };
+    let mut saved = false;
+    if let (Some(tree), Some(path)) = (&tree, &save) {
+        match tree.save(path) {
+            Ok(()) => saved = true,
+            Err(e) => eprintln!("failed to write {}: {e}", path.display()),
+        }
+    }
+    if let (Some(tree), true) = (&tree, args.history) {
+        let path = args
+            .history_db
+            .clone()
+            .unwrap_or_else(History::default_path);
+        if let Err(e) = History::open(&path).and_then(|history| history.record(tree)) {
+            eprintln!("failed to record history in {}: {e}", path.display());
+        }
+    }
+    let file_reports = args.ext_summary
+        || args.owner_summary
+        || args.top.is_some()
+        || args.age_summary
+        || args.older_than.is_some()
+        || args.empty;
+    match (&tree, &walk.spill) {
+        (Some(tree), Some(_)) if file_reports => {
+            // The tree of a spilled scan holds folders only; its files
+            // are read from the node table
+            if let Some(table) = open_node_table(tree) {
+                let empty = args.empty.then(|| {
+                    let unread = tree
+                        .errors
+                        .iter()
+                        .filter_map(|e| table.find(&tree.path(e.node)))
+                        .collect();
+                    (table.empty_dirs(&unread), table.empty_files())
+                });
+                print_file_reports(&args, tree, &table, table.root(), empty);
+            }
+        }
+        (Some(tree), _) => {
+            let empty = args.empty.then(|| (tree.empty_dirs(), tree.empty_files()));
+            print_file_reports(&args, tree, tree, tree.root, empty);
+        }
+        (None, _) => {}
+    }
+    if let (Some(tree), true) = (&tree, args.file_counts) {
+        let thresholds = CountThresholds {
+            files: args.files_threshold,
+            entries: args.entries_threshold,
+        };
+        print_file_counts(tree, thresholds, args.relative_paths);
+    }
+    if let Some(hint) = tree.as_ref().and_then(interference::detect) {
+        eprintln!("hint: {}", hint.message());
+        if let Ok(command) = interference::defender_exclusion_command() {
+            eprintln!("hint: to exclude it from Microsoft Defender, run as administrator:");
+            eprintln!("    {command}");
+        }
+    }
+    if let (Some(tree), Some(n)) = (&tree, args.slowest) {
+        if let Some(profile) = &tree.meta.profile {
+            print_slowest(tree, profile, n);
+        }
+    }
     println!(
         "Scanned {} / {} files, {} bytes",
-        files,
-        discovered.max(files),
-        bytes
+        summary.files,
+        summary.discovered.max(summary.files),
+        summary.bytes
     );
+    if let Some(tree) = &tree {
+        let cloud = cloud_bytes(tree)
+            .get(tree.root.0 as usize)
+            .copied()
+            .unwrap_or(0);
+        if cloud > 0 {
+            println!("{cloud} bytes of them are online-only and not stored locally");
+        }
+    }
+    if let Some(tree) = &tree {
+        if let (Some(estimate), Some(margin)) =
+            (&tree.meta.estimate, tree.estimate_margin(tree.root))
+        {
+            println!(
+                "Estimated from {} sized files ({:.1}% sample): {} ± {} at 95% confidence",
+                estimate.sampled,
+                estimate.fraction * 100.0,
+                human_bytes(tree.node(tree.root).size),
+                human_bytes(margin)
+            );
+        }
+    }
+    if let Some(tree) = &tree {
+        let aliases = tree.folder_aliases();
+        if !aliases.is_empty() {
+            println!(
+                "{} folders can be reached at more than one path and are counted once:",
+                aliases.len()
+            );
+            for (folder, links) in aliases {
+                let path = tree.path(folder);
+                println!(
+                    "    {}",
+                    shown_path(tree, &path, args.relative_paths).display()
+                );
+                for link in links {
+                    let path = tree.path(link);
+                    let path = shown_path(tree, &path, args.relative_paths);
+                    println!("      also at {}", path.display());
+                }
+            }
+        }
+    }
+    if let Some(tree) = &tree {
+        let denied: Vec<_> = tree.access_denied().collect();
+        if !denied.is_empty() {
+            println!(
+                "{} folders could not be read for lack of permission and are not counted:",
+                denied.len()
+            );
+            for error in denied {
+                let path = shown_path(tree, &error.path, args.relative_paths);
+                println!("    {}", path.display());
+            }
+            println!("Run again with administrator rights to include them");
+        }
+    }
+    if summary.vanished > 0 {
+        println!(
+            "{} entries were deleted or renamed while scanning and were skipped",
+            summary.vanished
+        );
+    }
+    let code = match (tree, &script) {
+        (Some(tree), Some(script)) => script::run(
+            script,
+            tree,
+            args.notify_command.as_deref(),
+            args.relative_paths,
+        ),
+        _ => 0,
+    };
+    match save {
+        Some(path) if args.open_gui && saved => gui::open(&path).max(code),
+        _ => code,
+    }
+}
+
+/// The `--only` patterns as one matcher, or `None` when there are none.
+fn only_matcher(patterns: &[String]) -> Result<Option<GlobSet>, globset::Error> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut matcher = GlobSetBuilder::new();
+    for pattern in patterns {
+        matcher.add(Glob::new(pattern)?);
+    }
+    matcher.build().map(Some)
+}
+
+/// The part of `tree` that `--only` writes: entries named like one of its
+/// patterns, what lies below them, and the folders above.
+fn only_matching(tree: &Tree, only: &GlobSet) -> Option<Tree> {
+    let view = tree.filtered(|node| {
+        tree.ancestors(node.id)
+            .any(|id| only.is_match(tree.node(id).name.as_str()))
+    });
+    if view.is_empty() {
+        eprintln!("warning: nothing matches --only; no reports written");
+        return None;
+    }
+    Some(view.to_tree(tree))
+}
+
+/// The node table a spilled scan kept its files in, warning when there
+/// is none or it can't be read.
+fn open_node_table(tree: &Tree) -> Option<NodeTable> {
+    let Some(dir) = &tree.meta.node_table else {
+        eprintln!("warning: no node table was written, so reports on files are left out");
+        for error in tree.errors.iter().filter(|e| e.node == tree.root) {
+            eprintln!("warning: {}: {}", error.path.display(), error.message);
+        }
+        return None;
+    };
+    match NodeTable::open(dir) {
+        Ok(table) => Some(table),
+        Err(e) => {
+            eprintln!("warning: could not read {}: {e}", dir.display());
+            None
+        }
+    }
+}
+
+/// The reports asked for on the files below `root` of `files`, which is
+/// `tree` itself or the node table of a spilled scan of it. `empty` holds
+/// the empty folders and files for `--empty`.
+fn print_file_reports(
+    args: &ReportArgs,
+    tree: &Tree,
+    files: &impl NodeSource,
+    root: NodeId,
+    empty: Option<(Vec<NodeId>, Vec<NodeId>)>,
+) {
+    let print_files = |ids: Vec<NodeId>| {
+        for id in ids {
+            let path = files.path(id);
+            let path = shown_path(tree, &path, args.relative_paths);
+            println!("{:>12}  {}", human_bytes(files.size(id)), path.display());
+        }
+    };
+    if args.ext_summary {
+        print_ext_summary(files, root);
+    }
+    if args.owner_summary {
+        print_owner_summary(files, root);
+    }
+    if let Some(n) = args.top {
+        print_files(files.top_files(n));
+    }
+    if args.age_summary {
+        print_age_summary(files, root);
+    }
+    if let Some(days) = args.older_than {
+        let min_age = Duration::from_secs(days * 24 * 60 * 60);
+        print_files(age::old_files(files, root, min_age, SystemTime::now()));
+    }
+    if let Some((dirs, empty_files)) = empty {
+        print_empty(tree, files, dirs, empty_files, args.relative_paths);
+    }
+}
+
+fn parse_locale_arg(tag: &str) -> Result<chrono::Locale, String> {
+    parse_locale(tag).ok_or_else(|| format!("unknown locale {tag:?}"))
+}
+
+/// `path` as the report prints it; see `--relative-paths`.
+fn shown_path<'a>(tree: &Tree, path: &'a Path, relative: bool) -> &'a Path {
+    if relative {
+        tree.relative_path(path)
+    } else {
+        path
+    }
+}
+
+/// A percentage of files to sample, as parts per thousand.
+fn parse_percent_arg(percent: &str) -> Result<u16, String> {
+    let value: f64 = percent
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("invalid percentage {percent:?}"))?;
+    if !(value > 0.0 && value <= 100.0) {
+        return Err(format!("{percent} must be above 0 and at most 100"));
+    }
+    Ok(((value * 10.0).round() as u16).max(1))
+}
+
+fn parse_size_arg(size: &str) -> Result<u64, String> {
+    parse_bytes(size)
+        .and_then(|bytes| u64::try_from(bytes).ok())
+        .ok_or_else(|| format!("invalid size {size:?}, expected e.g. 500KB or 1MB"))
+}
+
+fn print_empty(
+    tree: &Tree,
+    source: &impl NodeSource,
+    dirs: Vec<NodeId>,
+    files: Vec<NodeId>,
+    relative: bool,
+) {
+    println!("{} empty folders", dirs.len());
+    for id in dirs {
+        let path = source.path(id);
+        let path = shown_path(tree, &path, relative);
+        println!("    {}", path.display());
+    }
+    println!("{} empty files", files.len());
+    for id in files {
+        let path = source.path(id);
+        let path = shown_path(tree, &path, relative);
+        println!("    {}", path.display());
+    }
+}
+
+fn print_file_counts(tree: &Tree, thresholds: CountThresholds, relative: bool) {
+    const SHOWN: usize = 20;
+    let root = tree.path(tree.root);
+    if !root.as_os_str().is_empty() {
+        match inodes::filesystem_inodes(&root) {
+            Ok(usage) if usage.total > 0 => println!(
+                "Inodes: {} of {} used ({:.1}%), {} free",
+                usage.used(),
+                usage.total,
+                usage.used_fraction() * 100.0,
+                usage.free
+            ),
+            Ok(_) => println!("Inodes: not limited on this filesystem"),
+            Err(e) => eprintln!("warning: could not read inode use: {e}"),
+        }
+        let limit = |limit: Option<u64>| limit.map_or("none".to_string(), |n| n.to_string());
+        match inodes::user_quota(&root) {
+            Ok(Some(quota)) => println!(
+                "Quota: {} files used, soft limit {}, hard limit {}",
+                quota.used,
+                limit(quota.soft),
+                limit(quota.hard)
+            ),
+            Ok(None) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {}
+            Err(e) => eprintln!("warning: could not read inode quota: {e}"),
+        }
+    }
+    let heavy = inodes::heavy_dirs(tree, thresholds);
+    if heavy.is_empty() {
+        println!(
+            "No folder holds {} files or {} entries",
+            thresholds.files, thresholds.entries
+        );
+        return;
+    }
+    println!("{:>10} {:>10}  Folder", "Files", "Entries");
+    for dir in heavy.iter().take(SHOWN) {
+        let mark = |over: bool| if over { "!" } else { " " };
+        let path = tree.path(dir.id);
+        let path = shown_path(tree, &path, relative);
+        println!(
+            "{:>9}{} {:>9}{}  {}",
+            dir.files,
+            mark(dir.over_files),
+            dir.entries,
+            mark(dir.over_entries),
+            path.display()
+        );
+    }
+    if heavy.len() > SHOWN {
+        println!("… {} more", heavy.len() - SHOWN);
+    }
+}
+
+fn print_age_summary(source: &impl NodeSource, root: NodeId) {
+    let root = age::age_histogram(source, root, SystemTime::now());
+    println!("{:<16} {:>10} {:>12}", "Last modified", "Files", "Size");
+    for bucket in AgeBucket::ALL {
+        println!(
+            "{:<16} {:>10} {:>12}",
+            bucket.label(),
+            root.files_in(bucket),
+            human_bytes(root.bytes_in(bucket))
+        );
+    }
+}
+
+fn print_slowest(tree: &Tree, profile: &ScanProfile, n: usize) {
+    println!(
+        "{:>10} {:>10} {:>12}  Directory",
+        "Time", "Entries", "Per entry"
+    );
+    for dir in profile.slowest(n) {
+        println!(
+            "{:>10.1?} {:>10} {:>12.1?}  {}",
+            dir.subtree,
+            dir.subtree_entries,
+            dir.per_entry(),
+            dir.path.display()
+        );
+    }
+    println!("Total scan time: {:.1?}", profile.elapsed);
+    println!("Metadata lookups: {}", profile.metadata_lookups);
+    let memory = tree.memory_usage();
+    println!(
+        "Tree memory: {} ({} nodes, {} names, {} child lists, {} other)",
+        human_bytes(memory.total() as u128),
+        human_bytes(memory.nodes as u128),
+        human_bytes(memory.names as u128),
+        human_bytes(memory.children as u128),
+        human_bytes(memory.other as u128)
+    );
+}
+
+fn print_owner_summary(source: &impl NodeSource, root: NodeId) {
+    println!("{:<24} {:>10} {:>12}", "Owner", "Files", "Size");
+    for usage in usage_by_owner(source, root) {
+        println!(
+            "{:<24} {:>10} {:>12}",
+            usage.owner.to_string(),
+            usage.files,
+            human_bytes(usage.bytes)
+        );
+    }
+}
+
+fn print_ext_summary(source: &impl NodeSource, root: NodeId) {
+    println!("{:<16} {:>10} {:>12}", "Extension", "Files", "Size");
+    for stat in ext_stats(source, root) {
+        println!(
+            "{:<16} {:>10} {:>12}",
+            stat.label(),
+            stat.files,
+            human_bytes(stat.bytes)
+        );
+    }
 }
@@ -0,0 +1,358 @@
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use treesize_core::human::human_bytes;
+use treesize_core::journal::{Journal, JournalEntry, Operation};
+use treesize_core::model::NodeKind;
+use treesize_core::ops::{self, TransferOptions};
+
+use crate::{scan_tree, WalkArgs};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+/// Flags shared by every command that changes the filesystem.
+#[derive(Args, Debug)]
+pub struct MutateArgs {
+    /// Only print what would be done, without touching anything
+    #[arg(long)]
+    dry_run: bool,
+    /// Do not ask for confirmation; also required to act for commands that
+    /// default to a dry run
+    #[arg(short, long)]
+    yes: bool,
+    /// Confirm each item before acting (default for delete/archive in a terminal)
+    #[arg(short, long)]
+    interactive: bool,
+    /// How to print the plan or results
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct DeleteArgs {
+    /// Files or directories to delete
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    #[command(flatten)]
+    common: MutateArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ArchiveArgs {
+    /// Files or directories to move
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    /// Archive destination (local folder or network share)
+    #[arg(long)]
+    to: PathBuf,
+    /// Scan root the paths are relative to; their structure below it is kept
+    #[arg(long)]
+    root: PathBuf,
+    /// Verify copies with checksums before removing the source
+    #[arg(long)]
+    verify: bool,
+    #[command(flatten)]
+    common: MutateArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct CleanArgs {
+    /// Directory to clean
+    root: PathBuf,
+    /// Glob of entries to remove (repeatable), e.g. `node_modules` or `*.tmp`
+    #[arg(long = "match", value_name = "GLOB", required = true)]
+    patterns: Vec<String>,
+    #[command(flatten)]
+    walk: WalkArgs,
+    #[command(flatten)]
+    common: MutateArgs,
+}
+
+#[derive(Serialize)]
+struct Item {
+    path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    destination: Option<PathBuf>,
+    bytes: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    skipped: bool,
+    /// Why the item is skipped without asking, e.g. a mount point.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+    /// Known from the scan to be a directory; only set by `clean`.
+    #[serde(skip)]
+    is_dir: bool,
+}
+
+#[derive(Serialize)]
+struct Report {
+    action: &'static str,
+    dry_run: bool,
+    total_bytes: u128,
+    items: Vec<Item>,
+}
+
+pub fn delete(args: DeleteArgs) -> i32 {
+    let items = args
+        .paths
+        .iter()
+        .map(|p| planned(p, None, ops::path_size(p)))
+        .collect();
+    execute(
+        Operation::Delete,
+        items,
+        args.common.dry_run,
+        args.common.prompts(true),
+        args.common.format,
+        |item| ops::remove_path(&item.path).map_err(|e| e.to_string()),
+    )
+}
+
+pub fn archive(args: ArchiveArgs) -> i32 {
+    let mut items = Vec::new();
+    for path in &args.paths {
+        match ops::archive_destination(path, &args.root, &args.to) {
+            Ok(dest) => items.push(planned(path, Some(dest), ops::path_size(path))),
+            Err(e) => {
+                eprintln!("error: {e}");
+                return 2;
+            }
+        }
+    }
+    let options = TransferOptions {
+        verify: args.verify,
+    };
+    execute(
+        Operation::Archive,
+        items,
+        args.common.dry_run,
+        args.common.prompts(true),
+        args.common.format,
+        |item| {
+            ops::move_to_archive(&item.path, &args.root, &args.to, options, |_| {})
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        },
+    )
+}
+
+pub fn clean(args: CleanArgs) -> i32 {
+    let (tree, _) = scan_tree(std::slice::from_ref(&args.root), &args.walk);
+    let Some(tree) = tree else {
+        eprintln!("error: scan of {} did not complete", args.root.display());
+        return 1;
+    };
+    let ids = match ops::match_nodes(&tree, &args.patterns) {
+        Ok(ids) => ids,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return 2;
+        }
+    };
+    let mut items: Vec<Item> = ids
+        .into_iter()
+        .map(|id| {
+            let node = tree.node(id);
+            let mut item = planned(&tree.path(id), None, node.size);
+            item.is_dir = matches!(node.kind, NodeKind::Dir);
+            // Removing it would empty the filesystem mounted there, which
+            // the scan didn't enter
+            if matches!(node.kind, NodeKind::MountPoint) {
+                item.skipped = true;
+                item.reason = Some("mount point");
+            }
+            item
+        })
+        .collect();
+    items.sort_by_key(|item| std::cmp::Reverse(item.bytes));
+    // Cleaning is pattern driven, so it only acts when explicitly confirmed.
+    let dry_run = args.common.dry_run || !(args.common.yes || args.common.interactive);
+    let code = execute(
+        Operation::Clean,
+        items,
+        dry_run,
+        args.common.prompts(false),
+        args.common.format,
+        |item| ops::remove_entry(&item.path, item.is_dir).map_err(|e| e.to_string()),
+    );
+    if dry_run && !args.common.dry_run && args.common.format == OutputFormat::Human {
+        println!("Dry run only; pass --yes to remove these items.");
+    }
+    code
+}
+
+impl Item {
+    fn done(&self) -> bool {
+        self.error.is_none() && !self.skipped
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn planned(path: &Path, destination: Option<PathBuf>, bytes: u128) -> Item {
+    Item {
+        path: path.to_path_buf(),
+        destination,
+        bytes,
+        error: None,
+        skipped: false,
+        reason: None,
+        is_dir: false,
+    }
+}
+
+impl MutateArgs {
+    /// Whether to confirm each item. `terminal_default` enables prompting
+    /// when stdin is a terminal and neither `--yes` nor `--interactive` was
+    /// given.
+    fn prompts(&self, terminal_default: bool) -> bool {
+        !self.yes && (self.interactive || (terminal_default && std::io::stdin().is_terminal()))
+    }
+}
+
+enum Answer {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
+/// Asks on stderr so JSON on stdout stays machine readable. End of input
+/// counts as quit.
+fn ask(question: &str) -> Answer {
+    let stdin = std::io::stdin();
+    loop {
+        eprint!("{question} [y]es/[n]o/[a]ll/[q]uit: ");
+        let _ = std::io::stderr().flush();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            return Answer::Quit;
+        }
+        match line.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => return Answer::Yes,
+            "n" | "no" => return Answer::No,
+            "a" | "all" => return Answer::All,
+            "q" | "quit" => return Answer::Quit,
+            _ => {}
+        }
+    }
+}
+
+/// Prints the plan when `dry_run`, otherwise runs `op` on every item
+/// (confirming each one first when `prompt` is set), journals each outcome
+/// and prints the results. Returns the exit code.
+fn execute(
+    operation: Operation,
+    mut items: Vec<Item>,
+    dry_run: bool,
+    mut prompt: bool,
+    format: OutputFormat,
+    mut op: impl FnMut(&Item) -> Result<(), String>,
+) -> i32 {
+    let journal = Journal::default_location();
+    if !dry_run {
+        let mut quit = false;
+        for item in &mut items {
+            if item.reason.is_some() {
+                continue;
+            }
+            if prompt && !quit {
+                let question = format!(
+                    "{} {} ({})?",
+                    capitalize(operation.label()),
+                    item.path.display(),
+                    human_bytes(item.bytes)
+                );
+                match ask(&question) {
+                    Answer::Yes => {}
+                    Answer::All => prompt = false,
+                    Answer::No => item.skipped = true,
+                    Answer::Quit => quit = true,
+                }
+            }
+            if quit {
+                item.skipped = true;
+            }
+            if item.skipped {
+                continue;
+            }
+            let result = op(item);
+            let mut entry =
+                JournalEntry::new(operation, &item.path, item.bytes, "cli").outcome(&result);
+            if let Some(dest) = &item.destination {
+                entry = entry.destination(dest);
+            }
+            if let Err(e) = journal.append(&entry) {
+                eprintln!("warning: could not write activity log: {e}");
+            }
+            item.error = result.err();
+        }
+    }
+
+    let failed = items.iter().filter(|i| i.error.is_some()).count();
+    let report = Report {
+        action: operation.label(),
+        dry_run,
+        total_bytes: items.iter().filter(|i| i.done()).map(|i| i.bytes).sum(),
+        items,
+    };
+    match format {
+        OutputFormat::Json => match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => eprintln!("error: {e}"),
+        },
+        OutputFormat::Human => print_human(&report),
+    }
+    if failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+fn print_human(report: &Report) {
+    let verb = match (report.action, report.dry_run) {
+        ("delete", true) | ("clean", true) => "Would delete",
+        ("delete", false) | ("clean", false) => "Deleted",
+        (_, true) => "Would move",
+        (_, false) => "Moved",
+    };
+    for item in &report.items {
+        let size = human_bytes(item.bytes);
+        if item.skipped {
+            match item.reason {
+                Some(reason) => println!("  skipped      {} ({reason})", item.path.display()),
+                None => println!("  skipped      {}", item.path.display()),
+            }
+            continue;
+        }
+        match (&item.destination, &item.error) {
+            (_, Some(err)) => println!("  FAILED {}: {err}", item.path.display()),
+            (Some(dest), None) => println!(
+                "  {size:>12}  {} -> {}",
+                item.path.display(),
+                dest.display()
+            ),
+            (None, None) => println!("  {size:>12}  {}", item.path.display()),
+        }
+    }
+    let ok = report.items.iter().filter(|i| i.done()).count();
+    println!(
+        "{verb} {ok} item(s), {} total",
+        human_bytes(report.total_bytes)
+    );
+}
@@ -0,0 +1,170 @@
+use chrono::{DateTime, Local};
+use clap::Args;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use treesize_core::human::human_bytes;
+use treesize_core::model::{NodeId, NodeKind, Tree};
+
+use crate::mutate::OutputFormat;
+
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    /// Scan to query: a file saved with --save, or a --json report
+    snapshot: PathBuf,
+    /// Folder to report on, in full or relative to the scanned folder
+    /// (default: the scanned folder)
+    #[arg(long)]
+    path: Option<PathBuf>,
+    /// Levels below the folder to list; 0 reports the folder alone
+    #[arg(long, default_value_t = 1)]
+    depth: usize,
+    /// Most entries to list per folder, largest first; 0 lists all
+    #[arg(short = 'n', long, default_value_t = 0)]
+    limit: usize,
+    /// List folders only, leaving out files and links
+    #[arg(long)]
+    dirs_only: bool,
+    /// How to print the answer
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+/// A node of the answer, as printed with `--format json`.
+#[derive(Serialize)]
+struct Entry<'a> {
+    path: PathBuf,
+    name: &'a str,
+    kind: &'static str,
+    size_bytes: u128,
+    files: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<DateTime<Local>>,
+    /// Absent at the depth limit and for entries that hold nothing.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<Entry<'a>>,
+    /// Entries left out by `--limit`.
+    #[serde(skip_serializing_if = "is_zero")]
+    more: usize,
+}
+
+#[derive(Serialize)]
+struct Answer<'a> {
+    snapshot: &'a Path,
+    scanned_at: Option<DateTime<Local>>,
+    #[serde(flatten)]
+    entry: Entry<'a>,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// Answers from the saved scan alone; nothing on disk is looked at. Exits
+/// with 2 if the snapshot can't be read and 1 if the path isn't in it.
+pub fn run(args: QueryArgs) -> i32 {
+    let tree = match Tree::open(&args.snapshot) {
+        Ok(tree) => tree,
+        Err(e) => {
+            eprintln!("error: could not read {}: {e}", args.snapshot.display());
+            return 2;
+        }
+    };
+    let id = match &args.path {
+        Some(path) => match tree.find_by_path(path) {
+            Some(id) => id,
+            None => {
+                eprintln!(
+                    "error: {} is not in {}",
+                    path.display(),
+                    args.snapshot.display()
+                );
+                return 1;
+            }
+        },
+        None => tree.root,
+    };
+    let entry = entry(&tree, id, &args, args.depth);
+    match args.format {
+        OutputFormat::Json => {
+            let answer = Answer {
+                snapshot: &args.snapshot,
+                scanned_at: tree.meta.scanned_at,
+                entry,
+            };
+            match serde_json::to_string_pretty(&answer) {
+                Ok(json) => println!("{json}"),
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    return 1;
+                }
+            }
+        }
+        OutputFormat::Human => {
+            if let Some(at) = tree.meta.scanned_at {
+                println!("As scanned {}", at.format("%Y-%m-%d %H:%M"));
+            }
+            print_human(&entry, 0);
+        }
+    }
+    0
+}
+
+/// `path` as given, or else relative to the scanned folder.
+fn entry<'a>(tree: &'a Tree, id: NodeId, args: &QueryArgs, depth: usize) -> Entry<'a> {
+    let node = tree.node(id);
+    let mut children = Vec::new();
+    let mut more = 0;
+    if depth > 0 {
+        let mut ids = tree.children_sorted(id, |n| std::cmp::Reverse(n.size));
+        if args.dirs_only {
+            ids.retain(|&c| matches!(tree.node(c).kind, NodeKind::Dir));
+        }
+        if args.limit > 0 && ids.len() > args.limit {
+            more = ids.len() - args.limit;
+            ids.truncate(args.limit);
+        }
+        children = ids
+            .into_iter()
+            .map(|c| entry(tree, c, args, depth - 1))
+            .collect();
+    }
+    Entry {
+        path: tree.path(id),
+        name: &node.name,
+        kind: node.kind.as_str(),
+        size_bytes: node.size,
+        files: node.file_count,
+        modified: node.modified.map(DateTime::from),
+        children,
+        more,
+    }
+}
+
+fn print_human(entry: &Entry, level: usize) {
+    let name = if level == 0 {
+        entry.path.display().to_string()
+    } else {
+        entry.name.to_string()
+    };
+    let slash = if entry.kind == "dir" { "/" } else { "" };
+    println!(
+        "{:>10}  {:>9}  {:indent$}{name}{slash}",
+        human_bytes(entry.size_bytes),
+        entry.files,
+        "",
+        indent = level * 2
+    );
+    for child in &entry.children {
+        print_human(child, level + 1);
+    }
+    if entry.more > 0 {
+        println!(
+            "{:>10}  {:>9}  {:indent$}… {} more",
+            "",
+            "",
+            "",
+            entry.more,
+            indent = (level + 1) * 2
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use treesize_core::model::Tree;
+use treesize_core::script::{Script, ScriptLimits};
+
+/// Loads the `--script` before scanning, so a typo doesn't cost a scan.
+pub fn load(path: &Path, max_operations: u64) -> Option<Script> {
+    match Script::load(path) {
+        Ok(script) => Some(script.with_limits(ScriptLimits {
+            max_operations,
+            ..ScriptLimits::default()
+        })),
+        Err(e) => {
+            eprintln!("error: {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Runs `script` on the scanned tree, then lists what it tagged and sends
+/// what it notified to `notify_command`, or prints it without one.
+pub fn run(script: &Script, tree: Tree, notify_command: Option<&str>, relative: bool) -> i32 {
+    let tree = Arc::new(tree);
+    let outcome = match script.run(Arc::clone(&tree)) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return 1;
+        }
+    };
+    for path in &outcome.exported {
+        println!("Script wrote {}", path.display());
+    }
+    if !outcome.tags.is_empty() {
+        println!("Script tagged {} entries:", outcome.tags.len());
+        for (id, label) in &outcome.tags {
+            let path = tree.path(*id);
+            let path = crate::shown_path(&tree, &path, relative);
+            println!("    {label:<16} {}", path.display());
+        }
+    }
+    if outcome.notifications.is_empty() {
+        return 0;
+    }
+    let Some(command) = notify_command else {
+        for message in &outcome.notifications {
+            println!("notify: {message}");
+        }
+        return 0;
+    };
+    let mut message = outcome.notifications.join("\n");
+    message.push('\n');
+    match notify(command, &message) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: could not run the notify command: {e}");
+            1
+        }
+    }
+}
+
+/// Runs `command` through the shell with `message` on its standard input.
+fn notify(command: &str, message: &str) -> std::io::Result<()> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let mut child = shell.arg(command).stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(message.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("it exited with {status}")))
+    }
+}
@@ -0,0 +1,438 @@
+use chrono::Local;
+use clap::Args;
+use crossbeam_channel::{Receiver, Sender};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Read};
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, Server};
+use treesize_core::diff::{self, TreeDiff};
+use treesize_core::journal::{Journal, JournalEntry, Operation};
+use treesize_core::model::Tree;
+use treesize_core::quarantine::{
+    Quarantine, QuarantineError, QuarantinedEntry, DEFAULT_RETENTION_DAYS,
+};
+use treesize_core::store::{GenerationInfo, SnapshotStore};
+
+use crate::{scan_tree, WalkArgs};
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Directories to scan and serve; several are served as one combined
+    /// tree
+    #[arg(required = true)]
+    roots: Vec<PathBuf>,
+    /// Address to answer HTTP requests on
+    #[arg(long, default_value = "127.0.0.1:7480")]
+    listen: SocketAddr,
+    /// Minutes from the end of one scan to the start of the next
+    #[arg(long, value_name = "MINUTES", default_value_t = 60)]
+    every: u64,
+    /// Scans to keep for listing and comparing
+    #[arg(long, default_value_t = 10)]
+    keep: usize,
+    /// Token readers must send as `Authorization: Bearer TOKEN`; without
+    /// one, anyone who can connect may read
+    #[arg(long, env = "TREESIZE_READ_TOKEN", hide_env_values = true)]
+    read_token: Option<String>,
+    /// Token for requests that change anything, such as starting a rescan;
+    /// without one, those are refused. It also grants reading
+    #[arg(long, env = "TREESIZE_ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+    /// Folder deletions are moved to until purged (default: one in the
+    /// per-user data folder); best on the same filesystem as the roots
+    #[arg(long, value_name = "DIR")]
+    quarantine: Option<PathBuf>,
+    /// Days deletions stay restorable before they are purged for good
+    #[arg(long, value_name = "DAYS", default_value_t = DEFAULT_RETENTION_DAYS)]
+    retention: i64,
+    #[command(flatten)]
+    walk: WalkArgs,
+}
+
+/// Threads answering requests, each reading the store on its own.
+const WORKERS: usize = 4;
+
+/// Changes listed by `/diff` unless `limit` says otherwise.
+const DIFF_LIMIT: usize = 50;
+
+/// Largest request body read; bodies only ever name one path or entry.
+const MAX_BODY: u64 = 64 * 1024;
+
+type Reply = Response<Cursor<Vec<u8>>>;
+
+/// What a request may do, from the token it sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Role {
+    Reader,
+    Admin,
+}
+
+struct Access {
+    read_token: Option<String>,
+    admin_token: Option<String>,
+}
+
+impl Access {
+    /// The role `request` has; `None` if it sent a token that isn't
+    /// known, or none where reading needs one.
+    fn role(&self, request: &Request) -> Option<Role> {
+        let token = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Authorization"))
+            .and_then(|h| h.value.as_str().strip_prefix("Bearer "));
+        let Some(token) = token else {
+            return self.read_token.is_none().then_some(Role::Reader);
+        };
+        let matches = |known: &Option<String>| known.as_deref().is_some_and(|k| same(k, token));
+        if matches(&self.admin_token) {
+            Some(Role::Admin)
+        } else if matches(&self.read_token) {
+            Some(Role::Reader)
+        } else {
+            None
+        }
+    }
+}
+
+/// Compares tokens in time independent of where they differ, so the
+/// response time doesn't give a token away byte by byte.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |d, (x, y)| d | (x ^ y)) == 0
+}
+
+/// What the worker threads share.
+struct Service {
+    store: Arc<SnapshotStore>,
+    access: Access,
+    rescan: Sender<()>,
+    roots: Vec<PathBuf>,
+    quarantine: Arc<Quarantine>,
+    journal: Journal,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+#[derive(Serialize)]
+struct Queued {
+    queued: bool,
+}
+
+#[derive(Deserialize)]
+struct PathBody {
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct IdBody {
+    id: String,
+}
+
+#[derive(Serialize)]
+struct DiffBody {
+    from: GenerationInfo,
+    to: GenerationInfo,
+    #[serde(flatten)]
+    diff: TreeDiff,
+}
+
+/// Rescans the roots every `--every` minutes, keeping the last `--keep`
+/// scans, and answers over HTTP:
+///
+/// - `GET /generations`: the scans kept, newest first
+/// - `GET /diff?from=A&to=B`: what changed between two of them; `to`
+///   defaults to the newest, either may be `latest`, and `limit` caps the
+///   changes listed (0 lists all)
+/// - `POST /rescan`: starts the next scan now
+/// - `POST /delete` with `{"path": ...}`: moves a file or folder below a
+///   root into the quarantine folder rather than deleting it
+/// - `GET /quarantine`: what was deleted and is still restorable, newest
+///   first
+/// - `POST /restore` or `POST /purge` with `{"id": ...}`: puts a deletion
+///   back where it was, or deletes it for good
+///
+/// Requests that change anything need the admin token, and reading needs
+/// the read or admin token if `--read-token` is set. Deletions are purged
+/// once `--retention` days old. Runs until interrupted.
+pub fn run(args: ServeArgs) -> i32 {
+    let server = match Server::http(args.listen) {
+        Ok(server) => Arc::new(server),
+        Err(e) => {
+            eprintln!("error: could not listen on {}: {e}", args.listen);
+            return 2;
+        }
+    };
+    if args.read_token.is_none() && !args.listen.ip().is_loopback() {
+        eprintln!(
+            "warning: anyone who can reach {} can read the scans; set --read-token",
+            args.listen
+        );
+    }
+    let store = Arc::new(SnapshotStore::new(args.keep));
+    let quarantine = Arc::new(
+        Quarantine::new(
+            args.quarantine
+                .clone()
+                .unwrap_or_else(Quarantine::default_dir),
+        )
+        .with_retention(chrono::Duration::days(args.retention.max(0))),
+    );
+    // One pending request is enough; more would rescan for nothing
+    let (rescan, rescan_rx) = crossbeam_channel::bounded(1);
+    let service = Arc::new(Service {
+        store: Arc::clone(&store),
+        access: Access {
+            read_token: args.read_token.clone(),
+            admin_token: args.admin_token.clone(),
+        },
+        rescan,
+        roots: args.roots.clone(),
+        quarantine: Arc::clone(&quarantine),
+        journal: Journal::default_location(),
+    });
+    for _ in 0..WORKERS {
+        let server = Arc::clone(&server);
+        let service = Arc::clone(&service);
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                respond(&service, request);
+            }
+        });
+    }
+    eprintln!("Serving on http://{}", args.listen);
+    let every = Duration::from_secs(args.every.max(1) * 60);
+    scan_loop(&args, &store, &quarantine, every, &rescan_rx)
+}
+
+/// Purges expired deletions, scans, publishes and waits for the next scan,
+/// forever.
+fn scan_loop(
+    args: &ServeArgs,
+    store: &SnapshotStore,
+    quarantine: &Quarantine,
+    every: Duration,
+    rescan: &Receiver<()>,
+) -> i32 {
+    loop {
+        match quarantine.purge_expired(Local::now()) {
+            Ok(purged) if !purged.is_empty() => {
+                eprintln!("Purged {} expired deletions", purged.len());
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("warning: could not purge expired deletions: {e}"),
+        }
+        match scan_tree(&args.roots, &args.walk) {
+            (Some(tree), _) => {
+                let id = store.publish(tree);
+                eprintln!("Published generation {id}");
+            }
+            (None, _) => eprintln!("warning: the scan did not finish; still serving the last one"),
+        }
+        // Woken early by POST /rescan
+        let _ = rescan.recv_timeout(every);
+    }
+}
+
+fn respond(service: &Service, mut request: Request) {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let needs = match (request.method(), path) {
+        (Method::Get, "/generations" | "/diff" | "/quarantine") => Role::Reader,
+        (Method::Post, "/rescan" | "/delete" | "/restore" | "/purge") => Role::Admin,
+        (
+            _,
+            "/generations" | "/diff" | "/quarantine" | "/rescan" | "/delete" | "/restore"
+            | "/purge",
+        ) => {
+            let _ = request.respond(error(405, "method not allowed here"));
+            return;
+        }
+        _ => {
+            let _ = request.respond(error(404, "no such endpoint"));
+            return;
+        }
+    };
+    let reply = match service.access.role(&request) {
+        None => error(401, "a valid token is needed")
+            .with_header(Header::from_bytes("WWW-Authenticate", "Bearer").expect("valid header")),
+        Some(_) if needs == Role::Admin && service.access.admin_token.is_none() => {
+            error(403, "changes are off; start the server with --admin-token")
+        }
+        Some(role) if role < needs => error(403, "this needs the admin token"),
+        Some(_) => match path {
+            "/generations" => json(200, &service.store.generations()),
+            "/diff" => compare(&service.store, query),
+            "/quarantine" => match service.quarantine.entries() {
+                Ok(entries) => json(200, &entries),
+                Err(e) => error(500, &e.to_string()),
+            },
+            "/delete" => delete(service, &mut request),
+            "/restore" => restore(service, &mut request),
+            "/purge" => purge(service, &mut request),
+            _ => {
+                let _ = service.rescan.try_send(());
+                json(202, &Queued { queued: true })
+            }
+        },
+    };
+    let _ = request.respond(reply);
+}
+
+fn compare(store: &SnapshotStore, query: &str) -> Reply {
+    let Some(from) = param(query, "from") else {
+        return error(400, "give the generation to compare from as `from`");
+    };
+    let to = param(query, "to").unwrap_or("latest");
+    let limit = match param(query, "limit").map(str::parse) {
+        None => DIFF_LIMIT,
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => return error(400, "`limit` must be a number"),
+    };
+    let (Some((from, old)), Some((to, new))) = (generation(store, from), generation(store, to))
+    else {
+        return error(404, "no such generation; see /generations for those kept");
+    };
+    let mut diff = diff::diff(&old, &new);
+    if limit > 0 {
+        diff.changes.truncate(limit);
+    }
+    json(200, &DiffBody { from, to, diff })
+}
+
+/// Quarantines a path below one of the roots; the roots themselves, and
+/// paths the newest scan didn't find, are refused. Queues a rescan so the
+/// scans catch up.
+fn delete(service: &Service, request: &mut Request) -> Reply {
+    let path = match body::<PathBody>(request) {
+        Ok(body) => body.path,
+        Err(reply) => return reply,
+    };
+    let below_root = path.components().all(|c| c != Component::ParentDir)
+        && service
+            .roots
+            .iter()
+            .any(|root| path.starts_with(root) && path != *root);
+    if !below_root {
+        return error(403, "only paths below the served folders can be deleted");
+    }
+    let scanned = service
+        .store
+        .latest()
+        .is_some_and(|(_, tree)| tree.find(&path).is_some());
+    if !scanned {
+        return error(404, "not in the newest scan; see /diff for what is there");
+    }
+    let result = service.quarantine.quarantine(&path);
+    let held = result
+        .as_ref()
+        .ok()
+        .map(|entry| service.quarantine.dir().join(&entry.id));
+    record(service, Operation::Delete, &path, held.as_deref(), &result);
+    changed(service, result)
+}
+
+fn restore(service: &Service, request: &mut Request) -> Reply {
+    let id = match body::<IdBody>(request) {
+        Ok(body) => body.id,
+        Err(reply) => return reply,
+    };
+    let result = service.quarantine.restore(&id);
+    let original = result.as_ref().ok().map(|entry| entry.original.as_path());
+    let held = service.quarantine.dir().join(&id);
+    record(service, Operation::Move, &held, original, &result);
+    changed(service, result)
+}
+
+fn purge(service: &Service, request: &mut Request) -> Reply {
+    let id = match body::<IdBody>(request) {
+        Ok(body) => body.id,
+        Err(reply) => return reply,
+    };
+    let result = service.quarantine.purge(&id);
+    let held = service.quarantine.dir().join(&id);
+    record(service, Operation::Delete, &held, None, &result);
+    match result {
+        Ok(entry) => json(200, &entry),
+        Err(e) => quarantine_error(&e),
+    }
+}
+
+/// Journals a move into or out of the quarantine, or a purge from it.
+fn record(
+    service: &Service,
+    operation: Operation,
+    source: &Path,
+    destination: Option<&Path>,
+    result: &Result<QuarantinedEntry, QuarantineError>,
+) {
+    let bytes = result.as_ref().map_or(0, |entry| entry.size_bytes);
+    let mut entry = JournalEntry::new(operation, source, bytes, "serve").outcome(result);
+    if let Some(destination) = destination {
+        entry = entry.destination(destination);
+    }
+    if let Err(e) = service.journal.append(&entry) {
+        eprintln!("warning: could not journal {}: {e}", source.display());
+    }
+}
+
+/// Answers a change to the tree on disk, queueing a rescan if it was made.
+fn changed(service: &Service, result: Result<QuarantinedEntry, QuarantineError>) -> Reply {
+    match result {
+        Ok(entry) => {
+            let _ = service.rescan.try_send(());
+            json(200, &entry)
+        }
+        Err(e) => quarantine_error(&e),
+    }
+}
+
+fn quarantine_error(e: &QuarantineError) -> Reply {
+    let status = match e {
+        QuarantineError::NotFound(_) => 404,
+        QuarantineError::Occupied(_) => 409,
+        QuarantineError::Io(io) if io.kind() == std::io::ErrorKind::NotFound => 404,
+        _ => 500,
+    };
+    error(status, &e.to_string())
+}
+
+/// The JSON body of `request`, or the reply refusing it.
+fn body<T: DeserializeOwned>(request: &mut Request) -> Result<T, Reply> {
+    serde_json::from_reader(request.as_reader().take(MAX_BODY))
+        .map_err(|e| error(400, &format!("could not read the request body: {e}")))
+}
+
+/// A generation by id, or the newest one for `latest`.
+fn generation(store: &SnapshotStore, id: &str) -> Option<(GenerationInfo, Arc<Tree>)> {
+    match id {
+        "latest" => store.latest(),
+        id => store.get(id.parse().ok()?),
+    }
+}
+
+fn param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+fn json<T: Serialize>(status: u16, body: &T) -> Reply {
+    let content_type =
+        Header::from_bytes("Content-Type", "application/json").expect("valid header");
+    Response::from_data(serde_json::to_vec_pretty(body).unwrap_or_default())
+        .with_status_code(status)
+        .with_header(content_type)
+}
+
+fn error(status: u16, message: &str) -> Reply {
+    json(status, &ErrorBody { error: message })
+}
@@ -0,0 +1,135 @@
+use clap::{Args, Subcommand};
+use std::path::PathBuf;
+use treesize_core::annotations::Annotations;
+
+#[derive(Args, Debug)]
+pub struct TagArgs {
+    #[command(subcommand)]
+    action: Action,
+    /// File the tags and notes are kept in (default: annotations.json next
+    /// to the saved scans)
+    #[arg(long, value_name = "PATH", global = true)]
+    file: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// Tag a file or folder, e.g. `tag add ~/builds "safe to delete"`
+    Add {
+        path: PathBuf,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Take tags off a file or folder
+    Remove {
+        path: PathBuf,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Set the note of a file or folder; an empty note removes it
+    Note { path: PathBuf, note: String },
+    /// Take every tag and the note off a file or folder
+    Clear { path: PathBuf },
+    /// Rename a tag wherever it is used
+    Rename { from: String, to: String },
+    /// List what is tagged or has a note
+    List {
+        /// List only what is tagged TAG
+        #[arg(long)]
+        tag: Option<String>,
+    },
+}
+
+/// Adds, changes, removes or lists the tags and notes that reports and the
+/// app show with the files and folders they are attached to.
+pub fn run(args: TagArgs) -> i32 {
+    let file = args.file.unwrap_or_else(Annotations::default_path);
+    let mut annotations = match Annotations::load(&file) {
+        Ok(annotations) => annotations,
+        Err(e) => {
+            eprintln!("error: could not read {}: {e}", file.display());
+            return 2;
+        }
+    };
+    let changed = match args.action {
+        Action::Add { path, tags } => {
+            let mut added = 0;
+            for tag in &tags {
+                added += usize::from(annotations.add_tag(&path, tag));
+            }
+            if added == 0 {
+                println!("Nothing to add");
+            }
+            added > 0
+        }
+        Action::Remove { path, tags } => {
+            let mut removed = 0;
+            for tag in &tags {
+                removed += usize::from(annotations.remove_tag(&path, tag));
+            }
+            if removed == 0 {
+                println!("Nothing to remove");
+            }
+            removed > 0
+        }
+        Action::Note { path, note } => {
+            annotations.set_note(&path, &note);
+            true
+        }
+        Action::Clear { path } => {
+            let removed = annotations.remove(&path).is_some();
+            if !removed {
+                println!("Nothing to remove");
+            }
+            removed
+        }
+        Action::Rename { from, to } => {
+            let renamed = annotations.rename_tag(&from, &to);
+            println!("Renamed \"{from}\" on {renamed} entries");
+            renamed > 0
+        }
+        Action::List { tag } => {
+            list(&annotations, tag.as_deref());
+            false
+        }
+    };
+    if !changed {
+        return 0;
+    }
+    match annotations.save(&file) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: could not write {}: {e}", file.display());
+            1
+        }
+    }
+}
+
+fn list(annotations: &Annotations, tag: Option<&str>) {
+    for (path, annotation) in annotations.iter() {
+        if tag.is_some_and(|tag| !annotation.tags.contains(tag)) {
+            continue;
+        }
+        println!("{}", path.display());
+        if !annotation.tags.is_empty() {
+            println!("    tags: {}", annotation.tags_text());
+        }
+        if !annotation.note.is_empty() {
+            println!("    note: {}", annotation.note);
+        }
+    }
+}
+
+/// The tags and notes kept next to the saved scans, for reports to write
+/// with the nodes they are attached to. A file that can't be read is
+/// reported and left out.
+pub fn load_default() -> Annotations {
+    let file = Annotations::default_path();
+    Annotations::load(&file).unwrap_or_else(|e| {
+        eprintln!(
+            "warning: leaving out tags: could not read {}: {e}",
+            file.display()
+        );
+        Annotations::default()
+    })
+}
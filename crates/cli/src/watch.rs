@@ -0,0 +1,124 @@
+use clap::Args;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Instant;
+use treesize_core::human::human_bytes;
+use treesize_core::model::Tree;
+use treesize_core::scanner::{scan_channel, ScanMsg};
+use treesize_core::watch;
+use treesize_core::watchdog::{GrowthAlert, GrowthRate, Watchdog};
+
+use crate::mutate::OutputFormat;
+use crate::{scan_tree, WalkArgs};
+
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    /// Directories to scan once and then watch
+    #[arg(required = true)]
+    roots: Vec<PathBuf>,
+    /// Alarm when a directory grows faster than RATE, e.g. "1 GB/hour" or
+    /// "200MB/10min"
+    #[arg(long, value_name = "RATE")]
+    alarm: GrowthRate,
+    /// How to print alarms: one line, or one JSON object per alarm
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+    #[command(flatten)]
+    walk: WalkArgs,
+}
+
+#[derive(Serialize)]
+struct AlarmLine<'a> {
+    time: String,
+    path: &'a std::path::Path,
+    grown: u128,
+    limit_bytes: u128,
+    limit_secs: u64,
+}
+
+/// Scans the roots, then keeps their sizes current from filesystem events
+/// and prints an alarm for every directory growing faster than allowed.
+/// Runs until interrupted.
+pub fn run(args: WatchArgs) -> i32 {
+    let roots: Vec<PathBuf> = args
+        .roots
+        .iter()
+        .map(|root| std::fs::canonicalize(root).unwrap_or_else(|_| root.clone()))
+        .collect();
+    let (Some(mut tree), _) = scan_tree(&roots, &args.walk) else {
+        eprintln!("error: the scan did not finish");
+        return 2;
+    };
+    let (tx, rx) = scan_channel();
+    let watching =
+        watch::watch(&roots[0], &args.walk.scan_options(), tx).and_then(|mut watcher| {
+            for extra in &roots[1..] {
+                watcher.add(extra)?;
+            }
+            Ok(watcher)
+        });
+    let _watcher = match watching {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("error: could not watch for changes: {e}");
+            return 2;
+        }
+    };
+    eprintln!(
+        "Watching {} ({}) for growth over {}",
+        roots[0].display(),
+        human_bytes(tree.node(tree.root).size),
+        args.alarm
+    );
+    let mut dog = Watchdog::new(roots, args.alarm);
+    for msg in rx {
+        let (path, delta) = match msg {
+            ScanMsg::NodeUpdated {
+                path,
+                bytes,
+                modified,
+            } => {
+                let delta = bytes as i128 - size_at(&tree, &path);
+                tree.upsert_file(&path, bytes as u128, modified);
+                (path, delta)
+            }
+            ScanMsg::NodeRemoved { path } => {
+                let delta = -size_at(&tree, &path);
+                tree.remove_path(&path);
+                (path, delta)
+            }
+            ScanMsg::Error(e) => {
+                eprintln!("warning: {e}");
+                continue;
+            }
+            _ => continue,
+        };
+        if let Some(alert) = dog.record(&path, delta, Instant::now()) {
+            print_alert(&alert, args.format);
+        }
+    }
+    0
+}
+
+fn size_at(tree: &Tree, path: &std::path::Path) -> i128 {
+    tree.find(path).map_or(0, |id| tree.node(id).size as i128)
+}
+
+fn print_alert(alert: &GrowthAlert, format: OutputFormat) {
+    let time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    match format {
+        OutputFormat::Human => println!("{time}  {alert}"),
+        OutputFormat::Json => {
+            let line = AlarmLine {
+                time,
+                path: &alert.path,
+                grown: alert.grown,
+                limit_bytes: alert.limit.bytes,
+                limit_secs: alert.limit.per.as_secs(),
+            };
+            if let Ok(json) = serde_json::to_string(&line) {
+                println!("{json}");
+            }
+        }
+    }
+}
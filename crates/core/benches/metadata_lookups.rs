@@ -0,0 +1,82 @@
+//! Scans a generated tree and reports the metadata lookups the scanner made
+//! per entry, along with how long each scan took. Exits with an error when
+//! an entry is looked up more than once; `tests/metadata_lookups.rs` holds
+//! the scanner to the same bound.
+//!
+//! Run with `cargo bench -p treesize-core --bench metadata_lookups`. On
+//! Linux, running it under `strace -f -c -e trace=%stat` shows the system
+//! calls behind the counts.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use treesize_core::{scan_channel, ScanMsg, ScanOptions, Scanner, Tree};
+
+const DIRS: usize = 200;
+const FILES_PER_DIR: usize = 50;
+const RUNS: u32 = 5;
+
+fn main() {
+    let root = std::env::temp_dir().join(format!("treesize-bench-{}", std::process::id()));
+    if let Err(e) = populate(&root) {
+        eprintln!("could not create {}: {e}", root.display());
+        let _ = fs::remove_dir_all(&root);
+        std::process::exit(1);
+    }
+    let entries = (DIRS * (FILES_PER_DIR + 1) + 1) as f64;
+    println!("{DIRS} folders of {FILES_PER_DIR} files, best of {RUNS} scans");
+    let mut over = false;
+    for one_file_system in [false, true] {
+        let options = ScanOptions {
+            one_file_system,
+            ..ScanOptions::default()
+        };
+        let mut best = Duration::MAX;
+        let mut lookups = 0;
+        for _ in 0..RUNS {
+            let started = Instant::now();
+            let tree = scan(&root, options.clone());
+            best = best.min(started.elapsed());
+            lookups = tree
+                .and_then(|t| t.meta.profile)
+                .map_or(0, |p| p.metadata_lookups);
+        }
+        println!(
+            "one_file_system={one_file_system:<5}  {lookups} lookups, {:.2} per entry, {best:.1?}",
+            lookups as f64 / entries
+        );
+        over |= lookups as f64 > entries;
+    }
+    let _ = fs::remove_dir_all(&root);
+    if over {
+        eprintln!("error: more than one metadata lookup per entry");
+        std::process::exit(1);
+    }
+}
+
+fn populate(root: &Path) -> std::io::Result<()> {
+    for d in 0..DIRS {
+        let dir = root.join(format!("d{d}"));
+        fs::create_dir_all(&dir)?;
+        for f in 0..FILES_PER_DIR {
+            fs::write(dir.join(format!("f{f}")), [0u8; 64])?;
+        }
+    }
+    Ok(())
+}
+
+fn scan(root: &Path, options: ScanOptions) -> Option<Tree> {
+    let (tx, rx) = scan_channel();
+    let handle = Scanner::new()
+        .with_options(options)
+        .spawn(root.to_path_buf(), tx);
+    let mut tree = None;
+    for msg in rx {
+        if let ScanMsg::Done(done) = msg {
+            tree = Some(Arc::unwrap_or_clone(done));
+        }
+    }
+    let _ = handle.join();
+    tree
+}
@@ -0,0 +1,254 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::model::{NodeId, NodeKind, Tree};
+use crate::ops::{hash_file, hash_prefix, OpError};
+use crate::search::NameMatching;
+
+/// Why a node is proposed for removal, from the safest to remove to the
+/// one most worth double checking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// A directory that applications recreate on demand.
+    Cache,
+    /// Same name, size and contents as `original`, which is kept. Names are
+    /// compared according to [`AdvisorOptions::name_matching`].
+    Duplicate { original: NodeId },
+    /// A large file not modified for `age_days`.
+    OldLargeFile { age_days: u64 },
+}
+
+impl Reason {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Reason::Cache => "cache",
+            Reason::Duplicate { .. } => "duplicate",
+            Reason::OldLargeFile { .. } => "old large file",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub id: NodeId,
+    pub bytes: u128,
+    pub reason: Reason,
+}
+
+#[derive(Debug, Clone)]
+pub struct AdvisorOptions {
+    /// Files untouched for at least this long count as old.
+    pub min_age: Duration,
+    /// Old files smaller than this are not worth proposing.
+    pub min_large_size: u128,
+    /// Directory names (case-insensitive) treated as caches.
+    pub cache_names: Vec<String>,
+    /// How names are compared to find duplicates.
+    pub name_matching: NameMatching,
+    /// Read files sharing a name and size to confirm they are duplicates.
+    /// Without it every such file counts as one, which can overstate what
+    /// is freed but reads nothing from disk.
+    pub read_contents: bool,
+}
+
+/// Bytes read from the start of each possible duplicate; only files whose
+/// starts match are read in full.
+const PREFIX_LEN: u64 = 64 * 1024;
+
+impl Default for AdvisorOptions {
+    fn default() -> Self {
+        Self {
+            min_age: Duration::from_secs(180 * 24 * 60 * 60),
+            min_large_size: 50 * 1024 * 1024,
+            cache_names: [
+                ".cache",
+                "cache",
+                "caches",
+                "__pycache__",
+                ".npm",
+                "node_modules",
+                "temp",
+                "tmp",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            name_matching: NameMatching::native(),
+            read_contents: true,
+        }
+    }
+}
+
+/// Candidates proposed to free `target` bytes, in the order they were picked.
+#[derive(Debug, Clone, Default)]
+pub struct Advice {
+    pub target: u128,
+    pub total: u128,
+    pub candidates: Vec<Candidate>,
+}
+
+impl Advice {
+    pub fn meets_target(&self) -> bool {
+        self.total >= self.target
+    }
+}
+
+/// Proposes nodes whose combined size reaches `target`: caches first, then
+/// duplicates, then old large files, largest first within each group. If
+/// everything found together falls short, all of it is returned. Files
+/// sharing a name and size are read to tell whether they are duplicates,
+/// unless [`AdvisorOptions::read_contents`] is off.
+pub fn advise(tree: &Tree, target: u128, options: &AdvisorOptions) -> Advice {
+    let mut candidates = find_candidates(tree, options);
+    candidates.sort_by(|a, b| {
+        tier(&a.reason)
+            .cmp(&tier(&b.reason))
+            .then(b.bytes.cmp(&a.bytes))
+    });
+
+    let mut advice = Advice {
+        target,
+        ..Advice::default()
+    };
+    for candidate in candidates {
+        if advice.meets_target() {
+            break;
+        }
+        advice.total += candidate.bytes;
+        advice.candidates.push(candidate);
+    }
+    advice
+}
+
+/// Splits `ids`, files of one size, into groups with the same contents,
+/// dropping files that match no other. The start of each file is compared
+/// first, so files that differ early are never read in full.
+fn same_contents(tree: &Tree, ids: Vec<NodeId>) -> Vec<Vec<NodeId>> {
+    let size = tree.nodes[ids[0].0 as usize].size;
+    let by_prefix = group_by_hash(tree, ids, |path| hash_prefix(path, PREFIX_LEN));
+    if size <= u128::from(PREFIX_LEN) {
+        return by_prefix;
+    }
+    by_prefix
+        .into_iter()
+        .flat_map(|ids| group_by_hash(tree, ids, hash_file))
+        .collect()
+}
+
+/// Groups of `ids` whose `hash` matches, leaving out files that match no
+/// other or can't be read.
+fn group_by_hash(
+    tree: &Tree,
+    ids: Vec<NodeId>,
+    hash: impl Fn(&Path) -> Result<blake3::Hash, OpError>,
+) -> Vec<Vec<NodeId>> {
+    let mut by_hash: HashMap<blake3::Hash, Vec<NodeId>> = HashMap::new();
+    for id in ids {
+        if let Ok(hash) = hash(&tree.path(id)) {
+            by_hash.entry(hash).or_default().push(id);
+        }
+    }
+    by_hash.into_values().filter(|ids| ids.len() > 1).collect()
+}
+
+fn tier(reason: &Reason) -> u8 {
+    match reason {
+        Reason::Cache => 0,
+        Reason::Duplicate { .. } => 1,
+        Reason::OldLargeFile { .. } => 2,
+    }
+}
+
+fn find_candidates(tree: &Tree, options: &AdvisorOptions) -> Vec<Candidate> {
+    let now = SystemTime::now();
+    let mut candidates = Vec::new();
+    let mut by_name_size: HashMap<(Cow<str>, u128), Vec<NodeId>> = HashMap::new();
+    let mut old_files = Vec::new();
+
+    let mut stack = vec![tree.root];
+    while let Some(id) = stack.pop() {
+        let node = &tree.nodes[id.0 as usize];
+        match node.kind {
+            NodeKind::Dir => {
+                let is_cache = id != tree.root
+                    && options
+                        .cache_names
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(&node.name));
+                if is_cache {
+                    if node.size > 0 {
+                        candidates.push(Candidate {
+                            id,
+                            bytes: node.size,
+                            reason: Reason::Cache,
+                        });
+                    }
+                } else {
+                    stack.extend(node.children.iter().copied());
+                }
+            }
+            NodeKind::File | NodeKind::Archive if node.size > 0 => {
+                by_name_size
+                    .entry((options.name_matching.key(&node.name), node.size))
+                    .or_default()
+                    .push(id);
+                if node.size >= options.min_large_size {
+                    old_files.push(id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Name and size only narrow down what is worth reading; files are
+    // duplicates once their contents match too
+    let mut duplicates = HashSet::new();
+    for mut ids in by_name_size.into_values().filter(|ids| ids.len() > 1) {
+        ids.sort_by_key(|id| id.0);
+        let groups = if options.read_contents {
+            same_contents(tree, ids)
+        } else {
+            vec![ids]
+        };
+        for same in groups {
+            let original = same[0];
+            for &id in &same[1..] {
+                duplicates.insert(id);
+                candidates.push(Candidate {
+                    id,
+                    bytes: tree.nodes[id.0 as usize].size,
+                    reason: Reason::Duplicate { original },
+                });
+            }
+        }
+    }
+
+    for id in old_files {
+        if duplicates.contains(&id) {
+            continue;
+        }
+        let node = &tree.nodes[id.0 as usize];
+        // The scanner doesn't keep file times, so look them up for the few
+        // files large enough to matter
+        let modified = node.modified.or_else(|| {
+            std::fs::metadata(tree.path(id))
+                .and_then(|m| m.modified())
+                .ok()
+        });
+        let Some(age) = modified.and_then(|m| now.duration_since(m).ok()) else {
+            continue;
+        };
+        if age >= options.min_age {
+            candidates.push(Candidate {
+                id,
+                bytes: node.size,
+                reason: Reason::OldLargeFile {
+                    age_days: age.as_secs() / 86_400,
+                },
+            });
+        }
+    }
+    candidates
+}
@@ -0,0 +1,161 @@
+use serde::Serialize;
+use std::time::{Duration, SystemTime};
+
+use crate::model::{NodeId, NodeKind, NodeSource, Tree};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Modification-age range a file falls into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgeBucket {
+    /// Modified within the last 30 days.
+    Recent,
+    /// 30 to 365 days old.
+    Months,
+    /// One to three years old.
+    Years,
+    /// Untouched for more than three years.
+    Stale,
+}
+
+impl AgeBucket {
+    pub const ALL: [AgeBucket; 4] = [
+        AgeBucket::Recent,
+        AgeBucket::Months,
+        AgeBucket::Years,
+        AgeBucket::Stale,
+    ];
+
+    pub fn of(age: Duration) -> Self {
+        match age.as_secs() / DAY {
+            d if d < 30 => AgeBucket::Recent,
+            d if d < 365 => AgeBucket::Months,
+            d if d < 3 * 365 => AgeBucket::Years,
+            _ => AgeBucket::Stale,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AgeBucket::Recent => "0–30 days",
+            AgeBucket::Months => "30–365 days",
+            AgeBucket::Years => "1–3 years",
+            AgeBucket::Stale => "over 3 years",
+        }
+    }
+
+    /// Short form for column names, e.g. `30_365d`.
+    pub fn key(self) -> &'static str {
+        match self {
+            AgeBucket::Recent => "0_30d",
+            AgeBucket::Months => "30_365d",
+            AgeBucket::Years => "1_3y",
+            AgeBucket::Stale => "over_3y",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Bytes and file counts per [`AgeBucket`], indexed in [`AgeBucket::ALL`]
+/// order. Files without a modification time are not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct AgeHistogram {
+    pub bytes: [u128; 4],
+    pub files: [u64; 4],
+}
+
+impl AgeHistogram {
+    pub fn bytes_in(&self, bucket: AgeBucket) -> u128 {
+        self.bytes[bucket.index()]
+    }
+
+    pub fn files_in(&self, bucket: AgeBucket) -> u64 {
+        self.files[bucket.index()]
+    }
+
+    pub fn total_bytes(&self) -> u128 {
+        self.bytes.iter().sum()
+    }
+
+    /// The bucket holding the most bytes, if any file was counted.
+    pub fn dominant(&self) -> Option<AgeBucket> {
+        AgeBucket::ALL
+            .into_iter()
+            .filter(|b| self.files_in(*b) > 0)
+            .max_by_key(|b| self.bytes_in(*b))
+    }
+
+    fn add(&mut self, bucket: AgeBucket, bytes: u128) {
+        self.bytes[bucket.index()] += bytes;
+        self.files[bucket.index()] += 1;
+    }
+
+    fn merge(&mut self, other: &AgeHistogram) {
+        for i in 0..4 {
+            self.bytes[i] += other.bytes[i];
+            self.files[i] += other.files[i];
+        }
+    }
+}
+
+/// Age of a file modified at `modified`, as seen at `now`. Timestamps in
+/// the future count as brand new.
+pub fn age_at(modified: SystemTime, now: SystemTime) -> Duration {
+    now.duration_since(modified).unwrap_or_default()
+}
+
+/// Age histogram of every node, indexed by node id: a file's own bucket, or
+/// the sum over everything below a directory.
+pub fn age_histograms(tree: &Tree, now: SystemTime) -> Vec<AgeHistogram> {
+    tree.fold_up(|node, children| {
+        let mut histogram = AgeHistogram::default();
+        match node.kind {
+            NodeKind::File | NodeKind::Archive => {
+                if let Some(modified) = node.modified {
+                    histogram.add(AgeBucket::of(age_at(modified, now)), node.size);
+                }
+            }
+            NodeKind::Dir => {
+                for (_, child) in children {
+                    histogram.merge(child);
+                }
+            }
+            NodeKind::MountPoint | NodeKind::Symlink | NodeKind::ArchiveEntry | NodeKind::Other => {
+            }
+        }
+        histogram
+    })
+}
+
+/// Age histogram of the files in or below `id` alone; the same as its
+/// entry in [`age_histograms`], without folding the rest of the tree.
+pub fn age_histogram(source: &impl NodeSource, id: NodeId, now: SystemTime) -> AgeHistogram {
+    let mut histogram = AgeHistogram::default();
+    for file in source.files_below(id) {
+        if let Some(modified) = source.modified(file) {
+            histogram.add(AgeBucket::of(age_at(modified, now)), source.size(file));
+        }
+    }
+    histogram
+}
+
+/// Files under `id` not modified for at least `min_age`, largest first.
+pub fn old_files(
+    source: &impl NodeSource,
+    id: NodeId,
+    min_age: Duration,
+    now: SystemTime,
+) -> Vec<NodeId> {
+    let mut found = source.files_below(id);
+    found.retain(|&id| {
+        source
+            .modified(id)
+            .is_some_and(|m| age_at(m, now) >= min_age)
+    });
+    found.sort_by_key(|&id| std::cmp::Reverse(source.size(id)));
+    found
+}
@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::model::{NodeId, Tree};
+use crate::snapshot;
+
+#[derive(Debug, Error)]
+pub enum AnnotationError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The tags and note attached to one path.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Labels such as "safe to delete" or "archive to NAS", sorted.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+impl Annotation {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.note.is_empty()
+    }
+
+    /// The tags joined with `; `, as exports write them.
+    pub fn tags_text(&self) -> String {
+        self.tags
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// Tags and notes attached to files and folders. They are keyed by
+/// absolute path rather than [`NodeId`], so they outlive the scan they were
+/// made on and show up again on every later scan of the same folders, and
+/// are kept as JSON next to the saved scans; see
+/// [`Annotations::default_path`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Annotations {
+    entries: BTreeMap<PathBuf, Annotation>,
+}
+
+impl Annotations {
+    /// File in the folder scans are saved to, shared by the app and CLI.
+    pub fn default_path() -> PathBuf {
+        snapshot::default_dir().join("annotations.json")
+    }
+
+    /// Reads what [`Annotations::save`] wrote to `path`; nothing when it
+    /// was never written.
+    pub fn load(path: &Path) -> Result<Self, AnnotationError> {
+        match File::open(path) {
+            Ok(file) => Ok(serde_json::from_reader(BufReader::new(file))?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the annotations next to `path` and then moves them there, so
+    /// a crash while writing leaves the previous ones intact.
+    pub fn save(&self, path: &Path) -> Result<(), AnnotationError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let mut out = BufWriter::new(File::create(&partial)?);
+        serde_json::to_writer_pretty(&mut out, self)?;
+        out.write_all(b"\n")?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Every annotated path, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &Annotation)> {
+        self.entries.iter().map(|(path, a)| (path.as_path(), a))
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&Annotation> {
+        self.entries.get(&key(path)?)
+    }
+
+    /// Every tag in use, sorted.
+    pub fn labels(&self) -> BTreeSet<&str> {
+        self.entries
+            .values()
+            .flat_map(|a| a.tags.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Tags `path` with `tag`, trimmed. Returns whether it wasn't already;
+    /// blank tags and the synthetic root of a combined scan, which has no
+    /// path, are never tagged.
+    pub fn add_tag(&mut self, path: &Path, tag: &str) -> bool {
+        let tag = tag.trim();
+        match key(path) {
+            Some(path) if !tag.is_empty() => self
+                .entries
+                .entry(path)
+                .or_default()
+                .tags
+                .insert(tag.to_string()),
+            _ => false,
+        }
+    }
+
+    /// Takes `tag` off `path`. Returns whether it was there.
+    pub fn remove_tag(&mut self, path: &Path, tag: &str) -> bool {
+        self.edit(path, |a| a.tags.remove(tag.trim()))
+            .unwrap_or(false)
+    }
+
+    /// Renames `from` to `to` wherever it is used, merging it into `to`
+    /// where both are. Returns how many paths it was on.
+    pub fn rename_tag(&mut self, from: &str, to: &str) -> usize {
+        let (from, to) = (from.trim(), to.trim());
+        if to.is_empty() {
+            return 0;
+        }
+        let mut renamed = 0;
+        for annotation in self.entries.values_mut() {
+            if annotation.tags.remove(from) {
+                annotation.tags.insert(to.to_string());
+                renamed += 1;
+            }
+        }
+        renamed
+    }
+
+    /// Sets the note of `path`, trimmed; a blank one removes it.
+    pub fn set_note(&mut self, path: &Path, note: &str) {
+        let note = note.trim();
+        if note.is_empty() {
+            self.edit(path, |a| a.note.clear());
+        } else if let Some(path) = key(path) {
+            self.entries.entry(path).or_default().note = note.to_string();
+        }
+    }
+
+    /// Takes every tag and the note off `path`, returning them.
+    pub fn remove(&mut self, path: &Path) -> Option<Annotation> {
+        self.entries.remove(&key(path)?)
+    }
+
+    /// The annotated nodes of `tree`.
+    pub fn in_tree(&self, tree: &Tree) -> HashMap<NodeId, &Annotation> {
+        self.entries
+            .iter()
+            .filter_map(|(path, a)| Some((find(tree, path)?, a)))
+            .collect()
+    }
+
+    /// The nodes of `tree` tagged `tag`.
+    pub fn tagged(&self, tree: &Tree, tag: &str) -> Vec<NodeId> {
+        self.entries
+            .iter()
+            .filter(|(_, a)| a.tags.contains(tag))
+            .filter_map(|(path, _)| find(tree, path))
+            .collect()
+    }
+
+    /// Runs `f` on the annotation of `path`, if it has one, dropping it
+    /// once `f` leaves it empty.
+    fn edit<T>(&mut self, path: &Path, f: impl FnOnce(&mut Annotation) -> T) -> Option<T> {
+        let path = key(path)?;
+        let annotation = self.entries.get_mut(&path)?;
+        let result = f(annotation);
+        if annotation.is_empty() {
+            self.entries.remove(&path);
+        }
+        Some(result)
+    }
+}
+
+/// `path` as annotations are keyed: absolute, without `.` components.
+fn key(path: &Path) -> Option<PathBuf> {
+    std::path::absolute(path).ok()
+}
+
+/// The node of `tree` at the absolute `path`, also when the tree was
+/// scanned from a path relative to the working directory.
+fn find(tree: &Tree, path: &Path) -> Option<NodeId> {
+    tree.find(path).or_else(|| {
+        let cwd = std::env::current_dir().ok()?;
+        tree.find(path.strip_prefix(cwd).ok()?)
+    })
+}
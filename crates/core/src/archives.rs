@@ -0,0 +1,305 @@
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::attrs::Attributes;
+use crate::backend::{FoundFile, ScanBackend, ScanSink};
+use crate::model::{Name, NodeId, NodeKind, ScanError, ScanErrorKind, Tree, TreeNode};
+use crate::tuning::{detect_storage, StorageKind};
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("7z error: {0}")]
+    SevenZ(#[from] sevenz_rust::Error),
+    #[error("not a zip, tar, tar.gz or 7z archive")]
+    Unsupported,
+}
+
+/// A file or folder inside an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveItem {
+    /// Path inside the archive, e.g. `docs/readme.txt`.
+    pub path: PathBuf,
+    /// Uncompressed size; 0 for folders.
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+    SevenZ,
+}
+
+fn format_of(path: &Path) -> Option<Format> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        Some(Format::Zip)
+    } else if name.ends_with(".tar") {
+        Some(Format::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Format::TarGz)
+    } else if name.ends_with(".7z") {
+        Some(Format::SevenZ)
+    } else {
+        None
+    }
+}
+
+/// Whether `path` is named like an archive [`list`] can read.
+pub fn is_archive(path: &Path) -> bool {
+    format_of(path).is_some()
+}
+
+/// The files and folders inside the archive at `path`, read from its index
+/// (zip, 7z) or by streaming through it (tar, tar.gz) without extracting
+/// anything.
+pub fn list(path: &Path) -> Result<Vec<ArchiveItem>, ArchiveError> {
+    match format_of(path).ok_or(ArchiveError::Unsupported)? {
+        Format::Zip => list_zip(path),
+        Format::Tar => list_tar(BufReader::new(File::open(path)?)),
+        Format::TarGz => list_tar(flate2::read::GzDecoder::new(BufReader::new(File::open(
+            path,
+        )?))),
+        Format::SevenZ => list_7z(path),
+    }
+}
+
+fn list_zip(path: &Path) -> Result<Vec<ArchiveItem>, ArchiveError> {
+    let mut archive = zip::ZipArchive::new(BufReader::new(File::open(path)?))?;
+    let mut items = Vec::with_capacity(archive.len());
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index)?;
+        let modified = entry.last_modified().and_then(|t| {
+            let date =
+                chrono::NaiveDate::from_ymd_opt(t.year().into(), t.month().into(), t.day().into())?;
+            let local = date.and_hms_opt(t.hour().into(), t.minute().into(), t.second().into())?;
+            // Zip stores local time without a zone
+            let local = local.and_local_timezone(chrono::Local).earliest()?;
+            Some(SystemTime::from(local))
+        });
+        items.push(ArchiveItem {
+            path: PathBuf::from(entry.name()),
+            size: entry.size(),
+            modified,
+            is_dir: entry.is_dir(),
+        });
+    }
+    Ok(items)
+}
+
+fn list_tar(reader: impl Read) -> Result<Vec<ArchiveItem>, ArchiveError> {
+    let mut archive = tar::Archive::new(reader);
+    let mut items = Vec::new();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        let kind = header.entry_type();
+        if !(kind.is_file() || kind.is_dir() || kind.is_gnu_sparse()) {
+            continue;
+        }
+        items.push(ArchiveItem {
+            path: entry.path()?.into_owned(),
+            size: if kind.is_dir() { 0 } else { entry.size() },
+            modified: header
+                .mtime()
+                .ok()
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            is_dir: kind.is_dir(),
+        });
+    }
+    Ok(items)
+}
+
+fn list_7z(path: &Path) -> Result<Vec<ArchiveItem>, ArchiveError> {
+    let reader = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty())?;
+    Ok(reader
+        .archive()
+        .files
+        .iter()
+        .filter(|entry| !entry.is_anti_item())
+        .map(|entry| ArchiveItem {
+            path: PathBuf::from(entry.name()),
+            size: entry.size(),
+            modified: entry
+                .has_last_modified_date
+                .then(|| entry.last_modified_date().to_unix_time())
+                .and_then(|secs| u64::try_from(secs).ok())
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            is_dir: entry.is_directory(),
+        })
+        .collect())
+}
+
+/// Scans the contents of an archive as if it were a folder, with
+/// [`Scanner::scan_with`](crate::Scanner::scan_with); the root is the
+/// archive file. Sizes are uncompressed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ArchiveBackend;
+
+impl ScanBackend for ArchiveBackend {
+    fn walk(&self, root: &Path, sink: &mut ScanSink<'_>) {
+        let items = match list(root) {
+            Ok(items) => items,
+            Err(e) => {
+                let message = format!("could not list archive: {e}");
+                sink.error(root.to_path_buf(), ScanErrorKind::Io, message);
+                return;
+            }
+        };
+        for item in items {
+            if sink.is_cancelled() {
+                return;
+            }
+            let path = root.join(inner_path(&item.path));
+            if item.is_dir {
+                sink.dir(path, Attributes::default());
+            } else {
+                let file = FoundFile {
+                    size: item.size,
+                    modified: item.modified,
+                    ..FoundFile::default()
+                };
+                sink.file(path, file);
+            }
+        }
+    }
+
+    fn storage(&self, root: &Path) -> StorageKind {
+        detect_storage(root)
+    }
+}
+
+/// `path` inside an archive without `..`, roots and the like, so nothing
+/// escapes the archive.
+fn inner_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+/// Lists every archive among the files of `tree`, turning it into a
+/// [`NodeKind::Archive`] with its contents below it as
+/// [`NodeKind::ArchiveEntry`] nodes. Archives that can't be read are
+/// recorded as errors on their folder and stay plain files.
+pub fn expand_archives(tree: &mut Tree) {
+    let archives: Vec<(NodeId, PathBuf)> = tree
+        .nodes
+        .iter()
+        .filter(|n| matches!(n.kind, NodeKind::File) && n.children.is_empty())
+        .filter(|n| is_archive(Path::new(&n.name)))
+        .map(|n| (n.id, tree.path(n.id)))
+        .collect();
+    let listed: Vec<_> = archives
+        .into_par_iter()
+        .map(|(id, path)| (id, list(&path), path))
+        .collect();
+    for (id, result, path) in listed {
+        match result {
+            Ok(items) => {
+                tree.nodes[id.0 as usize].kind = NodeKind::Archive;
+                add_items(tree, id, items);
+            }
+            Err(e) => {
+                let node = tree.nodes[id.0 as usize].parent.unwrap_or(tree.root);
+                tree.errors.push(ScanError {
+                    node,
+                    path,
+                    kind: ScanErrorKind::Io,
+                    message: format!("could not list archive: {e}"),
+                });
+            }
+        }
+    }
+}
+
+/// Adds `items` below the archive node `archive`. Only the entries inside
+/// it carry their uncompressed sizes; the archive and everything above it
+/// keep the size it takes on disk.
+fn add_items(tree: &mut Tree, archive: NodeId, items: Vec<ArchiveItem>) {
+    let mut folders: HashMap<PathBuf, NodeId> = HashMap::new();
+    folders.insert(PathBuf::new(), archive);
+    for item in items {
+        let inner = inner_path(&item.path);
+        if inner.as_os_str().is_empty() {
+            continue;
+        }
+        let id = if item.is_dir {
+            folder(tree, &mut folders, archive, &inner)
+        } else {
+            let parent = inner.parent().unwrap_or(Path::new(""));
+            let parent = folder(tree, &mut folders, archive, parent);
+            let id = push_entry(tree, parent, &inner, archive);
+            let node = &mut tree.nodes[id.0 as usize];
+            node.size = item.size as u128;
+            node.file_count = 1;
+            let mut cur = Some(parent);
+            while let Some(dir) = cur.filter(|dir| *dir != archive) {
+                let node = &mut tree.nodes[dir.0 as usize];
+                node.size += item.size as u128;
+                node.file_count += 1;
+                cur = node.parent;
+            }
+            id
+        };
+        tree.nodes[id.0 as usize].modified = item.modified;
+    }
+}
+
+/// The folder node at `inner` inside `archive`, created along with any
+/// missing parents.
+fn folder(
+    tree: &mut Tree,
+    folders: &mut HashMap<PathBuf, NodeId>,
+    archive: NodeId,
+    inner: &Path,
+) -> NodeId {
+    if let Some(id) = folders.get(inner) {
+        return *id;
+    }
+    let parent = folder(
+        tree,
+        folders,
+        archive,
+        inner.parent().unwrap_or(Path::new("")),
+    );
+    let id = push_entry(tree, parent, inner, archive);
+    folders.insert(inner.to_path_buf(), id);
+    id
+}
+
+fn push_entry(tree: &mut Tree, parent: NodeId, inner: &Path, archive: NodeId) -> NodeId {
+    let id = NodeId(tree.nodes.len() as u32);
+    let path = tree.path(archive).join(inner);
+    tree.nodes.push(TreeNode {
+        id,
+        parent: Some(parent),
+        name: inner
+            .file_name()
+            .map(|n| Name::from(n.to_string_lossy().as_ref()))
+            .unwrap_or_default(),
+        kind: NodeKind::ArchiveEntry,
+        size: 0,
+        file_count: 0,
+        children: Vec::new(),
+        modified: None,
+        link_target: None,
+        online_only: false,
+        owner: None,
+        attributes: Attributes::default(),
+    });
+    tree.nodes[parent.0 as usize].children.push(id);
+    tree.note_path(id, &path);
+    id
+}
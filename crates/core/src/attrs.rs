@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::fs::Metadata;
+use std::path::Path;
+
+/// Attributes and permission bits of a file or directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Attributes {
+    pub readonly: bool,
+    /// Hidden by attribute, or on Unix by a leading `.` in its name.
+    pub hidden: bool,
+    /// A Windows system file.
+    pub system: bool,
+    /// Permission bits (`st_mode & 0o7777`) on Unix.
+    pub mode: Option<u32>,
+}
+
+impl Attributes {
+    /// The attributes of the entry at `path` with metadata `md`.
+    pub fn of(path: &Path, md: &Metadata) -> Self {
+        platform::attributes(path, md)
+    }
+
+    /// `R`, `H` and `S` for each attribute set, e.g. `RH`.
+    pub fn flags(&self) -> String {
+        [(self.readonly, 'R'), (self.hidden, 'H'), (self.system, 'S')]
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, flag)| *flag)
+            .collect()
+    }
+
+    /// The permission bits as `ls -l` shows them, e.g. `rwxr-xr-x`.
+    pub fn mode_string(&self) -> Option<String> {
+        let mode = self.mode?;
+        let mut text = String::with_capacity(9);
+        for (shift, special, set, unset) in [
+            (6, 0o4000, 's', 'S'),
+            (3, 0o2000, 's', 'S'),
+            (0, 0o1000, 't', 'T'),
+        ] {
+            let bits = (mode >> shift) & 0o7;
+            text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+            text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+            text.push(match (bits & 0o1 != 0, mode & special != 0) {
+                (true, true) => set,
+                (false, true) => unset,
+                (true, false) => 'x',
+                (false, false) => '-',
+            });
+        }
+        Some(text)
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::Attributes;
+    use std::fs::Metadata;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    pub(super) fn attributes(path: &Path, md: &Metadata) -> Attributes {
+        let mode = md.mode() & 0o7777;
+        let dotfile = path
+            .file_name()
+            .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."));
+        Attributes {
+            readonly: mode & 0o222 == 0,
+            hidden: dotfile || hidden_flag(md),
+            system: false,
+            mode: Some(mode),
+        }
+    }
+
+    /// `UF_HIDDEN` from `<sys/stat.h>`, which Finder honours.
+    #[cfg(target_os = "macos")]
+    fn hidden_flag(md: &Metadata) -> bool {
+        use std::os::macos::fs::MetadataExt;
+        const UF_HIDDEN: u32 = 0x8000;
+        md.st_flags() & UF_HIDDEN != 0
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn hidden_flag(_md: &Metadata) -> bool {
+        false
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::Attributes;
+    use std::fs::Metadata;
+    use std::os::windows::fs::MetadataExt;
+    use std::path::Path;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_READONLY, FILE_ATTRIBUTE_SYSTEM,
+    };
+
+    pub(super) fn attributes(_path: &Path, md: &Metadata) -> Attributes {
+        let attributes = md.file_attributes();
+        Attributes {
+            readonly: attributes & FILE_ATTRIBUTE_READONLY != 0,
+            hidden: attributes & FILE_ATTRIBUTE_HIDDEN != 0,
+            system: attributes & FILE_ATTRIBUTE_SYSTEM != 0,
+            mode: None,
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::Attributes;
+    use std::fs::Metadata;
+    use std::path::Path;
+
+    pub(super) fn attributes(_path: &Path, md: &Metadata) -> Attributes {
+        Attributes {
+            readonly: md.permissions().readonly(),
+            ..Attributes::default()
+        }
+    }
+}
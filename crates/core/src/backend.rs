@@ -0,0 +1,418 @@
+use crossbeam_channel::Sender;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::attrs::Attributes;
+use crate::checkpoint::Checkpoint;
+use crate::handle::ScanControl;
+use crate::model::{ScanErrorKind, ScanMeta, Tree};
+use crate::owner::OwnerId;
+use crate::progress::{Progress, ProgressGate};
+use crate::scanner::{
+    send_partial, CachedFile, Failure, FileEntry, LinkEntry, ScanMsg, ScanOptions, Scanner,
+    TreeBuilder,
+};
+use crate::tuning::StorageKind;
+
+/// A source of files and folders for [`Scanner::scan_with`], which does
+/// the rest of what a scan does: progress, pausing and cancelling, the
+/// [`ScanOptions`] filters, partial trees and assembling the [`Tree`].
+///
+/// [`Scanner::scan`] runs the built-in parallel walk of a local folder
+/// through this trait too, with caching, checkpoints and per-directory
+/// completion on top. Other sources, such as
+/// [`S3Config`](crate::s3::S3Config) buckets and
+/// [`ArchiveBackend`](crate::archives::ArchiveBackend), implement it the
+/// same way.
+pub trait ScanBackend: Send + Sync {
+    /// Reports every file and folder below `root` to `sink`, in any order.
+    /// Should return early once [`ScanSink::is_cancelled`]. A failure that
+    /// ends the walk is reported with [`ScanSink::error`] like any other.
+    fn walk(&self, root: &Path, sink: &mut ScanSink<'_>);
+
+    /// The kind of storage `root` is on, for
+    /// [`ScanMeta::storage`](crate::ScanMeta::storage).
+    fn storage(&self, _root: &Path) -> StorageKind {
+        StorageKind::Unknown
+    }
+
+    /// Adds what only this backend can to the tree built from its walk of
+    /// `root`. Errors it appends to [`Tree::errors`] are reported like
+    /// those met while walking. Not called when the scan was cancelled.
+    fn finish(&self, _root: &Path, _tree: &mut Tree) {}
+}
+
+/// A file found by a [`ScanBackend`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FoundFile {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// Kept in the cloud rather than on the device.
+    pub online_only: bool,
+    pub owner: Option<OwnerId>,
+    pub attributes: Attributes,
+}
+
+impl From<FoundFile> for CachedFile {
+    fn from(file: FoundFile) -> Self {
+        CachedFile {
+            size: file.size,
+            modified: file.modified,
+            online: file.online_only,
+            owner: file.owner,
+            attributes: file.attributes,
+        }
+    }
+}
+
+/// Takes what a [`ScanBackend`] finds, keeps the running totals and builds
+/// the tree from it.
+pub struct ScanSink<'a> {
+    scanner: &'a Scanner,
+    options: &'a ScanOptions,
+    control: ScanControl,
+    tx: &'a Sender<ScanMsg>,
+    root: PathBuf,
+    builder: TreeBuilder,
+    dirs: HashMap<PathBuf, Attributes>,
+    failures: Vec<Failure>,
+    progress: Progress,
+    started: Instant,
+    gate: ProgressGate,
+    partial_every: Option<Duration>,
+    next_partial: Option<Instant>,
+    file_messages: bool,
+    dir_done: bool,
+    /// Everything found besides folders and failures, when a checkpoint or
+    /// the backend needs it after the walk.
+    kept: Option<Kept>,
+    checkpoint: Option<CheckpointSchedule>,
+    meta: ScanMeta,
+}
+
+/// What a sink keeps of the entries it is given; see [`ScanSink::keep_found`].
+#[derive(Default)]
+pub(crate) struct Kept {
+    pub(crate) files: Vec<FileEntry>,
+    pub(crate) links: Vec<LinkEntry>,
+    pub(crate) mounts: Vec<PathBuf>,
+    pub(crate) specials: Vec<PathBuf>,
+}
+
+/// Where and how often a sink writes [`Checkpoint`]s.
+struct CheckpointSchedule {
+    path: PathBuf,
+    every: Duration,
+    next: Instant,
+    /// Every directory completed so far.
+    completed: Vec<PathBuf>,
+}
+
+impl<'a> ScanSink<'a> {
+    pub(crate) fn new(scanner: &'a Scanner, root: &Path, tx: &'a Sender<ScanMsg>) -> Self {
+        let started = Instant::now();
+        let options = &scanner.options;
+        Self {
+            scanner,
+            options,
+            control: scanner.control(),
+            tx,
+            root: root.to_path_buf(),
+            builder: TreeBuilder::new(root, options),
+            dirs: HashMap::new(),
+            failures: Vec::new(),
+            progress: Progress::default(),
+            started,
+            gate: ProgressGate::new(scanner.throttle),
+            partial_every: scanner.partial_every,
+            next_partial: scanner.partial_every.map(|every| started + every),
+            file_messages: scanner.file_messages,
+            dir_done: scanner.dir_done,
+            kept: None,
+            checkpoint: None,
+            meta: ScanMeta::default(),
+        }
+    }
+
+    /// Options of the scan. Filters on files are applied by the sink; the
+    /// rest, such as exclude patterns, are left to the backend.
+    pub fn options(&self) -> &ScanOptions {
+        self.options
+    }
+
+    /// Whether the scan has been cancelled. Waits while it is paused, so a
+    /// backend that checks this regularly pauses along with it.
+    pub fn is_cancelled(&self) -> bool {
+        while self.control.is_paused() && !self.control.is_cancelled() {
+            sleep(Duration::from_millis(40));
+        }
+        self.control.is_cancelled()
+    }
+
+    /// Token of the scan, for backends checking it from threads of their
+    /// own.
+    pub(crate) fn control(&self) -> ScanControl {
+        self.control.clone()
+    }
+
+    /// Records a folder, which is listed even if nothing is found in it.
+    /// Folders holding files needn't be reported.
+    pub fn dir(&mut self, path: PathBuf, attributes: Attributes) {
+        self.progress.current = Some(path.clone());
+        self.dirs.insert(path, attributes);
+    }
+
+    /// Counts a file, unless the [`ScanOptions`] filters leave it out.
+    pub fn file(&mut self, path: PathBuf, file: FoundFile) {
+        self.add_file(path, file.into());
+    }
+
+    pub(crate) fn add_file(&mut self, path: PathBuf, file: CachedFile) {
+        if !self.options.keeps_extension(&path) {
+            return;
+        }
+        if !self.options.is_sampled(&path) {
+            return self.unsampled(path);
+        }
+        if file.size >= self.options.min_file_size {
+            self.progress.bytes += file.size as u128;
+            if self.file_messages {
+                let _ = self.tx.try_send(ScanMsg::File {
+                    path: path.clone(),
+                    bytes: file.size,
+                });
+            }
+            if let Some(kept) = &mut self.kept {
+                kept.files.push((path.clone(), file.clone()));
+            }
+            self.builder.add_file((path, file));
+            self.progress.files += 1;
+            self.progress.discovered += 1;
+            self.tick();
+        }
+    }
+
+    /// Counts a file left out of the sample, which the sized files beside
+    /// it stand in for.
+    pub(crate) fn unsampled(&mut self, path: PathBuf) {
+        if let Some(dir) = path.parent() {
+            self.builder.add_unsampled(dir, 1);
+        }
+        self.progress.files += 1;
+        self.progress.discovered += 1;
+        self.tick();
+    }
+
+    /// Records a symbolic link that isn't followed, which takes up `size`
+    /// bytes of its own. `target` is where it leads, if known.
+    pub fn symlink(&mut self, path: PathBuf, size: u64, target: Option<PathBuf>) {
+        if let Some(kept) = &mut self.kept {
+            kept.links.push((path.clone(), size, target.clone()));
+        }
+        self.builder.add_link((path, size, target));
+    }
+
+    /// Records a folder on another filesystem that isn't walked; see
+    /// [`ScanOptions::one_file_system`].
+    pub fn mount_point(&mut self, path: PathBuf) {
+        if let Some(kept) = &mut self.kept {
+            kept.mounts.push(path.clone());
+        }
+        self.builder.add_mount(path);
+    }
+
+    /// Records a socket, pipe, device or the like, which takes no space.
+    pub fn special(&mut self, path: PathBuf) {
+        if !self.options.keeps_extension(&path) {
+            return;
+        }
+        if let Some(kept) = &mut self.kept {
+            kept.specials.push(path.clone());
+        }
+        self.builder.add_special(path);
+    }
+
+    /// Records that `path` couldn't be read; the scan carries on.
+    pub fn error(&mut self, path: PathBuf, kind: ScanErrorKind, message: String) {
+        self.scanner
+            .report_error(self.tx, format!("{}: {message}", path.display()));
+        self.failures.push((path, kind, message));
+    }
+
+    /// Records that `path` was deleted or renamed after its folder was
+    /// listed. It is left out and counted in [`Progress::vanished`] rather
+    /// than reported as an error.
+    pub fn vanished(&mut self, path: PathBuf, message: String) {
+        self.progress.vanished += 1;
+        self.failures.push((path, ScanErrorKind::Vanished, message));
+    }
+
+    /// Reports a problem that concerns no path in particular, such as an
+    /// invalid option; the scan carries on.
+    pub fn warn(&mut self, message: String) {
+        self.scanner.report_error(self.tx, message);
+    }
+
+    /// Details of the scan for the tree's [`ScanMeta`], such as a timing
+    /// profile. Storage, scan time and what the sink tracks itself are
+    /// filled in when the scan ends.
+    pub fn meta(&mut self) -> &mut ScanMeta {
+        &mut self.meta
+    }
+
+    /// Whether the scan wants to know when each folder is complete, with
+    /// [`ScanSink::dir_done`].
+    pub(crate) fn wants_dir_done(&self) -> bool {
+        self.dir_done || self.checkpoint.is_some()
+    }
+
+    /// Whether entries must reach the sink before the folder they are in
+    /// is reported complete, so a checkpoint never claims a folder whose
+    /// entries it doesn't have.
+    pub(crate) fn is_checkpointing(&self) -> bool {
+        self.checkpoint.is_some()
+    }
+
+    /// Records that everything below `path` has been reported, with the
+    /// totals of its subtree, not counting `path` itself.
+    pub(crate) fn dir_done(&mut self, path: PathBuf, bytes: u128, files: u64, dirs: u64) {
+        if let Some(checkpoint) = &mut self.checkpoint {
+            checkpoint.completed.push(path.clone());
+        }
+        if self.dir_done {
+            let _ = self.tx.send(ScanMsg::DirDone {
+                path,
+                bytes,
+                files,
+                dirs,
+            });
+        }
+    }
+
+    /// Keeps the entries given to the sink from now on, for
+    /// [`ScanSink::kept`].
+    pub(crate) fn keep_found(&mut self) {
+        self.kept.get_or_insert_with(Kept::default);
+    }
+
+    pub(crate) fn kept(&self) -> Option<&Kept> {
+        self.kept.as_ref()
+    }
+
+    /// Writes a [`Checkpoint`] to `path` about every `every`, and once more
+    /// if the scan is cancelled; removes it when the scan completes.
+    pub(crate) fn checkpoint_to(&mut self, path: PathBuf, every: Duration) {
+        self.keep_found();
+        self.checkpoint = Some(CheckpointSchedule {
+            path,
+            every,
+            next: Instant::now() + every,
+            completed: Vec::new(),
+        });
+    }
+
+    /// Takes in everything `checkpoint` found, as if found by this scan.
+    pub(crate) fn restore(&mut self, checkpoint: Checkpoint) {
+        self.progress.vanished += checkpoint.vanished();
+        for (path, file) in checkpoint.files {
+            self.progress.files += 1;
+            self.progress.discovered += 1;
+            self.progress.bytes += file.size as u128;
+            if let Some(kept) = &mut self.kept {
+                kept.files.push((path.clone(), file.clone()));
+            }
+            self.builder.add_file((path, file));
+        }
+        for (path, size, target) in checkpoint.links {
+            self.symlink(path, size, target);
+        }
+        for path in checkpoint.mounts {
+            self.mount_point(path);
+        }
+        for path in checkpoint.specials {
+            self.special(path);
+        }
+        self.failures.extend(checkpoint.failures);
+        if let Some(schedule) = &mut self.checkpoint {
+            schedule.completed.extend(checkpoint.done);
+        }
+    }
+
+    /// Reports progress, and a partial tree or checkpoint when one is due.
+    pub(crate) fn tick(&mut self) {
+        if self.gate.due(self.progress.files) {
+            self.progress.elapsed = self.started.elapsed();
+            self.scanner.report(self.tx, self.progress.clone());
+        }
+        if let (Some(every), Some(due)) = (self.partial_every, self.next_partial) {
+            if Instant::now() >= due {
+                let copying = Instant::now();
+                send_partial(self.tx, || self.builder.snapshot());
+                // Less often when copying the tree takes a noticeable share
+                self.next_partial = Some(Instant::now() + every.max(copying.elapsed() * 4));
+            }
+        }
+        if let Some(schedule) = &mut self.checkpoint {
+            if Instant::now() >= schedule.next {
+                schedule.next = Instant::now() + schedule.every;
+                self.save_checkpoint();
+            }
+        }
+    }
+
+    fn save_checkpoint(&self) {
+        let (Some(schedule), Some(kept)) = (&self.checkpoint, &self.kept) else {
+            return;
+        };
+        let checkpoint = Checkpoint::build(
+            &self.root,
+            &schedule.completed,
+            &kept.files,
+            &kept.links,
+            &kept.mounts,
+            &kept.specials,
+            &self.failures,
+        );
+        if let Err(e) = checkpoint.save(&schedule.path) {
+            self.scanner.report_error(
+                self.tx,
+                format!(
+                    "could not write checkpoint {}: {e}",
+                    schedule.path.display()
+                ),
+            );
+        }
+    }
+
+    /// The finished tree and the final totals.
+    pub(crate) fn finish(mut self) -> (Tree, Progress) {
+        if let Some(schedule) = &self.checkpoint {
+            if self.control.is_cancelled() {
+                self.save_checkpoint();
+            } else if let Err(e) = std::fs::remove_file(&schedule.path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    self.scanner.report_error(
+                        self.tx,
+                        format!(
+                            "could not remove checkpoint {}: {e}",
+                            schedule.path.display()
+                        ),
+                    );
+                }
+            }
+        }
+        self.builder.set_dir_attributes(self.dirs);
+        let mut tree = self.builder.finish(self.failures);
+        tree.meta = ScanMeta {
+            max_depth: tree.meta.max_depth,
+            estimate: tree.meta.estimate.take(),
+            node_table: tree.meta.node_table.take(),
+            ..self.meta
+        };
+        self.progress.elapsed = self.started.elapsed();
+        self.progress.current = None;
+        (tree, self.progress)
+    }
+}
@@ -0,0 +1,360 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+use crate::diff::{diff, TreeDiff};
+use crate::human::{human_bytes, parse_bytes};
+use crate::model::Tree;
+use crate::snapshot::SnapshotError;
+
+/// Name budgets are looked up under when no file is given, meant to be
+/// checked in next to the project it describes.
+pub const BUDGET_FILE: &str = "treesize-budgets.toml";
+
+#[derive(Debug, Error)]
+pub enum BudgetError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("toml error: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("invalid pattern: {0}")]
+    Glob(#[from] globset::Error),
+    #[error("invalid size {size} for {path:?}")]
+    Size { path: String, size: String },
+    #[error("budget for {0:?} sets no limit")]
+    NoLimit(String),
+}
+
+/// Limits for the paths of a report matching one pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetRule {
+    /// Glob relative to the scanned root, e.g. `target` or
+    /// `**/node_modules`; `.` is the root itself. `*` stays within one
+    /// path component.
+    pub pattern: String,
+    pub max_bytes: Option<u128>,
+    /// Most a path may grow compared to the old report; a path missing
+    /// there grows by its whole size.
+    pub max_growth: Option<u128>,
+    pub max_files: Option<u64>,
+}
+
+/// Size limits a report is checked against with [`Report::check`].
+#[derive(Debug, Clone)]
+pub struct Budget {
+    rules: Vec<BudgetRule>,
+    matcher: GlobSet,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget {
+            rules: Vec::new(),
+            matcher: GlobSet::empty(),
+        }
+    }
+}
+
+impl Budget {
+    pub fn new(rules: Vec<BudgetRule>) -> Result<Budget, BudgetError> {
+        let mut matcher = GlobSetBuilder::new();
+        for rule in &rules {
+            if rule.max_bytes.is_none() && rule.max_growth.is_none() && rule.max_files.is_none() {
+                return Err(BudgetError::NoLimit(rule.pattern.clone()));
+            }
+            matcher.add(
+                GlobBuilder::new(normalize(&rule.pattern))
+                    .literal_separator(true)
+                    .build()?,
+            );
+        }
+        Ok(Budget {
+            rules,
+            matcher: matcher.build()?,
+        })
+    }
+
+    pub fn rules(&self) -> &[BudgetRule] {
+        &self.rules
+    }
+
+    /// Reads a budget file: [`Budget::from_json`] if its name ends in
+    /// `.json`, [`Budget::from_toml`] otherwise.
+    pub fn load(path: &Path) -> Result<Budget, BudgetError> {
+        let text = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Budget::from_json(&text)
+        } else {
+            Budget::from_toml(&text)
+        }
+    }
+
+    /// Parses the [`BUDGET_FILE`] format: a `[[budget]]` table per pattern
+    /// with any of `max_size`, `max_growth` (a byte count or a size such as
+    /// `"500 MB"`) and `max_files`:
+    ///
+    /// ```toml
+    /// [[budget]]
+    /// path = "."
+    /// max_size = "20 GB"
+    ///
+    /// [[budget]]
+    /// path = "**/node_modules"
+    /// max_size = "1 GB"
+    /// max_growth = "100 MB"
+    /// max_files = 50000
+    /// ```
+    pub fn from_toml(text: &str) -> Result<Budget, BudgetError> {
+        let file: BudgetFile = toml::from_str(text)?;
+        let rules = file
+            .budget
+            .into_iter()
+            .map(|spec| {
+                let size = |limit: Option<SizeLimit>| {
+                    limit.map(|limit| limit.bytes(&spec.path)).transpose()
+                };
+                Ok(BudgetRule {
+                    max_bytes: size(spec.max_size)?,
+                    max_growth: size(spec.max_growth)?,
+                    max_files: spec.max_files,
+                    pattern: spec.path,
+                })
+            })
+            .collect::<Result<_, BudgetError>>()?;
+        Budget::new(rules)
+    }
+
+    /// Parses a JSON object mapping paths to their largest allowed size,
+    /// as a byte count or a size such as `"500 MB"`:
+    ///
+    /// ```json
+    /// { ".": "20 GB", "target": "2 GB", "node_modules/.cache": 500000000 }
+    /// ```
+    pub fn from_json(text: &str) -> Result<Budget, BudgetError> {
+        let entries: BTreeMap<String, serde_json::Value> = serde_json::from_str(text)?;
+        let rules = entries
+            .into_iter()
+            .map(|(path, size)| {
+                let max_bytes = match &size {
+                    serde_json::Value::Number(n) => n.as_u64().map(u128::from),
+                    serde_json::Value::String(s) => parse_bytes(s),
+                    _ => None,
+                };
+                match max_bytes {
+                    Some(max_bytes) => Ok(BudgetRule {
+                        pattern: path,
+                        max_bytes: Some(max_bytes),
+                        max_growth: None,
+                        max_files: None,
+                    }),
+                    None => Err(BudgetError::Size {
+                        path,
+                        size: size.to_string(),
+                    }),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Budget::new(rules)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BudgetFile {
+    #[serde(default)]
+    budget: Vec<RuleSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    path: String,
+    max_size: Option<SizeLimit>,
+    max_growth: Option<SizeLimit>,
+    max_files: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SizeLimit {
+    Bytes(u64),
+    Text(String),
+}
+
+impl SizeLimit {
+    fn bytes(self, path: &str) -> Result<u128, BudgetError> {
+        match self {
+            SizeLimit::Bytes(n) => Ok(n.into()),
+            SizeLimit::Text(text) => parse_bytes(&text).ok_or_else(|| BudgetError::Size {
+                path: path.to_string(),
+                size: text,
+            }),
+        }
+    }
+}
+
+/// Which limit of a [`BudgetRule`] a path broke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Limit {
+    Size,
+    Growth,
+    Files,
+}
+
+/// A path of the new report that broke a limit of its budget.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    pub path: PathBuf,
+    /// Pattern of the rule broken.
+    pub rule: String,
+    pub limit: Limit,
+    /// The limit, and the size, growth or file count that exceeded it.
+    pub max: u128,
+    pub actual: u128,
+    /// Size in the old report; `None` if the path is new.
+    pub old_bytes: Option<u128>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.display();
+        match self.limit {
+            Limit::Size => write!(
+                f,
+                "{path} is {}, over its budget of {}",
+                human_bytes(self.actual),
+                human_bytes(self.max)
+            )?,
+            Limit::Growth => {
+                return write!(
+                    f,
+                    "{path} grew by {}, more than the {} allowed",
+                    human_bytes(self.actual),
+                    human_bytes(self.max)
+                )
+            }
+            Limit::Files => write!(
+                f,
+                "{path} has {} files, over its budget of {}",
+                self.actual, self.max
+            )?,
+        }
+        match self.old_bytes {
+            Some(old) if self.limit == Limit::Size && old < self.actual => {
+                write!(f, " (grew by {})", human_bytes(self.actual - old))
+            }
+            Some(_) => Ok(()),
+            None => write!(f, " (new)"),
+        }
+    }
+}
+
+/// Two reports of the same root and how they differ, from
+/// [`compare_reports`].
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub diff: TreeDiff,
+    old: Tree,
+    new: Tree,
+}
+
+impl Report {
+    /// Every limit the new report breaks, in the order of the rules and
+    /// then of the paths. Rules matching no path are met.
+    pub fn check(&self, budget: &Budget) -> Vec<Violation> {
+        let new_root = root_of(&self.new);
+        let old_root = root_of(&self.old);
+        let mut violations = Vec::new();
+        let paths = self.new.paths();
+        for (node, path) in self.new.nodes.iter().zip(&paths) {
+            let Ok(relative) = path.strip_prefix(&new_root) else {
+                continue;
+            };
+            let key = relative_key(relative);
+            for index in budget.matcher.matches(&key) {
+                let rule = &budget.rules[index];
+                let old_bytes = self
+                    .old
+                    .find(&old_root.join(relative))
+                    .map(|id| self.old.nodes[id.0 as usize].size);
+                let growth = node.size.saturating_sub(old_bytes.unwrap_or(0));
+                let limits = [
+                    (Limit::Size, rule.max_bytes, node.size),
+                    (Limit::Growth, rule.max_growth, growth),
+                    (
+                        Limit::Files,
+                        rule.max_files.map(u128::from),
+                        node.file_count.into(),
+                    ),
+                ];
+                for (limit, max, actual) in limits {
+                    if let Some(max) = max.filter(|max| actual > *max) {
+                        let violation = Violation {
+                            path: path.clone(),
+                            rule: rule.pattern.clone(),
+                            limit,
+                            max,
+                            actual,
+                            old_bytes,
+                        };
+                        violations.push((index, violation));
+                    }
+                }
+            }
+        }
+        violations.sort_by_key(|(index, _)| *index);
+        violations.into_iter().map(|(_, v)| v).collect()
+    }
+}
+
+/// Compares two reports of the same root, each a saved scan or a JSON
+/// report from [`export_json`](crate::export::export_json). Meant for CI
+/// pipelines: check the result against a [`Budget`] to catch artifacts or
+/// caches that grew too large.
+pub fn compare_reports(old: &Path, new: &Path) -> Result<Report, SnapshotError> {
+    let old = Tree::open(old)?;
+    let new = Tree::open(new)?;
+    Ok(Report {
+        diff: diff(&old, &new),
+        old,
+        new,
+    })
+}
+
+fn root_of(tree: &Tree) -> PathBuf {
+    if tree.nodes.is_empty() {
+        return PathBuf::new();
+    }
+    tree.path(tree.root)
+}
+
+/// `relative` as rule patterns see it: `/`-separated, `.` for the root.
+fn relative_key(relative: &Path) -> String {
+    let parts: Vec<_> = relative
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+/// A rule pattern without a leading `./` or trailing `/`.
+fn normalize(pattern: &str) -> &str {
+    let pattern = pattern.trim_end_matches('/');
+    let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+    if pattern.is_empty() {
+        "."
+    } else {
+        pattern
+    }
+}
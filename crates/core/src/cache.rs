@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use thiserror::Error;
+
+use crate::scanner::{CachedFile, DirSizeStore};
+
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("cache database error: {0}")]
+    Db(#[from] sled::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// File sizes and modification times of one directory as of its last scan.
+/// Records in older layouts fail to parse and count as misses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DirRecord {
+    mtime: SystemTime,
+    files: Vec<(String, CachedFile)>,
+}
+
+/// On-disk cache of per-directory file sizes, keyed by directory path.
+///
+/// A record is trusted while the directory's mtime is unchanged, which covers
+/// files being added, removed or renamed. Rewriting a file in place does not
+/// touch its directory's mtime, so such size changes are only picked up once
+/// something else in that directory changes.
+pub struct DirCache {
+    db: sled::Db,
+}
+
+impl DirCache {
+    pub fn open(path: &Path) -> Result<Self, CacheError> {
+        Ok(Self {
+            db: sled::open(path)?,
+        })
+    }
+
+    fn key(dir: &Path) -> Vec<u8> {
+        dir.to_string_lossy().into_owned().into_bytes()
+    }
+
+    fn get(&self, dir: &Path) -> Result<Option<DirRecord>, CacheError> {
+        match self.db.get(Self::key(dir))? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn put(&self, dir: &Path, record: &DirRecord) -> Result<(), CacheError> {
+        self.db
+            .insert(Self::key(dir), serde_json::to_vec(record)?)?;
+        Ok(())
+    }
+
+    /// Drops records for directories under `root` that the last scan did not
+    /// visit, then flushes to disk.
+    fn prune(&self, root: &Path, visited: &HashSet<PathBuf>) -> Result<(), CacheError> {
+        for item in self.db.scan_prefix(Self::key(root)) {
+            let (key, _) = item?;
+            let dir = PathBuf::from(String::from_utf8_lossy(&key).into_owned());
+            if dir.starts_with(root) && !visited.contains(&dir) {
+                self.db.remove(key)?;
+            }
+        }
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+impl DirSizeStore for DirCache {
+    fn lookup(&self, dir: &Path, mtime: SystemTime) -> Option<HashMap<String, CachedFile>> {
+        let record = self.get(dir).ok().flatten()?;
+        (record.mtime == mtime).then(|| record.files.into_iter().collect())
+    }
+
+    fn store(&self, dir: &Path, mtime: SystemTime, files: Vec<(String, CachedFile)>) {
+        if let Err(e) = self.put(dir, &DirRecord { mtime, files }) {
+            tracing::warn!(dir = %dir.display(), "could not update scan cache: {e}");
+        }
+    }
+
+    fn finish(&self, root: &Path, visited: &HashSet<PathBuf>) {
+        if let Err(e) = self.prune(root, visited) {
+            tracing::warn!("could not flush scan cache: {e}");
+        }
+    }
+}
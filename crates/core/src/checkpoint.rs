@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::model::ScanErrorKind;
+use crate::scanner::{Failure, FileEntry, LinkEntry};
+
+/// Identifies a checkpoint; followed by a little-endian `u32` format version.
+const MAGIC: &[u8; 8] = b"TSZCKPT\0";
+/// Bumped whenever the encoded layout of [`Checkpoint`] changes.
+pub const CHECKPOINT_VERSION: u32 = 5;
+
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("encoding error: {0}")]
+    Encode(#[from] bincode::Error),
+    #[error("not a scan checkpoint")]
+    NotACheckpoint,
+    #[error("written with format version {0}, this build reads version {CHECKPOINT_VERSION}")]
+    UnsupportedVersion(u32),
+}
+
+/// What an interrupted scan had finished: the directories it walked
+/// completely and everything found in them. Written while scanning by a
+/// [`Scanner`](crate::Scanner) set up with
+/// [`with_checkpoints`](crate::Scanner::with_checkpoints), and continued
+/// with [`Scanner::resume`](crate::Scanner::resume).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub(crate) root: PathBuf,
+    /// Directories walked completely, none inside another.
+    pub(crate) done: Vec<PathBuf>,
+    pub(crate) files: Vec<FileEntry>,
+    pub(crate) links: Vec<LinkEntry>,
+    pub(crate) mounts: Vec<PathBuf>,
+    /// Sockets, pipes, devices and the like.
+    pub(crate) specials: Vec<PathBuf>,
+    pub(crate) failures: Vec<Failure>,
+}
+
+impl Checkpoint {
+    /// Reads a checkpoint written during a scan.
+    pub fn load(path: &Path) -> Result<Checkpoint, CheckpointError> {
+        let mut input = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        let mut version = [0u8; 4];
+        input
+            .read_exact(&mut magic)
+            .and_then(|_| input.read_exact(&mut version))
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => CheckpointError::NotACheckpoint,
+                _ => CheckpointError::Io(e),
+            })?;
+        if &magic != MAGIC {
+            return Err(CheckpointError::NotACheckpoint);
+        }
+        match u32::from_le_bytes(version) {
+            CHECKPOINT_VERSION => Ok(bincode::deserialize_from(input)?),
+            other => Err(CheckpointError::UnsupportedVersion(other)),
+        }
+    }
+
+    /// Writes the checkpoint next to `path` and then moves it there, so a
+    /// crash while writing leaves the previous one intact.
+    pub(crate) fn save(&self, path: &Path) -> Result<(), CheckpointError> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let mut out = BufWriter::new(File::create(&partial)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&CHECKPOINT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut out, self)?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    /// Root of the interrupted scan.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Files the checkpoint has sizes for, which a resumed scan won't
+    /// examine again.
+    pub fn files(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Keeps what the scan found inside the `completed` directories and
+    /// drops the rest, which a resumed scan walks again.
+    pub(crate) fn build(
+        root: &Path,
+        completed: &[PathBuf],
+        files: &[FileEntry],
+        links: &[LinkEntry],
+        mounts: &[PathBuf],
+        specials: &[PathBuf],
+        failures: &[Failure],
+    ) -> Checkpoint {
+        let mut by_depth: Vec<&PathBuf> = completed.iter().collect();
+        by_depth.sort_by_key(|dir| dir.components().count());
+        let mut done = HashSet::new();
+        for dir in by_depth {
+            if !covers(&done, dir) {
+                done.insert(dir.clone());
+            }
+        }
+        Checkpoint {
+            root: root.to_path_buf(),
+            files: files
+                .iter()
+                .filter(|(path, ..)| covers(&done, path))
+                .cloned()
+                .collect(),
+            links: links
+                .iter()
+                .filter(|(path, ..)| covers(&done, path))
+                .cloned()
+                .collect(),
+            mounts: mounts
+                .iter()
+                .filter(|path| covers(&done, path))
+                .cloned()
+                .collect(),
+            specials: specials
+                .iter()
+                .filter(|path| covers(&done, path))
+                .cloned()
+                .collect(),
+            failures: failures
+                .iter()
+                .filter(|(path, ..)| covers(&done, path))
+                .cloned()
+                .collect(),
+            done: done.into_iter().collect(),
+        }
+    }
+
+    /// Bytes of files and links, and files, recorded below each completed
+    /// directory.
+    pub(crate) fn subtotals(&self) -> HashMap<PathBuf, (u128, u64)> {
+        let mut totals: HashMap<PathBuf, (u128, u64)> =
+            self.done.iter().map(|dir| (dir.clone(), (0, 0))).collect();
+        let entries = self.files.iter().map(|(path, file)| (path, file.size, 1));
+        let links = self.links.iter().map(|(path, size, _)| (path, *size, 0));
+        for (path, size, files) in entries.chain(links) {
+            let dir = path
+                .ancestors()
+                .skip(1)
+                .find(|dir| totals.contains_key(*dir));
+            if let Some(total) = dir.and_then(|dir| totals.get_mut(dir)) {
+                total.0 += size as u128;
+                total.1 += files;
+            }
+        }
+        totals
+    }
+
+    /// Entries found vanished before the checkpoint was taken.
+    pub(crate) fn vanished(&self) -> u64 {
+        self.failures
+            .iter()
+            .filter(|(_, kind, _)| *kind == ScanErrorKind::Vanished)
+            .count() as u64
+    }
+}
+
+/// Whether `path` lies inside one of the `done` directories.
+fn covers(done: &HashSet<PathBuf>, path: &Path) -> bool {
+    path.ancestors().skip(1).any(|dir| done.contains(dir))
+}
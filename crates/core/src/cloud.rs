@@ -0,0 +1,60 @@
+use std::fs::Metadata;
+
+use crate::model::{NodeKind, Tree};
+
+/// Whether a file is a cloud placeholder, as OneDrive, Dropbox or iCloud
+/// leave for files kept online only: it reports its full size but takes
+/// next to nothing on disk until it is opened.
+pub fn is_online_only(md: &Metadata) -> bool {
+    platform::is_online_only(md)
+}
+
+/// Bytes of online-only files per node, indexed by node id: a placeholder's
+/// own size, or the sum over everything below a directory. What remains of
+/// [`TreeNode::size`](crate::model::TreeNode::size) is stored locally.
+pub fn cloud_bytes(tree: &Tree) -> Vec<u128> {
+    tree.fold_up(|node, children| match node.kind {
+        NodeKind::File | NodeKind::Archive if node.online_only => node.size,
+        NodeKind::Dir => children.map(|(_, bytes)| bytes).sum(),
+        _ => 0,
+    })
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::fs::Metadata;
+    use std::os::windows::fs::MetadataExt;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_ATTRIBUTE_OFFLINE, FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS, FILE_ATTRIBUTE_RECALL_ON_OPEN,
+    };
+
+    pub(super) fn is_online_only(md: &Metadata) -> bool {
+        let placeholder = FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS
+            | FILE_ATTRIBUTE_RECALL_ON_OPEN
+            | FILE_ATTRIBUTE_OFFLINE;
+        md.file_attributes() & placeholder != 0
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::fs::Metadata;
+    use std::os::macos::fs::MetadataExt;
+
+    /// `SF_DATALESS` from `<sys/stat.h>`: the file's content is not present
+    /// and is fetched by its file provider when read.
+    const SF_DATALESS: u32 = 0x4000_0000;
+
+    pub(super) fn is_online_only(md: &Metadata) -> bool {
+        md.st_flags() & SF_DATALESS != 0
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+mod platform {
+    use std::fs::Metadata;
+
+    pub(super) fn is_online_only(_md: &Metadata) -> bool {
+        false
+    }
+}
@@ -0,0 +1,127 @@
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::model::{NodeKind, Tree};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Grown,
+    Shrunk,
+}
+
+impl ChangeKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "new",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Grown => "grown",
+            ChangeKind::Shrunk => "shrunk",
+        }
+    }
+}
+
+/// One node that differs between two trees. Sizes are 0 on the side where
+/// the node doesn't exist.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeChange {
+    pub path: PathBuf,
+    pub change: ChangeKind,
+    pub is_dir: bool,
+    pub old_bytes: u128,
+    pub new_bytes: u128,
+}
+
+impl NodeChange {
+    pub fn delta(&self) -> i128 {
+        self.new_bytes as i128 - self.old_bytes as i128
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TreeDiff {
+    pub old_total: u128,
+    pub new_total: u128,
+    /// Largest change first. An added or removed directory is listed once,
+    /// without its contents.
+    pub changes: Vec<NodeChange>,
+}
+
+impl TreeDiff {
+    pub fn delta(&self) -> i128 {
+        self.new_total as i128 - self.old_total as i128
+    }
+}
+
+/// Compares two scans of the same root, matching nodes by path.
+pub fn diff(old: &Tree, new: &Tree) -> TreeDiff {
+    let remap = old.remap_to(new);
+    let mut matched = vec![false; new.nodes.len()];
+    let mut changes = Vec::new();
+
+    for node in &old.nodes {
+        match remap.get(node.id) {
+            Some(id) => {
+                matched[id.0 as usize] = true;
+                let now = &new.nodes[id.0 as usize];
+                let change = match now.size.cmp(&node.size) {
+                    std::cmp::Ordering::Greater => ChangeKind::Grown,
+                    std::cmp::Ordering::Less => ChangeKind::Shrunk,
+                    std::cmp::Ordering::Equal => continue,
+                };
+                changes.push(NodeChange {
+                    path: new.path(id),
+                    change,
+                    is_dir: is_dir(&now.kind),
+                    old_bytes: node.size,
+                    new_bytes: now.size,
+                });
+            }
+            // Only the topmost node of a removed branch is reported
+            None if node.parent.is_none_or(|p| remap.get(p).is_some()) => {
+                changes.push(NodeChange {
+                    path: old.path(node.id),
+                    change: ChangeKind::Removed,
+                    is_dir: is_dir(&node.kind),
+                    old_bytes: node.size,
+                    new_bytes: 0,
+                });
+            }
+            None => {}
+        }
+    }
+    for node in &new.nodes {
+        let parent_matched = node.parent.is_none_or(|p| matched[p.0 as usize]);
+        if !matched[node.id.0 as usize] && parent_matched {
+            changes.push(NodeChange {
+                path: new.path(node.id),
+                change: ChangeKind::Added,
+                is_dir: is_dir(&node.kind),
+                old_bytes: 0,
+                new_bytes: node.size,
+            });
+        }
+    }
+
+    changes.sort_by(|a, b| {
+        b.delta()
+            .unsigned_abs()
+            .cmp(&a.delta().unsigned_abs())
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    TreeDiff {
+        old_total: root_size(old),
+        new_total: root_size(new),
+        changes,
+    }
+}
+
+fn is_dir(kind: &NodeKind) -> bool {
+    matches!(kind, NodeKind::Dir | NodeKind::MountPoint)
+}
+
+fn root_size(tree: &Tree) -> u128 {
+    tree.nodes.get(tree.root.0 as usize).map_or(0, |n| n.size)
+}
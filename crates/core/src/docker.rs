@@ -0,0 +1,435 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+use crate::model::{NodeId, NodeKind, Tree, TreeNode};
+
+#[derive(Debug, Error)]
+pub enum DockerError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid response: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Docker API error (HTTP {status}): {message}")]
+    Api { status: u16, message: String },
+    #[error("not a `docker save` archive: {0}")]
+    InvalidImage(String),
+    #[error("the Docker daemon can only be reached over a Unix socket")]
+    Unsupported,
+}
+
+/// What `GET /system/df` reports, as far as it's used here.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DiskUsage {
+    #[serde(default)]
+    images: Vec<ImageUsage>,
+    #[serde(default)]
+    containers: Vec<ContainerUsage>,
+    #[serde(default)]
+    volumes: Vec<VolumeUsage>,
+    #[serde(default)]
+    build_cache: Vec<BuildCacheUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ImageUsage {
+    id: String,
+    #[serde(default)]
+    repo_tags: Option<Vec<String>>,
+    #[serde(default)]
+    created: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerUsage {
+    id: String,
+    #[serde(default)]
+    names: Vec<String>,
+    #[serde(default)]
+    size_rw: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VolumeUsage {
+    name: String,
+    #[serde(default)]
+    usage_data: Option<VolumeUsageData>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VolumeUsageData {
+    size: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BuildCacheUsage {
+    #[serde(rename = "ID")]
+    id: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    size: i64,
+}
+
+/// One entry of `GET /images/{id}/history`, newest first.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HistoryEntry {
+    #[serde(default)]
+    created_by: String,
+    #[serde(default)]
+    size: i64,
+}
+
+/// An image in the `manifest.json` of a `docker save` archive.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ManifestEntry {
+    config: String,
+    #[serde(default)]
+    repo_tags: Option<Vec<String>>,
+    layers: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ImageConfig {
+    #[serde(default)]
+    history: Vec<ConfigHistory>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigHistory {
+    #[serde(default)]
+    created_by: String,
+    #[serde(default)]
+    empty_layer: bool,
+}
+
+/// Disk used by the local Docker daemon as a tree: its images broken down
+/// by layer, the writable layers of containers, volumes and build cache.
+/// Layers shared by several images are counted under each of them.
+pub fn daemon_tree() -> Result<Tree, DockerError> {
+    let usage: DiskUsage = serde_json::from_slice(&api_get("/system/df")?)?;
+    let root = PathBuf::from("docker://");
+    let mut tree = Tree::default();
+    tree.nodes
+        .push(node(NodeId(0), None, "Docker".into(), NodeKind::Dir));
+    tree.note_path(NodeId(0), &root);
+
+    let images = push(&mut tree, NodeId(0), "Images", NodeKind::Dir);
+    for image in &usage.images {
+        let name = image
+            .repo_tags
+            .iter()
+            .flatten()
+            .find(|tag| *tag != "<none>:<none>")
+            .cloned()
+            .unwrap_or_else(|| short_id(&image.id).to_string());
+        let dir = push(&mut tree, images, &name, NodeKind::Dir);
+        tree.nodes[dir.0 as usize].modified = u64::try_from(image.created)
+            .ok()
+            .map(|secs| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs));
+        let path = format!("/images/{}/history", image.id);
+        let history: Vec<HistoryEntry> = serde_json::from_slice(&api_get(&path)?)?;
+        let layers = history.iter().rev().filter(|h| h.size > 0);
+        for (index, layer) in layers.enumerate() {
+            let name = layer_name(index + 1, &layer.created_by);
+            let id = push(&mut tree, dir, &name, NodeKind::File);
+            set_size(&mut tree, id, layer.size);
+        }
+    }
+
+    let containers = push(&mut tree, NodeId(0), "Containers", NodeKind::Dir);
+    for container in &usage.containers {
+        let name = container
+            .names
+            .first()
+            .map(|n| n.trim_start_matches('/').to_string())
+            .unwrap_or_else(|| short_id(&container.id).to_string());
+        let id = push(&mut tree, containers, &name, NodeKind::File);
+        set_size(&mut tree, id, container.size_rw.unwrap_or(0));
+    }
+
+    let volumes = push(&mut tree, NodeId(0), "Volumes", NodeKind::Dir);
+    for volume in &usage.volumes {
+        let id = push(&mut tree, volumes, &volume.name, NodeKind::File);
+        // -1 while the daemon hasn't measured it
+        let size = volume.usage_data.as_ref().map_or(0, |u| u.size);
+        set_size(&mut tree, id, size);
+    }
+
+    let cache = push(&mut tree, NodeId(0), "Build cache", NodeKind::Dir);
+    for entry in &usage.build_cache {
+        let name = match entry.description.as_str() {
+            "" => short_id(&entry.id).to_string(),
+            description => truncate(description),
+        };
+        let id = push(&mut tree, cache, &name, NodeKind::File);
+        set_size(&mut tree, id, entry.size);
+    }
+
+    sum_up(&mut tree);
+    tree.meta.scanned_at = Some(chrono::Local::now());
+    Ok(tree)
+}
+
+/// The images in a `docker save` archive at `path` as a tree, one folder
+/// per layer holding the files that layer adds or changes. Files a layer
+/// deletes (its whiteouts) are left out.
+pub fn image_archive_tree(path: &Path) -> Result<Tree, DockerError> {
+    let mut manifest: Option<Vec<ManifestEntry>> = None;
+    let mut configs: HashMap<String, ImageConfig> = HashMap::new();
+    let mut layers: HashMap<String, Vec<(PathBuf, u64)>> = HashMap::new();
+    let mut archive = tar::Archive::new(BufReader::new(File::open(path)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut head = Vec::with_capacity(2);
+        entry.by_ref().take(2).read_to_end(&mut head)?;
+        let head = head.as_slice();
+        // Layers are tar files, possibly gzipped; the rest is JSON
+        if head.starts_with(b"{") || head.starts_with(b"[") {
+            let mut json = head.to_vec();
+            entry.read_to_end(&mut json)?;
+            if name == "manifest.json" {
+                manifest = Some(serde_json::from_slice(&json)?);
+            } else if let Ok(config) = serde_json::from_slice(&json) {
+                configs.insert(name, config);
+            }
+        } else {
+            let reader = head.chain(entry);
+            let files = if head == [0x1f, 0x8b] {
+                list_layer(flate2::read::GzDecoder::new(reader))
+            } else {
+                list_layer(reader)
+            };
+            // Anything else that isn't a tar file isn't a layer either
+            if let Ok(files) = files {
+                layers.insert(name, files);
+            }
+        }
+    }
+    let manifest =
+        manifest.ok_or_else(|| DockerError::InvalidImage("no manifest.json".to_string()))?;
+
+    let root_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut tree = Tree::default();
+    tree.nodes
+        .push(node(NodeId(0), None, root_name, NodeKind::Dir));
+    tree.note_path(NodeId(0), path);
+    for image in manifest {
+        let name = image
+            .repo_tags
+            .as_ref()
+            .and_then(|tags| tags.first())
+            .cloned()
+            .unwrap_or_else(|| short_id(&image.config).to_string());
+        let dir = push(&mut tree, NodeId(0), &name, NodeKind::Dir);
+        let config = configs.remove(&image.config).unwrap_or_default();
+        let mut commands = config
+            .history
+            .iter()
+            .filter(|h| !h.empty_layer)
+            .map(|h| h.created_by.as_str());
+        for (index, layer) in image.layers.iter().enumerate() {
+            let name = layer_name(index + 1, commands.next().unwrap_or(layer));
+            let layer_id = push(&mut tree, dir, &name, NodeKind::Dir);
+            let files = layers.get(layer).map(Vec::as_slice).unwrap_or_default();
+            add_files(&mut tree, layer_id, files);
+        }
+    }
+    sum_up(&mut tree);
+    tree.meta.scanned_at = Some(chrono::Local::now());
+    Ok(tree)
+}
+
+/// The regular files in a layer tarball, without its whiteouts.
+fn list_layer(reader: impl Read) -> io::Result<Vec<(PathBuf, u64)>> {
+    let mut files = Vec::new();
+    for entry in tar::Archive::new(reader).entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        let whiteout = path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with(".wh."));
+        if !whiteout {
+            files.push((path, entry.size()));
+        }
+    }
+    Ok(files)
+}
+
+/// Adds `files` below the layer node `layer`, with folders for their
+/// directories.
+fn add_files(tree: &mut Tree, layer: NodeId, files: &[(PathBuf, u64)]) {
+    let mut folders: HashMap<PathBuf, NodeId> = HashMap::new();
+    for (path, size) in files {
+        let parts: Vec<_> = path
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        let Some((file, dirs)) = parts.split_last() else {
+            continue;
+        };
+        let mut parent = layer;
+        let mut inner = PathBuf::new();
+        for dir in dirs {
+            inner.push(dir);
+            parent = match folders.get(&inner) {
+                Some(id) => *id,
+                None => {
+                    let id = push(tree, parent, dir, NodeKind::Dir);
+                    folders.insert(inner.clone(), id);
+                    id
+                }
+            };
+        }
+        let id = push(tree, parent, file, NodeKind::File);
+        set_size(tree, id, *size as i64);
+    }
+}
+
+/// `12: RUN apt-get install …` for the `index`th layer created by
+/// `command`.
+fn layer_name(index: usize, command: &str) -> String {
+    let command = command
+        .trim_start_matches("/bin/sh -c #(nop) ")
+        .trim_start_matches("|0 /bin/sh -c ")
+        .trim();
+    let command = match command.strip_prefix("/bin/sh -c ") {
+        Some(run) => format!("RUN {run}"),
+        None => command.to_string(),
+    };
+    truncate(&format!("{index}: {command}"))
+}
+
+fn truncate(text: &str) -> String {
+    const MAX: usize = 80;
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(MAX) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+/// The first 12 hex digits of an image or container id, as `docker`
+/// prints them.
+fn short_id(id: &str) -> &str {
+    let id = id.trim_start_matches("sha256:");
+    let id = id.rsplit('/').next().unwrap_or(id);
+    let id = id.trim_end_matches(".json");
+    &id[..id.len().min(12)]
+}
+
+fn node(id: NodeId, parent: Option<NodeId>, name: String, kind: NodeKind) -> TreeNode {
+    TreeNode {
+        id,
+        parent,
+        name: name.into(),
+        kind,
+        size: 0,
+        file_count: 0,
+        children: Vec::new(),
+        modified: None,
+        link_target: None,
+        online_only: false,
+        owner: None,
+        attributes: crate::attrs::Attributes::default(),
+    }
+}
+
+fn push(tree: &mut Tree, parent: NodeId, name: &str, kind: NodeKind) -> NodeId {
+    let id = NodeId(tree.nodes.len() as u32);
+    // Names such as image tags and layer commands may hold slashes
+    let path = tree.path(parent).join(name.replace(['/', '\\'], "∕"));
+    tree.nodes
+        .push(node(id, Some(parent), name.to_string(), kind));
+    tree.nodes[parent.0 as usize].children.push(id);
+    tree.note_path(id, &path);
+    id
+}
+
+fn set_size(tree: &mut Tree, id: NodeId, size: i64) {
+    let node = &mut tree.nodes[id.0 as usize];
+    node.size = u128::try_from(size).unwrap_or(0);
+    node.file_count = 1;
+}
+
+/// Adds every node's size and file count to its parent's.
+fn sum_up(tree: &mut Tree) {
+    // Children always come after their parent, so one reverse pass suffices
+    for idx in (0..tree.nodes.len()).rev() {
+        let (size, files) = (tree.nodes[idx].size, tree.nodes[idx].file_count);
+        if let Some(parent) = tree.nodes[idx].parent {
+            let parent = &mut tree.nodes[parent.0 as usize];
+            parent.size += size;
+            parent.file_count += files;
+        }
+    }
+}
+
+/// The body of a GET to the Docker Engine API at `path`.
+#[cfg(unix)]
+fn api_get(path: &str) -> Result<Vec<u8>, DockerError> {
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    let socket = match std::env::var("DOCKER_HOST") {
+        Ok(host) if host.starts_with("unix://") => PathBuf::from(&host["unix://".len()..]),
+        Ok(host) if !host.is_empty() => return Err(DockerError::Unsupported),
+        _ => PathBuf::from("/var/run/docker.sock"),
+    };
+    let mut stream = UnixStream::connect(socket)?;
+    // HTTP/1.0 keeps the body unchunked and ends it by closing the socket
+    write!(stream, "GET {path} HTTP/1.0\r\nHost: docker\r\n\r\n")?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated HTTP response"))?;
+    let head = String::from_utf8_lossy(&response[..split]);
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    let body = response[split + 4..].to_vec();
+    if status != 200 {
+        #[derive(Deserialize)]
+        struct ApiError {
+            message: String,
+        }
+        let message = serde_json::from_slice::<ApiError>(&body)
+            .map(|e| e.message)
+            .unwrap_or_else(|_| String::from_utf8_lossy(&body).into_owned());
+        return Err(DockerError::Api { status, message });
+    }
+    Ok(body)
+}
+
+#[cfg(not(unix))]
+fn api_get(_path: &str) -> Result<Vec<u8>, DockerError> {
+    Err(DockerError::Unsupported)
+}
@@ -1,10 +1,16 @@
+use crate::age::{age_histograms, AgeBucket};
+use crate::annotations::Annotations;
+use crate::cloud::cloud_bytes;
+use crate::human::{group_digits, human_bytes_in, percent, system_locale, DateFormat, ISO_8601};
+use crate::i18n::Language;
 use crate::model::*;
-use chrono::{DateTime, Local};
-use serde::Serialize;
+use crate::profile::SlowDir;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::path::Path;
-use std::time::SystemTime;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -17,6 +23,32 @@ pub enum ExportError {
     Json(#[from] serde_json::Error),
     #[error("pdf error: {0}")]
     Pdf(#[from] printpdf::Error),
+    #[error("not a TreeSize JSON export: {0}")]
+    Import(String),
+}
+
+/// Settings shared by [`export_csv`], [`export_json`] and [`export_pdf`].
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// How modification times are written. Only the default is read back
+    /// by [`import_json`]; with any other, times are left out on import.
+    pub date_format: DateFormat,
+    /// Language and number formatting of the PDF report, and the locale of
+    /// [`DateFormat::Locale`] dates in every format. `None` writes the PDF
+    /// in English with plain byte counts, and dates in the system locale.
+    /// CSV and JSON keep their column names and raw numbers either way.
+    pub locale: Option<chrono::Locale>,
+    /// Write paths from the scanned folder down, as
+    /// [`Tree::relative_path`] gives them, instead of in full.
+    pub relative_paths: bool,
+    /// Tags and notes written with the nodes they are attached to.
+    pub annotations: Annotations,
+}
+
+impl ExportOptions {
+    pub fn language(&self) -> Language {
+        self.locale.map(Language::of).unwrap_or_default()
+    }
 }
 
 #[derive(Serialize)]
@@ -24,107 +56,447 @@ struct ExportRow {
     path: String,
     kind: &'static str,
     size_bytes: u128,
+    /// Part of `size_bytes` in online-only cloud placeholders.
+    cloud_bytes: u128,
     files: u64,
     folders: u64,
     modified: String,
+    /// [`Attributes::flags`](crate::attrs::Attributes::flags).
+    attributes: String,
+    /// Unix permission bits as `ls -l` shows them; empty elsewhere.
+    mode: String,
+    /// A directory the scan could not enter; see [`Tree::access_denied`].
+    access_denied: bool,
+    /// Bytes per modification age, in [`AgeBucket::ALL`] order.
+    bytes_0_30d: u128,
+    bytes_30_365d: u128,
+    bytes_1_3y: u128,
+    bytes_over_3y: u128,
+    /// Half the width of the 95% confidence interval of `size_bytes`, when
+    /// it was estimated from a sample; see [`Tree::estimate_margin`].
+    size_margin_bytes: u128,
+    /// [`Tree::share_of_parent`] and [`Tree::share_of_root`] as
+    /// percentages, rounded by [`percent`].
+    percent_of_parent: f64,
+    percent_of_root: f64,
+    /// [`Annotation::tags_text`](crate::annotations::Annotation::tags_text).
+    tags: String,
+    note: String,
 }
 
-fn build_rows(tree: &Tree) -> Vec<ExportRow> {
+fn build_rows(tree: &Tree, options: &ExportOptions) -> Vec<ExportRow> {
     let dir_counts = compute_dir_counts(tree);
+    let now = std::time::SystemTime::now();
+    let ages = age_histograms(tree, now);
+    let cloud = cloud_bytes(tree);
+    let denied: HashSet<NodeId> = tree.access_denied().map(|e| e.node).collect();
+    let locale = options.locale.unwrap_or_else(system_locale);
+    let paths = tree.paths();
+    let annotations = options.annotations.in_tree(tree);
     tree.nodes
         .iter()
         .enumerate()
         .map(|(idx, node)| {
-            let kind = match node.kind {
-                NodeKind::File => "file",
-                NodeKind::Dir => "dir",
-            };
-            let (files, dirs) = if matches!(node.kind, NodeKind::File) {
+            let annotation = annotations.get(&node.id);
+            let (files, dirs) = if node.kind.is_file() {
                 (0, 0)
             } else {
                 (node.file_count, dir_counts[idx])
             };
-            let modified = format_modified(node.modified);
+            let modified = node
+                .modified
+                .map(|time| options.date_format.format_in(time, now, locale))
+                .unwrap_or_default();
+            let age = &ages[idx];
+            let path = if options.relative_paths {
+                tree.relative_path(&paths[idx])
+            } else {
+                &paths[idx]
+            };
             ExportRow {
-                path: node.path.display().to_string(),
-                kind,
+                path: path.display().to_string(),
+                kind: node.kind.as_str(),
                 size_bytes: node.size,
+                cloud_bytes: cloud[idx],
                 files,
                 folders: dirs,
                 modified,
+                attributes: node.attributes.flags(),
+                mode: node.attributes.mode_string().unwrap_or_default(),
+                access_denied: denied.contains(&node.id),
+                bytes_0_30d: age.bytes_in(AgeBucket::Recent),
+                bytes_30_365d: age.bytes_in(AgeBucket::Months),
+                bytes_1_3y: age.bytes_in(AgeBucket::Years),
+                bytes_over_3y: age.bytes_in(AgeBucket::Stale),
+                size_margin_bytes: tree.estimate_margin(node.id).unwrap_or(0),
+                percent_of_parent: percent(tree.share_of_parent(node.id)),
+                percent_of_root: percent(tree.share_of_root(node.id)),
+                tags: annotation.map(|a| a.tags_text()).unwrap_or_default(),
+                note: annotation.map(|a| a.note.clone()).unwrap_or_default(),
             }
         })
         .collect()
 }
 
 fn compute_dir_counts(tree: &Tree) -> Vec<u64> {
-    let mut counts = vec![0; tree.nodes.len()];
-    for idx in (0..tree.nodes.len()).rev() {
-        if matches!(tree.nodes[idx].kind, NodeKind::Dir) {
-            let mut total = 0;
-            for &child in &tree.nodes[idx].children {
-                let cidx = child.0 as usize;
-                if matches!(tree.nodes[cidx].kind, NodeKind::Dir) {
-                    total += 1 + counts[cidx];
-                }
-            }
-            counts[idx] = total;
-        }
-    }
-    counts
-}
-
-fn format_modified(modified: Option<std::time::SystemTime>) -> String {
-    modified
-        .map(|ts| {
-            DateTime::<Local>::from(ts)
-                .format("%Y-%m-%d %H:%M:%S")
-                .to_string()
-        })
-        .unwrap_or_else(|| "".to_string())
+    tree.fold_up(|node, children| match node.kind {
+        NodeKind::Dir => children
+            .filter(|(child, _)| matches!(child.kind, NodeKind::Dir))
+            .map(|(_, count)| 1 + count)
+            .sum(),
+        _ => 0,
+    })
 }
 
-pub fn export_csv(tree: &Tree, path: &Path) -> Result<(), ExportError> {
-    let rows = build_rows(tree);
+pub fn export_csv(tree: &Tree, path: &Path, options: &ExportOptions) -> Result<(), ExportError> {
+    let rows = build_rows(tree, options);
     let file = File::create(path)?;
     let mut writer = csv::Writer::from_writer(BufWriter::new(file));
-    writer.write_record(["path", "kind", "size_bytes", "files", "folders", "modified"])?;
+    writer.write_record([
+        "path",
+        "kind",
+        "size_bytes",
+        "cloud_bytes",
+        "files",
+        "folders",
+        "modified",
+        "attributes",
+        "mode",
+        "access_denied",
+        "bytes_0_30d",
+        "bytes_30_365d",
+        "bytes_1_3y",
+        "bytes_over_3y",
+        "size_margin_bytes",
+        "percent_of_parent",
+        "percent_of_root",
+        "tags",
+        "note",
+    ])?;
     for row in rows {
         writer.write_record([
             row.path,
             row.kind.to_string(),
             row.size_bytes.to_string(),
+            row.cloud_bytes.to_string(),
             row.files.to_string(),
             row.folders.to_string(),
             row.modified,
+            row.attributes,
+            row.mode,
+            row.access_denied.to_string(),
+            row.bytes_0_30d.to_string(),
+            row.bytes_30_365d.to_string(),
+            row.bytes_1_3y.to_string(),
+            row.bytes_over_3y.to_string(),
+            row.size_margin_bytes.to_string(),
+            row.percent_of_parent.to_string(),
+            row.percent_of_root.to_string(),
+            row.tags,
+            row.note,
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes rows from [`ScanProfile::slowest`](crate::profile::ScanProfile::slowest)
+/// as CSV, with times in milliseconds.
+pub fn export_slowest_csv(rows: &[SlowDir], path: &Path) -> Result<(), ExportError> {
+    let file = File::create(path)?;
+    let mut writer = csv::Writer::from_writer(BufWriter::new(file));
+    writer.write_record([
+        "path",
+        "entries",
+        "own_ms",
+        "subtree_entries",
+        "subtree_ms",
+        "ms_per_entry",
+    ])?;
+    let ms = |d: std::time::Duration| format!("{:.3}", d.as_secs_f64() * 1000.0);
+    for row in rows {
+        writer.write_record([
+            row.path.display().to_string(),
+            row.entries.to_string(),
+            ms(row.own),
+            row.subtree_entries.to_string(),
+            ms(row.subtree),
+            ms(row.per_entry()),
         ])?;
     }
     writer.flush()?;
     Ok(())
 }
 
-pub fn export_json(tree: &Tree, path: &Path) -> Result<(), ExportError> {
-    let rows = build_rows(tree);
+pub fn export_json(tree: &Tree, path: &Path, options: &ExportOptions) -> Result<(), ExportError> {
+    let rows = build_rows(tree, options);
     let file = File::create(path)?;
     serde_json::to_writer_pretty(BufWriter::new(file), &rows)?;
     Ok(())
 }
 
-pub fn export_pdf(tree: &Tree, path: &Path) -> Result<(), ExportError> {
+/// Writes the tree as the nested `{"name", "value", "children"}` JSON that
+/// d3 hierarchies, sunbursts and flame graphs read. `value` is the size of
+/// everything below a node, so with d3's `hierarchy().sum()` take it from
+/// leaves only; d3-flame-graph uses it as is. Children are largest first.
+pub fn export_hierarchy_json(tree: &Tree, path: &Path) -> Result<(), ExportError> {
+    let file = File::create(path)?;
+    let root = HierarchyNode {
+        tree,
+        id: tree.root,
+    };
+    serde_json::to_writer(BufWriter::new(file), &root)?;
+    Ok(())
+}
+
+/// A node of [`export_hierarchy_json`], serialized straight from the tree.
+struct HierarchyNode<'a> {
+    tree: &'a Tree,
+    id: NodeId,
+}
+
+impl Serialize for HierarchyNode<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let node = &self.tree.nodes[self.id.0 as usize];
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("name", display_name(node))?;
+        map.serialize_entry("value", &node.size)?;
+        if !node.children.is_empty() {
+            let children: Vec<_> = largest_first(self.tree, &node.children)
+                .into_iter()
+                .map(|id| HierarchyNode {
+                    tree: self.tree,
+                    id,
+                })
+                .collect();
+            map.serialize_entry("children", &children)?;
+        }
+        map.end()
+    }
+}
+
+/// Writes the tree in the folded stack format of Brendan Gregg's
+/// `flamegraph.pl` and inferno: one `root;folder;file bytes` line per
+/// file, plus one for each folder holding bytes none of its entries
+/// account for, such as those below the depth limit.
+pub fn export_folded(tree: &Tree, path: &Path) -> Result<(), ExportError> {
+    use std::io::Write;
+    let mut out = BufWriter::new(File::create(path)?);
+    let Some(root) = tree.nodes.get(tree.root.0 as usize) else {
+        return Ok(());
+    };
+    let mut stack = vec![(tree.root, folded_frame(root))];
+    while let Some((id, frames)) = stack.pop() {
+        let node = &tree.nodes[id.0 as usize];
+        let listed: u128 = node
+            .children
+            .iter()
+            .map(|c| tree.nodes[c.0 as usize].size)
+            .sum();
+        let own = node.size.saturating_sub(listed);
+        if own > 0 {
+            writeln!(out, "{frames} {own}")?;
+        }
+        // Pushed smallest first so the largest are written first
+        for child in largest_first(tree, &node.children).into_iter().rev() {
+            let frame = folded_frame(&tree.nodes[child.0 as usize]);
+            stack.push((child, format!("{frames};{frame}")));
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The name of `node` as one frame of a folded stack, which can't contain
+/// the `;` separating frames or a line break.
+fn folded_frame(node: &TreeNode) -> String {
+    display_name(node).replace([';', '\n', '\r'], "_")
+}
+
+/// The name of `node`; the root of a combined tree has none of its own.
+fn display_name(node: &TreeNode) -> &str {
+    if node.name.is_empty() {
+        "All roots"
+    } else {
+        &node.name
+    }
+}
+
+fn largest_first(tree: &Tree, ids: &[NodeId]) -> Vec<NodeId> {
+    let mut ids = ids.to_vec();
+    ids.sort_by_key(|id| std::cmp::Reverse(tree.nodes[id.0 as usize].size));
+    ids
+}
+
+/// The fields of an [`ExportRow`] needed to rebuild the tree.
+#[derive(Deserialize)]
+struct ImportRow {
+    path: PathBuf,
+    kind: String,
+    size_bytes: u128,
+    #[serde(default)]
+    cloud_bytes: u128,
+    #[serde(default)]
+    files: u64,
+    #[serde(default)]
+    modified: String,
+}
+
+/// Rebuilds a tree from a report written by [`export_json`], so older
+/// reports can be compared with a new scan. Sizes are taken as exported;
+/// scan errors and metadata are not part of the report.
+pub fn import_json(path: &Path) -> Result<Tree, ExportError> {
+    let rows: Vec<ImportRow> = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let mut nodes: Vec<TreeNode> = Vec::with_capacity(rows.len());
+    let mut id_by_path: HashMap<PathBuf, NodeId> = HashMap::new();
+    let mut anchors = HashMap::new();
+    let mut names = Interner::new();
+    for row in rows {
+        let id = NodeId(nodes.len() as u32);
+        // Rows are written parents first, starting with the root
+        let parent = match row.path.parent().and_then(|p| id_by_path.get(p)) {
+            Some(pid) => Some(*pid),
+            None if nodes.is_empty() => None,
+            None => {
+                return Err(ExportError::Import(format!(
+                    "{} is not under an earlier row",
+                    row.path.display()
+                )))
+            }
+        };
+        let kind = match row.kind.as_str() {
+            "file" => NodeKind::File,
+            "dir" => NodeKind::Dir,
+            "mount" => NodeKind::MountPoint,
+            "symlink" => NodeKind::Symlink,
+            "archive_entry" => NodeKind::ArchiveEntry,
+            "archive" => NodeKind::Archive,
+            "other" => NodeKind::Other,
+            other => return Err(ExportError::Import(format!("unknown kind {other:?}"))),
+        };
+        let file_count = if kind.is_file() { 1 } else { row.files };
+        let online_only = kind.is_file() && row.cloud_bytes > 0;
+        let name = row
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| row.path.display().to_string());
+        if let Some(pid) = parent {
+            nodes[pid.0 as usize].children.push(id);
+        }
+        nodes.push(TreeNode {
+            id,
+            parent,
+            name: names.intern(&name),
+            kind,
+            size: row.size_bytes,
+            file_count,
+            children: Vec::new(),
+            modified: parse_modified(&row.modified),
+            link_target: None,
+            online_only,
+            owner: None,
+            attributes: crate::attrs::Attributes::default(),
+        });
+        if nodes[id.0 as usize].needs_anchor(&row.path) {
+            anchors.insert(id, row.path.clone());
+        }
+        id_by_path.insert(row.path, id);
+    }
+    if nodes.is_empty() {
+        return Err(ExportError::Import("no rows".to_string()));
+    }
+    Ok(Tree {
+        root: NodeId(0),
+        nodes,
+        errors: Vec::new(),
+        meta: ScanMeta::default(),
+        anchors,
+    })
+}
+
+/// Reads a time written with [`DateFormat::Iso8601`].
+fn parse_modified(text: &str) -> Option<std::time::SystemTime> {
+    let naive = chrono::NaiveDateTime::parse_from_str(text, ISO_8601).ok()?;
+    naive
+        .and_local_timezone(Local)
+        .earliest()
+        .map(std::time::SystemTime::from)
+}
+
+pub fn export_pdf(tree: &Tree, path: &Path, options: &ExportOptions) -> Result<(), ExportError> {
     use printpdf::*;
-    let rows = build_rows(tree);
-    let (doc, page, layer) = PdfDocument::new("TreeSize Export", Mm(210.0), Mm(297.0), "Layer 1");
+    let rows = build_rows(tree, options);
+    let text = options.language().text();
+    let count = |n: u64| match options.locale {
+        Some(locale) => group_digits(n, locale),
+        None => n.to_string(),
+    };
+    let bytes = |n: u128| match options.locale {
+        Some(locale) => human_bytes_in(n, locale),
+        None => n.to_string(),
+    };
+    let [recent, months, years, stale] = text.age_buckets;
+    let (doc, page, layer) = PdfDocument::new(text.title, Mm(210.0), Mm(297.0), "Layer 1");
     let font = doc.add_builtin_font(BuiltinFont::Helvetica)?;
     let mut current_page = page;
     let mut current_layer = doc.get_page(current_page).get_layer(layer);
     let mut y = Mm(280.0);
-    current_layer.use_text("TreeSize Export", 14.0, Mm(10.0), y, &font);
+    let title = match &tree.meta.estimate {
+        Some(estimate) => {
+            let percent = format!("{:.1}", estimate.fraction * 100.0);
+            format!(
+                "{} ({})",
+                text.title,
+                text.estimated.replace("{}", &percent)
+            )
+        }
+        None => text.title.to_string(),
+    };
+    current_layer.use_text(title, 14.0, Mm(10.0), y, &font);
     y -= Mm(10.0);
     let line_height = Mm(5.0);
     for row in rows {
+        let cloud = if row.cloud_bytes > 0 {
+            format!(" ({} {})", bytes(row.cloud_bytes), text.online_only)
+        } else {
+            String::new()
+        };
+        let margin = if row.size_margin_bytes > 0 {
+            format!(" ±{}", bytes(row.size_margin_bytes))
+        } else {
+            String::new()
+        };
+        let denied = if row.access_denied {
+            format!(" [{}]", text.access_denied)
+        } else {
+            String::new()
+        };
+        let mut annotation = String::new();
+        if !row.tags.is_empty() {
+            annotation += &format!(" | {}: {}", text.tags, row.tags);
+        }
+        if !row.note.is_empty() {
+            annotation += &format!(" | {}: {}", text.note, row.note);
+        }
         let line = format!(
-            "{} | {} | size={} | files={} | folders={} | {}",
-            row.path, row.kind, row.size_bytes, row.files, row.folders, row.modified
+            "{}{denied} | {} | {}={} ({:.1}%){margin}{cloud} | {}={} | {}={} | {} | {} {recent}={} {months}={} {years}={} {stale}={}{annotation}",
+            row.path,
+            text.kind(row.kind),
+            text.size,
+            bytes(row.size_bytes),
+            row.percent_of_parent,
+            text.files,
+            count(row.files),
+            text.folders,
+            count(row.folders),
+            row.modified,
+            text.age,
+            bytes(row.bytes_0_30d),
+            bytes(row.bytes_30_365d),
+            bytes(row.bytes_1_3y),
+            bytes(row.bytes_over_3y)
         );
         if y.0 < 20.0 {
             let (new_page, new_layer) = doc.add_page(Mm(210.0), Mm(297.0), "Layer");
@@ -0,0 +1,78 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+
+use crate::model::{NodeId, NodeSource};
+
+/// Total size and number of files sharing one extension.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtStat {
+    /// Lowercase extension without the dot; empty for files without one.
+    pub extension: String,
+    pub bytes: u128,
+    pub files: u64,
+}
+
+impl ExtStat {
+    /// The extension for display, e.g. `.jpg` or `(none)`.
+    pub fn label(&self) -> String {
+        if self.extension.is_empty() {
+            "(none)".to_string()
+        } else {
+            format!(".{}", self.extension)
+        }
+    }
+}
+
+/// Aggregates the files under `id` (or `id` itself, if it's a file) by
+/// extension, largest total first.
+pub fn ext_stats(source: &impl NodeSource, id: NodeId) -> Vec<ExtStat> {
+    let files = source.files_below(id);
+    // Keyed by the extension as borrowed from the names, so files only
+    // cost a lookup; counted per thread, then case variants are merged
+    // once per extension below
+    let by_ext = files
+        .par_iter()
+        .fold(
+            HashMap::new,
+            |mut by_ext: HashMap<&str, (u128, u64)>, &id| {
+                let extension = Path::new(source.name(id))
+                    .extension()
+                    .and_then(OsStr::to_str);
+                let entry = by_ext.entry(extension.unwrap_or_default()).or_default();
+                entry.0 += source.size(id);
+                entry.1 += 1;
+                by_ext
+            },
+        )
+        .reduce(HashMap::new, |mut by_ext, other| {
+            for (ext, (bytes, files)) in other {
+                let entry = by_ext.entry(ext).or_default();
+                entry.0 += bytes;
+                entry.1 += files;
+            }
+            by_ext
+        });
+    let mut merged: HashMap<String, (u128, u64)> = HashMap::new();
+    for (ext, (bytes, files)) in by_ext {
+        let entry = merged.entry(ext.to_lowercase()).or_default();
+        entry.0 += bytes;
+        entry.1 += files;
+    }
+    let mut stats: Vec<ExtStat> = merged
+        .into_iter()
+        .map(|(extension, (bytes, files))| ExtStat {
+            extension,
+            bytes,
+            files,
+        })
+        .collect();
+    stats.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.extension.cmp(&b.extension))
+    });
+    stats
+}
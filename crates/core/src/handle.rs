@@ -0,0 +1,111 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::progress::{ProgressEvent, ProgressHub};
+
+#[derive(Debug, Default)]
+struct Flags {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+}
+
+/// Cancel/pause token shared between a [`Scanner`](crate::Scanner) and
+/// whoever drives it. Clones control the same scan. Cancellation is final;
+/// a cancelled scan stops as soon as its walker threads notice, paused or not.
+#[derive(Debug, Clone, Default)]
+pub struct ScanControl {
+    flags: Arc<Flags>,
+}
+
+impl ScanControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.flags.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flags.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn pause(&self) {
+        self.flags.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.flags.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.flags.paused.load(Ordering::Relaxed)
+    }
+}
+
+/// A scan running on its own thread, returned by
+/// [`Scanner::spawn`](crate::Scanner::spawn). Dropping the handle detaches
+/// the scan rather than cancelling it.
+#[derive(Debug)]
+pub struct ScanHandle {
+    control: ScanControl,
+    progress: ProgressHub,
+    thread: JoinHandle<()>,
+}
+
+impl ScanHandle {
+    pub(crate) fn spawn(
+        control: ScanControl,
+        progress: ProgressHub,
+        run: impl FnOnce() + Send + 'static,
+    ) -> Self {
+        Self {
+            control,
+            progress,
+            thread: std::thread::spawn(run),
+        }
+    }
+
+    /// A token controlling this scan, e.g. for another thread.
+    pub fn control(&self) -> &ScanControl {
+        &self.control
+    }
+
+    /// Listens to this scan's progress alongside any other listeners; see
+    /// [`ProgressHub`]. Events published before subscribing are missed.
+    pub fn subscribe(&self) -> crossbeam_channel::Receiver<ProgressEvent> {
+        self.progress.subscribe()
+    }
+
+    pub fn progress(&self) -> &ProgressHub {
+        &self.progress
+    }
+
+    pub fn cancel(&self) {
+        self.control.cancel();
+    }
+
+    pub fn pause(&self) {
+        self.control.pause();
+    }
+
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.control.is_paused()
+    }
+
+    /// Whether the scan thread has exited, after sending `Done` or being
+    /// cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Waits for the scan thread to exit. Errors only if it panicked.
+    pub fn join(self) -> std::thread::Result<()> {
+        self.thread.join()
+    }
+}
@@ -0,0 +1,227 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::human::human_bytes;
+use crate::model::{NodeId, NodeKind, Tree};
+
+/// Levels below the root whose folders [`History::record`] keeps, unless
+/// changed with [`History::with_depth`].
+pub const DEFAULT_DEPTH: usize = 3;
+
+/// Most sizes kept per folder; older ones are dropped first.
+const MAX_POINTS: usize = 520;
+
+const SECS_PER_WEEK: f64 = 7.0 * 24.0 * 60.0 * 60.0;
+
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    #[error("history database error: {0}")]
+    Db(#[from] sled::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The size of a folder as of one scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizePoint {
+    pub at: DateTime<Local>,
+    pub bytes: u128,
+    pub files: u64,
+}
+
+/// The recorded sizes of one folder, oldest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trend {
+    pub path: PathBuf,
+    pub points: Vec<SizePoint>,
+}
+
+impl Trend {
+    pub fn latest(&self) -> Option<&SizePoint> {
+        self.points.last()
+    }
+
+    /// Bytes added between the first and the latest scan; negative if the
+    /// folder shrank.
+    pub fn change(&self) -> i128 {
+        match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => last.bytes as i128 - first.bytes as i128,
+            _ => 0,
+        }
+    }
+
+    /// Average growth in bytes per week, fitted through every scan so one
+    /// unusual scan doesn't dominate. `None` until there are two scans at
+    /// different times.
+    pub fn per_week(&self) -> Option<f64> {
+        let first = self.points.first()?.at;
+        let xs: Vec<f64> = self
+            .points
+            .iter()
+            .map(|p| (p.at - first).num_milliseconds() as f64 / 1000.0 / SECS_PER_WEEK)
+            .collect();
+        let n = xs.len() as f64;
+        let mean_x = xs.iter().sum::<f64>() / n;
+        let mean_y = self.points.iter().map(|p| p.bytes as f64).sum::<f64>() / n;
+        let mut covariance = 0.0;
+        let mut variance = 0.0;
+        for (x, point) in xs.iter().zip(&self.points) {
+            covariance += (x - mean_x) * (point.bytes as f64 - mean_y);
+            variance += (x - mean_x) * (x - mean_x);
+        }
+        (variance > 0.0).then(|| covariance / variance)
+    }
+}
+
+impl fmt::Display for Trend {
+    /// `Downloads grew 12.0 GB/week`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_else(|| self.path.to_string_lossy());
+        match self.per_week() {
+            Some(rate) => {
+                let verb = if rate < 0.0 { "shrank" } else { "grew" };
+                write!(f, "{name} {verb} {}", rate_text(rate.abs()))
+            }
+            None => write!(f, "{name}: not enough scans for a trend"),
+        }
+    }
+}
+
+/// A rate in bytes per week, as `12.0 GB/week`.
+pub fn rate_text(per_week: f64) -> String {
+    format!("{}/week", human_bytes(per_week.max(0.0).round() as u128))
+}
+
+/// Folder sizes from successive scans, kept in a small database so growth
+/// can be followed over weeks and months.
+///
+/// Sizes are keyed by folder path, so scans of the same root with different
+/// options (such as a file size filter) end up in one series.
+pub struct History {
+    db: sled::Db,
+    depth: usize,
+}
+
+impl History {
+    pub fn open(path: &Path) -> Result<Self, HistoryError> {
+        Ok(Self {
+            db: sled::open(path)?,
+            depth: DEFAULT_DEPTH,
+        })
+    }
+
+    /// Database in the per-user data directory, shared by the app and CLI.
+    pub fn default_path() -> PathBuf {
+        let base = dirs::data_local_dir().unwrap_or_else(std::env::temp_dir);
+        base.join("treesize").join("history")
+    }
+
+    /// Levels below the root [`record`](Self::record) keeps; deeper folders
+    /// are many and rarely worth following.
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    fn key(path: &Path) -> Vec<u8> {
+        path.to_string_lossy().into_owned().into_bytes()
+    }
+
+    fn points(&self, key: &[u8]) -> Result<Vec<SizePoint>, HistoryError> {
+        match self.db.get(key)? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Adds the size of every folder of `tree`, down to the depth limit, as
+    /// of when it was scanned. Recording the same scan again replaces it.
+    /// Returns how many folders were recorded.
+    pub fn record(&self, tree: &Tree) -> Result<usize, HistoryError> {
+        let at = tree.meta.scanned_at.unwrap_or_else(Local::now);
+        let mut recorded = 0;
+        let mut pending: Vec<(NodeId, usize)> = vec![(tree.root, 0)];
+        while let Some((id, depth)) = pending.pop() {
+            let node = &tree.nodes[id.0 as usize];
+            let path = tree.path(id);
+            // The synthetic root of a combined scan has no path of its own
+            if !path.as_os_str().is_empty() {
+                let key = Self::key(&path);
+                let mut points = self.points(&key)?;
+                points.retain(|p| p.at != at);
+                points.push(SizePoint {
+                    at,
+                    bytes: node.size,
+                    files: node.file_count,
+                });
+                points.sort_by_key(|p| p.at);
+                let surplus = points.len().saturating_sub(MAX_POINTS);
+                points.drain(..surplus);
+                self.db.insert(key, serde_json::to_vec(&points)?)?;
+                recorded += 1;
+            }
+            if depth < self.depth {
+                pending.extend(
+                    node.children
+                        .iter()
+                        .filter(|c| matches!(tree.nodes[c.0 as usize].kind, NodeKind::Dir))
+                        .map(|&c| (c, depth + 1)),
+                );
+            }
+        }
+        self.db.flush()?;
+        Ok(recorded)
+    }
+
+    /// The recorded sizes of `path`; `None` if it was never recorded.
+    pub fn trend(&self, path: &Path) -> Result<Option<Trend>, HistoryError> {
+        let points = self.points(&Self::key(path))?;
+        Ok((!points.is_empty()).then(|| Trend {
+            path: path.to_path_buf(),
+            points,
+        }))
+    }
+
+    /// `root` and every recorded folder below it, fastest growing first.
+    /// Folders without a trend yet come last.
+    pub fn trends(&self, root: &Path) -> Result<Vec<Trend>, HistoryError> {
+        let mut trends = Vec::new();
+        for item in self.db.scan_prefix(Self::key(root)) {
+            let (key, value) = item?;
+            let path = PathBuf::from(String::from_utf8_lossy(&key).into_owned());
+            // The prefix also matches siblings such as `/data2` for `/data`
+            if !path.starts_with(root) {
+                continue;
+            }
+            trends.push(Trend {
+                path,
+                points: serde_json::from_slice(&value)?,
+            });
+        }
+        trends.sort_by(|a, b| {
+            let rate = |t: &Trend| t.per_week().unwrap_or(f64::NEG_INFINITY);
+            rate(b).total_cmp(&rate(a))
+        });
+        Ok(trends)
+    }
+
+    /// Drops everything recorded for `root` and below.
+    pub fn forget(&self, root: &Path) -> Result<(), HistoryError> {
+        for item in self.db.scan_prefix(Self::key(root)) {
+            let (key, _) = item?;
+            let path = PathBuf::from(String::from_utf8_lossy(&key).into_owned());
+            if path.starts_with(root) {
+                self.db.remove(key)?;
+            }
+        }
+        self.db.flush()?;
+        Ok(())
+    }
+}
@@ -1,3 +1,10 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Locale};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
 pub fn human_bytes(b: impl Into<u128>) -> String {
     let mut n: f64 = b.into() as f64;
     let units = ["B", "KB", "MB", "GB", "TB", "PB"];
@@ -8,3 +15,190 @@ pub fn human_bytes(b: impl Into<u128>) -> String {
     }
     format!("{:.2} {}", n, units[u])
 }
+
+/// [`human_bytes`] with the decimal separator of `locale`, e.g. `1,50 GB`
+/// in Germany.
+pub fn human_bytes_in(b: impl Into<u128>, locale: Locale) -> String {
+    let decimal = pure_rust_locales::locale_match!(locale => LC_NUMERIC::DECIMAL_POINT);
+    human_bytes(b).replacen('.', decimal, 1)
+}
+
+/// A share from 0 to 1 as a percentage rounded to one decimal, the
+/// precision reports and the app show.
+pub fn percent(share: f64) -> f64 {
+    (share * 1000.0).round() / 10.0
+}
+
+/// [`percent`] written out, e.g. `12.5%`.
+pub fn human_percent(share: f64) -> String {
+    format!("{:.1}%", percent(share))
+}
+
+/// Writes `n` with the thousands separator of `locale`, e.g. `1.234.567` in
+/// Germany. Digits are grouped in threes, as in nearly every locale.
+pub fn group_digits(n: impl Into<u128>, locale: Locale) -> String {
+    let digits = n.into().to_string();
+    let separator = pure_rust_locales::locale_match!(locale => LC_NUMERIC::THOUSANDS_SEP);
+    if separator.is_empty() {
+        return digits;
+    }
+    let mut out = String::with_capacity(digits.len() * 2);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            out.push_str(separator);
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// Formats a duration to the nearest second in its two largest units, e.g.
+/// `40s`, `3m 05s` or `2h 10m`.
+pub fn human_duration(d: Duration) -> String {
+    let secs = d.as_secs_f64().round() as u64;
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Parses sizes such as `500`, `10GB`, `1.5 tb` or `200 MiB` into bytes,
+/// using the same 1024-based units as [`human_bytes`].
+pub fn parse_bytes(input: &str) -> Option<u128> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let exponent = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 1,
+        "m" | "mb" | "mib" => 2,
+        "g" | "gb" | "gib" => 3,
+        "t" | "tb" | "tib" => 4,
+        "p" | "pb" | "pib" => 5,
+        _ => return None,
+    };
+    Some((number * 1024f64.powi(exponent)) as u128)
+}
+
+/// How dates and times are shown in the app and written to exports.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DateFormat {
+    /// `2024-05-01 14:03:00`: ISO 8601 with a space, which sorts as text.
+    #[default]
+    Iso8601,
+    /// The system locale's date and time, e.g. `01.05.2024 14:03:00` in
+    /// Germany.
+    Locale,
+    /// Time since then, e.g. `3 days ago`.
+    Relative,
+    /// A [strftime](chrono::format::strftime) pattern such as `%d/%m/%Y`.
+    Custom(String),
+}
+
+impl DateFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DateFormat::Iso8601 => "ISO 8601",
+            DateFormat::Locale => "System locale",
+            DateFormat::Relative => "Relative",
+            DateFormat::Custom(_) => "Custom",
+        }
+    }
+
+    pub fn format(&self, time: SystemTime) -> String {
+        self.format_at(time, SystemTime::now())
+    }
+
+    /// Like [`DateFormat::format`], with relative times measured from `now`.
+    /// An invalid custom pattern falls back to ISO 8601.
+    pub fn format_at(&self, time: SystemTime, now: SystemTime) -> String {
+        self.format_in(time, now, system_locale())
+    }
+
+    /// Like [`DateFormat::format_at`], with [`DateFormat::Locale`] dates
+    /// written for `locale` instead of the system's.
+    pub fn format_in(&self, time: SystemTime, now: SystemTime, locale: Locale) -> String {
+        let local = DateTime::<Local>::from(time);
+        let mut out = String::new();
+        let written = match self {
+            DateFormat::Iso8601 => write!(out, "{}", local.format(ISO_8601)),
+            DateFormat::Locale => write!(out, "{}", local.format_localized("%x %X", locale)),
+            DateFormat::Relative => {
+                out = relative(now.duration_since(time).unwrap_or_default());
+                Ok(())
+            }
+            DateFormat::Custom(pattern) => write!(out, "{}", local.format(pattern)),
+        };
+        if written.is_err() {
+            return local.format(ISO_8601).to_string();
+        }
+        out
+    }
+}
+
+impl std::str::FromStr for DateFormat {
+    type Err = String;
+
+    /// `iso`, `locale`, `relative`, or a strftime pattern containing `%`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "iso" | "iso8601" => Ok(DateFormat::Iso8601),
+            "locale" => Ok(DateFormat::Locale),
+            "relative" => Ok(DateFormat::Relative),
+            _ if s.contains('%') => {
+                if StrftimeItems::new(s).any(|item| item == Item::Error) {
+                    return Err(format!("invalid date pattern {s:?}"));
+                }
+                Ok(DateFormat::Custom(s.to_string()))
+            }
+            _ => Err(format!(
+                "unknown date format {s:?} (expected iso, locale, relative or a strftime pattern)"
+            )),
+        }
+    }
+}
+
+/// Pattern of [`DateFormat::Iso8601`].
+pub const ISO_8601: &str = "%Y-%m-%d %H:%M:%S";
+
+fn relative(age: Duration) -> String {
+    let plural = |n: u64, unit: &str| {
+        if n == 1 {
+            format!("1 {unit} ago")
+        } else {
+            format!("{n} {unit}s ago")
+        }
+    };
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => plural(secs / 60, "minute"),
+        3600..=86_399 => plural(secs / 3600, "hour"),
+        86_400..=2_591_999 => plural(secs / 86_400, "day"),
+        2_592_000..=31_535_999 => plural(secs / 2_592_000, "month"),
+        _ => plural(secs / 31_536_000, "year"),
+    }
+}
+
+/// The locale dates are formatted in for [`DateFormat::Locale`], read
+/// from the system once.
+pub fn system_locale() -> Locale {
+    static LOCALE: OnceLock<Locale> = OnceLock::new();
+    *LOCALE.get_or_init(|| {
+        sys_locale::get_locale()
+            .and_then(|tag| parse_locale(&tag))
+            .unwrap_or(Locale::POSIX)
+    })
+}
+
+/// Reads a locale name such as `de_DE`, `de_DE.UTF-8` or the BCP 47 form
+/// `de-DE`.
+pub fn parse_locale(tag: &str) -> Option<Locale> {
+    // chrono wants POSIX names without the encoding or modifier
+    let tag = tag.split(['.', '@']).next().unwrap_or_default();
+    Locale::try_from(tag.trim().replace('-', "_").as_str()).ok()
+}
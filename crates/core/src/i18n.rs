@@ -0,0 +1,186 @@
+use chrono::Locale;
+
+/// Language a human-readable report is written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Language {
+    #[default]
+    English,
+    German,
+    French,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [
+        Language::English,
+        Language::German,
+        Language::French,
+        Language::Spanish,
+    ];
+
+    /// The language of `locale`, or English when reports can't be written
+    /// in it yet.
+    pub fn of(locale: Locale) -> Language {
+        let name = locale.to_string();
+        match name.split('_').next().unwrap_or_default() {
+            "de" => Language::German,
+            "fr" => Language::French,
+            "es" => Language::Spanish,
+            _ => Language::English,
+        }
+    }
+
+    /// The language's own name for itself.
+    pub fn label(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+            Language::French => "Français",
+            Language::Spanish => "Español",
+        }
+    }
+
+    pub fn text(self) -> &'static ReportText {
+        match self {
+            Language::English => &ENGLISH,
+            Language::German => &GERMAN,
+            Language::French => &FRENCH,
+            Language::Spanish => &SPANISH,
+        }
+    }
+}
+
+/// Words of a report in one language. Column keys of CSV and JSON exports
+/// are not translated, so they can be read back.
+#[derive(Debug)]
+pub struct ReportText {
+    pub title: &'static str,
+    pub size: &'static str,
+    pub files: &'static str,
+    pub folders: &'static str,
+    pub age: &'static str,
+    /// Follows the bytes of a size kept only in the cloud.
+    pub online_only: &'static str,
+    /// Marks a folder the scan could not enter.
+    pub access_denied: &'static str,
+    /// Follows the title of a report on a tree estimated from a sample;
+    /// `{}` stands for the percentage of files sized.
+    pub estimated: &'static str,
+    /// Short names of the [`AgeBucket`](crate::age::AgeBucket)s, in
+    /// [`AgeBucket::ALL`](crate::age::AgeBucket::ALL) order.
+    pub age_buckets: [&'static str; 4],
+    /// Precedes the tags attached to a node.
+    pub tags: &'static str,
+    /// Precedes the note attached to a node.
+    pub note: &'static str,
+    file: &'static str,
+    dir: &'static str,
+    mount: &'static str,
+    symlink: &'static str,
+    archive_entry: &'static str,
+    archive: &'static str,
+    other: &'static str,
+}
+
+impl ReportText {
+    /// Translates an export `kind`, as [`NodeKind::as_str`] gives it.
+    ///
+    /// [`NodeKind::as_str`]: crate::model::NodeKind::as_str
+    pub fn kind<'a>(&self, kind: &'a str) -> &'a str {
+        match kind {
+            "file" => self.file,
+            "dir" => self.dir,
+            "mount" => self.mount,
+            "symlink" => self.symlink,
+            "archive_entry" => self.archive_entry,
+            "archive" => self.archive,
+            "other" => self.other,
+            other => other,
+        }
+    }
+}
+
+static ENGLISH: ReportText = ReportText {
+    title: "TreeSize Export",
+    size: "size",
+    files: "files",
+    folders: "folders",
+    age: "age",
+    online_only: "online-only",
+    access_denied: "access denied",
+    estimated: "estimated from a {}% sample of files",
+    age_buckets: ["0-30d", "30-365d", "1-3y", ">3y"],
+    tags: "tags",
+    note: "note",
+    file: "file",
+    dir: "dir",
+    mount: "mount",
+    symlink: "symlink",
+    archive_entry: "in archive",
+    archive: "archive",
+    other: "special",
+};
+
+static GERMAN: ReportText = ReportText {
+    title: "TreeSize-Export",
+    size: "Größe",
+    files: "Dateien",
+    folders: "Ordner",
+    age: "Alter",
+    online_only: "nur online",
+    access_denied: "Zugriff verweigert",
+    estimated: "geschätzt aus einer Stichprobe von {} % der Dateien",
+    age_buckets: ["0-30 T.", "30-365 T.", "1-3 J.", ">3 J."],
+    tags: "Schlagwörter",
+    note: "Notiz",
+    file: "Datei",
+    dir: "Ordner",
+    mount: "Laufwerk",
+    symlink: "Verknüpfung",
+    archive_entry: "im Archiv",
+    archive: "Archiv",
+    other: "Sonderdatei",
+};
+
+static FRENCH: ReportText = ReportText {
+    title: "Export TreeSize",
+    size: "taille",
+    files: "fichiers",
+    folders: "dossiers",
+    age: "âge",
+    online_only: "en ligne uniquement",
+    access_denied: "accès refusé",
+    estimated: "estimé sur un échantillon de {} % des fichiers",
+    age_buckets: ["0-30 j", "30-365 j", "1-3 a", ">3 a"],
+    tags: "étiquettes",
+    note: "note",
+    file: "fichier",
+    dir: "dossier",
+    mount: "volume",
+    symlink: "lien",
+    archive_entry: "dans l'archive",
+    archive: "archive",
+    other: "fichier spécial",
+};
+
+static SPANISH: ReportText = ReportText {
+    title: "Exportación de TreeSize",
+    size: "tamaño",
+    files: "archivos",
+    folders: "carpetas",
+    age: "antigüedad",
+    online_only: "solo en línea",
+    access_denied: "acceso denegado",
+    estimated: "estimado con una muestra del {} % de los archivos",
+    age_buckets: ["0-30 d", "30-365 d", "1-3 a", ">3 a"],
+    tags: "etiquetas",
+    note: "nota",
+    file: "archivo",
+    dir: "carpeta",
+    mount: "volumen",
+    symlink: "enlace",
+    archive_entry: "en archivo",
+    archive: "archivo comprimido",
+    other: "archivo especial",
+};
@@ -0,0 +1,206 @@
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+
+use crate::model::{NodeId, NodeKind, Tree};
+
+/// File counts above which a folder is reported by [`heavy_dirs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountThresholds {
+    /// Files anywhere below the folder.
+    pub files: u64,
+    /// Entries directly inside the folder, which slow down listing it.
+    pub entries: u64,
+}
+
+impl Default for CountThresholds {
+    fn default() -> Self {
+        Self {
+            files: 100_000,
+            entries: 10_000,
+        }
+    }
+}
+
+/// A folder holding more files than a [`CountThresholds`] allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeavyDir {
+    pub id: NodeId,
+    pub files: u64,
+    pub entries: u64,
+    pub over_files: bool,
+    pub over_entries: bool,
+}
+
+/// Folders over either threshold, most files first. A folder is listed
+/// whenever it is over, even if a folder inside it is too, since both
+/// count against the same inodes.
+pub fn heavy_dirs(tree: &Tree, thresholds: CountThresholds) -> Vec<HeavyDir> {
+    let mut heavy: Vec<HeavyDir> = tree
+        .nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| matches!(node.kind, NodeKind::Dir))
+        .filter_map(|(index, node)| {
+            let entries = node.children.len() as u64;
+            let over_files = node.file_count >= thresholds.files;
+            let over_entries = entries >= thresholds.entries;
+            (over_files || over_entries).then_some(HeavyDir {
+                id: NodeId(index as u32),
+                files: node.file_count,
+                entries,
+                over_files,
+                over_entries,
+            })
+        })
+        .collect();
+    heavy.sort_by_key(|d| std::cmp::Reverse(d.files));
+    heavy
+}
+
+/// Inodes of the filesystem holding a path; each file and folder takes one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct InodeUsage {
+    pub total: u64,
+    /// Inodes the current user can still use.
+    pub free: u64,
+}
+
+impl InodeUsage {
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    /// Used share of the inodes, from 0.0 to 1.0.
+    pub fn used_fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.used() as f64 / self.total as f64) as f32
+    }
+}
+
+/// Inode use of the filesystem holding `path`. Filesystems without a fixed
+/// inode table, such as btrfs, report a total of 0.
+pub fn filesystem_inodes(path: &Path) -> io::Result<InodeUsage> {
+    platform::filesystem_inodes(path)
+}
+
+/// The current user's file count quota on the filesystem holding a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct InodeQuota {
+    pub used: u64,
+    /// Past this, a grace period starts; `None` if not limited.
+    pub soft: Option<u64>,
+    /// No more files can be created past this; `None` if not limited.
+    pub hard: Option<u64>,
+}
+
+/// The current user's inode quota where `path` is, read with `quotactl`.
+/// `Ok(None)` if quotas aren't enabled there; an error of kind
+/// `Unsupported` on systems other than Linux.
+pub fn user_quota(path: &Path) -> io::Result<Option<InodeQuota>> {
+    platform::user_quota(path)
+}
+
+#[cfg(unix)]
+// The statvfs field types vary between platforms
+#[allow(clippy::useless_conversion)]
+fn statvfs_inodes(path: &Path) -> io::Result<InodeUsage> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stat` is only read on success
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        stat.assume_init()
+    };
+    Ok(InodeUsage {
+        total: u64::from(stat.f_files),
+        free: u64::from(stat.f_favail),
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::InodeQuota;
+    use crate::tuning::linux::mount_of;
+    use std::ffi::CString;
+    use std::io;
+    use std::path::Path;
+
+    pub(super) use super::statvfs_inodes as filesystem_inodes;
+
+    pub(super) fn user_quota(path: &Path) -> io::Result<Option<InodeQuota>> {
+        let path = std::fs::canonicalize(path)?;
+        let Some((_, source)) = mount_of(&path) else {
+            return Ok(None);
+        };
+        let device = CString::new(source)?;
+        let mut quota = std::mem::MaybeUninit::<libc::dqblk>::uninit();
+        // SAFETY: `device` is NUL-terminated and `quota` is only read on
+        // success
+        let quota = unsafe {
+            let result = libc::quotactl(
+                libc::QCMD(libc::Q_GETQUOTA, libc::USRQUOTA),
+                device.as_ptr(),
+                libc::getuid() as libc::c_int,
+                quota.as_mut_ptr().cast(),
+            );
+            if result != 0 {
+                let err = io::Error::last_os_error();
+                // Quotas off, not supported there, or no such device
+                return match err.raw_os_error() {
+                    Some(
+                        libc::ESRCH
+                        | libc::ENOSYS
+                        | libc::ENOTBLK
+                        | libc::ENODEV
+                        | libc::EOPNOTSUPP
+                        | libc::ENOENT,
+                    ) => Ok(None),
+                    _ => Err(err),
+                };
+            }
+            quota.assume_init()
+        };
+        let limit = |value: u64| (value > 0).then_some(value);
+        Ok(Some(InodeQuota {
+            used: quota.dqb_curinodes,
+            soft: limit(quota.dqb_isoftlimit),
+            hard: limit(quota.dqb_ihardlimit),
+        }))
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+mod platform {
+    use super::InodeQuota;
+    use std::io;
+    use std::path::Path;
+
+    pub(super) use super::statvfs_inodes as filesystem_inodes;
+
+    pub(super) fn user_quota(_path: &Path) -> io::Result<Option<InodeQuota>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{InodeQuota, InodeUsage};
+    use std::io;
+    use std::path::Path;
+
+    pub(super) fn filesystem_inodes(_path: &Path) -> io::Result<InodeUsage> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn user_quota(_path: &Path) -> io::Result<Option<InodeQuota>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
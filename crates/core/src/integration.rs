@@ -0,0 +1,522 @@
+use std::io;
+use std::path::Path;
+
+/// What the file manager's context menu entry for opening a folder in the
+/// app reads.
+pub const MENU_LABEL: &str = "Analyze with TreeSize RS";
+
+/// Adds the context menu entry for the current user, running `app` with
+/// the folders selected as its arguments, which the app scans right away.
+/// Replaces an entry added before, such as one for a build that has since
+/// moved. Returns where entries were written: registry keys on Windows,
+/// files elsewhere.
+///
+/// - Windows: Explorer's menu for folders, drives and folder backgrounds
+/// - Linux and other Unix: a Nautilus script (under Scripts), a Dolphin
+///   service menu and a Nemo action, whichever of them is in use
+/// - macOS: a Finder Quick Action for folders
+pub fn install(app: &Path) -> io::Result<Vec<String>> {
+    platform::install(app)
+}
+
+/// Removes the entries; doing nothing if they aren't there. Returns where
+/// entries were removed from.
+pub fn uninstall() -> io::Result<Vec<String>> {
+    platform::uninstall()
+}
+
+/// Whether an entry is there for the current user.
+pub fn is_installed() -> bool {
+    platform::is_installed()
+}
+
+/// `arg` as typed into a terminal: PowerShell on Windows and a POSIX shell
+/// elsewhere, which both take single quotes literally. Left as it is when
+/// nothing in it needs quoting.
+pub fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    let escaped = if cfg!(windows) {
+        arg.replace('\'', "''")
+    } else {
+        arg.replace('\'', r"'\''")
+    };
+    format!("'{escaped}'")
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::MENU_LABEL;
+    use std::io;
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+
+    /// Where Explorer looks for verbs, per user so no administrator rights
+    /// are needed, with the argument it fills in with the folder:
+    /// right-clicking a folder or drive, or the background of an open
+    /// folder. Drive roots such as `C:\` are left unquoted, since a
+    /// backslash before the closing quote would escape it and hand the app
+    /// `C:"`; they never hold spaces.
+    const KEYS: [(&str, &str); 3] = [
+        (
+            r"HKCU\Software\Classes\Directory\shell\TreeSizeRS",
+            "\"%1\"",
+        ),
+        (
+            r"HKCU\Software\Classes\Directory\Background\shell\TreeSizeRS",
+            "\"%V\"",
+        ),
+        (r"HKCU\Software\Classes\Drive\shell\TreeSizeRS", "%1"),
+    ];
+
+    pub(super) fn install(app: &Path) -> io::Result<Vec<String>> {
+        let app = app.display();
+        for (key, argument) in KEYS {
+            reg(&["add", key, "/ve", "/d", MENU_LABEL, "/f"])?;
+            reg(&["add", key, "/v", "Icon", "/d", &format!("\"{app}\""), "/f"])?;
+            let command = format!("\"{app}\" {argument}");
+            reg(&[
+                "add",
+                &format!(r"{key}\command"),
+                "/ve",
+                "/d",
+                &command,
+                "/f",
+            ])?;
+        }
+        Ok(KEYS.iter().map(|(key, _)| key.to_string()).collect())
+    }
+
+    pub(super) fn uninstall() -> io::Result<Vec<String>> {
+        let mut removed = Vec::new();
+        for (key, _) in KEYS {
+            if exists(key) {
+                reg(&["delete", key, "/f"])?;
+                removed.push(key.to_string());
+            }
+        }
+        Ok(removed)
+    }
+
+    pub(super) fn is_installed() -> bool {
+        KEYS.iter().any(|(key, _)| exists(key))
+    }
+
+    fn exists(key: &str) -> bool {
+        Command::new("reg")
+            .args(["query", key])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    fn reg(args: &[&str]) -> io::Result<()> {
+        let output = Command::new("reg").args(args).output()?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "reg {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))
+        }
+    }
+}
+
+/// Entries made of files, written to the places file managers read them
+/// from.
+#[cfg(unix)]
+mod files {
+    use std::fs;
+    use std::io;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    use crate::ops;
+
+    pub(super) struct EntryFile {
+        pub(super) path: PathBuf,
+        pub(super) contents: String,
+        /// Nautilus only runs scripts, and Dolphin only service menus,
+        /// that are executable.
+        pub(super) executable: bool,
+    }
+
+    pub(super) fn write(files: &[EntryFile]) -> io::Result<()> {
+        for file in files {
+            if let Some(parent) = file.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&file.path, &file.contents)?;
+            if file.executable {
+                fs::set_permissions(&file.path, fs::Permissions::from_mode(0o755))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes those of `locations` that are there, files or whole
+    /// bundles.
+    pub(super) fn remove(locations: &[PathBuf]) -> io::Result<Vec<String>> {
+        let mut removed = Vec::new();
+        for location in locations {
+            if fs::symlink_metadata(location).is_ok() {
+                ops::remove_path(location).map_err(io::Error::other)?;
+                removed.push(location.display().to_string());
+            }
+        }
+        Ok(removed)
+    }
+
+    /// `text` as one word for `sh`.
+    pub(super) fn sh_quote(text: &str) -> String {
+        format!("'{}'", text.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use super::files::{self, sh_quote, EntryFile};
+    use super::MENU_LABEL;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    pub(super) fn install(app: &Path) -> io::Result<Vec<String>> {
+        let app = app.display().to_string();
+        let [nautilus, dolphin, nemo] = locations()?;
+        // Nautilus runs scripts in the folder shown, with the selection,
+        // if any, one path per line
+        let script = format!(
+            "#!/bin/sh\n\
+             # Added by `treesize-cli integrate install`\n\
+             set -f\n\
+             IFS='\n'\n\
+             if [ -n \"$NAUTILUS_SCRIPT_SELECTED_FILE_PATHS\" ]; then\n\
+             \x20   set -- $NAUTILUS_SCRIPT_SELECTED_FILE_PATHS\n\
+             else\n\
+             \x20   set -- \"$PWD\"\n\
+             fi\n\
+             exec {} \"$@\"\n",
+            sh_quote(&app)
+        );
+        let exec = desktop_quote(&app);
+        let service_menu = format!(
+            "[Desktop Entry]\n\
+             Type=Service\n\
+             MimeType=inode/directory;\n\
+             Actions=analyze;\n\
+             X-KDE-ServiceTypes=KonqPopupMenu/Plugin\n\
+             \n\
+             [Desktop Action analyze]\n\
+             Name={MENU_LABEL}\n\
+             Icon=drive-harddisk\n\
+             Exec={exec} %F\n"
+        );
+        let action = format!(
+            "[Nemo Action]\n\
+             Name={MENU_LABEL}\n\
+             Comment=Show what takes up the space in the selected folders\n\
+             Icon-Name=drive-harddisk\n\
+             Exec={exec} %F\n\
+             Selection=any\n\
+             Extensions=dir;\n"
+        );
+        let entries = [
+            (nautilus, script, true),
+            (dolphin, service_menu, true),
+            (nemo, action, false),
+        ]
+        .map(|(path, contents, executable)| EntryFile {
+            path,
+            contents,
+            executable,
+        });
+        files::write(&entries)?;
+        Ok(entries
+            .iter()
+            .map(|entry| entry.path.display().to_string())
+            .collect())
+    }
+
+    pub(super) fn uninstall() -> io::Result<Vec<String>> {
+        files::remove(&locations()?)
+    }
+
+    pub(super) fn is_installed() -> bool {
+        locations().is_ok_and(|locations| locations.iter().any(|l| l.exists()))
+    }
+
+    /// The Nautilus script, Dolphin service menu and Nemo action.
+    fn locations() -> io::Result<[PathBuf; 3]> {
+        let data = dirs::data_local_dir().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no home folder to install into")
+        })?;
+        Ok([
+            data.join("nautilus").join("scripts").join(MENU_LABEL),
+            data.join("kio")
+                .join("servicemenus")
+                .join("treesize-rs.desktop"),
+            data.join("nemo")
+                .join("actions")
+                .join("treesize-rs.nemo_action"),
+        ])
+    }
+
+    /// `text` as one argument of a desktop entry's `Exec` line.
+    fn desktop_quote(text: &str) -> String {
+        let mut quoted = String::from("\"");
+        for c in text.chars() {
+            match c {
+                '"' | '`' | '$' | '\\' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                '%' => quoted.push_str("%%"),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::files::{self, sh_quote, EntryFile};
+    use super::MENU_LABEL;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    pub(super) fn install(app: &Path) -> io::Result<Vec<String>> {
+        let bundle = location()?;
+        let app_text = sh_quote(&app.display().to_string());
+        // Started in the background, so Finder doesn't wait for the app to
+        // quit; an .app bundle is opened as a new instance
+        let command = if app.extension().is_some_and(|ext| ext == "app") {
+            format!("open -n -a {app_text} --args \"$@\"")
+        } else {
+            format!("nohup {app_text} \"$@\" >/dev/null 2>&1 &")
+        };
+        let contents = bundle.join("Contents");
+        files::write(&[
+            EntryFile {
+                path: contents.join("Info.plist"),
+                contents: info_plist(),
+                executable: false,
+            },
+            EntryFile {
+                path: contents.join("document.wflow"),
+                contents: workflow(&command),
+                executable: false,
+            },
+        ])?;
+        // Has Finder pick up the new service without logging out
+        let _ = Command::new("/System/Library/CoreServices/pbs")
+            .arg("-update")
+            .status();
+        Ok(vec![bundle.display().to_string()])
+    }
+
+    pub(super) fn uninstall() -> io::Result<Vec<String>> {
+        files::remove(&[location()?])
+    }
+
+    pub(super) fn is_installed() -> bool {
+        location().is_ok_and(|bundle| bundle.exists())
+    }
+
+    /// The Quick Action, a bundle in the user's Services folder.
+    fn location() -> io::Result<PathBuf> {
+        let home = dirs::home_dir().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no home folder to install into")
+        })?;
+        Ok(home
+            .join("Library")
+            .join("Services")
+            .join(format!("{MENU_LABEL}.workflow")))
+    }
+
+    fn xml_escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    fn info_plist() -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>NSServices</key>
+	<array>
+		<dict>
+			<key>NSMenuItem</key>
+			<dict>
+				<key>default</key>
+				<string>{MENU_LABEL}</string>
+			</dict>
+			<key>NSMessage</key>
+			<string>runWorkflowAsService</string>
+			<key>NSRequiredContext</key>
+			<dict>
+				<key>NSApplicationIdentifier</key>
+				<string>com.apple.finder</string>
+			</dict>
+			<key>NSSendFileTypes</key>
+			<array>
+				<string>public.folder</string>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#
+        )
+    }
+
+    /// An Automator workflow with a single Run Shell Script action, given
+    /// the folders as arguments.
+    fn workflow(command: &str) -> String {
+        let command = xml_escape(command);
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AMApplicationBuild</key>
+	<string>521</string>
+	<key>AMApplicationVersion</key>
+	<string>2.10</string>
+	<key>AMDocumentVersion</key>
+	<string>2</string>
+	<key>actions</key>
+	<array>
+		<dict>
+			<key>action</key>
+			<dict>
+				<key>AMAccepts</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Optional</key>
+					<true/>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.path</string>
+					</array>
+				</dict>
+				<key>AMActionVersion</key>
+				<string>2.0.3</string>
+				<key>AMApplication</key>
+				<array>
+					<string>Automator</string>
+				</array>
+				<key>AMProvides</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.path</string>
+					</array>
+				</dict>
+				<key>ActionBundlePath</key>
+				<string>/System/Library/Automator/Run Shell Script.action</string>
+				<key>ActionName</key>
+				<string>Run Shell Script</string>
+				<key>ActionParameters</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<string>{command}</string>
+					<key>CheckedForUserDefaultShell</key>
+					<true/>
+					<key>inputMethod</key>
+					<integer>1</integer>
+					<key>shell</key>
+					<string>/bin/sh</string>
+					<key>source</key>
+					<string></string>
+				</dict>
+				<key>BundleIdentifier</key>
+				<string>com.apple.RunShellScript</string>
+				<key>CFBundleVersion</key>
+				<string>2.0.3</string>
+				<key>CanShowSelectedItemsWhenRun</key>
+				<false/>
+				<key>CanShowWhenRun</key>
+				<true/>
+				<key>Category</key>
+				<array>
+					<string>AMCategoryUtilities</string>
+				</array>
+				<key>Class Name</key>
+				<string>RunShellScriptAction</string>
+				<key>InputUUID</key>
+				<string>9A3F3E64-4B8E-4C55-9D2F-7C1D0C6B1A01</string>
+				<key>Keywords</key>
+				<array>
+					<string>Shell</string>
+					<string>Script</string>
+				</array>
+				<key>OutputUUID</key>
+				<string>9A3F3E64-4B8E-4C55-9D2F-7C1D0C6B1A02</string>
+				<key>UUID</key>
+				<string>9A3F3E64-4B8E-4C55-9D2F-7C1D0C6B1A03</string>
+				<key>UnlocalizedApplications</key>
+				<array>
+					<string>Automator</string>
+				</array>
+				<key>arguments</key>
+				<dict/>
+				<key>isViewVisible</key>
+				<integer>1</integer>
+				<key>location</key>
+				<string>309.5:253.0</string>
+				<key>nibPath</key>
+				<string>/System/Library/Automator/Run Shell Script.action/Contents/Resources/Base.lproj/main.nib</string>
+			</dict>
+			<key>isViewVisible</key>
+			<integer>1</integer>
+		</dict>
+	</array>
+	<key>connectors</key>
+	<dict/>
+	<key>workflowMetaData</key>
+	<dict>
+		<key>applicationBundleIDsByPath</key>
+		<dict/>
+		<key>applicationPaths</key>
+		<array/>
+		<key>inputTypeIdentifier</key>
+		<string>com.apple.Automator.fileSystemObject.folder</string>
+		<key>outputTypeIdentifier</key>
+		<string>com.apple.Automator.nothing</string>
+		<key>presentationMode</key>
+		<integer>15</integer>
+		<key>processesInput</key>
+		<false/>
+		<key>serviceInputTypeIdentifier</key>
+		<string>com.apple.Automator.fileSystemObject.folder</string>
+		<key>serviceOutputTypeIdentifier</key>
+		<string>com.apple.Automator.nothing</string>
+		<key>serviceProcessesInput</key>
+		<false/>
+		<key>systemImageName</key>
+		<string>NSActionTemplate</string>
+		<key>useAutomaticInputType</key>
+		<false/>
+		<key>workflowTypeIdentifier</key>
+		<string>com.apple.Automator.servicesMenu</string>
+	</dict>
+</dict>
+</plist>
+"#
+        )
+    }
+}
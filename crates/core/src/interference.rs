@@ -0,0 +1,125 @@
+use std::io;
+use std::time::Duration;
+
+use crate::model::Tree;
+use crate::tuning::StorageKind;
+
+/// Typical time per entry above which local storage is considered throttled.
+/// Directory listings on an unhindered local disk cost microseconds per
+/// entry; on-access scanners opening every file push this into milliseconds.
+pub const SLOW_ENTRY_THRESHOLD: Duration = Duration::from_micros(500);
+/// Entries a scan must have measured before its latency is judged.
+const MIN_ENTRIES: u64 = 2_000;
+/// Directories smaller than this are too noisy to time on their own.
+const MIN_DIR_ENTRIES: u64 = 16;
+/// What an entry costs on a healthy local disk, for estimating overhead.
+const BASELINE_PER_ENTRY: Duration = Duration::from_micros(20);
+
+/// A scan whose per-entry cost points at an on-access (antivirus) scanner
+/// inspecting every file the walker touches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterferenceHint {
+    /// Median time per entry over the directories measured.
+    pub per_entry: Duration,
+    pub entries: u64,
+    /// Rough time the scan lost to the slowdown.
+    pub overhead: Duration,
+}
+
+impl InterferenceHint {
+    pub fn message(&self) -> String {
+        format!(
+            "Scanning took about {:.1?} per file, which usually means an on-access \
+             antivirus scanner is inspecting each one; it cost roughly {:.0?} here. \
+             Excluding this program from real-time scanning can speed scans up.",
+            self.per_entry, self.overhead
+        )
+    }
+}
+
+/// Looks for the latency pattern on-access scanners cause, using the
+/// profile recorded while scanning `tree`. Only Windows scans of local
+/// storage are judged; network shares are slow for reasons of their own.
+pub fn detect(tree: &Tree) -> Option<InterferenceHint> {
+    if !cfg!(windows) || tree.meta.storage == StorageKind::Network {
+        return None;
+    }
+    let profile = tree.meta.profile.as_ref()?;
+    let mut samples: Vec<(Duration, u64)> = profile
+        .dirs
+        .values()
+        .filter(|t| t.entries >= MIN_DIR_ENTRIES)
+        .map(|t| (t.busy.div_f64(t.entries as f64), t.entries))
+        .collect();
+    let entries: u64 = samples.iter().map(|(_, n)| n).sum();
+    if entries < MIN_ENTRIES {
+        return None;
+    }
+    // Weighted median, so one slow directory can't raise the alarm alone
+    samples.sort_unstable();
+    let mut counted = 0;
+    let per_entry = samples
+        .iter()
+        .find(|(_, n)| {
+            counted += n;
+            counted * 2 >= entries
+        })
+        .map(|(latency, _)| *latency)?;
+    if per_entry < SLOW_ENTRY_THRESHOLD {
+        return None;
+    }
+    let total: u64 = profile.dirs.values().map(|t| t.entries).sum();
+    Some(InterferenceHint {
+        per_entry,
+        entries: total,
+        overhead: per_entry
+            .saturating_sub(BASELINE_PER_ENTRY)
+            .mul_f64(total as f64),
+    })
+}
+
+/// PowerShell command that exempts the running executable from Microsoft
+/// Defender real-time scanning. Needs administrator rights and may be
+/// refused by policy or tamper protection.
+pub fn defender_exclusion_command() -> io::Result<String> {
+    let exe = std::env::current_exe()?;
+    Ok(format!(
+        "Add-MpPreference -ExclusionProcess {}",
+        ps_quote(&exe.display().to_string())
+    ))
+}
+
+/// Asks Windows, through a UAC prompt, to run
+/// [`defender_exclusion_command`] elevated, and waits for it. Fails if the
+/// prompt is declined or Defender refuses the exclusion.
+#[cfg(windows)]
+pub fn request_defender_exclusion() -> io::Result<()> {
+    let elevated = format!(
+        "$p = Start-Process powershell -Verb RunAs -Wait -PassThru \
+         -ArgumentList '-NoProfile','-Command',{}; exit $p.ExitCode",
+        ps_quote(&defender_exclusion_command()?)
+    );
+    let status = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-Command", &elevated])
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "adding the Defender exclusion failed ({status})"
+        )))
+    }
+}
+
+#[cfg(not(windows))]
+pub fn request_defender_exclusion() -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Defender exclusions only exist on Windows",
+    ))
+}
+
+/// Quotes `s` as a PowerShell single-quoted string.
+fn ps_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
@@ -0,0 +1,173 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum JournalError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("csv error: {0}")]
+    Csv(#[from] csv::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Delete,
+    Move,
+    Archive,
+    Rename,
+    Clean,
+}
+
+impl Operation {
+    pub fn label(self) -> &'static str {
+        match self {
+            Operation::Delete => "delete",
+            Operation::Move => "move",
+            Operation::Archive => "archive",
+            Operation::Rename => "rename",
+            Operation::Clean => "clean",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "message", rename_all = "lowercase")]
+pub enum Outcome {
+    Ok,
+    Failed(String),
+}
+
+/// One mutating operation, as recorded in the journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub timestamp: DateTime<Local>,
+    pub operation: Operation,
+    pub source: PathBuf,
+    pub destination: Option<PathBuf>,
+    pub bytes: u128,
+    pub outcome: Outcome,
+    /// Which front end performed the operation ("app", "cli", ...).
+    pub origin: String,
+}
+
+impl JournalEntry {
+    pub fn new(operation: Operation, source: &Path, bytes: u128, origin: &str) -> Self {
+        Self {
+            timestamp: Local::now(),
+            operation,
+            source: source.to_path_buf(),
+            destination: None,
+            bytes,
+            outcome: Outcome::Ok,
+            origin: origin.to_string(),
+        }
+    }
+
+    pub fn destination(mut self, destination: &Path) -> Self {
+        self.destination = Some(destination.to_path_buf());
+        self
+    }
+
+    pub fn outcome<T, E: std::fmt::Display>(mut self, result: &Result<T, E>) -> Self {
+        self.outcome = match result {
+            Ok(_) => Outcome::Ok,
+            Err(e) => Outcome::Failed(e.to_string()),
+        };
+        self
+    }
+}
+
+/// Append-only log of mutating operations, stored as one JSON object per line.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Journal in the per-user data directory, shared by the app and CLI.
+    pub fn default_location() -> Self {
+        let base = dirs::data_local_dir().unwrap_or_else(std::env::temp_dir);
+        Self::new(base.join("treesize").join("journal.jsonl"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, entry: &JournalEntry) -> Result<(), JournalError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        // A single write keeps concurrent appenders from interleaving lines.
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads every entry, oldest first. Lines that fail to parse are skipped.
+    pub fn entries(&self) -> Result<Vec<JournalEntry>, JournalError> {
+        let file = match File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if let Ok(entry) = serde_json::from_str(&line) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn export_csv(&self, dest: &Path) -> Result<(), JournalError> {
+        let entries = self.entries()?;
+        let mut writer = csv::Writer::from_writer(BufWriter::new(File::create(dest)?));
+        writer.write_record([
+            "timestamp",
+            "operation",
+            "source",
+            "destination",
+            "size_bytes",
+            "outcome",
+            "origin",
+        ])?;
+        for e in entries {
+            let outcome = match &e.outcome {
+                Outcome::Ok => "ok".to_string(),
+                Outcome::Failed(msg) => format!("failed: {msg}"),
+            };
+            writer.write_record([
+                e.timestamp.to_rfc3339(),
+                e.operation.label().to_string(),
+                e.source.display().to_string(),
+                e.destination
+                    .as_ref()
+                    .map(|d| d.display().to_string())
+                    .unwrap_or_default(),
+                e.bytes.to_string(),
+                outcome,
+                e.origin,
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
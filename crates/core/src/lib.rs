@@ -1,13 +1,83 @@
+//! Disk usage scanning and analysis behind the TreeSize app and CLI.
+//!
+//! Start with [`prelude`], which brings in the scanner and tree types most
+//! embedders need. The items re-exported here and in `prelude`, together
+//! with the public modules below, are the supported API and follow semver:
+//! removing or changing them requires a major version bump.
+//!
+//! - Enums marked `#[non_exhaustive]` ([`ScanMsg`], [`ScanErrorKind`],
+//!   [`ProgressEvent`]) may gain variants in minor releases; match them
+//!   with a wildcard arm.
+//! - [`NodeId`]s index into the [`Tree`] that produced them and are only
+//!   meaningful for that tree; use [`IdRemap`] to carry them across changes.
+//! - Anything not reachable from this crate root is an implementation
+//!   detail and may change at any time.
+
+pub mod advisor;
+pub mod age;
+pub mod annotations;
+#[cfg(feature = "archives")]
+pub mod archives;
+pub mod attrs;
+pub mod backend;
+pub mod budget;
+#[cfg(feature = "cache")]
+pub mod cache;
+pub mod checkpoint;
+pub mod cloud;
+pub mod diff;
+#[cfg(feature = "docker")]
+pub mod docker;
 pub mod export;
+pub mod extstats;
+pub mod handle;
+#[cfg(feature = "history")]
+pub mod history;
 pub mod human;
+pub mod i18n;
+pub mod inodes;
+pub mod integration;
+pub mod interference;
+pub mod journal;
+mod local;
+pub mod mapped;
 pub mod model;
+pub mod ops;
+pub mod overview;
+pub mod owner;
+pub mod prelude;
+pub mod profile;
 pub mod progress;
+pub mod quarantine;
+#[cfg(feature = "s3")]
+pub mod s3;
 pub mod scanner;
+pub mod schedule;
+#[cfg(feature = "scripting")]
+pub mod script;
 pub mod search;
+pub mod share;
+pub mod snapshot;
+pub mod store;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod streams;
 pub mod treemap;
+pub mod tuning;
+pub mod volumes;
+pub mod watch;
+pub mod watchdog;
 
-pub use export::{export_csv, export_json, export_pdf, ExportError};
+pub use backend::{FoundFile, ScanBackend, ScanSink};
+pub use budget::{compare_reports, Budget, Limit, Report, Violation};
+pub use checkpoint::{Checkpoint, CheckpointError};
+pub use export::{export_csv, export_json, export_pdf, ExportError, ExportOptions};
+pub use handle::{ScanControl, ScanHandle};
 
-pub use model::*;
-pub use progress::*;
-pub use scanner::*;
+pub use model::{
+    DirStats, Estimate, IdRemap, NodeId, NodeKind, ScanError, ScanErrorKind, ScanMeta, Tree,
+    TreeNode,
+};
+pub use progress::{Progress, ProgressEvent, ProgressHub, ProgressThrottle};
+pub use scanner::{scan_channel, ScanMsg, ScanOptions, Scanner, SCAN_CHANNEL_CAPACITY};
+pub use snapshot::SnapshotError;
@@ -0,0 +1,870 @@
+//! The walk of a local folder behind [`Scanner::scan`](crate::Scanner::scan),
+//! run as a [`ScanBackend`] like any other source.
+
+use crossbeam_channel::{RecvTimeoutError, Sender};
+use ignore::{DirEntry, WalkBuilder, WalkState};
+use parking_lot::Mutex;
+use std::cell::OnceCell;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::attrs::Attributes;
+use crate::backend::{ScanBackend, ScanSink};
+use crate::checkpoint::Checkpoint;
+use crate::cloud::is_online_only;
+use crate::handle::ScanControl;
+use crate::model::ScanErrorKind;
+use crate::owner::owner_of;
+use crate::profile::{DirTiming, ScanProfile};
+use crate::scanner::{
+    device_id, exclude_override, CachedFile, DirSizeStore, FileEntry, LinkEntry, ScanOptions,
+};
+use crate::streams::alternate_stream_bytes;
+use crate::tuning::{detect_storage, enter_background, ScanTuning, StorageKind};
+
+/// How often the sink is given what the walker threads found, and gets to
+/// report progress while they find nothing.
+const SINK_TICK: Duration = Duration::from_millis(50);
+
+/// Walks a folder of the local filesystem with a pool of threads, honouring
+/// every [`ScanOptions`] field, and optionally reusing file sizes kept by a
+/// [`DirSizeStore`] or skipping what a resumed [`Checkpoint`] completed.
+pub(crate) struct LocalBackend<'a> {
+    options: &'a ScanOptions,
+    storage: StorageKind,
+    store: Option<&'a dyn DirSizeStore>,
+    /// Bytes and files below each directory a resumed checkpoint
+    /// completed, which isn't walked again.
+    skipped: HashMap<PathBuf, (u128, u64)>,
+}
+
+impl<'a> LocalBackend<'a> {
+    pub(crate) fn new(
+        root: &Path,
+        options: &'a ScanOptions,
+        store: Option<&'a dyn DirSizeStore>,
+        resumed: Option<&Checkpoint>,
+    ) -> Self {
+        Self {
+            options,
+            storage: options.storage.unwrap_or_else(|| detect_storage(root)),
+            store,
+            skipped: resumed.map(Checkpoint::subtotals).unwrap_or_default(),
+        }
+    }
+}
+
+impl ScanBackend for LocalBackend<'_> {
+    fn walk(&self, root: &Path, sink: &mut ScanSink<'_>) {
+        let started = Instant::now();
+        let options = self.options;
+        let tuning = options.tuning.resolve(self.storage);
+        // A checkpoint may only claim a directory once all of its files
+        // are in the sink, so they aren't held back in batches
+        let batch_size = if sink.is_checkpointing() {
+            1
+        } else {
+            tuning.batch_size.unwrap_or(1)
+        };
+        if self.store.is_some() {
+            sink.keep_found();
+        }
+
+        let mut builder = WalkBuilder::new(root);
+        // Hidden and .gitignore'd files take up space like any other
+        builder
+            .standard_filters(false)
+            .follow_links(options.follow_links)
+            .threads(tuning.threads.unwrap_or(1));
+        if let Some(excludes) = exclude_override(root, &options.excludes, |e| sink.warn(e)) {
+            builder.overrides(excludes);
+        }
+        let tracker = sink
+            .wants_dir_done()
+            .then(|| Arc::new(DirTracker::default()));
+        if let Some(tracker) = &tracker {
+            // Called for each entry the walker queues while listing its
+            // directory, after exclusions, so nothing is listed twice
+            let tracker = tracker.clone();
+            builder.filter_entry(move |entry| {
+                tracker.counted(entry.path());
+                true
+            });
+        }
+
+        // (device, inode) of every directory or followed link target
+        // entered, with the path it was first reached at, so bind mounts,
+        // junctions and links that lead back to it are walked once
+        let mut visited = HashMap::new();
+        if let Some(key) = file_key(root) {
+            visited.insert(key, root.to_path_buf());
+        }
+        let walk = Walk {
+            root,
+            canonical_root: std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf()),
+            options,
+            control: sink.control(),
+            store: self.store,
+            skipped: &self.skipped,
+            tracker: tracker.as_deref(),
+            root_device: options.one_file_system.then(|| device_id(root)).flatten(),
+            tuning,
+            visited: Mutex::new(visited),
+            dir_mtimes: Mutex::new(HashMap::new()),
+            fresh: Mutex::new(HashMap::new()),
+            profile: Mutex::new(ScanProfile::default()),
+            stream_bytes: Mutex::new(HashMap::new()),
+            lookups: AtomicU64::new(0),
+        };
+
+        let walker = builder.build_parallel();
+        std::thread::scope(|scope| {
+            // Bounded, so a sink held up by a stalled receiver holds up the
+            // walkers too instead of queueing the rest of the tree: at most
+            // two batches per walker thread wait for it
+            let threads = tuning.threads.unwrap_or(1);
+            let (found_tx, found_rx) = crossbeam_channel::bounded(threads * 2);
+            let walk = &walk;
+            scope.spawn(move || {
+                walker.run(|| {
+                    let mut visitor = Visitor::new(walk, found_tx.clone(), batch_size);
+                    Box::new(move |entry| visitor.visit(entry))
+                })
+            });
+            loop {
+                match found_rx.recv_timeout(SINK_TICK) {
+                    Ok(batch) => {
+                        for found in batch {
+                            found.give(sink);
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // Every walker thread has flushed its batch by now
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                sink.tick();
+            }
+        });
+        let cancelled = walk.control.is_cancelled();
+        // Every entry queued has been walked, so nothing should be left
+        // open; report what was seen of any directory that is
+        if let Some(tracker) = tracker.as_deref().filter(|_| !cancelled) {
+            for found in tracker.leftovers() {
+                found.give(sink);
+            }
+        }
+        if let (Some(store), Some(kept)) = (self.store, sink.kept()) {
+            if !cancelled {
+                update_store(
+                    store,
+                    root,
+                    &kept.files,
+                    walk.dir_mtimes.into_inner(),
+                    walk.fresh.into_inner(),
+                );
+            }
+        }
+        let mut profile = walk.profile.into_inner();
+        profile.elapsed = started.elapsed();
+        profile.metadata_lookups = walk.lookups.into_inner();
+        let meta = sink.meta();
+        meta.profile = Some(profile);
+        meta.alternate_streams = walk.stream_bytes.into_inner();
+    }
+
+    fn storage(&self, _root: &Path) -> StorageKind {
+        self.storage
+    }
+
+    #[cfg(feature = "archives")]
+    fn finish(&self, _root: &Path, tree: &mut crate::model::Tree) {
+        if self.options.archives {
+            crate::archives::expand_archives(tree);
+        }
+    }
+}
+
+/// What the walker threads share while walking.
+struct Walk<'a> {
+    root: &'a Path,
+    canonical_root: PathBuf,
+    options: &'a ScanOptions,
+    control: ScanControl,
+    store: Option<&'a dyn DirSizeStore>,
+    skipped: &'a HashMap<PathBuf, (u128, u64)>,
+    tracker: Option<&'a DirTracker>,
+    /// Device of the root, when the walk stays on its filesystem.
+    root_device: Option<u64>,
+    tuning: ScanTuning,
+    visited: Mutex<HashMap<(u64, u64), PathBuf>>,
+    /// Modification time of every directory walked, and the cached file
+    /// sizes of those whose mtime still matches the store.
+    dir_mtimes: Mutex<HashMap<PathBuf, SystemTime>>,
+    fresh: Mutex<HashMap<PathBuf, HashMap<String, CachedFile>>>,
+    profile: Mutex<ScanProfile>,
+    /// Alternate stream bytes of each file examined that has any.
+    stream_bytes: Mutex<HashMap<PathBuf, u64>>,
+    /// Metadata lookups made for walked entries; see [`ScanProfile`].
+    lookups: AtomicU64,
+}
+
+/// Something a walker thread found, passed on to the sink in the order the
+/// thread found it.
+enum Found {
+    Dir(PathBuf, Attributes),
+    File(PathBuf, CachedFile),
+    /// A file left out of the sample, so not examined.
+    Unsampled(PathBuf),
+    Link(LinkEntry),
+    Mount(PathBuf),
+    Special(PathBuf),
+    Error(PathBuf, ScanErrorKind, String),
+    Vanished(PathBuf, String),
+    DirDone {
+        path: PathBuf,
+        bytes: u128,
+        files: u64,
+        dirs: u64,
+    },
+}
+
+impl Found {
+    fn give(self, sink: &mut ScanSink<'_>) {
+        match self {
+            Found::Dir(path, attributes) => sink.dir(path, attributes),
+            Found::File(path, file) => sink.add_file(path, file),
+            Found::Unsampled(path) => sink.unsampled(path),
+            Found::Link((path, size, target)) => sink.symlink(path, size, target),
+            Found::Mount(path) => sink.mount_point(path),
+            Found::Special(path) => sink.special(path),
+            Found::Error(path, kind, message) => sink.error(path, kind, message),
+            Found::Vanished(path, message) => sink.vanished(path, message),
+            Found::DirDone {
+                path,
+                bytes,
+                files,
+                dirs,
+            } => sink.dir_done(path, bytes, files, dirs),
+        }
+    }
+}
+
+/// What a walker thread has found but not yet passed on. It is sent every
+/// `capacity` entries, right away for errors and completed directories, and
+/// when the thread finishes.
+struct Batch {
+    pending: Vec<Found>,
+    capacity: usize,
+    tx: Sender<Vec<Found>>,
+}
+
+impl Batch {
+    fn push(&mut self, found: Found) {
+        let urgent = matches!(found, Found::Error(..) | Found::DirDone { .. });
+        self.pending.push(found);
+        if urgent || self.pending.len() >= self.capacity {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            let batch = std::mem::replace(&mut self.pending, Vec::with_capacity(self.capacity));
+            let _ = self.tx.send(batch);
+        }
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// How a walker thread handled an entry, for the [`DirTracker`].
+#[derive(Default)]
+struct Walked {
+    /// Bytes of a file or link, or below a directory restored from a
+    /// checkpoint.
+    bytes: u128,
+    files: u64,
+    /// A directory whose entries the walker goes on to visit.
+    descend: bool,
+}
+
+/// The visitor of one walker thread.
+struct Visitor<'w> {
+    walk: &'w Walk<'w>,
+    found: Batch,
+    timer: DirTimer<'w>,
+    /// The directory this thread entered last, which it lists once its
+    /// visit returns; settled when the thread visits its next entry or
+    /// stops.
+    listing: Option<PathBuf>,
+    /// Whether to lower the thread's priority on its first visit. The
+    /// walker builds visitors on the calling thread and runs each on a
+    /// worker of its own, so it can't be done sooner.
+    background: bool,
+}
+
+impl<'w> Visitor<'w> {
+    fn new(walk: &'w Walk<'w>, tx: Sender<Vec<Found>>, batch_size: usize) -> Self {
+        Self {
+            walk,
+            found: Batch {
+                pending: Vec::with_capacity(batch_size),
+                capacity: batch_size,
+                tx,
+            },
+            timer: DirTimer {
+                local: HashMap::new(),
+                profile: &walk.profile,
+            },
+            listing: None,
+            background: walk.tuning.background,
+        }
+    }
+
+    fn visit(&mut self, entry: Result<DirEntry, ignore::Error>) -> WalkState {
+        if std::mem::take(&mut self.background) {
+            if let Err(e) = enter_background() {
+                tracing::debug!("could not lower walker priority: {e}");
+            }
+        }
+        let control = &self.walk.control;
+        while control.is_paused() {
+            if control.is_cancelled() {
+                return WalkState::Quit;
+            }
+            sleep(Duration::from_millis(40));
+        }
+        if control.is_cancelled() {
+            return WalkState::Quit;
+        }
+        if let Some(delay) = self.walk.tuning.entry_delay {
+            sleep(delay);
+        }
+        match entry {
+            Ok(ent) => {
+                // This thread finished listing the last directory it
+                // entered before coming here
+                self.settle();
+                let started = Instant::now();
+                let mut walked = Walked::default();
+                let state = self.visit_entry(&ent, &mut walked);
+                if ent.depth() > 0 {
+                    self.timer.record(ent.path(), started.elapsed());
+                }
+                self.walked(ent.path(), walked);
+                state
+            }
+            Err(e) => {
+                self.failed(e);
+                WalkState::Continue
+            }
+        }
+    }
+
+    fn visit_entry(&mut self, ent: &DirEntry, walked: &mut Walked) -> WalkState {
+        let walk = self.walk;
+        let options = walk.options;
+        let path = ent.path();
+        if let Some(&(bytes, files)) = walk.skipped.get(path) {
+            walked.bytes = bytes;
+            walked.files = files;
+            return WalkState::Skip;
+        }
+        // A directory is looked up once, for the mount and alias checks as
+        // well as its attributes
+        let is_dir = ent.file_type().is_some_and(|ft| ft.is_dir());
+        let dir_md = OnceCell::new();
+        let dir_metadata = || {
+            dir_md
+                .get_or_init(|| {
+                    walk.lookups.fetch_add(1, Ordering::Relaxed);
+                    ent.metadata().ok()
+                })
+                .as_ref()
+        };
+        if let Some(root_dev) = walk.root_device {
+            if is_dir
+                && ent.depth() > 0
+                && dir_metadata()
+                    .and_then(|md| entry_device(path, md))
+                    .is_some_and(|dev| dev != root_dev)
+            {
+                self.found.push(Found::Mount(path.to_path_buf()));
+                return WalkState::Skip;
+            }
+        }
+        if ent.path_is_symlink()
+            && !(options.follow_links
+                && claim_link_target(path, &walk.canonical_root, &walk.visited))
+        {
+            walk.lookups.fetch_add(1, Ordering::Relaxed);
+            let size = match std::fs::symlink_metadata(path) {
+                Ok(md) => md.len(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    self.found
+                        .push(Found::Vanished(path.to_path_buf(), e.to_string()));
+                    return WalkState::Skip;
+                }
+                Err(_) => 0,
+            };
+            let target = std::fs::read_link(path).ok();
+            self.found
+                .push(Found::Link((path.to_path_buf(), size, target)));
+            walked.bytes = size as u128;
+            return WalkState::Skip;
+        }
+        if !ent.path_is_symlink() && ent.depth() > 0 && is_dir {
+            // Reached a directory already walked elsewhere, e.g. through a
+            // bind mount or a followed link; kept as an alias of it
+            if let Some(key) = dir_metadata().and_then(|md| entry_key(path, md)) {
+                let first = match walk.visited.lock().entry(key) {
+                    Entry::Occupied(first) => Some(first.get().clone()),
+                    Entry::Vacant(slot) => {
+                        slot.insert(path.to_path_buf());
+                        None
+                    }
+                };
+                if let Some(first) = first {
+                    self.found
+                        .push(Found::Link((path.to_path_buf(), 0, Some(first))));
+                    return WalkState::Skip;
+                }
+            }
+        }
+        if is_dir {
+            if let Some(md) = dir_metadata() {
+                let dir = path.to_path_buf();
+                if let (Some(store), Ok(mtime)) = (walk.store, md.modified()) {
+                    if let Some(sizes) = store.lookup(&dir, mtime) {
+                        walk.fresh.lock().insert(dir.clone(), sizes);
+                    }
+                    walk.dir_mtimes.lock().insert(dir.clone(), mtime);
+                }
+                let attributes = Attributes::of(&dir, md);
+                self.found.push(Found::Dir(dir, attributes));
+            }
+            walked.descend = true;
+            return WalkState::Continue;
+        }
+        let Some(ft) = ent.file_type() else {
+            return WalkState::Continue;
+        };
+        if !options.keeps_extension(path) {
+            return WalkState::Continue;
+        }
+        if !ft.is_file() && !ft.is_symlink() {
+            self.found.push(Found::Special(path.to_path_buf()));
+            return WalkState::Continue;
+        }
+        if !ft.is_file() {
+            return WalkState::Continue;
+        }
+        if !options.is_sampled(path) {
+            // Counted, and left to the sized files beside it to stand in for
+            self.found.push(Found::Unsampled(path.to_path_buf()));
+            walked.files = 1;
+            return WalkState::Continue;
+        }
+        match self.examine(ent) {
+            Ok(file) if file.size < options.min_file_size => {}
+            Ok(file) => {
+                walked.bytes = file.size as u128;
+                walked.files = 1;
+                self.found.push(Found::File(path.to_path_buf(), file));
+            }
+            Err(e) => {
+                let (_, kind) = walk_error_details(&e);
+                let message = walk_error_message(&e);
+                self.found.push(if has_vanished(path, kind) {
+                    Found::Vanished(path.to_path_buf(), message)
+                } else {
+                    Found::Error(path.to_path_buf(), kind, message)
+                });
+                walked.files = 1;
+            }
+        }
+        WalkState::Continue
+    }
+
+    /// Size and details of the file `ent`, from the store when its
+    /// directory hasn't changed.
+    fn examine(&self, ent: &DirEntry) -> Result<CachedFile, ignore::Error> {
+        let walk = self.walk;
+        let path = ent.path();
+        let cached = walk
+            .store
+            .and_then(|_| {
+                let name = path.file_name()?.to_str()?;
+                walk.fresh.lock().get(path.parent()?)?.get(name).cloned()
+            })
+            // Recorded by a scan that didn't look up owners
+            .filter(|file| !walk.options.owners || file.owner.is_some());
+        if let Some(file) = cached {
+            return Ok(file);
+        }
+        walk.lookups.fetch_add(1, Ordering::Relaxed);
+        let md = ent.metadata()?;
+        let mut size = md.len();
+        if walk.options.alternate_streams {
+            match alternate_stream_bytes(path) {
+                Ok(0) => {}
+                Ok(extra) => {
+                    size += extra;
+                    walk.stream_bytes.lock().insert(path.to_path_buf(), extra);
+                }
+                Err(e) => tracing::debug!("could not list streams of {}: {e}", path.display()),
+            }
+        }
+        Ok(CachedFile {
+            size,
+            modified: md.modified().ok(),
+            online: is_online_only(&md),
+            attributes: Attributes::of(path, &md),
+            owner: walk.options.owners.then(|| owner_of(path, &md)).flatten(),
+        })
+    }
+
+    /// Handles an error the walker met instead of an entry.
+    fn failed(&mut self, e: ignore::Error) {
+        let root = self.walk.root;
+        let (path, kind) = walk_error_details(&e);
+        let path = path.map_or_else(|| root.to_path_buf(), Path::to_path_buf);
+        let message = walk_error_message(&e);
+        self.found
+            .push(if path != root && has_vanished(&path, kind) {
+                Found::Vanished(path, message)
+            } else {
+                Found::Error(path, kind, message)
+            });
+    }
+
+    /// Tells the tracker, if any, that this thread is done with `path`.
+    fn walked(&mut self, path: &Path, walked: Walked) {
+        let Some(tracker) = self.walk.tracker else {
+            return;
+        };
+        if walked.descend {
+            tracker.opened(path);
+            self.listing = Some(path.to_path_buf());
+        } else {
+            for done in tracker.leaf(path, walked.bytes, walked.files) {
+                self.found.push(done);
+            }
+        }
+    }
+
+    fn settle(&mut self) {
+        let (Some(tracker), Some(dir)) = (self.walk.tracker, self.listing.take()) else {
+            return;
+        };
+        for done in tracker.listed(&dir) {
+            self.found.push(done);
+        }
+    }
+}
+
+impl Drop for Visitor<'_> {
+    fn drop(&mut self) {
+        self.settle();
+    }
+}
+
+/// Per-directory timings a walker thread has measured, merged into the
+/// shared profile when the thread finishes.
+struct DirTimer<'w> {
+    local: HashMap<PathBuf, DirTiming>,
+    profile: &'w Mutex<ScanProfile>,
+}
+
+impl DirTimer<'_> {
+    /// Charges `busy` to the directory holding `entry`.
+    fn record(&mut self, entry: &Path, busy: Duration) {
+        let Some(dir) = entry.parent() else {
+            return;
+        };
+        let timing = match self.local.get_mut(dir) {
+            Some(timing) => timing,
+            None => self.local.entry(dir.to_path_buf()).or_default(),
+        };
+        timing.entries += 1;
+        timing.busy += busy;
+    }
+}
+
+impl Drop for DirTimer<'_> {
+    fn drop(&mut self) {
+        self.profile.lock().merge(std::mem::take(&mut self.local));
+    }
+}
+
+/// Counts down the entries of every directory still being walked, to tell
+/// the sink once nothing below one is left.
+///
+/// Entries are counted as the walker queues them while listing their
+/// directory, and counted off as they are walked. A directory can't be done
+/// until the thread listing it has finished, which is known once that
+/// thread moves on to another entry.
+#[derive(Default)]
+struct DirTracker {
+    open: Mutex<HashMap<PathBuf, OpenDir>>,
+}
+
+#[derive(Default)]
+struct OpenDir {
+    /// Entries queued and not yet walked; subdirectories count until they
+    /// are done.
+    remaining: u64,
+    /// Whether the walker has finished listing the directory, so no more
+    /// entries are to be queued.
+    listed: bool,
+    bytes: u128,
+    files: u64,
+    dirs: u64,
+}
+
+impl DirTracker {
+    /// Starts counting the entries of `dir`, which the walker is about to
+    /// list.
+    fn opened(&self, dir: &Path) {
+        self.open
+            .lock()
+            .insert(dir.to_path_buf(), OpenDir::default());
+    }
+
+    /// Counts `entry` in its directory as the walker queues it.
+    fn counted(&self, entry: &Path) {
+        let Some(dir) = entry.parent() else {
+            return;
+        };
+        if let Some(state) = self.open.lock().get_mut(dir) {
+            state.remaining += 1;
+        }
+    }
+
+    /// Records that the walker has queued every entry of `dir`. Returns the
+    /// directories this completed.
+    fn listed(&self, dir: &Path) -> Vec<Found> {
+        let mut open = self.open.lock();
+        let mut done = Vec::new();
+        if let Some(state) = open.get_mut(dir) {
+            state.listed = true;
+            if state.remaining == 0 {
+                Self::close(&mut open, dir.to_path_buf(), &mut done);
+            }
+        }
+        done
+    }
+
+    /// Records an entry that has no entries of its own to wait for: a file,
+    /// a link, or a directory that isn't walked.
+    fn leaf(&self, entry: &Path, bytes: u128, files: u64) -> Vec<Found> {
+        let mut done = Vec::new();
+        let Some(dir) = entry.parent() else {
+            return done;
+        };
+        let mut open = self.open.lock();
+        let Some(state) = open.get_mut(dir) else {
+            return done;
+        };
+        state.remaining = state.remaining.saturating_sub(1);
+        state.bytes += bytes;
+        state.files += files;
+        if state.remaining == 0 && state.listed {
+            Self::close(&mut open, dir.to_path_buf(), &mut done);
+        }
+        done
+    }
+
+    /// Directories still waiting for entries once the walk is over,
+    /// innermost first, with whatever was counted in them.
+    fn leftovers(&self) -> Vec<Found> {
+        let mut open = self.open.lock();
+        let mut dirs: Vec<PathBuf> = open.keys().cloned().collect();
+        dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+        let mut done = Vec::new();
+        for dir in dirs {
+            if open.contains_key(&dir) {
+                Self::close(&mut open, dir, &mut done);
+            }
+        }
+        done
+    }
+
+    /// Reports `dir` as done and counts it off its parent, closing the
+    /// parent too if it was the last entry there.
+    fn close(open: &mut HashMap<PathBuf, OpenDir>, dir: PathBuf, done: &mut Vec<Found>) {
+        let mut dir = dir;
+        while let Some(state) = open.remove(&dir) {
+            let parent = dir.parent().map(Path::to_path_buf);
+            done.push(Found::DirDone {
+                path: dir,
+                bytes: state.bytes,
+                files: state.files,
+                dirs: state.dirs,
+            });
+            let Some(up) = parent.as_ref().and_then(|p| open.get_mut(p)) else {
+                return;
+            };
+            up.remaining = up.remaining.saturating_sub(1);
+            up.bytes += state.bytes;
+            up.files += state.files;
+            up.dirs += state.dirs + 1;
+            if up.remaining > 0 || !up.listed {
+                return;
+            }
+            dir = parent.expect("checked above");
+        }
+    }
+}
+
+/// Whether an error of `kind` on `path` came from it being deleted or
+/// renamed after its directory was listed, as opposed to, say, a dangling
+/// link that was followed.
+fn has_vanished(path: &Path, kind: ScanErrorKind) -> bool {
+    kind == ScanErrorKind::NotFound
+        && std::fs::symlink_metadata(path).is_err_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+}
+
+/// The path (if any) and kind of a walk error, looking through the context
+/// layers `ignore` wraps around the underlying error.
+fn walk_error_details(err: &ignore::Error) -> (Option<&Path>, ScanErrorKind) {
+    match err {
+        ignore::Error::WithPath { path, err } => {
+            let (inner, kind) = walk_error_details(err);
+            (inner.or(Some(path)), kind)
+        }
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            walk_error_details(err)
+        }
+        ignore::Error::Loop { child, .. } => (Some(child), ScanErrorKind::Loop),
+        ignore::Error::Io(e) => (None, ScanErrorKind::from_io(e)),
+        ignore::Error::Partial(errs) => errs
+            .first()
+            .map_or((None, ScanErrorKind::Io), walk_error_details),
+        _ => (None, ScanErrorKind::Io),
+    }
+}
+
+/// A walk error without the path `ignore` puts in front, which the sink
+/// adds itself.
+fn walk_error_message(err: &ignore::Error) -> String {
+    match err {
+        ignore::Error::WithPath { err, .. } | ignore::Error::WithDepth { err, .. } => {
+            walk_error_message(err)
+        }
+        err => err.to_string(),
+    }
+}
+
+/// Records the file sizes of every walked directory whose cached entry was
+/// missing or stale.
+fn update_store(
+    store: &dyn DirSizeStore,
+    root: &Path,
+    files: &[FileEntry],
+    dir_mtimes: HashMap<PathBuf, SystemTime>,
+    fresh: HashMap<PathBuf, HashMap<String, CachedFile>>,
+) {
+    let mut by_dir: HashMap<&Path, Vec<(String, CachedFile)>> = HashMap::new();
+    for (path, file) in files {
+        if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+            if !fresh.contains_key(parent) {
+                by_dir
+                    .entry(parent)
+                    .or_default()
+                    .push((name.to_string_lossy().into_owned(), file.clone()));
+            }
+        }
+    }
+    for (dir, mtime) in &dir_mtimes {
+        if !fresh.contains_key(dir) {
+            store.store(
+                dir,
+                *mtime,
+                by_dir.remove(dir.as_path()).unwrap_or_default(),
+            );
+        }
+    }
+    let visited: HashSet<PathBuf> = dir_mtimes.into_keys().collect();
+    store.finish(root, &visited);
+}
+
+/// Decides whether a symlink should be followed: its target must exist, lie
+/// outside the scan root and not have been entered before.
+fn claim_link_target(
+    link: &Path,
+    canonical_root: &Path,
+    visited: &Mutex<HashMap<(u64, u64), PathBuf>>,
+) -> bool {
+    let Ok(target) = std::fs::canonicalize(link) else {
+        return false;
+    };
+    if target.starts_with(canonical_root) {
+        return false;
+    }
+    match file_key(&target) {
+        Some(key) => match visited.lock().entry(key) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(slot) => {
+                slot.insert(link.to_path_buf());
+                true
+            }
+        },
+        None => false,
+    }
+}
+
+/// (device, inode) pair identifying the file `path` resolves to.
+#[cfg(unix)]
+fn file_key(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|md| (md.dev(), md.ino()))
+}
+
+#[cfg(windows)]
+fn file_key(path: &Path) -> Option<(u64, u64)> {
+    let handle = winapi_util::Handle::from_path_any(path).ok()?;
+    winapi_util::file::information(&handle)
+        .ok()
+        .map(|info| (info.volume_serial_number(), info.file_index()))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn file_key(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// [`file_key`] of a walked directory, from its metadata where that holds
+/// the key.
+#[cfg(unix)]
+fn entry_key(_path: &Path, md: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((md.dev(), md.ino()))
+}
+
+#[cfg(not(unix))]
+fn entry_key(path: &Path, _md: &std::fs::Metadata) -> Option<(u64, u64)> {
+    file_key(path)
+}
+
+/// [`device_id`] of a walked directory, from its metadata where that holds
+/// the device.
+#[cfg(unix)]
+fn entry_device(_path: &Path, md: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(md.dev())
+}
+
+#[cfg(not(unix))]
+fn entry_device(path: &Path, _md: &std::fs::Metadata) -> Option<u64> {
+    device_id(path)
+}
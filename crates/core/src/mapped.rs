@@ -0,0 +1,750 @@
+use memmap2::{Mmap, MmapMut};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::model::{Interner, NodeId, NodeKind, NodeSource, ScanMeta, Tree, TreeNode};
+use crate::owner::OwnerId;
+
+#[derive(Debug, Error)]
+pub enum TableError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("not a node table")]
+    NotATable,
+    #[error("written with table version {0}, this build reads version {TABLE_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("node table is damaged: {0}")]
+    Damaged(&'static str),
+}
+
+/// Bumped whenever the layout of the files of a [`NodeTable`] changes.
+pub const TABLE_VERSION: u32 = 2;
+
+/// Written last by [`NodeTableWriter::finish`]; a folder without it holds
+/// no finished table.
+const MANIFEST: &str = "table.json";
+/// One [`RECORD`]-byte record per node, by id.
+const NODES: &str = "nodes.bin";
+/// The names of all nodes back to back, as UTF-8.
+const NAMES: &str = "names.bin";
+/// The ids of every node's children, grouped by parent, as `u32`s; the
+/// index the records point into.
+const CHILDREN: &str = "children.bin";
+/// Bytes per node in [`NODES`].
+const RECORD: usize = 72;
+/// Parent of the root in a record.
+const NO_PARENT: u32 = u32::MAX;
+/// Owner of a node whose owner wasn't looked up.
+const NO_OWNER: u32 = u32::MAX;
+const ONLINE_ONLY: u8 = 1;
+const HAS_MODIFIED: u8 = 2;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    root: PathBuf,
+    nodes: u32,
+    /// Every owner of a node, which records refer to by index.
+    #[serde(default)]
+    owners: Vec<OwnerId>,
+}
+
+/// A tree too large to keep in memory, kept in files mapped into memory
+/// instead: fixed-size records of every node, their names, and an index of
+/// each node's children. The operating system pages in what is read and
+/// drops it again under memory pressure, so a table of hundreds of millions
+/// of nodes can be browsed in a few megabytes of resident memory.
+///
+/// Written node by node with a [`NodeTableWriter`], which is what
+/// [`ScanOptions::spill`](crate::scanner::ScanOptions::spill) does while
+/// scanning. Tables hold names, kinds, sizes, file counts, modification
+/// times, owners and whether files are online-only; attributes and link
+/// targets are left out, and paths are made from names below the root.
+///
+/// Reports that take a [`NodeSource`], such as
+/// [`ext_stats`](crate::extstats::ext_stats) and
+/// [`old_files`](crate::age::old_files), read a table as they read a
+/// [`Tree`].
+pub struct NodeTable {
+    root: PathBuf,
+    len: u32,
+    owners: Vec<OwnerId>,
+    nodes: Mmap,
+    names: Option<Mmap>,
+    children: Option<Mmap>,
+}
+
+impl NodeTable {
+    /// Opens the table written to the folder `dir`. Every record is
+    /// checked, so a damaged table is refused rather than read wrong.
+    pub fn open(dir: &Path) -> Result<Self, TableError> {
+        let manifest: Manifest = match fs::read(dir.join(MANIFEST)) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|_| TableError::NotATable)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(TableError::NotATable),
+            Err(e) => return Err(e.into()),
+        };
+        if manifest.version != TABLE_VERSION {
+            return Err(TableError::UnsupportedVersion(manifest.version));
+        }
+        if manifest.nodes == 0 {
+            return Err(TableError::Damaged("no nodes"));
+        }
+        let nodes = map(&dir.join(NODES))?.ok_or(TableError::Damaged("no nodes"))?;
+        let names = map(&dir.join(NAMES))?;
+        let children = map(&dir.join(CHILDREN))?;
+        let children_len = children.as_ref().map_or(0, |c| c.len());
+        if nodes.len() != manifest.nodes as usize * RECORD
+            || children_len != (manifest.nodes as usize - 1) * 4
+        {
+            return Err(TableError::Damaged("files of the wrong size"));
+        }
+        let table = Self {
+            root: manifest.root,
+            len: manifest.nodes,
+            owners: manifest.owners,
+            nodes,
+            names,
+            children,
+        };
+        table.validate()?;
+        Ok(table)
+    }
+
+    /// Checks that every record only points at nodes, names, children and
+    /// owners within the table, and that parents come before their
+    /// children, so reading the table can neither panic nor loop.
+    fn validate(&self) -> Result<(), TableError> {
+        let len = u64::from(self.len);
+        let names_len = self.names.as_ref().map_or(0, |n| n.len() as u64);
+        for id in 0..self.len {
+            let record = Record::read(&self.nodes, id as usize);
+            let parent_ok = match id {
+                0 => record.parent == NO_PARENT,
+                _ => record.parent < id,
+            };
+            if !parent_ok {
+                return Err(TableError::Damaged("a parent out of order"));
+            }
+            if u64::from(record.children_start) + u64::from(record.children_len) > len - 1 {
+                return Err(TableError::Damaged("children out of range"));
+            }
+            if record
+                .name_offset
+                .saturating_add(u64::from(record.name_len))
+                > names_len
+            {
+                return Err(TableError::Damaged("a name out of range"));
+            }
+            if record.owner != NO_OWNER && record.owner as usize >= self.owners.len() {
+                return Err(TableError::Damaged("an owner out of range"));
+            }
+        }
+        let valid_child = |index| (1..self.len).contains(&self.child(index).0);
+        if !(0..self.len as usize - 1).all(valid_child) {
+            return Err(TableError::Damaged("a child out of range"));
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Always the first node.
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// Path of the scanned folder.
+    pub fn root_path(&self) -> &Path {
+        &self.root
+    }
+
+    /// The node `id`. Panics if there is no such node, like indexing
+    /// [`Tree::nodes`].
+    pub fn node(&self, id: NodeId) -> TableNode<'_> {
+        assert!(
+            id.0 < self.len,
+            "no node {} in a table of {}",
+            id.0,
+            self.len
+        );
+        TableNode {
+            table: self,
+            id,
+            record: Record::read(&self.nodes, id.0 as usize),
+        }
+    }
+
+    /// Every node, by id.
+    pub fn nodes(&self) -> impl Iterator<Item = TableNode<'_>> {
+        (0..self.len).map(|id| self.node(NodeId(id)))
+    }
+
+    /// Full path of the node `id`.
+    pub fn path(&self, id: NodeId) -> PathBuf {
+        let mut names = Vec::new();
+        let mut node = self.node(id);
+        while let Some(parent) = node.parent() {
+            names.push(node.name());
+            node = self.node(parent);
+        }
+        let mut path = self.root.clone();
+        path.extend(names.into_iter().rev());
+        path
+    }
+
+    /// The node at `path`, reading only the folders along the way.
+    pub fn find(&self, path: &Path) -> Option<NodeId> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let mut cur = self.root();
+        for component in relative.components() {
+            let name = component.as_os_str();
+            cur = self
+                .node(cur)
+                .children()
+                .find(|&child| std::ffi::OsStr::new(self.node(child).name()) == name)?;
+        }
+        Some(cur)
+    }
+
+    /// The `n` largest files, largest first, as [`Tree::top_files`] finds
+    /// them; every record is read once, on several threads.
+    pub fn top_files(&self, n: usize) -> Vec<NodeId> {
+        use rayon::prelude::*;
+        use std::cmp::Reverse;
+        use std::collections::BinaryHeap;
+
+        if n == 0 {
+            return Vec::new();
+        }
+        let keep = |mut heap: BinaryHeap<_>, item| {
+            heap.push(item);
+            if heap.len() > n {
+                heap.pop();
+            }
+            heap
+        };
+        let heap = (0..self.len)
+            .into_par_iter()
+            .map(|id| self.node(NodeId(id)))
+            .filter(|node| node.kind().is_file())
+            .map(|node| Reverse((node.size(), Reverse(node.id.0))))
+            .fold(|| BinaryHeap::with_capacity(n + 1), keep)
+            .reduce(BinaryHeap::new, |heap, other| {
+                other.into_iter().fold(heap, keep)
+            });
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((_, Reverse(id)))| NodeId(id))
+            .collect()
+    }
+
+    /// Files of zero bytes, by id, as [`Tree::empty_files`] finds them.
+    pub fn empty_files(&self) -> Vec<NodeId> {
+        self.nodes()
+            .filter(|node| node.kind().is_file() && node.size() == 0)
+            .map(|node| node.id)
+            .collect()
+    }
+
+    /// Folders holding nothing but other empty folders, outermost only, as
+    /// [`Tree::empty_dirs`] finds them. The table doesn't record which
+    /// folders couldn't be read, so those are passed as `unread`.
+    pub fn empty_dirs(&self, unread: &HashSet<NodeId>) -> Vec<NodeId> {
+        let mut empty = vec![true; self.len()];
+        // Children come after their parent, so they are settled first
+        for node in (0..self.len).rev().map(|id| self.node(NodeId(id))) {
+            let own = node.parent().is_some()
+                && matches!(node.kind(), NodeKind::Dir)
+                && !unread.contains(&node.id);
+            let is_empty = own && node.children().all(|c| empty[c.0 as usize]);
+            empty[node.id.0 as usize] = is_empty;
+        }
+        self.nodes()
+            .filter(|node| {
+                empty[node.id.0 as usize] && node.parent().is_some_and(|p| !empty[p.0 as usize])
+            })
+            .map(|node| node.id)
+            .collect()
+    }
+
+    /// The whole table read into a [`Tree`] with the same ids, for tables
+    /// that fit in memory after all.
+    pub fn to_tree(&self) -> Tree {
+        let mut names = Interner::new();
+        let nodes = self
+            .nodes()
+            .map(|node| TreeNode {
+                id: node.id,
+                parent: node.parent(),
+                name: names.intern(node.name()),
+                kind: node.kind(),
+                size: node.size(),
+                file_count: node.file_count(),
+                children: node.children().collect(),
+                modified: node.modified(),
+                link_target: None,
+                online_only: node.online_only(),
+                owner: node.owner().cloned(),
+                attributes: Default::default(),
+            })
+            .collect();
+        Tree {
+            root: self.root(),
+            nodes,
+            errors: Vec::new(),
+            meta: ScanMeta::default(),
+            anchors: [(self.root(), self.root.clone())].into(),
+        }
+    }
+
+    fn name(&self, record: &Record) -> &str {
+        let Some(names) = &self.names else {
+            return "";
+        };
+        let start = record.name_offset as usize;
+        names
+            .get(start..start + record.name_len as usize)
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .unwrap_or_default()
+    }
+
+    fn child(&self, index: usize) -> NodeId {
+        let children = self.children.as_ref().expect("only the root has no parent");
+        NodeId(u32::from_le_bytes(
+            children[index * 4..index * 4 + 4].try_into().unwrap(),
+        ))
+    }
+}
+
+/// A node read from a [`NodeTable`], with the accessors of [`TreeNode`]'s
+/// fields.
+#[derive(Clone, Copy)]
+pub struct TableNode<'a> {
+    table: &'a NodeTable,
+    pub id: NodeId,
+    record: Record,
+}
+
+impl<'a> TableNode<'a> {
+    pub fn parent(&self) -> Option<NodeId> {
+        (self.record.parent != NO_PARENT).then_some(NodeId(self.record.parent))
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.table.name(&self.record)
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        kind_of(self.record.kind)
+    }
+
+    pub fn size(&self) -> u128 {
+        self.record.size
+    }
+
+    pub fn file_count(&self) -> u64 {
+        self.record.file_count
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.record.modified
+    }
+
+    pub fn online_only(&self) -> bool {
+        self.record.online_only
+    }
+
+    pub fn owner(&self) -> Option<&'a OwnerId> {
+        self.table.owners.get(self.record.owner as usize)
+    }
+
+    /// Ids of the children, in the order they were written.
+    pub fn children(&self) -> impl Iterator<Item = NodeId> + 'a {
+        let table = self.table;
+        let start = self.record.children_start as usize;
+        (start..start + self.record.children_len as usize).map(move |index| table.child(index))
+    }
+}
+
+impl NodeSource for NodeTable {
+    fn kind(&self, id: NodeId) -> NodeKind {
+        self.node(id).kind()
+    }
+
+    fn size(&self, id: NodeId) -> u128 {
+        self.node(id).size()
+    }
+
+    fn modified(&self, id: NodeId) -> Option<SystemTime> {
+        self.node(id).modified()
+    }
+
+    fn name(&self, id: NodeId) -> &str {
+        self.node(id).name()
+    }
+
+    fn owner(&self, id: NodeId) -> Option<&OwnerId> {
+        self.node(id).owner()
+    }
+
+    fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.node(id).children()
+    }
+
+    fn path(&self, id: NodeId) -> PathBuf {
+        NodeTable::path(self, id)
+    }
+
+    fn top_files(&self, n: usize) -> Vec<NodeId> {
+        NodeTable::top_files(self, n)
+    }
+}
+
+/// Writes a [`NodeTable`] one node at a time, parents before their
+/// children, keeping nothing of the nodes in memory.
+///
+/// Written to files of a folder of their own; a table already there is
+/// only replaced by [`NodeTableWriter::finish`], so a table that is mapped
+/// elsewhere keeps its contents on systems that allow replacing open files.
+pub struct NodeTableWriter {
+    dir: PathBuf,
+    root: PathBuf,
+    nodes: BufWriter<File>,
+    names: BufWriter<File>,
+    names_len: u64,
+    len: u32,
+    owners: Vec<OwnerId>,
+    /// Index in `owners` of each owner seen so far.
+    owner_ids: HashMap<OwnerId, u32>,
+}
+
+impl NodeTableWriter {
+    /// Starts a table in the folder `dir`, created if need be, for a scan
+    /// of `root`.
+    pub fn create(dir: &Path, root: &Path) -> Result<Self, TableError> {
+        fs::create_dir_all(dir)?;
+        // Read too, as the records are mapped to finish them
+        let create = |name: &str| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(dir.join(partial(name)))
+                .map(BufWriter::new)
+        };
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            root: root.to_path_buf(),
+            nodes: create(NODES)?,
+            names: create(NAMES)?,
+            names_len: 0,
+            len: 0,
+            owners: Vec::new(),
+            owner_ids: HashMap::new(),
+        })
+    }
+
+    /// Nodes written so far.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends `node` below `parent`, which must have been pushed already,
+    /// and returns its id in the table. The first node pushed is the root
+    /// and has no parent; every other node has one. The node's own id,
+    /// parent and children are ignored.
+    pub fn push(&mut self, parent: Option<NodeId>, node: &TreeNode) -> Result<NodeId, TableError> {
+        match parent {
+            None if self.len > 0 => return Err(TableError::Damaged("a second root")),
+            Some(_) if self.len == 0 => return Err(TableError::Damaged("a root with a parent")),
+            Some(parent) if parent.0 >= self.len => {
+                return Err(TableError::Damaged("a parent pushed after its child"))
+            }
+            _ => {}
+        }
+        let id = NodeId(self.len);
+        let name = node.name.as_bytes();
+        let owner = node.owner.as_ref().map_or(NO_OWNER, |owner| {
+            *self.owner_ids.entry(owner.clone()).or_insert_with(|| {
+                self.owners.push(owner.clone());
+                self.owners.len() as u32 - 1
+            })
+        });
+        let record = Record {
+            parent: parent.map_or(NO_PARENT, |p| p.0),
+            kind: kind_code(&node.kind),
+            online_only: node.online_only,
+            name_offset: self.names_len,
+            name_len: name.len() as u32,
+            modified: node.modified,
+            size: node.size,
+            file_count: node.file_count,
+            children_start: 0,
+            children_len: 0,
+            owner,
+        };
+        self.names.write_all(name)?;
+        self.nodes.write_all(&record.encode())?;
+        self.names_len += name.len() as u64;
+        self.len += 1;
+        Ok(id)
+    }
+
+    /// Sets the size and file count of the nodes in `totals`, typically
+    /// folders whose totals weren't known yet when they were pushed, builds
+    /// the index of children and puts the table in place of any table in
+    /// the folder before.
+    pub fn finish(
+        self,
+        totals: impl IntoIterator<Item = (NodeId, u128, u64)>,
+    ) -> Result<NodeTable, TableError> {
+        let nodes = self.nodes.into_inner().map_err(|e| e.into_error())?;
+        self.names.into_inner().map_err(|e| e.into_error())?;
+        let len = self.len as usize;
+        if len == 0 {
+            return Err(TableError::Damaged("no nodes"));
+        }
+        // SAFETY: the files were just created by this writer and aren't
+        // shared; nothing else truncates them while they are mapped
+        let mut records = unsafe { MmapMut::map_mut(&nodes)? };
+        for (id, size, files) in totals {
+            if id.0 < self.len {
+                let mut record = Record::read(&records, id.0 as usize);
+                record.size = size;
+                record.file_count = files;
+                record.write(&mut records, id.0 as usize);
+            }
+        }
+
+        // A counting sort by parent: count the children, turn the counts
+        // into where each group starts, then fill the groups in id order,
+        // using the counts as cursors
+        let parent_of = |records: &[u8], id: usize| {
+            let parent = Record::read(records, id).parent;
+            (parent != NO_PARENT).then_some(parent as usize)
+        };
+        for id in 1..len {
+            if let Some(parent) = parent_of(&records, id) {
+                let mut record = Record::read(&records, parent);
+                record.children_len += 1;
+                record.write(&mut records, parent);
+            }
+        }
+        let mut start = 0;
+        for id in 0..len {
+            let mut record = Record::read(&records, id);
+            record.children_start = start;
+            start += record.children_len;
+            record.children_len = 0;
+            record.write(&mut records, id);
+        }
+        let children = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(self.dir.join(partial(CHILDREN)))?;
+        if len > 1 {
+            children.set_len((len as u64 - 1) * 4)?;
+            // SAFETY: as for the records
+            let mut index = unsafe { MmapMut::map_mut(&children)? };
+            for id in 1..len {
+                let Some(parent) = parent_of(&records, id) else {
+                    continue;
+                };
+                let mut record = Record::read(&records, parent);
+                let slot = (record.children_start + record.children_len) as usize;
+                record.children_len += 1;
+                record.write(&mut records, parent);
+                index[slot * 4..slot * 4 + 4].copy_from_slice(&(id as u32).to_le_bytes());
+            }
+            index.flush()?;
+        }
+        records.flush()?;
+        drop(records);
+
+        let _ = fs::remove_file(self.dir.join(MANIFEST));
+        for name in [NODES, NAMES, CHILDREN] {
+            fs::rename(self.dir.join(partial(name)), self.dir.join(name))?;
+        }
+        let manifest = Manifest {
+            version: TABLE_VERSION,
+            root: self.root,
+            nodes: self.len,
+            owners: self.owners,
+        };
+        fs::write(
+            self.dir.join(MANIFEST),
+            serde_json::to_vec_pretty(&manifest)?,
+        )?;
+        NodeTable::open(&self.dir)
+    }
+}
+
+/// Name a table file is written under until the table is finished.
+fn partial(name: &str) -> String {
+    format!("{name}.partial")
+}
+
+/// `path` mapped read-only; `None` for an empty file, which can't be mapped.
+fn map(path: &Path) -> Result<Option<Mmap>, TableError> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() == 0 {
+        return Ok(None);
+    }
+    // SAFETY: finished tables are never written to again; a new table is
+    // written to other files and renamed over them
+    Ok(Some(unsafe { Mmap::map(&file)? }))
+}
+
+/// A node as laid out in [`NODES`], little-endian:
+///
+/// | bytes | field |
+/// |---|---|
+/// | 0..4 | parent id, or `u32::MAX` for the root |
+/// | 4 | kind |
+/// | 5 | flags: online-only, has a modification time |
+/// | 8..12 | name length |
+/// | 12..16 | nanoseconds of the modification time |
+/// | 16..24 | name offset in [`NAMES`] |
+/// | 24..32 | seconds of the modification time since the Unix epoch |
+/// | 32..48 | size |
+/// | 48..56 | file count |
+/// | 56..60 | first child's index in [`CHILDREN`] |
+/// | 60..64 | number of children |
+/// | 64..68 | owner's index in the manifest, or `u32::MAX` if not known |
+/// | 68..72 | unused |
+#[derive(Clone, Copy)]
+struct Record {
+    parent: u32,
+    /// See [`kind_code`].
+    kind: u8,
+    online_only: bool,
+    name_offset: u64,
+    name_len: u32,
+    modified: Option<SystemTime>,
+    size: u128,
+    file_count: u64,
+    children_start: u32,
+    children_len: u32,
+    owner: u32,
+}
+
+impl Record {
+    /// The record of node `id` in `records`.
+    fn read(records: &[u8], id: usize) -> Self {
+        Self::decode(&records[id * RECORD..(id + 1) * RECORD])
+    }
+
+    fn write(&self, records: &mut [u8], id: usize) {
+        records[id * RECORD..(id + 1) * RECORD].copy_from_slice(&self.encode());
+    }
+
+    fn encode(&self) -> [u8; RECORD] {
+        let mut bytes = [0; RECORD];
+        let (secs, nanos) = self.modified.map_or((0, 0), |modified| {
+            match modified.duration_since(UNIX_EPOCH) {
+                Ok(after) => (after.as_secs() as i64, after.subsec_nanos()),
+                Err(e) => {
+                    // Before the epoch: whole seconds down, nanoseconds up
+                    let before = e.duration();
+                    let secs = -(before.as_secs() as i64);
+                    match before.subsec_nanos() {
+                        0 => (secs, 0),
+                        n => (secs - 1, 1_000_000_000 - n),
+                    }
+                }
+            }
+        });
+        let mut flags = 0;
+        if self.online_only {
+            flags |= ONLINE_ONLY;
+        }
+        if self.modified.is_some() {
+            flags |= HAS_MODIFIED;
+        }
+        bytes[0..4].copy_from_slice(&self.parent.to_le_bytes());
+        bytes[4] = self.kind;
+        bytes[5] = flags;
+        bytes[8..12].copy_from_slice(&self.name_len.to_le_bytes());
+        bytes[12..16].copy_from_slice(&nanos.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.name_offset.to_le_bytes());
+        bytes[24..32].copy_from_slice(&secs.to_le_bytes());
+        bytes[32..48].copy_from_slice(&self.size.to_le_bytes());
+        bytes[48..56].copy_from_slice(&self.file_count.to_le_bytes());
+        bytes[56..60].copy_from_slice(&self.children_start.to_le_bytes());
+        bytes[60..64].copy_from_slice(&self.children_len.to_le_bytes());
+        bytes[64..68].copy_from_slice(&self.owner.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let flags = bytes[5];
+        let modified = (flags & HAS_MODIFIED != 0).then(|| {
+            let secs = u64_at(24) as i64;
+            let nanos = Duration::from_nanos(u64::from(u32_at(12)));
+            if secs >= 0 {
+                UNIX_EPOCH + Duration::from_secs(secs as u64) + nanos
+            } else {
+                UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()) + nanos
+            }
+        });
+        Self {
+            parent: u32_at(0),
+            kind: bytes[4],
+            online_only: flags & ONLINE_ONLY != 0,
+            name_offset: u64_at(16),
+            name_len: u32_at(8),
+            modified,
+            size: u128::from_le_bytes(bytes[32..48].try_into().unwrap()),
+            file_count: u64_at(48),
+            children_start: u32_at(56),
+            children_len: u32_at(60),
+            owner: u32_at(64),
+        }
+    }
+}
+
+fn kind_code(kind: &NodeKind) -> u8 {
+    match kind {
+        NodeKind::File => 0,
+        NodeKind::Dir => 1,
+        NodeKind::MountPoint => 2,
+        NodeKind::Symlink => 3,
+        NodeKind::ArchiveEntry => 4,
+        NodeKind::Archive => 5,
+        NodeKind::Other => 6,
+    }
+}
+
+fn kind_of(code: u8) -> NodeKind {
+    match code {
+        1 => NodeKind::Dir,
+        2 => NodeKind::MountPoint,
+        3 => NodeKind::Symlink,
+        4 => NodeKind::ArchiveEntry,
+        5 => NodeKind::Archive,
+        6 => NodeKind::Other,
+        _ => NodeKind::File,
+    }
+}
@@ -1,13 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub struct NodeId(pub u64);
-
-impl Default for NodeId {
-    fn default() -> Self {
-        NodeId(0)
-    }
-}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub struct NodeId(pub u32);
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct DirStats {
@@ -20,23 +15,1477 @@ pub struct DirStats {
 pub enum NodeKind {
     File,
     Dir,
+    /// A directory on another filesystem that was not descended into.
+    MountPoint,
+    /// A symbolic link that was not followed, or a directory reached again
+    /// through a bind mount, junction or followed link, and counted only
+    /// where it was first walked; see [`TreeNode::link_target`].
+    Symlink,
+    /// A file or folder inside an archive, listed without extracting it;
+    /// see [`crate::archives`]. Its size is uncompressed and not part of
+    /// the sizes above the archive, which count the archive as stored.
+    ArchiveEntry,
+    /// An archive file whose contents are listed below it as
+    /// [`NodeKind::ArchiveEntry`] nodes; otherwise counted like a file, at
+    /// the size it takes on disk.
+    Archive,
+    /// A socket, named pipe, device or other entry that is neither a file,
+    /// a folder nor a link. Listed, but takes no space and isn't counted
+    /// as a file.
+    Other,
 }
 
+impl NodeKind {
+    /// The name reports use for the kind, e.g. `dir`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeKind::File => "file",
+            NodeKind::Dir => "dir",
+            NodeKind::MountPoint => "mount",
+            NodeKind::Symlink => "symlink",
+            NodeKind::ArchiveEntry => "archive_entry",
+            NodeKind::Archive => "archive",
+            NodeKind::Other => "other",
+        }
+    }
+
+    /// Whether the node is a file of the scanned disk, archives included,
+    /// as counted in [`TreeNode::file_count`].
+    pub fn is_file(&self) -> bool {
+        matches!(self, NodeKind::File | NodeKind::Archive)
+    }
+}
+
+/// One file or folder of a [`Tree`]. Nodes hold their name only; see
+/// [`Tree::path`] for the full path.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TreeNode {
     pub id: NodeId,
     pub parent: Option<NodeId>,
-    pub path: std::path::PathBuf,
-    pub name: String,
+    pub name: Name,
     pub kind: NodeKind,
     pub size: u128,
     pub file_count: u64,
     pub children: Vec<NodeId>,
     pub modified: Option<std::time::SystemTime>,
+    /// Where a [`NodeKind::Symlink`] points; for a directory reached twice,
+    /// the path it was first walked at.
+    #[serde(default)]
+    pub link_target: Option<std::path::PathBuf>,
+    /// A cloud placeholder whose content isn't stored locally; see
+    /// [`crate::cloud::is_online_only`].
+    #[serde(default)]
+    pub online_only: bool,
+    /// Who owns a file, when scanned with
+    /// [`ScanOptions::owners`](crate::scanner::ScanOptions::owners).
+    #[serde(default)]
+    pub owner: Option<crate::owner::OwnerId>,
+    /// Attributes and permission bits, as of the scan.
+    #[serde(default)]
+    pub attributes: crate::attrs::Attributes,
+}
+
+impl TreeNode {
+    /// Whether the node's `path` has to be kept, as it can't be derived
+    /// from its name; `path` must lie directly inside the parent's path.
+    pub(crate) fn needs_anchor(&self, path: &std::path::Path) -> bool {
+        self.parent.is_none() || path.file_name() != Some(std::ffi::OsStr::new(&self.name))
+    }
+}
+
+/// A file or folder name, shared by every node with the same name: a scan
+/// of thousands of `Cargo.toml` or `.DS_Store` keeps one copy of each.
+/// Reads as a `str`; names are shared when made by an [`Interner`].
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Name(std::sync::Arc<str>);
+
+impl Name {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The part after the last dot, as [`std::path::Path::extension`]
+    /// finds it, borrowed from the name.
+    pub fn extension(&self) -> Option<&str> {
+        std::path::Path::new(self.as_str())
+            .extension()
+            .and_then(std::ffi::OsStr::to_str)
+    }
+
+    /// Bytes the name takes on the heap, once for all nodes sharing it.
+    fn heap_size(&self) -> usize {
+        // The shared allocation holds the two reference counts too
+        self.0.len() + 2 * std::mem::size_of::<usize>()
+    }
+}
+
+impl Default for Name {
+    fn default() -> Self {
+        Self::from("")
+    }
+}
+
+impl std::ops::Deref for Name {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Name {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<std::ffi::OsStr> for Name {
+    fn as_ref(&self) -> &std::ffi::OsStr {
+        self.as_str().as_ref()
+    }
+}
+
+impl AsRef<std::path::Path> for Name {
+    fn as_ref(&self) -> &std::path::Path {
+        self.as_str().as_ref()
+    }
+}
+
+impl std::borrow::Borrow<str> for Name {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        Self(name.into())
+    }
+}
+
+impl From<String> for Name {
+    fn from(name: String) -> Self {
+        Self(name.into())
+    }
+}
+
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl std::fmt::Debug for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl std::fmt::Display for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Name {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Name::from)
+    }
+}
+
+/// Hands out one shared [`Name`] per distinct name.
+#[derive(Debug, Default)]
+pub struct Interner {
+    names: std::collections::HashSet<Name>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, name: &str) -> Name {
+        if let Some(known) = self.names.get(name) {
+            return known.clone();
+        }
+        let name = Name::from(name);
+        self.names.insert(name.clone());
+        name
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum ScanErrorKind {
+    PermissionDenied,
+    NotFound,
+    /// A symlink cycle.
+    Loop,
+    Io,
+    /// Deleted or renamed while the scan was running, after its directory
+    /// was listed. Not an error as such: the entry is simply left out.
+    Vanished,
+}
+
+impl ScanErrorKind {
+    pub fn from_io(err: &std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::PermissionDenied => ScanErrorKind::PermissionDenied,
+            std::io::ErrorKind::NotFound => ScanErrorKind::NotFound,
+            _ => ScanErrorKind::Io,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ScanErrorKind::PermissionDenied => "access denied",
+            ScanErrorKind::NotFound => "not found",
+            ScanErrorKind::Loop => "symlink loop",
+            ScanErrorKind::Io => "I/O error",
+            ScanErrorKind::Vanished => "vanished during scan",
+        }
+    }
+}
+
+/// A path the scanner could not read, attached to the directory node it
+/// affects (the unreadable directory itself, or a file's parent). Entries
+/// that vanished mid-scan are recorded here too, with
+/// [`ScanErrorKind::Vanished`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanError {
+    pub node: NodeId,
+    pub path: std::path::PathBuf,
+    pub kind: ScanErrorKind,
+    pub message: String,
+}
+
+/// Facts about how a tree was scanned.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ScanMeta {
+    /// Storage the root lives on, as detected or overridden.
+    pub storage: crate::tuning::StorageKind,
+    /// When the scan finished.
+    #[serde(default)]
+    pub scanned_at: Option<chrono::DateTime<chrono::Local>>,
+    /// Where the scan spent its time; only present right after scanning.
+    #[serde(skip)]
+    pub profile: Option<crate::profile::ScanProfile>,
+    /// Bytes in the alternate data streams of every file that has any,
+    /// when [`ScanOptions::alternate_streams`](crate::ScanOptions::alternate_streams)
+    /// was set; only present right after scanning.
+    #[serde(skip)]
+    pub alternate_streams: std::collections::HashMap<std::path::PathBuf, u64>,
+    /// The [`ScanOptions::max_depth`](crate::ScanOptions::max_depth) the
+    /// tree was listed to; folders at that depth hold no nodes of their own.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// How sizes were extrapolated, when only a sample of the files was
+    /// sized; see [`ScanOptions::sample_per_mille`](crate::ScanOptions::sample_per_mille).
+    #[serde(default)]
+    pub estimate: Option<Estimate>,
+    /// Where the tree's files are, when scanned with
+    /// [`ScanOptions::spill`](crate::ScanOptions::spill); the tree itself
+    /// holds folders only.
+    #[serde(default)]
+    pub node_table: Option<std::path::PathBuf>,
+}
+
+/// How the sizes of a tree sized from a random sample of its files were
+/// extrapolated. Each sized file stands in for the files of its folder
+/// that weren't, so folder sizes are estimates while file sizes are exact.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Estimate {
+    /// Share of files that were sized, from 0 to 1.
+    pub fraction: f64,
+    /// Files that were sized.
+    pub sampled: u64,
+    /// Half the width of the 95% confidence interval of each folder's
+    /// size.
+    pub margins: std::collections::HashMap<std::path::PathBuf, u128>,
+}
+
+impl ScanMeta {
+    /// Bytes in alternate data streams at or below `path`, as far as the
+    /// scan recorded them.
+    pub fn alternate_stream_bytes(&self, path: &std::path::Path) -> u64 {
+        self.alternate_streams
+            .iter()
+            .filter(|(file, _)| file.starts_with(path))
+            .map(|(_, bytes)| bytes)
+            .sum()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Tree {
     pub root: NodeId,
     pub nodes: Vec<TreeNode>,
+    /// Errors met while scanning; sizes under these nodes may be too small.
+    #[serde(default)]
+    pub errors: Vec<ScanError>,
+    #[serde(default)]
+    pub meta: ScanMeta,
+    /// Paths of the nodes whose path isn't their parent's joined with their
+    /// name: scan roots, and nodes named other than on disk, such as names
+    /// that aren't valid Unicode.
+    #[serde(default)]
+    pub(crate) anchors: std::collections::HashMap<NodeId, std::path::PathBuf>,
+}
+
+/// Heap and inline bytes held by a [`Tree`]; see [`Tree::memory_usage`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryUsage {
+    /// The node table itself.
+    pub nodes: usize,
+    pub names: usize,
+    /// Child lists.
+    pub children: usize,
+    /// Link targets, stored paths, errors and scan details.
+    pub other: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.nodes + self.names + self.children + self.other
+    }
+}
+
+/// Iterator returned by [`Tree::descendants`].
+pub struct Descendants<'a> {
+    tree: &'a Tree,
+    stack: Vec<NodeId>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.stack.pop()?;
+        let children = &self.tree.node(id).children;
+        self.stack.extend(children.iter().rev().copied());
+        Some(id)
+    }
+}
+
+/// The nodes of a tree kept by [`Tree::filtered`]: those the predicate
+/// matched and the folders above them, with sizes and file counts summed
+/// over what is kept. It holds no borrow of the tree, so it can be kept
+/// next to it; pass the same tree to the methods that need it.
+#[derive(Debug, Clone, Default)]
+pub struct TreeView {
+    matched: Vec<bool>,
+    kept: Vec<bool>,
+    totals: Vec<(u128, u64)>,
+}
+
+impl TreeView {
+    /// Whether the predicate held for `id` itself.
+    pub fn matches(&self, id: NodeId) -> bool {
+        self.matched.get(id.0 as usize).copied().unwrap_or(false)
+    }
+
+    /// Whether `id` is in the view: it matched, or something below it did.
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.kept.get(id.0 as usize).copied().unwrap_or(false)
+    }
+
+    /// Bytes of `id` counting only what the view keeps; 0 outside it.
+    pub fn size(&self, id: NodeId) -> u128 {
+        self.totals.get(id.0 as usize).map_or(0, |&(size, _)| size)
+    }
+
+    /// Files of `id` counting only what the view keeps; 0 outside it.
+    pub fn file_count(&self, id: NodeId) -> u64 {
+        self.totals
+            .get(id.0 as usize)
+            .map_or(0, |&(_, files)| files)
+    }
+
+    /// Number of nodes in the view.
+    pub fn len(&self) -> usize {
+        self.kept.iter().filter(|&&kept| kept).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.kept.contains(&true)
+    }
+
+    /// The children of `id` in `tree` that are in the view.
+    pub fn children<'a>(&'a self, tree: &'a Tree, id: NodeId) -> impl Iterator<Item = NodeId> + 'a {
+        tree.node(id)
+            .children
+            .iter()
+            .copied()
+            .filter(|&child| self.contains(child))
+    }
+
+    /// A copy of `tree` holding only the view, renumbered, with the sizes
+    /// and file counts of the view; for exporting a filtered tree with the
+    /// usual writers. Empty if not even the root is kept.
+    pub fn to_tree(&self, tree: &Tree) -> Tree {
+        if !self.contains(tree.root) {
+            return Tree::default();
+        }
+        let mut pruned = tree.clone();
+        for node in &mut pruned.nodes {
+            (node.size, node.file_count) = self.totals[node.id.0 as usize];
+        }
+        let removed: Vec<bool> = self.kept.iter().map(|&kept| !kept).collect();
+        pruned.compact(&removed);
+        pruned
+    }
+}
+
+/// Files below a folder past which [`Tree::fold_down`] and
+/// [`Tree::fold_up`] hand its children to separate threads.
+const FILES_PER_THREAD: u64 = 16_384;
+
+/// `part` as a fraction of `whole`, or 0 when `whole` is empty. Shares
+/// of sizes go through this, and onto the screen through
+/// [`human_percent`](crate::human::human_percent), so every view rounds
+/// them alike.
+pub fn share(part: u128, whole: u128) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+/// Old-to-new id mapping produced when nodes are removed from a [`Tree`],
+/// or when a tree is matched against a fresh scan of the same root.
+#[derive(Debug, Clone)]
+pub struct IdRemap(Vec<Option<NodeId>>);
+
+impl IdRemap {
+    /// The new id of `old`, or `None` if the node was removed.
+    pub fn get(&self, old: NodeId) -> Option<NodeId> {
+        self.0.get(old.0 as usize).copied().flatten()
+    }
+}
+
+impl Tree {
+    /// Full path of `id`, put together from the names on the way up to the
+    /// nearest node with a stored path. Empty for the synthetic root of a
+    /// combined tree.
+    pub fn path(&self, id: NodeId) -> std::path::PathBuf {
+        let mut names = Vec::new();
+        let mut cur = id;
+        let mut path = loop {
+            if let Some(anchor) = self.anchors.get(&cur) {
+                break anchor.clone();
+            }
+            let node = &self.nodes[cur.0 as usize];
+            match node.parent {
+                Some(parent) => {
+                    names.push(node.name.as_str());
+                    cur = parent;
+                }
+                None => break std::path::PathBuf::new(),
+            }
+        };
+        path.extend(names.iter().rev());
+        path
+    }
+
+    /// Paths of every node, indexed by id, built in one pass down the
+    /// tree; cheaper than [`Tree::path`] per node when most are needed.
+    pub fn paths(&self) -> Vec<std::path::PathBuf> {
+        if self.nodes.is_empty() {
+            return Vec::new();
+        }
+        self.fold_down(self.path(self.root), |parent, node| {
+            match self.anchors.get(&node.id) {
+                Some(anchor) => anchor.clone(),
+                None => parent.join(&node.name),
+            }
+        })
+    }
+
+    /// A value for every node, indexed by id, made from its parent's value
+    /// and the node itself, starting from `root` for [`Tree::root`].
+    /// Subtrees holding many files are walked on several threads. Nodes
+    /// not reachable from the root get `T::default()`.
+    pub fn fold_down<T, F>(&self, root: T, f: F) -> Vec<T>
+    where
+        T: Send + Sync + Default,
+        F: Fn(&T, &TreeNode) -> T + Sync,
+    {
+        use rayon::prelude::*;
+
+        let values: Vec<OnceLock<T>> = (0..self.nodes.len()).map(|_| OnceLock::new()).collect();
+        let value = |id: NodeId| values[id.0 as usize].get().expect("parents come first");
+        let set = |id: NodeId, value: T| {
+            let _ = values[id.0 as usize].set(value);
+        };
+        let from_parent = |node: &TreeNode| node.parent.map(|parent| f(value(parent), node));
+        if !self.nodes.is_empty() {
+            set(self.root, root);
+            let (spine, parts) = self.split_for_threads();
+            for &id in &spine {
+                if let Some(v) = from_parent(&self.nodes[id.0 as usize]) {
+                    set(id, v);
+                }
+            }
+            parts.par_iter().for_each(|&part| {
+                let node = &self.nodes[part.0 as usize];
+                if part != self.root {
+                    set(
+                        part,
+                        from_parent(node).expect("only the root has no parent"),
+                    );
+                }
+                let mut stack = vec![part];
+                while let Some(id) = stack.pop() {
+                    for &child in &self.nodes[id.0 as usize].children {
+                        set(child, f(value(id), &self.nodes[child.0 as usize]));
+                        stack.push(child);
+                    }
+                }
+            });
+        }
+        values
+            .into_par_iter()
+            .map(|value| value.into_inner().unwrap_or_default())
+            .collect()
+    }
+
+    /// A value for every node, indexed by id, made from the node itself and
+    /// each of its children with the child's value, children first; such as
+    /// a total over everything below a folder. Subtrees holding many files
+    /// are folded on several threads. Nodes not reachable from the root get
+    /// `T::default()`.
+    pub fn fold_up<T, F>(&self, f: F) -> Vec<T>
+    where
+        T: Send + Sync + Default,
+        F: Fn(&TreeNode, &mut dyn Iterator<Item = (&TreeNode, &T)>) -> T + Sync,
+    {
+        use rayon::prelude::*;
+
+        let values: Vec<OnceLock<T>> = (0..self.nodes.len()).map(|_| OnceLock::new()).collect();
+        let fold = |id: NodeId| {
+            let node = &self.nodes[id.0 as usize];
+            let mut children = node.children.iter().map(|&child| {
+                let value = values[child.0 as usize].get();
+                (
+                    &self.nodes[child.0 as usize],
+                    value.expect("children come first"),
+                )
+            });
+            let _ = values[id.0 as usize].set(f(node, &mut children));
+        };
+        if !self.nodes.is_empty() {
+            let (spine, parts) = self.split_for_threads();
+            parts.par_iter().for_each(|&part| {
+                // Post-order: a node is folded when it comes up the second
+                // time, after everything below it
+                let mut stack = vec![(part, false)];
+                while let Some((id, children_done)) = stack.pop() {
+                    if children_done {
+                        fold(id);
+                    } else {
+                        stack.push((id, true));
+                        let children = &self.nodes[id.0 as usize].children;
+                        stack.extend(children.iter().map(|&child| (child, false)));
+                    }
+                }
+            });
+            for &id in spine.iter().rev() {
+                fold(id);
+            }
+        }
+        values
+            .into_par_iter()
+            .map(|value| value.into_inner().unwrap_or_default())
+            .collect()
+    }
+
+    /// Splits the tree for [`Tree::fold_down`] and [`Tree::fold_up`]: the
+    /// folders holding too many files for one thread, parents first, and
+    /// the subtrees below them, each small enough for one.
+    fn split_for_threads(&self) -> (Vec<NodeId>, Vec<NodeId>) {
+        let mut spine = Vec::new();
+        let mut parts = Vec::new();
+        let mut stack = vec![self.root];
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id.0 as usize];
+            if node.file_count > FILES_PER_THREAD && !node.children.is_empty() {
+                spine.push(id);
+                stack.extend(node.children.iter().copied());
+            } else {
+                parts.push(id);
+            }
+        }
+        (spine, parts)
+    }
+
+    /// Keeps `path` for `id` if [`Tree::path`] couldn't derive it from the
+    /// node's name, for a node whose path lies directly inside its
+    /// parent's.
+    pub(crate) fn note_path(&mut self, id: NodeId, path: &std::path::Path) {
+        if self.nodes[id.0 as usize].needs_anchor(path) {
+            self.anchors.insert(id, path.to_path_buf());
+        } else {
+            self.anchors.remove(&id);
+        }
+    }
+
+    /// Shares one copy of each distinct name between the nodes, as a scan
+    /// does. Trees read back from a file or report hold a copy per node
+    /// until this is called.
+    pub fn intern_names(&mut self) {
+        let mut names = Interner::new();
+        for node in &mut self.nodes {
+            node.name = names.intern(&node.name);
+        }
+    }
+
+    /// Roughly how much memory the tree takes, counting allocated capacity
+    /// and each shared name once.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        use std::mem::size_of;
+
+        let path_bytes = |p: &std::path::Path| p.as_os_str().len();
+        let mut usage = MemoryUsage {
+            nodes: size_of::<Tree>() + self.nodes.capacity() * size_of::<TreeNode>(),
+            ..MemoryUsage::default()
+        };
+        let mut shared = std::collections::HashSet::new();
+        for node in &self.nodes {
+            if shared.insert(node.name.as_ptr()) {
+                usage.names += node.name.heap_size();
+            }
+            usage.children += node.children.capacity() * size_of::<NodeId>();
+            usage.other += node.link_target.as_deref().map_or(0, path_bytes);
+        }
+        usage.other += self.anchors.capacity()
+            * (size_of::<NodeId>() + size_of::<std::path::PathBuf>())
+            + self.anchors.values().map(|p| path_bytes(p)).sum::<usize>();
+        usage.other += self.errors.capacity() * size_of::<ScanError>()
+            + self
+                .errors
+                .iter()
+                .map(|e| path_bytes(&e.path) + e.message.capacity())
+                .sum::<usize>();
+        usage.other += self
+            .meta
+            .alternate_streams
+            .keys()
+            .chain(self.meta.estimate.iter().flat_map(|e| e.margins.keys()))
+            .map(|p| path_bytes(p) + size_of::<std::path::PathBuf>() + size_of::<u128>())
+            .sum::<usize>();
+        usage
+    }
+
+    /// The `n` largest files, largest first, found with a bounded heap
+    /// rather than by sorting every node.
+    pub fn top_files(&self, n: usize) -> Vec<NodeId> {
+        use rayon::prelude::*;
+        use std::cmp::Reverse;
+        use std::collections::BinaryHeap;
+
+        if n == 0 {
+            return Vec::new();
+        }
+        // Min-heaps on (size, id), one per thread and merged after; ties
+        // favour earlier nodes
+        let keep = |mut heap: BinaryHeap<_>, item| {
+            heap.push(item);
+            if heap.len() > n {
+                heap.pop();
+            }
+            heap
+        };
+        let heap = self
+            .nodes
+            .par_iter()
+            .filter(|node| node.kind.is_file())
+            .map(|node| Reverse((node.size, Reverse(node.id.0))))
+            .fold(|| BinaryHeap::with_capacity(n + 1), keep)
+            .reduce(BinaryHeap::new, |heap, other| {
+                other.into_iter().fold(heap, keep)
+            });
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((_, Reverse(id)))| NodeId(id))
+            .collect()
+    }
+
+    /// Files of zero bytes, in tree order.
+    pub fn empty_files(&self) -> Vec<NodeId> {
+        self.nodes
+            .iter()
+            .filter(|n| n.kind.is_file() && n.size == 0)
+            .map(|n| n.id)
+            .collect()
+    }
+
+    /// Folders holding nothing but other empty folders, in tree order.
+    /// Only the outermost of nested empty folders is listed, so each can
+    /// be removed whole. Scan roots and folders that could not be read
+    /// completely never count as empty.
+    pub fn empty_dirs(&self) -> Vec<NodeId> {
+        let unread: std::collections::HashSet<NodeId> =
+            self.errors.iter().map(|e| e.node).collect();
+        let combined = self.is_combined();
+        let mut empty = vec![false; self.nodes.len()];
+        // Children come after their parent, so they are settled first
+        for (idx, node) in self.nodes.iter().enumerate().rev() {
+            let scan_root = node.parent.is_none() || (combined && node.parent == Some(self.root));
+            empty[idx] = !scan_root
+                && matches!(node.kind, NodeKind::Dir)
+                && !unread.contains(&node.id)
+                && node.children.iter().all(|c| empty[c.0 as usize]);
+        }
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(idx, node)| empty[*idx] && node.parent.is_some_and(|p| !empty[p.0 as usize]))
+            .map(|(_, node)| node.id)
+            .collect()
+    }
+
+    /// Maps every node to the node at the same path in `newer`, typically a
+    /// rescan of the same root, so ids held for this tree can be carried
+    /// over. Nodes whose path is gone, or whose root differs, map to `None`.
+    pub fn remap_to(&self, newer: &Tree) -> IdRemap {
+        let mut map = vec![None; self.nodes.len()];
+        let (Some(old_root), Some(new_root)) = (
+            self.nodes.get(self.root.0 as usize),
+            newer.nodes.get(newer.root.0 as usize),
+        ) else {
+            return IdRemap(map);
+        };
+        if self.path(old_root.id) != newer.path(new_root.id) {
+            return IdRemap(map);
+        }
+        let mut stack = vec![(self.root, newer.root)];
+        while let Some((old, new)) = stack.pop() {
+            map[old.0 as usize] = Some(new);
+            let old_children = &self.nodes[old.0 as usize].children;
+            if old_children.is_empty() {
+                continue;
+            }
+            let by_name: std::collections::HashMap<&str, NodeId> = newer.nodes[new.0 as usize]
+                .children
+                .iter()
+                .map(|c| (newer.nodes[c.0 as usize].name.as_str(), *c))
+                .collect();
+            for child in old_children {
+                if let Some(&matched) = by_name.get(self.nodes[child.0 as usize].name.as_str()) {
+                    stack.push((*child, matched));
+                }
+            }
+        }
+        IdRemap(map)
+    }
+
+    /// Errors attached to `id` itself.
+    pub fn errors_at(&self, id: NodeId) -> impl Iterator<Item = &ScanError> {
+        self.errors.iter().filter(move |e| e.node == id)
+    }
+
+    /// Directories the scan could not enter for lack of permission, so
+    /// whatever they hold is missing from the sizes.
+    pub fn access_denied(&self) -> impl Iterator<Item = &ScanError> {
+        self.errors.iter().filter(|e| {
+            e.kind == ScanErrorKind::PermissionDenied
+                && self.nodes.get(e.node.0 as usize).is_some()
+                && self.path(e.node) == e.path
+        })
+    }
+
+    /// The node at `path`, found by walking down from the root one path
+    /// component at a time.
+    pub fn find(&self, path: &std::path::Path) -> Option<NodeId> {
+        let base = self.scan_root_of(path)?;
+        let base_path = self.path(base);
+        let relative = path.strip_prefix(&base_path).ok()?;
+        let mut cur = base;
+        for component in relative.components() {
+            cur = self.child_named(cur, component.as_os_str())?;
+        }
+        Some(cur)
+    }
+
+    /// A copy of the branch at `id` as a tree of its own, rooted at `id`,
+    /// with the errors recorded within it.
+    pub fn subtree(&self, id: NodeId) -> Option<Tree> {
+        self.nodes.get(id.0 as usize)?;
+        let mut map = vec![None; self.nodes.len()];
+        let mut nodes = Vec::new();
+        let mut stack = vec![id];
+        while let Some(old) = stack.pop() {
+            map[old.0 as usize] = Some(NodeId(nodes.len() as u32));
+            let node = &self.nodes[old.0 as usize];
+            nodes.push(node.clone());
+            stack.extend(node.children.iter().rev().copied());
+        }
+        let remap = IdRemap(map);
+        for node in &mut nodes {
+            node.id = remap.get(node.id).unwrap_or(node.id);
+            // The branch root's parent isn't copied, so it maps to `None`
+            node.parent = node.parent.and_then(|p| remap.get(p));
+            node.children = node.children.iter().filter_map(|c| remap.get(*c)).collect();
+        }
+        let errors = self
+            .errors
+            .iter()
+            .filter_map(|e| {
+                let node = remap.get(e.node)?;
+                Some(ScanError { node, ..e.clone() })
+            })
+            .collect();
+        let mut anchors: std::collections::HashMap<_, _> = self
+            .anchors
+            .iter()
+            .filter_map(|(id, path)| Some((remap.get(*id)?, path.clone())))
+            .collect();
+        anchors.insert(NodeId(0), self.path(id));
+        Some(Tree {
+            root: NodeId(0),
+            nodes,
+            errors,
+            meta: ScanMeta {
+                storage: self.meta.storage,
+                scanned_at: self.meta.scanned_at,
+                max_depth: self
+                    .meta
+                    .max_depth
+                    .map(|max| max.saturating_sub(self.depth(id))),
+                estimate: self.meta.estimate.clone(),
+                ..ScanMeta::default()
+            },
+            anchors,
+        })
+    }
+
+    /// Half the width of the 95% confidence interval of the size of `id`,
+    /// when the tree is an [`Estimate`].
+    pub fn estimate_margin(&self, id: NodeId) -> Option<u128> {
+        let estimate = self.meta.estimate.as_ref()?;
+        self.nodes.get(id.0 as usize)?;
+        Some(estimate.margins.get(&self.path(id)).copied().unwrap_or(0))
+    }
+
+    /// The node a [`NodeKind::Symlink`] at `id` stands for, if it points
+    /// at something inside the tree.
+    pub fn link_target_of(&self, id: NodeId) -> Option<NodeId> {
+        let node = self.nodes.get(id.0 as usize)?;
+        let target = node.link_target.as_ref()?;
+        let target = self.path(id).parent()?.join(target);
+        // Relative targets may climb out of the link's folder first
+        let mut resolved = std::path::PathBuf::new();
+        for component in target.components() {
+            match component {
+                std::path::Component::CurDir => {}
+                std::path::Component::ParentDir => {
+                    resolved.pop();
+                }
+                other => resolved.push(other),
+            }
+        }
+        self.find(&resolved).filter(|found| *found != id)
+    }
+
+    /// Every other path a node can be reached at within the tree, through
+    /// links or bind mounts, none of which count its size again.
+    pub fn aliases_of(&self, id: NodeId) -> Vec<NodeId> {
+        self.nodes
+            .iter()
+            .filter(|n| matches!(n.kind, NodeKind::Symlink))
+            .filter(|n| self.link_target_of(n.id) == Some(id))
+            .map(|n| n.id)
+            .collect()
+    }
+
+    /// Folders that appear more than once in the tree, each with the links
+    /// and bind mounts leading to it; sizes are counted at the folder only.
+    pub fn folder_aliases(&self) -> Vec<(NodeId, Vec<NodeId>)> {
+        let mut aliases: std::collections::HashMap<NodeId, Vec<NodeId>> = Default::default();
+        for node in &self.nodes {
+            if !matches!(node.kind, NodeKind::Symlink) {
+                continue;
+            }
+            let Some(target) = self.link_target_of(node.id) else {
+                continue;
+            };
+            if matches!(self.nodes[target.0 as usize].kind, NodeKind::Dir) {
+                aliases.entry(target).or_default().push(node.id);
+            }
+        }
+        let mut aliases: Vec<_> = aliases.into_iter().collect();
+        aliases.sort_by_key(|(target, _)| target.0);
+        aliases
+    }
+
+    /// The node `id`. Panics if there is no such node, like indexing
+    /// [`Tree::nodes`].
+    pub fn node(&self, id: NodeId) -> &TreeNode {
+        &self.nodes[id.0 as usize]
+    }
+
+    /// `id` itself, then the folders above it up to the root, like
+    /// [`Path::ancestors`](std::path::Path::ancestors).
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(Some(id), |&id| self.node(id).parent)
+    }
+
+    /// Whether `id` is `ancestor` or lies somewhere below it.
+    pub fn is_within(&self, id: NodeId, ancestor: NodeId) -> bool {
+        self.ancestors(id).any(|id| id == ancestor)
+    }
+
+    /// Everything below `id`, not counting `id` itself: depth first, each
+    /// node before its children and siblings in the order they are listed.
+    pub fn descendants(&self, id: NodeId) -> Descendants<'_> {
+        Descendants {
+            tree: self,
+            stack: self.node(id).children.iter().rev().copied().collect(),
+        }
+    }
+
+    /// `id` and everything below it for which `predicate` holds, in the
+    /// order of [`Tree::descendants`].
+    pub fn nodes_where<'a>(
+        &'a self,
+        id: NodeId,
+        mut predicate: impl FnMut(&TreeNode) -> bool + 'a,
+    ) -> impl Iterator<Item = &'a TreeNode> + 'a {
+        std::iter::once(id)
+            .chain(self.descendants(id))
+            .map(|id| self.node(id))
+            .filter(move |node| predicate(node))
+    }
+
+    /// The children of `id`, ordered by `key`; those with equal keys stay
+    /// in the order they are listed. Wrap the key in
+    /// [`Reverse`](std::cmp::Reverse) for largest first.
+    pub fn children_sorted<K: Ord>(
+        &self,
+        id: NodeId,
+        mut key: impl FnMut(&TreeNode) -> K,
+    ) -> Vec<NodeId> {
+        let mut children = self.node(id).children.clone();
+        children.sort_by_key(|&child| key(self.node(child)));
+        children
+    }
+
+    /// How much of its parent's size `id` takes up, from 0 to 1, and 1 for
+    /// the root. Entries listed inside an archive carry their uncompressed
+    /// sizes, so theirs can be more than 1.
+    pub fn share_of_parent(&self, id: NodeId) -> f64 {
+        match self.node(id).parent {
+            Some(parent) => share(self.node(id).size, self.node(parent).size),
+            None => 1.0,
+        }
+    }
+
+    /// How much of the scanned folder's size `id` takes up, from 0 to 1.
+    pub fn share_of_root(&self, id: NodeId) -> f64 {
+        share(self.node(id).size, self.node(self.root).size)
+    }
+
+    /// A view of the nodes `predicate` holds for and the folders above
+    /// them, sized by what it keeps: a folder counts the kept nodes below
+    /// it, anything else, and folders whose contents aren't listed, their
+    /// own size if they match. Use it for searches and size filters, and
+    /// [`TreeView::to_tree`] to export the result.
+    pub fn filtered(&self, predicate: impl Fn(&TreeNode) -> bool + Sync) -> TreeView {
+        use rayon::prelude::*;
+
+        let matched: Vec<bool> = self.nodes.par_iter().map(&predicate).collect();
+        let folded = self.fold_up(|node, children| {
+            let mut kept = matched[node.id.0 as usize];
+            let mut totals = (0, 0);
+            let summed = matches!(node.kind, NodeKind::Dir) && !node.children.is_empty();
+            for (_, &(child_kept, (size, files))) in children {
+                kept |= child_kept;
+                if summed {
+                    totals.0 += size;
+                    totals.1 += files;
+                }
+            }
+            if !summed && matched[node.id.0 as usize] {
+                totals = (node.size, node.file_count);
+            }
+            (kept, totals)
+        });
+        let (kept, totals) = folded.into_par_iter().unzip();
+        TreeView {
+            matched,
+            kept,
+            totals,
+        }
+    }
+
+    /// Like [`Tree::find`], but also takes `path` relative to the scanned
+    /// folder, as users tend to type it.
+    pub fn find_by_path(&self, path: &std::path::Path) -> Option<NodeId> {
+        self.find(path).or_else(|| {
+            path.is_relative()
+                .then(|| self.find(&self.path(self.root).join(path)))
+                .flatten()
+        })
+    }
+
+    /// Levels between the root and `id`; 0 for the root itself.
+    pub fn depth(&self, id: NodeId) -> usize {
+        std::iter::successors(self.nodes.get(id.0 as usize), |node| {
+            node.parent.and_then(|p| self.nodes.get(p.0 as usize))
+        })
+        .count()
+        .saturating_sub(1)
+    }
+
+    /// Whether `id` is a folder at the depth limit of the scan, whose
+    /// contents were counted but not listed. Folders listed since with
+    /// [`Tree::splice_subtree`] no longer are, though the ones at the limit
+    /// of that scan below them are.
+    pub fn is_cut_off(&self, id: NodeId) -> bool {
+        let Some(node) = self.nodes.get(id.0 as usize) else {
+            return false;
+        };
+        matches!(node.kind, NodeKind::Dir)
+            && node.file_count > 0
+            && node.children.is_empty()
+            && self.meta.max_depth.is_some_and(|max| self.depth(id) >= max)
+    }
+
+    /// Whether this tree joins several scan roots; see [`Tree::combine`].
+    pub fn is_combined(&self) -> bool {
+        self.nodes.get(self.root.0 as usize).is_some() && !self.anchors.contains_key(&self.root)
+    }
+
+    /// The scanned root `path` lies under: the tree's root, or for a
+    /// combined tree, the child standing for one of its roots.
+    pub fn scan_root_of(&self, path: &std::path::Path) -> Option<NodeId> {
+        let root = self.nodes.get(self.root.0 as usize)?;
+        if !self.is_combined() {
+            return path.starts_with(self.path(self.root)).then_some(self.root);
+        }
+        root.children
+            .iter()
+            .copied()
+            .find(|c| path.starts_with(self.path(*c)))
+    }
+
+    /// `path` from the scanned folder down, e.g. `photos/2020/a.jpg` for
+    /// `/home/me/photos/2020/a.jpg` in a scan of `/home/me/photos`. Paths
+    /// outside every scan root are returned whole.
+    pub fn relative_path<'a>(&self, path: &'a std::path::Path) -> &'a std::path::Path {
+        let Some(base) = self.scan_root_of(path) else {
+            return path;
+        };
+        let base = self.path(base);
+        match base.parent() {
+            Some(parent) => path.strip_prefix(parent).unwrap_or(path),
+            None => path,
+        }
+    }
+
+    /// Joins separately scanned trees under a synthetic root with an empty
+    /// path, one child per tree. Former roots are named by their full path
+    /// so that roots sharing a final component stay distinguishable.
+    pub fn combine(trees: Vec<Tree>) -> Tree {
+        let mut combined = Tree::default();
+        combined.nodes.push(TreeNode {
+            id: NodeId(0),
+            parent: None,
+            name: Name::default(),
+            kind: NodeKind::Dir,
+            size: 0,
+            file_count: 0,
+            children: Vec::new(),
+            modified: None,
+            link_target: None,
+            online_only: false,
+            owner: None,
+            attributes: crate::attrs::Attributes::default(),
+        });
+        let mut names = Vec::new();
+        let mut storage = None;
+        for tree in trees {
+            let Some(sub_root) = tree.nodes.get(tree.root.0 as usize) else {
+                continue;
+            };
+            let offset = combined.nodes.len() as u32;
+            let shift = |id: NodeId| NodeId(id.0 + offset);
+            let sub_root_path = tree.path(tree.root);
+            names.push(sub_root_path.display().to_string());
+            let top = &mut combined.nodes[0];
+            top.size += sub_root.size;
+            top.file_count += sub_root.file_count;
+            top.children.push(shift(tree.root));
+
+            storage = match storage {
+                None => Some(tree.meta.storage),
+                Some(kind) if kind == tree.meta.storage => Some(kind),
+                Some(_) => Some(crate::tuning::StorageKind::Unknown),
+            };
+            combined.meta.scanned_at = combined.meta.scanned_at.max(tree.meta.scanned_at);
+            if let Some(max) = tree.meta.max_depth {
+                // One level further down, below the synthetic root
+                combined.meta.max_depth = Some(max + 1);
+            }
+            if let Some(estimate) = tree.meta.estimate {
+                let into = combined.meta.estimate.get_or_insert_with(Default::default);
+                into.fraction = estimate.fraction;
+                into.sampled += estimate.sampled;
+                // Roots are sampled independently, so their variances add up
+                let margin = estimate.margins.get(&sub_root_path).copied().unwrap_or(0) as f64;
+                let total = into.margins.entry(std::path::PathBuf::new()).or_default();
+                *total = (*total as f64).hypot(margin) as u128;
+                into.margins.extend(estimate.margins);
+            }
+            combined
+                .meta
+                .alternate_streams
+                .extend(tree.meta.alternate_streams);
+            if let Some(profile) = tree.meta.profile {
+                combined
+                    .meta
+                    .profile
+                    .get_or_insert_with(Default::default)
+                    .append(profile);
+            }
+
+            let sub_root_id = tree.root;
+            combined.nodes.extend(tree.nodes.into_iter().map(|mut n| {
+                if n.id == sub_root_id {
+                    n.name = sub_root_path.display().to_string().into();
+                }
+                n.id = shift(n.id);
+                n.parent = Some(n.parent.map_or(NodeId(0), shift));
+                n.children = n.children.iter().map(|c| shift(*c)).collect();
+                n
+            }));
+            combined.errors.extend(tree.errors.into_iter().map(|mut e| {
+                e.node = shift(e.node);
+                e
+            }));
+            combined
+                .anchors
+                .extend(tree.anchors.into_iter().map(|(id, path)| (shift(id), path)));
+            // Named by its full path now, so it needs its path kept
+            combined.anchors.insert(shift(sub_root_id), sub_root_path);
+        }
+        combined.nodes[0].name = names.join(" + ").into();
+        combined.meta.storage = storage.unwrap_or_default();
+        combined
+    }
+
+    /// Inserts or updates the file at `path`, creating missing directories
+    /// between it and the root and adjusting every ancestor's size and file
+    /// count. Existing ids are left untouched. Returns `None` if `path` is
+    /// outside the tree or an existing node there is not a file.
+    pub fn upsert_file(
+        &mut self,
+        path: &std::path::Path,
+        size: u128,
+        modified: Option<std::time::SystemTime>,
+    ) -> Option<NodeId> {
+        let base = self.scan_root_of(path)?;
+        let base_path = self.path(base);
+        let relative = path.strip_prefix(&base_path).ok()?.to_path_buf();
+        let mut components: Vec<_> = relative.components().collect();
+        let file_name = components.pop()?;
+
+        let mut cur = base;
+        let mut cur_path = base_path;
+        for component in components {
+            cur_path.push(component);
+            cur = match self.child_named(cur, component.as_os_str()) {
+                Some(id) if matches!(self.nodes[id.0 as usize].kind, NodeKind::Dir) => id,
+                Some(_) => return None,
+                None => self.push_child(cur, cur_path.clone(), NodeKind::Dir, 0, None),
+            };
+        }
+
+        let (id, old_size, added) = match self.child_named(cur, file_name.as_os_str()) {
+            Some(id) if matches!(self.nodes[id.0 as usize].kind, NodeKind::File) => {
+                (id, self.nodes[id.0 as usize].size, 0)
+            }
+            Some(_) => return None,
+            None => (
+                self.push_child(cur, path.to_path_buf(), NodeKind::File, 1, modified),
+                0,
+                1,
+            ),
+        };
+        let mut next = Some(id);
+        while let Some(nid) = next {
+            let n = &mut self.nodes[nid.0 as usize];
+            n.size = n.size.saturating_sub(old_size).saturating_add(size);
+            if nid != id {
+                n.file_count += added;
+            }
+            next = n.parent;
+        }
+        self.nodes[id.0 as usize].modified = modified;
+        Some(id)
+    }
+
+    /// Removes the node at `path`, if present; see [`Tree::remove_subtree`].
+    pub fn remove_path(&mut self, path: &std::path::Path) -> Option<IdRemap> {
+        let id = self.find(path)?;
+        self.remove_subtree(id)
+    }
+
+    fn child_named(&self, parent: NodeId, name: &std::ffi::OsStr) -> Option<NodeId> {
+        self.nodes[parent.0 as usize]
+            .children
+            .iter()
+            .copied()
+            .find(|c| std::ffi::OsStr::new(&self.nodes[c.0 as usize].name) == name)
+    }
+
+    /// Appends an empty node under `parent`. Sizes are left for the caller
+    /// to propagate.
+    fn push_child(
+        &mut self,
+        parent: NodeId,
+        path: std::path::PathBuf,
+        kind: NodeKind,
+        file_count: u64,
+        modified: Option<std::time::SystemTime>,
+    ) -> NodeId {
+        let id = NodeId(self.nodes.len() as u32);
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.nodes.push(TreeNode {
+            id,
+            parent: Some(parent),
+            name: name.into(),
+            kind,
+            size: 0,
+            file_count,
+            children: Vec::new(),
+            modified,
+            link_target: None,
+            online_only: false,
+            owner: None,
+            attributes: crate::attrs::Attributes::default(),
+        });
+        self.nodes[parent.0 as usize].children.push(id);
+        self.note_path(id, &path);
+        id
+    }
+
+    /// Removes `id` and all of its descendants, subtracting their size and
+    /// file count from every ancestor. Remaining nodes are compacted, so any
+    /// ids held elsewhere must be translated through the returned remap.
+    /// The root cannot be removed.
+    pub fn remove_subtree(&mut self, id: NodeId) -> Option<IdRemap> {
+        let node = self.nodes.get(id.0 as usize)?;
+        let parent = node.parent?;
+        let (size, files) = (node.size, node.file_count);
+
+        self.nodes[parent.0 as usize].children.retain(|c| *c != id);
+        let mut cur = Some(parent);
+        while let Some(pid) = cur {
+            let p = &mut self.nodes[pid.0 as usize];
+            p.size = p.size.saturating_sub(size);
+            p.file_count = p.file_count.saturating_sub(files);
+            cur = p.parent;
+        }
+
+        let mut removed = vec![false; self.nodes.len()];
+        self.mark_subtree(id, &mut removed);
+        Some(self.compact(&removed))
+    }
+
+    /// Replaces the contents of directory `id` with `subtree`, a fresh scan
+    /// of the same path (e.g. from [`crate::Scanner::scan_subtree`]), and
+    /// adjusts every ancestor by the size and file count difference. The
+    /// node keeps its place in the tree; previous descendants are dropped,
+    /// so ids held elsewhere must be translated through the returned remap.
+    pub fn splice_subtree(&mut self, id: NodeId, subtree: Tree) -> Option<IdRemap> {
+        let node = self.nodes.get(id.0 as usize)?;
+        let sub_root = subtree.nodes.get(subtree.root.0 as usize)?;
+        let (old_size, old_files) = (node.size, node.file_count);
+        let (new_size, new_files) = (sub_root.size, sub_root.file_count);
+
+        let mut cur = Some(id);
+        while let Some(nid) = cur {
+            let n = &mut self.nodes[nid.0 as usize];
+            n.size = n.size.saturating_sub(old_size).saturating_add(new_size);
+            n.file_count = n.file_count.saturating_sub(old_files) + new_files;
+            cur = n.parent;
+        }
+
+        let mut removed = vec![false; self.nodes.len()];
+        for child in std::mem::take(&mut self.nodes[id.0 as usize].children) {
+            self.mark_subtree(child, &mut removed);
+        }
+        let remap = self.compact(&removed);
+        let target = remap.get(id)?;
+
+        // Append the scanned descendants after the surviving nodes, with the
+        // subtree root standing in for `target`.
+        let offset = self.nodes.len() as u32;
+        let sub_root_id = subtree.root;
+        let translate = |old: NodeId| -> NodeId {
+            if old == sub_root_id {
+                target
+            } else if old.0 < sub_root_id.0 {
+                NodeId(old.0 + offset)
+            } else {
+                NodeId(old.0 - 1 + offset)
+            }
+        };
+        let mut sub_nodes = subtree.nodes;
+        let root_node = sub_nodes.remove(sub_root_id.0 as usize);
+        let dest = &mut self.nodes[target.0 as usize];
+        dest.children = root_node.children.iter().map(|c| translate(*c)).collect();
+        dest.modified = root_node.modified;
+        self.nodes.extend(sub_nodes.into_iter().map(|mut n| {
+            n.id = translate(n.id);
+            n.parent = n.parent.map(translate);
+            n.children = n.children.iter().map(|c| translate(*c)).collect();
+            n
+        }));
+        self.anchors.extend(
+            subtree
+                .anchors
+                .into_iter()
+                .filter(|(id, _)| *id != sub_root_id)
+                .map(|(id, path)| (translate(id), path)),
+        );
+        self.errors.retain(|e| e.node != target);
+        self.errors.extend(subtree.errors.into_iter().map(|mut e| {
+            e.node = translate(e.node);
+            e
+        }));
+        Some(remap)
+    }
+
+    fn mark_subtree(&self, id: NodeId, marks: &mut [bool]) {
+        let mut stack = vec![id];
+        while let Some(n) = stack.pop() {
+            marks[n.0 as usize] = true;
+            stack.extend(self.nodes[n.0 as usize].children.iter().copied());
+        }
+    }
+
+    /// Drops every node flagged in `removed` and renumbers the rest.
+    fn compact(&mut self, removed: &[bool]) -> IdRemap {
+        let mut map = vec![None; self.nodes.len()];
+        let mut next = 0u32;
+        for (idx, gone) in removed.iter().enumerate() {
+            if !gone {
+                map[idx] = Some(NodeId(next));
+                next += 1;
+            }
+        }
+        let remap = IdRemap(map);
+
+        let old = std::mem::take(&mut self.nodes);
+        self.nodes = old
+            .into_iter()
+            .filter_map(|mut n| {
+                n.id = remap.get(n.id)?;
+                n.parent = n.parent.and_then(|p| remap.get(p));
+                n.children = n.children.iter().filter_map(|c| remap.get(*c)).collect();
+                Some(n)
+            })
+            .collect();
+        self.root = remap.get(self.root).unwrap_or_default();
+        self.anchors = std::mem::take(&mut self.anchors)
+            .into_iter()
+            .filter_map(|(id, path)| Some((remap.get(id)?, path)))
+            .collect();
+        self.errors.retain_mut(|e| match remap.get(e.node) {
+            Some(id) => {
+                e.node = id;
+                true
+            }
+            None => false,
+        });
+        remap
+    }
+}
+
+/// Read access to the nodes of a scan, whether they are held in a [`Tree`]
+/// or in a [`NodeTable`](crate::mapped::NodeTable) too large for memory,
+/// so reports such as [`ext_stats`](crate::extstats::ext_stats) work from
+/// either.
+pub trait NodeSource: Sync {
+    fn kind(&self, id: NodeId) -> NodeKind;
+    fn size(&self, id: NodeId) -> u128;
+    fn modified(&self, id: NodeId) -> Option<std::time::SystemTime>;
+    fn name(&self, id: NodeId) -> &str;
+    /// Who owns the file `id`, when the scan looked owners up.
+    fn owner(&self, id: NodeId) -> Option<&crate::owner::OwnerId>;
+    fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_;
+    /// Full path of `id`.
+    fn path(&self, id: NodeId) -> std::path::PathBuf;
+    /// The `n` largest files, largest first.
+    fn top_files(&self, n: usize) -> Vec<NodeId>;
+
+    /// Files and archives in or below `id`, looking inside folders but not
+    /// mount points, links or archives.
+    fn files_below(&self, id: NodeId) -> Vec<NodeId> {
+        let mut files = Vec::new();
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            match self.kind(id) {
+                NodeKind::File | NodeKind::Archive => files.push(id),
+                NodeKind::Dir => stack.extend(self.children(id)),
+                NodeKind::MountPoint
+                | NodeKind::Symlink
+                | NodeKind::ArchiveEntry
+                | NodeKind::Other => {}
+            }
+        }
+        files
+    }
+}
+
+impl NodeSource for Tree {
+    fn kind(&self, id: NodeId) -> NodeKind {
+        self.node(id).kind.clone()
+    }
+
+    fn size(&self, id: NodeId) -> u128 {
+        self.node(id).size
+    }
+
+    fn modified(&self, id: NodeId) -> Option<std::time::SystemTime> {
+        self.node(id).modified
+    }
+
+    fn name(&self, id: NodeId) -> &str {
+        self.node(id).name.as_str()
+    }
+
+    fn owner(&self, id: NodeId) -> Option<&crate::owner::OwnerId> {
+        self.node(id).owner.as_ref()
+    }
+
+    fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.node(id).children.iter().copied()
+    }
+
+    fn path(&self, id: NodeId) -> std::path::PathBuf {
+        Tree::path(self, id)
+    }
+
+    fn top_files(&self, n: usize) -> Vec<NodeId> {
+        Tree::top_files(self, n)
+    }
 }
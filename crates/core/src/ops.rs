@@ -0,0 +1,343 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+use crate::model::{NodeId, NodeKind, Tree};
+
+#[derive(Debug, Error)]
+pub enum OpError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("walk error: {0}")]
+    Walk(#[from] walkdir::Error),
+    #[error("{} is not inside the scan root {}", .0.display(), .1.display())]
+    OutsideRoot(PathBuf, PathBuf),
+    #[error("destination already exists: {}", .0.display())]
+    DestinationExists(PathBuf),
+    #[error("checksum mismatch after copying {}", .0.display())]
+    VerificationFailed(PathBuf),
+    #[error("invalid pattern: {0}")]
+    Pattern(#[from] ignore::Error),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransferOptions {
+    /// Hash every copied file and compare it with the source before the
+    /// source is removed.
+    pub verify: bool,
+}
+
+/// Per-file progress of a copy or move.
+#[derive(Debug, Clone, Default)]
+pub struct TransferProgress {
+    pub current: PathBuf,
+    pub files_done: u64,
+    pub files_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+/// Where `src` ends up when archived:
+/// `archive_root/<scan root name>/<src relative to scan_root>`.
+pub fn archive_destination(
+    src: &Path,
+    scan_root: &Path,
+    archive_root: &Path,
+) -> Result<PathBuf, OpError> {
+    let relative = src
+        .strip_prefix(scan_root)
+        .map_err(|_| OpError::OutsideRoot(src.to_path_buf(), scan_root.to_path_buf()))?;
+    let base = scan_root
+        .file_name()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("root"));
+    Ok(archive_root.join(base).join(relative))
+}
+
+/// Moves `src` into `archive_root`, preserving its location relative to
+/// `scan_root`. Returns the destination path.
+pub fn move_to_archive(
+    src: &Path,
+    scan_root: &Path,
+    archive_root: &Path,
+    options: TransferOptions,
+    progress: impl FnMut(&TransferProgress),
+) -> Result<PathBuf, OpError> {
+    let dest = archive_destination(src, scan_root, archive_root)?;
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    move_path(src, &dest, options, progress)?;
+    tracing::info!(source = %src.display(), destination = %dest.display(), "archived");
+    Ok(dest)
+}
+
+/// Renames `src` to `dest`, falling back to copy-then-delete when the rename
+/// fails (typically because the destination is on another filesystem). The
+/// source is only removed once the whole copy has succeeded (and verified, if
+/// requested); on any failure the partial copy is discarded instead. Only
+/// what was copied is removed, so anything written into the source during
+/// the copy stays there, along with the directories holding it.
+pub fn move_path(
+    src: &Path,
+    dest: &Path,
+    options: TransferOptions,
+    progress: impl FnMut(&TransferProgress),
+) -> Result<(), OpError> {
+    if dest.exists() {
+        return Err(OpError::DestinationExists(dest.to_path_buf()));
+    }
+    if fs::rename(src, dest).is_ok() {
+        return Ok(());
+    }
+    let copied = match copy_tree(src, dest, options, progress) {
+        Ok(copied) => copied,
+        Err(e) => {
+            let _ = remove_path(dest);
+            return Err(e);
+        }
+    };
+    // Walk order puts every entry after its directory, so in reverse a
+    // directory's copied entries are gone before it is tried
+    for (path, is_dir) in copied.iter().rev() {
+        let removed = if *is_dir {
+            fs::remove_dir(path)
+        } else {
+            fs::remove_file(path)
+        };
+        match removed {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            // Something new was written into it during the copy
+            Err(e) if *is_dir && e.kind() == io::ErrorKind::DirectoryNotEmpty => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Copies a file or directory tree to `dest`, reporting progress after each
+/// file.
+pub fn copy_path(
+    src: &Path,
+    dest: &Path,
+    options: TransferOptions,
+    progress: impl FnMut(&TransferProgress),
+) -> Result<(), OpError> {
+    copy_tree(src, dest, options, progress).map(drop)
+}
+
+/// [`copy_path`], returning each source entry copied in walk order, with
+/// whether it is a directory.
+fn copy_tree(
+    src: &Path,
+    dest: &Path,
+    options: TransferOptions,
+    mut progress: impl FnMut(&TransferProgress),
+) -> Result<Vec<(PathBuf, bool)>, OpError> {
+    let mut state = TransferProgress::default();
+    for entry in WalkDir::new(src).follow_links(false) {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+            state.files_total += 1;
+            state.bytes_total += entry.metadata().map(|m| m.len()).unwrap_or(0);
+        }
+    }
+
+    let mut copied = Vec::new();
+    for entry in WalkDir::new(src).follow_links(false) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(src).unwrap_or(Path::new(""));
+        let target = if relative.as_os_str().is_empty() {
+            dest.to_path_buf()
+        } else {
+            dest.join(relative)
+        };
+        let ft = entry.file_type();
+        if ft.is_dir() {
+            fs::create_dir_all(&target)?;
+            copied.push((entry.into_path(), true));
+            continue;
+        }
+        if ft.is_symlink() {
+            copy_symlink(entry.path(), &target)?;
+        } else {
+            copy_file(entry.path(), &target, options.verify)?;
+        }
+        state.current = entry.path().to_path_buf();
+        state.files_done += 1;
+        state.bytes_done += entry.metadata().map(|m| m.len()).unwrap_or(0);
+        progress(&state);
+        copied.push((entry.into_path(), false));
+    }
+    Ok(copied)
+}
+
+fn copy_file(src: &Path, dest: &Path, verify: bool) -> Result<(), OpError> {
+    if !verify {
+        fs::copy(src, dest)?;
+        return Ok(());
+    }
+
+    let mut reader = BufReader::new(File::open(src)?);
+    let mut writer = File::create(dest)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+    }
+    writer.sync_all()?;
+    drop(writer);
+    fs::set_permissions(dest, fs::metadata(src)?.permissions())?;
+
+    if hash_file(dest)? != hasher.finalize() {
+        return Err(OpError::VerificationFailed(src.to_path_buf()));
+    }
+    Ok(())
+}
+
+pub(crate) fn hash_file(path: &Path) -> Result<blake3::Hash, OpError> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(File::open(path)?)?;
+    Ok(hasher.finalize())
+}
+
+/// Hash of the first `len` bytes of the file at `path`.
+pub(crate) fn hash_prefix(path: &Path, len: u64) -> Result<blake3::Hash, OpError> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(File::open(path)?.take(len))?;
+    Ok(hasher.finalize())
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dest: &Path) -> Result<(), OpError> {
+    let target = fs::read_link(src)?;
+    std::os::unix::fs::symlink(target, dest)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(src: &Path, dest: &Path) -> Result<(), OpError> {
+    fs::copy(src, dest)?;
+    Ok(())
+}
+
+/// Removes a file or a directory tree without following symlinks.
+pub fn remove_path(path: &Path) -> Result<(), OpError> {
+    let md = fs::symlink_metadata(path)?;
+    if md.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Removes what a scan found to be a directory tree (`is_dir`) or a file or
+/// link, without looking it up again as [`remove_path`] does. Symlinks are
+/// not followed either way.
+pub fn remove_entry(path: &Path, is_dir: bool) -> Result<(), OpError> {
+    if is_dir {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Total size in bytes of a file or directory tree, without following symlinks.
+pub fn path_size(path: &Path) -> u128 {
+    WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_type().is_dir())
+        .filter_map(|e| e.metadata().ok())
+        .map(|md| md.len() as u128)
+        .sum()
+}
+
+/// Allocation unit (cluster/block size) of the filesystem holding `path`, or
+/// of its nearest existing ancestor when `path` doesn't exist yet.
+pub fn allocation_unit(path: &Path) -> u64 {
+    const FALLBACK: u64 = 4096;
+    let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+        return FALLBACK;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        fs::metadata(existing)
+            .map(|md| md.blksize())
+            .ok()
+            .filter(|&unit| unit > 0)
+            .unwrap_or(FALLBACK)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = existing;
+        FALLBACK
+    }
+}
+
+/// Space `len` bytes occupy when allocated in whole `unit`-byte blocks.
+pub fn allocated_size(len: u128, unit: u64) -> u128 {
+    let unit = unit.max(1) as u128;
+    len.div_ceil(unit) * unit
+}
+
+/// Estimated space the subtree at `id` would occupy on a filesystem with the
+/// given allocation unit: every file and link rounded up to whole blocks,
+/// plus one block per directory. Transparent compression or deduplication
+/// at the destination is not accounted for, so this is an upper bound there.
+pub fn estimate_allocated(tree: &Tree, id: NodeId, unit: u64) -> u128 {
+    let mut total = 0u128;
+    let mut stack = vec![id];
+    while let Some(n) = stack.pop() {
+        let node = &tree.nodes[n.0 as usize];
+        total += match node.kind {
+            NodeKind::File | NodeKind::Archive | NodeKind::Symlink => {
+                allocated_size(node.size, unit)
+            }
+            NodeKind::Dir => unit as u128,
+            NodeKind::MountPoint | NodeKind::ArchiveEntry | NodeKind::Other => 0,
+        };
+        stack.extend(node.children.iter().copied());
+    }
+    total
+}
+
+/// The topmost nodes of `tree` matching any of `patterns` (gitignore glob
+/// syntax, relative to the tree root). Descendants of a matched node are not
+/// reported separately, so the result can be removed without double counting.
+pub fn match_nodes(tree: &Tree, patterns: &[String]) -> Result<Vec<NodeId>, OpError> {
+    let Some(root) = tree.nodes.get(tree.root.0 as usize) else {
+        return Ok(Vec::new());
+    };
+    let root_path = tree.path(tree.root);
+    let mut builder = ignore::overrides::OverrideBuilder::new(&root_path);
+    for pattern in patterns {
+        builder.add(pattern)?;
+    }
+    let matcher = builder.build()?;
+
+    let mut matched = Vec::new();
+    let mut stack: Vec<NodeId> = root.children.clone();
+    while let Some(id) = stack.pop() {
+        let node = &tree.nodes[id.0 as usize];
+        let is_dir = matches!(node.kind, NodeKind::Dir);
+        if matcher.matched(tree.path(id), is_dir).is_whitelist() {
+            matched.push(id);
+        } else {
+            stack.extend(node.children.iter().copied());
+        }
+    }
+    Ok(matched)
+}
@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use crate::advisor::{advise, AdvisorOptions};
+use crate::model::{NodeKind, Tree};
+use crate::volumes::Volume;
+
+/// One scanned root as shown on an [`Overview`].
+#[derive(Debug, Clone)]
+pub struct ScanSummary {
+    pub root: PathBuf,
+    pub bytes: u128,
+    pub files: u64,
+    pub scanned_at: Option<chrono::DateTime<chrono::Local>>,
+    /// The volume the root lives on, if it is one of those listed.
+    pub volume: Option<Volume>,
+    /// What the [advisor](crate::advisor) would propose removing, with
+    /// every file sharing a name and size counted as a duplicate.
+    pub reclaimable: u128,
+}
+
+/// A folder directly below a scanned root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopFolder {
+    pub path: PathBuf,
+    pub bytes: u128,
+}
+
+/// Several scans side by side, e.g. one per drive of a computer.
+#[derive(Debug, Clone, Default)]
+pub struct Overview {
+    pub scans: Vec<ScanSummary>,
+    /// The largest folders directly below any of the roots, largest first.
+    pub biggest: Vec<TopFolder>,
+    pub reclaimable: u128,
+}
+
+/// Summarises `trees` against the `volumes` they live on, listing the `top`
+/// largest folders across all of them. Combined trees are left out; their
+/// roots have trees of their own. No file contents are read, whatever
+/// `advisor` says, so this stays cheap enough to run while drawing.
+pub fn overview<'a>(
+    trees: impl IntoIterator<Item = &'a Tree>,
+    volumes: &[Volume],
+    advisor: &AdvisorOptions,
+    top: usize,
+) -> Overview {
+    let advisor = AdvisorOptions {
+        read_contents: false,
+        ..advisor.clone()
+    };
+    let mut overview = Overview::default();
+    for tree in trees {
+        let Some(root) = tree.nodes.get(tree.root.0 as usize) else {
+            continue;
+        };
+        if tree.is_combined() {
+            continue;
+        }
+        let root_path = tree.path(tree.root);
+        let volume = volumes
+            .iter()
+            .filter(|v| root_path.starts_with(&v.mount_point))
+            .max_by_key(|v| v.mount_point.components().count())
+            .cloned();
+        let reclaimable = advise(tree, u128::MAX, &advisor).total;
+        overview.reclaimable += reclaimable;
+        overview.biggest.extend(
+            root.children
+                .iter()
+                .map(|id| &tree.nodes[id.0 as usize])
+                .filter(|n| matches!(n.kind, NodeKind::Dir))
+                .map(|n| TopFolder {
+                    path: tree.path(n.id),
+                    bytes: n.size,
+                }),
+        );
+        overview.scans.push(ScanSummary {
+            root: root_path,
+            bytes: root.size,
+            files: root.file_count,
+            scanned_at: tree.meta.scanned_at,
+            volume,
+            reclaimable,
+        });
+    }
+    overview.biggest.sort_by_key(|f| std::cmp::Reverse(f.bytes));
+    overview.biggest.truncate(top);
+    overview
+}
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::Metadata;
+use std::path::Path;
+
+use crate::model::NodeSource;
+
+/// Who owns a file, as the filesystem records it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum OwnerId {
+    /// User and group ids on Unix.
+    Unix { uid: u32, gid: u32 },
+    /// Owner security identifier on Windows, e.g. `S-1-5-21-…-1001`.
+    Sid(String),
+}
+
+impl fmt::Display for OwnerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OwnerId::Unix { uid, gid } => write!(f, "{uid}:{gid}"),
+            OwnerId::Sid(sid) => f.write_str(sid),
+        }
+    }
+}
+
+/// An [`OwnerId`] with the names it resolves to on this machine.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Owner {
+    pub id: OwnerId,
+    /// User name, or `DOMAIN\user` on Windows; `None` if the account is
+    /// unknown here, e.g. on a disk from another machine.
+    pub name: Option<String>,
+    /// Group name on Unix.
+    pub group: Option<String>,
+}
+
+impl fmt::Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.name, &self.group) {
+            (Some(name), Some(group)) => write!(f, "{name}:{group}"),
+            (Some(name), None) => f.write_str(name),
+            _ => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// Space taken by the files of one owner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OwnerUsage {
+    pub owner: Owner,
+    pub bytes: u128,
+    pub files: u64,
+}
+
+/// The owner of the file at `path` with metadata `md`. On Windows this
+/// reads the file's security descriptor, an extra system call per file.
+pub fn owner_of(path: &Path, md: &Metadata) -> Option<OwnerId> {
+    platform::owner_of(path, md)
+}
+
+/// Looks up the user and group names of `id`.
+pub fn resolve(id: &OwnerId) -> Owner {
+    let (name, group) = platform::names(id);
+    Owner {
+        id: id.clone(),
+        name,
+        group,
+    }
+}
+
+/// Bytes and files below `id` per owner, largest first. Files scanned
+/// without [`ScanOptions::owners`](crate::scanner::ScanOptions::owners)
+/// are left out.
+pub fn usage_by_owner(source: &impl NodeSource, id: crate::model::NodeId) -> Vec<OwnerUsage> {
+    let mut totals: HashMap<&OwnerId, (u128, u64)> = HashMap::new();
+    for file in source.files_below(id) {
+        if let Some(owner) = source.owner(file) {
+            let total = totals.entry(owner).or_default();
+            total.0 += source.size(file);
+            total.1 += 1;
+        }
+    }
+    let mut usage: Vec<OwnerUsage> = totals
+        .into_iter()
+        .map(|(owner, (bytes, files))| OwnerUsage {
+            owner: resolve(owner),
+            bytes,
+            files,
+        })
+        .collect();
+    usage.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.owner.id.cmp(&b.owner.id))
+    });
+    usage
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::OwnerId;
+    use std::ffi::CStr;
+    use std::fs::Metadata;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    pub(super) fn owner_of(_path: &Path, md: &Metadata) -> Option<OwnerId> {
+        Some(OwnerId::Unix {
+            uid: md.uid(),
+            gid: md.gid(),
+        })
+    }
+
+    pub(super) fn names(id: &OwnerId) -> (Option<String>, Option<String>) {
+        match id {
+            OwnerId::Unix { uid, gid } => (user_name(*uid), group_name(*gid)),
+            OwnerId::Sid(_) => (None, None),
+        }
+    }
+
+    fn user_name(uid: u32) -> Option<String> {
+        let mut buf = vec![0 as libc::c_char; 1024];
+        loop {
+            // SAFETY: a plain C struct, valid when zeroed
+            let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+            let mut found = std::ptr::null_mut();
+            // SAFETY: `buf` outlives `pwd`, whose strings point into it
+            let err =
+                unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut found) };
+            if err == libc::ERANGE && buf.len() < 1 << 20 {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if err != 0 || found.is_null() {
+                return None;
+            }
+            // SAFETY: set by getpwuid_r to a NUL-terminated string in `buf`
+            let name = unsafe { CStr::from_ptr(pwd.pw_name) };
+            return Some(name.to_string_lossy().into_owned());
+        }
+    }
+
+    fn group_name(gid: u32) -> Option<String> {
+        let mut buf = vec![0 as libc::c_char; 1024];
+        loop {
+            // SAFETY: a plain C struct, valid when zeroed
+            let mut grp: libc::group = unsafe { std::mem::zeroed() };
+            let mut found = std::ptr::null_mut();
+            // SAFETY: `buf` outlives `grp`, whose strings point into it
+            let err =
+                unsafe { libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut found) };
+            if err == libc::ERANGE && buf.len() < 1 << 20 {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if err != 0 || found.is_null() {
+                return None;
+            }
+            // SAFETY: set by getgrgid_r to a NUL-terminated string in `buf`
+            let name = unsafe { CStr::from_ptr(grp.gr_name) };
+            return Some(name.to_string_lossy().into_owned());
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::OwnerId;
+    use std::ffi::OsString;
+    use std::fs::Metadata;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{LocalFree, ERROR_SUCCESS, PSID};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertSidToStringSidW, ConvertStringSidToSidW, GetNamedSecurityInfoW, SE_FILE_OBJECT,
+    };
+    use windows_sys::Win32::Security::{
+        LookupAccountSidW, OWNER_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, SID_NAME_USE,
+    };
+
+    fn wide(s: &std::ffi::OsStr) -> Vec<u16> {
+        s.encode_wide().chain(Some(0)).collect()
+    }
+
+    fn from_wide(buf: &[u16]) -> String {
+        let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        OsString::from_wide(&buf[..len])
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub(super) fn owner_of(path: &Path, _md: &Metadata) -> Option<OwnerId> {
+        let path = wide(path.as_os_str());
+        let mut owner: PSID = std::ptr::null_mut();
+        let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+        // SAFETY: `path` is NUL-terminated; `owner` points into
+        // `descriptor`, which is freed below
+        let err = unsafe {
+            GetNamedSecurityInfoW(
+                path.as_ptr(),
+                SE_FILE_OBJECT,
+                OWNER_SECURITY_INFORMATION,
+                &mut owner,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &mut descriptor,
+            )
+        };
+        if err != ERROR_SUCCESS {
+            return None;
+        }
+        let mut text = std::ptr::null_mut();
+        // SAFETY: `owner` is a valid SID while `descriptor` lives
+        let sid = if unsafe { ConvertSidToStringSidW(owner, &mut text) } != 0 {
+            // SAFETY: a NUL-terminated string allocated by the call above
+            let len = unsafe { (0..).take_while(|&i| *text.add(i) != 0).count() };
+            // SAFETY: `len` characters were just read from `text`
+            let sid = from_wide(unsafe { std::slice::from_raw_parts(text, len) });
+            // SAFETY: allocated with LocalAlloc by ConvertSidToStringSidW
+            unsafe { LocalFree(text.cast()) };
+            Some(OwnerId::Sid(sid))
+        } else {
+            None
+        };
+        // SAFETY: allocated with LocalAlloc by GetNamedSecurityInfoW
+        unsafe { LocalFree(descriptor) };
+        sid
+    }
+
+    pub(super) fn names(id: &OwnerId) -> (Option<String>, Option<String>) {
+        let OwnerId::Sid(text) = id else {
+            return (None, None);
+        };
+        let text = wide(std::ffi::OsStr::new(text));
+        let mut sid: PSID = std::ptr::null_mut();
+        // SAFETY: `text` is NUL-terminated
+        if unsafe { ConvertStringSidToSidW(text.as_ptr(), &mut sid) } == 0 {
+            return (None, None);
+        }
+        let mut name = [0u16; 256];
+        let mut domain = [0u16; 256];
+        let (mut name_len, mut domain_len) = (name.len() as u32, domain.len() as u32);
+        let mut kind: SID_NAME_USE = 0;
+        // SAFETY: the buffers are as long as the lengths passed
+        let found = unsafe {
+            LookupAccountSidW(
+                std::ptr::null(),
+                sid,
+                name.as_mut_ptr(),
+                &mut name_len,
+                domain.as_mut_ptr(),
+                &mut domain_len,
+                &mut kind,
+            )
+        } != 0;
+        // SAFETY: allocated with LocalAlloc by ConvertStringSidToSidW
+        unsafe { LocalFree(sid) };
+        if !found {
+            return (None, None);
+        }
+        let (name, domain) = (from_wide(&name), from_wide(&domain));
+        let name = if domain.is_empty() {
+            name
+        } else {
+            format!("{domain}\\{name}")
+        };
+        (Some(name), None)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::OwnerId;
+    use std::fs::Metadata;
+    use std::path::Path;
+
+    pub(super) fn owner_of(_path: &Path, _md: &Metadata) -> Option<OwnerId> {
+        None
+    }
+
+    pub(super) fn names(_id: &OwnerId) -> (Option<String>, Option<String>) {
+        (None, None)
+    }
+}
@@ -0,0 +1,14 @@
+//! The types most users of the crate need, for a single glob import:
+//!
+//! ```no_run
+//! use treesize_core::prelude::*;
+//! ```
+
+pub use crate::export::ExportError;
+pub use crate::handle::{ScanControl, ScanHandle};
+pub use crate::human::{human_bytes, human_duration};
+pub use crate::model::{NodeId, NodeKind, ScanError, ScanErrorKind, Tree, TreeNode};
+pub use crate::progress::{ProgressEvent, ProgressHub};
+pub use crate::scanner::{scan_channel, ScanMsg, ScanOptions, Scanner};
+pub use crate::snapshot::SnapshotError;
+pub use crate::tuning::{ScanTuning, StorageKind};
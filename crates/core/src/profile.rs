@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Time the scanner spent on the entries directly inside one directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirTiming {
+    /// Files, links and subdirectories met in the directory.
+    pub entries: u64,
+    /// Time spent examining those entries (metadata lookups, cache checks),
+    /// summed over walker threads.
+    pub busy: Duration,
+}
+
+impl DirTiming {
+    fn add(&mut self, other: DirTiming) {
+        self.entries += other.entries;
+        self.busy += other.busy;
+    }
+}
+
+/// Where a scan spent its time, directory by directory. Recorded by
+/// [`Scanner`](crate::Scanner) into [`ScanMeta::profile`](crate::ScanMeta::profile)
+/// and not kept when a tree is saved.
+#[derive(Debug, Clone, Default)]
+pub struct ScanProfile {
+    /// Wall-clock duration of the whole scan.
+    pub elapsed: Duration,
+    /// Metadata lookups made for the files, folders and links walked; one
+    /// per entry unless a cache spared some. Lookups when following links
+    /// or telling whether a failed entry vanished aren't counted.
+    pub metadata_lookups: u64,
+    pub dirs: HashMap<PathBuf, DirTiming>,
+}
+
+/// One line of [`ScanProfile::slowest`].
+#[derive(Debug, Clone)]
+pub struct SlowDir {
+    pub path: PathBuf,
+    /// Entries directly inside the directory.
+    pub entries: u64,
+    pub own: Duration,
+    /// Time spent on the directory and everything below it.
+    pub subtree: Duration,
+    pub subtree_entries: u64,
+}
+
+impl SlowDir {
+    /// Average time per entry below the directory; high values point at
+    /// slow storage or a filter driver rather than sheer entry count.
+    pub fn per_entry(&self) -> Duration {
+        if self.subtree_entries == 0 {
+            return Duration::ZERO;
+        }
+        self.subtree.div_f64(self.subtree_entries as f64)
+    }
+}
+
+impl ScanProfile {
+    pub(crate) fn merge(&mut self, dirs: HashMap<PathBuf, DirTiming>) {
+        for (path, timing) in dirs {
+            self.dirs.entry(path).or_default().add(timing);
+        }
+    }
+
+    /// Adds the timings of a scan run after this one.
+    pub(crate) fn append(&mut self, other: ScanProfile) {
+        self.elapsed += other.elapsed;
+        self.metadata_lookups += other.metadata_lookups;
+        self.merge(other.dirs);
+    }
+
+    /// Time spent on `dir` and everything below it.
+    pub fn subtree(&self, dir: &Path) -> DirTiming {
+        let mut total = DirTiming::default();
+        for (path, timing) in &self.dirs {
+            if path.starts_with(dir) {
+                total.add(*timing);
+            }
+        }
+        total
+    }
+
+    /// The `n` directories whose subtrees took longest, slowest first. A
+    /// directory is left out when a single child accounts for nearly all of
+    /// its time, so the list points at the directory actually responsible.
+    pub fn slowest(&self, n: usize) -> Vec<SlowDir> {
+        let mut subtrees: HashMap<&Path, DirTiming> = HashMap::with_capacity(self.dirs.len());
+        let mut heaviest_child: HashMap<&Path, Duration> = HashMap::new();
+        for (path, timing) in &self.dirs {
+            for ancestor in path.ancestors() {
+                if self.dirs.contains_key(ancestor) {
+                    subtrees.entry(ancestor).or_default().add(*timing);
+                }
+            }
+        }
+        for (path, timing) in &subtrees {
+            if let Some(parent) = path.parent() {
+                let heaviest = heaviest_child.entry(parent).or_default();
+                *heaviest = (*heaviest).max(timing.busy);
+            }
+        }
+        let mut rows: Vec<SlowDir> = subtrees
+            .iter()
+            .filter(|(path, timing)| {
+                let child = heaviest_child.get(*path).copied().unwrap_or_default();
+                child.as_secs_f64() < timing.busy.as_secs_f64() * 0.9
+            })
+            .map(|(path, timing)| {
+                let own = self.dirs[*path];
+                SlowDir {
+                    path: path.to_path_buf(),
+                    entries: own.entries,
+                    own: own.busy,
+                    subtree: timing.busy,
+                    subtree_entries: timing.entries,
+                }
+            })
+            .collect();
+        rows.sort_by(|a, b| b.subtree.cmp(&a.subtree).then_with(|| a.path.cmp(&b.path)));
+        rows.truncate(n);
+        rows
+    }
+}
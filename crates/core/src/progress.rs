@@ -1,6 +1,213 @@
-#[derive(Debug, Clone, Default)]
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use parking_lot::Mutex;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Room each [`ProgressHub`] listener has for events it hasn't read yet.
+pub const PROGRESS_CHANNEL_CAPACITY: usize = 64;
+
+/// Running totals of a scan.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Progress {
+    /// Files sized so far.
     pub files: u64,
-    pub dirs: u64,
+    /// Files found so far, sized or not, leaving out those that vanished.
+    pub discovered: u64,
     pub bytes: u128,
+    /// Entries deleted or renamed between being listed and being examined;
+    /// they are skipped rather than reported as errors.
+    pub vanished: u64,
+    /// Time since the scan started.
+    pub elapsed: Duration,
+    /// Directory the walker entered most recently, updated a few times a
+    /// second; `None` once the scan has finished.
+    pub current: Option<PathBuf>,
+}
+
+impl Progress {
+    /// These totals followed by those of a later scan.
+    pub(crate) fn plus(&self, later: &Progress) -> Progress {
+        Progress {
+            files: self.files + later.files,
+            discovered: self.discovered + later.discovered,
+            bytes: self.bytes + later.bytes,
+            vanished: self.vanished + later.vanished,
+            elapsed: self.elapsed + later.elapsed,
+            current: later.current.clone(),
+        }
+    }
+
+    /// Files sized per second, on average since the scan started.
+    pub fn files_per_sec(&self) -> f64 {
+        self.rate(self.files as f64)
+    }
+
+    /// Bytes counted per second, on average since the scan started.
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.rate(self.bytes as f64)
+    }
+
+    fn rate(&self, amount: f64) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            amount / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Time left to size the files discovered but not yet sized, at the
+    /// rate so far. Files the walker hasn't reached are unknown, so this is
+    /// a lower bound; `None` in the first second, before the rate settles.
+    pub fn eta(&self) -> Option<Duration> {
+        let rate = self.files_per_sec();
+        if self.elapsed < Duration::from_secs(1) || rate <= 0.0 {
+            return None;
+        }
+        let left = self.discovered.saturating_sub(self.files);
+        Some(Duration::from_secs_f64(left as f64 / rate))
+    }
+}
+
+/// How often a scan reports its running totals. Counting every file is
+/// cheap, but sending each count to the channel and the hub is not, so
+/// totals are reported at most this often; the final totals always are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressThrottle {
+    /// Least time between reports.
+    pub interval: Duration,
+    /// Reports sooner once this many more files were counted since the
+    /// last report; `None` goes by time alone.
+    pub files: Option<u64>,
+}
+
+impl Default for ProgressThrottle {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(50),
+            files: None,
+        }
+    }
+}
+
+/// Tells the threads of one scan when a report is due, so that only one of
+/// them sends it.
+pub(crate) struct ProgressGate {
+    throttle: ProgressThrottle,
+    started: Instant,
+    /// Milliseconds since `started` of the last report.
+    last_at: AtomicU64,
+    /// Files counted as of the last report.
+    last_files: AtomicU64,
+}
+
+impl ProgressGate {
+    pub(crate) fn new(throttle: ProgressThrottle) -> Self {
+        Self {
+            throttle,
+            started: Instant::now(),
+            last_at: AtomicU64::new(0),
+            last_files: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether totals of `files` counted files should be reported now.
+    /// Claims the report when it is, so a thread asking at the same time
+    /// is told no.
+    pub(crate) fn due(&self, files: u64) -> bool {
+        let now = self.started.elapsed().as_millis() as u64;
+        let last = self.last_at.load(Ordering::Relaxed);
+        let interval = self.throttle.interval.as_millis() as u64;
+        let by_time = now.saturating_sub(last) >= interval;
+        let by_files = self.throttle.files.is_some_and(|every| {
+            files.saturating_sub(self.last_files.load(Ordering::Relaxed)) >= every
+        });
+        if !by_time && !by_files {
+            return false;
+        }
+        let claimed = self
+            .last_at
+            .compare_exchange(last, now, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok();
+        if claimed {
+            self.last_files.store(files, Ordering::Relaxed);
+        }
+        claimed
+    }
+}
+
+/// What [`ProgressHub`] listeners receive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProgressEvent {
+    Progress(Progress),
+    /// A path could not be read; the scan carries on.
+    Error(String),
+    /// The scan ended with these totals. Nothing follows it.
+    Finished {
+        progress: Progress,
+        cancelled: bool,
+    },
+}
+
+/// Fans a scan's progress out to any number of listeners, e.g. a progress
+/// panel, a status bar and a logger, independently of the [`ScanMsg`]
+/// channel that receives the tree. Clones share their listeners.
+///
+/// Delivery follows the same rules as [`ScanMsg`]: a listener that falls
+/// behind misses `Progress` events (each carries running totals), while
+/// `Error` and `Finished` wait for room. Dropping a receiver unsubscribes it.
+///
+/// [`ScanMsg`]: crate::ScanMsg
+#[derive(Debug, Clone, Default)]
+pub struct ProgressHub {
+    listeners: Arc<Mutex<Vec<Sender<ProgressEvent>>>>,
+}
+
+impl ProgressHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a listener for events published from now on.
+    pub fn subscribe(&self) -> Receiver<ProgressEvent> {
+        let (tx, rx) = crossbeam_channel::bounded(PROGRESS_CHANNEL_CAPACITY);
+        self.listeners.lock().push(tx);
+        rx
+    }
+
+    /// Listeners whose receiver is still alive, as of the last event.
+    pub fn listeners(&self) -> usize {
+        self.listeners.lock().len()
+    }
+
+    pub(crate) fn publish(&self, event: ProgressEvent) {
+        let listeners = {
+            let listeners = self.listeners.lock();
+            if listeners.is_empty() {
+                return;
+            }
+            listeners.clone()
+        };
+        // Sent outside the lock so a slow listener doesn't hold up subscribe()
+        let mut gone = Vec::new();
+        for (i, tx) in listeners.iter().enumerate() {
+            let delivered = match &event {
+                ProgressEvent::Progress(_) => match tx.try_send(event.clone()) {
+                    Err(TrySendError::Disconnected(_)) => false,
+                    Ok(()) | Err(TrySendError::Full(_)) => true,
+                },
+                _ => tx.send(event.clone()).is_ok(),
+            };
+            if !delivered {
+                gone.push(i);
+            }
+        }
+        if !gone.is_empty() {
+            let mut current = self.listeners.lock();
+            current.retain(|tx| !gone.iter().any(|&i| listeners[i].same_channel(tx)));
+        }
+    }
 }
@@ -0,0 +1,214 @@
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::ops::{self, OpError, TransferOptions};
+
+#[derive(Debug, Error)]
+pub enum QuarantineError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Op(#[from] OpError),
+    #[error("nothing is quarantined as {0}")]
+    NotFound(String),
+    #[error("{} exists again; move it away to restore", .0.display())]
+    Occupied(PathBuf),
+}
+
+/// Days an entry is kept unless [`Quarantine::with_retention`] says
+/// otherwise.
+pub const DEFAULT_RETENTION_DAYS: i64 = 7;
+
+/// Name of the manifest in an entry's folder.
+const MANIFEST: &str = "entry.json";
+/// Name the quarantined file or folder is moved to in an entry's folder.
+const ITEM: &str = "item";
+
+/// A file or folder moved into a [`Quarantine`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantinedEntry {
+    /// Names the entry for [`Quarantine::restore`] and
+    /// [`Quarantine::purge`].
+    pub id: String,
+    /// Where it was, and is put back to on restore.
+    pub original: PathBuf,
+    pub quarantined_at: DateTime<Local>,
+    /// When [`Quarantine::purge_expired`] deletes it for good.
+    pub expires_at: DateTime<Local>,
+    pub size_bytes: u128,
+    pub is_dir: bool,
+}
+
+/// A staging folder that deletions are moved to instead of being removed,
+/// so they can be restored until their retention runs out.
+///
+/// Each entry is a folder of its own holding the moved item and a JSON
+/// manifest, so entries survive restarts and can be handled from several
+/// threads or processes at once. Moves are renames when the staging folder
+/// is on the same filesystem, and copies otherwise.
+#[derive(Debug, Clone)]
+pub struct Quarantine {
+    dir: PathBuf,
+    retention: Duration,
+}
+
+impl Quarantine {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            retention: Duration::days(DEFAULT_RETENTION_DAYS),
+        }
+    }
+
+    /// Staging folder in the per-user data directory.
+    pub fn default_dir() -> PathBuf {
+        let base = dirs::data_local_dir().unwrap_or_else(std::env::temp_dir);
+        base.join("treesize").join("quarantine")
+    }
+
+    /// How long entries are kept before [`purge_expired`](Self::purge_expired)
+    /// deletes them.
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Moves `path` into the quarantine, without following symlinks.
+    pub fn quarantine(&self, path: &Path) -> Result<QuarantinedEntry, QuarantineError> {
+        let metadata = fs::symlink_metadata(path)?;
+        let size_bytes = ops::path_size(path);
+        let quarantined_at = Local::now();
+        let (id, folder) = self.claim(quarantined_at)?;
+        if let Err(e) = ops::move_path(path, &folder.join(ITEM), TransferOptions::default(), |_| {})
+        {
+            let _ = fs::remove_dir_all(&folder);
+            return Err(e.into());
+        }
+        let entry = QuarantinedEntry {
+            id,
+            original: path.to_path_buf(),
+            quarantined_at,
+            expires_at: quarantined_at + self.retention,
+            size_bytes,
+            is_dir: metadata.is_dir(),
+        };
+        // Written last: an entry without a manifest is one still being moved
+        fs::write(folder.join(MANIFEST), serde_json::to_vec_pretty(&entry)?)?;
+        tracing::info!(path = %path.display(), id = %entry.id, "quarantined");
+        Ok(entry)
+    }
+
+    /// Every entry, most recently quarantined first.
+    pub fn entries(&self) -> Result<Vec<QuarantinedEntry>, QuarantineError> {
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = Vec::new();
+        for folder in dir {
+            let manifest = folder?.path().join(MANIFEST);
+            // Still being moved, or not an entry at all
+            let Ok(bytes) = fs::read(&manifest) else {
+                continue;
+            };
+            if let Ok(entry) = serde_json::from_slice(&bytes) {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by_key(|entry: &QuarantinedEntry| std::cmp::Reverse(entry.quarantined_at));
+        Ok(entries)
+    }
+
+    /// Moves an entry back to where it was, recreating the folders above
+    /// it if they have gone. Refused if something is there again.
+    pub fn restore(&self, id: &str) -> Result<QuarantinedEntry, QuarantineError> {
+        let (entry, folder) = self.entry(id)?;
+        if fs::symlink_metadata(&entry.original).is_ok() {
+            return Err(QuarantineError::Occupied(entry.original));
+        }
+        if let Some(parent) = entry.original.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        ops::move_path(
+            &folder.join(ITEM),
+            &entry.original,
+            TransferOptions::default(),
+            |_| {},
+        )?;
+        fs::remove_dir_all(&folder)?;
+        tracing::info!(path = %entry.original.display(), id, "restored");
+        Ok(entry)
+    }
+
+    /// Deletes an entry for good.
+    pub fn purge(&self, id: &str) -> Result<QuarantinedEntry, QuarantineError> {
+        let (entry, folder) = self.entry(id)?;
+        // The manifest goes first so a failed removal isn't listed half gone
+        fs::remove_file(folder.join(MANIFEST))?;
+        fs::remove_dir_all(&folder)?;
+        tracing::info!(path = %entry.original.display(), id, "purged");
+        Ok(entry)
+    }
+
+    /// Deletes the entries whose retention ran out by `now` and returns
+    /// them. Entries that can't be removed are left for the next call.
+    pub fn purge_expired(
+        &self,
+        now: DateTime<Local>,
+    ) -> Result<Vec<QuarantinedEntry>, QuarantineError> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter(|entry| entry.expires_at <= now)
+            .filter_map(|entry| self.purge(&entry.id).ok())
+            .collect())
+    }
+
+    /// Creates a new entry folder, named after the time so they list in
+    /// order, with a count appended if several are made in the same second.
+    fn claim(&self, at: DateTime<Local>) -> Result<(String, PathBuf), QuarantineError> {
+        fs::create_dir_all(&self.dir)?;
+        let stamp = at.format("%Y%m%d-%H%M%S").to_string();
+        for n in 0u32.. {
+            let id = if n == 0 {
+                stamp.clone()
+            } else {
+                format!("{stamp}-{n}")
+            };
+            let folder = self.dir.join(&id);
+            match fs::create_dir(&folder) {
+                Ok(()) => return Ok((id, folder)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        unreachable!("entry ids ran out")
+    }
+
+    fn entry(&self, id: &str) -> Result<(QuarantinedEntry, PathBuf), QuarantineError> {
+        // Ids come from clients; only names `claim` makes are looked up
+        let valid = !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit() || b == b'-');
+        let not_found = || QuarantineError::NotFound(id.to_string());
+        if !valid {
+            return Err(not_found());
+        }
+        let folder = self.dir.join(id);
+        let bytes = match fs::read(folder.join(MANIFEST)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(not_found()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok((serde_json::from_slice(&bytes)?, folder))
+    }
+}
@@ -0,0 +1,390 @@
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use thiserror::Error;
+
+use crate::attrs::Attributes;
+use crate::backend::{FoundFile, ScanBackend, ScanSink};
+use crate::model::{ScanErrorKind, Tree};
+use crate::scanner::Scanner;
+use crate::tuning::StorageKind;
+
+#[derive(Debug, Error)]
+pub enum S3Error {
+    #[error("not an s3://bucket/prefix URL: {0}")]
+    InvalidUrl(String),
+    #[error("request failed: {0}")]
+    Transport(#[from] Box<ureq::Transport>),
+    #[error("{code}: {message} (HTTP {status})")]
+    Service {
+        status: u16,
+        code: String,
+        message: String,
+    },
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid listing: {0}")]
+    Xml(#[from] roxmltree::Error),
+}
+
+/// Access keys for signing requests.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    /// For temporary credentials.
+    pub session_token: Option<String>,
+}
+
+/// A bucket, or part of one, to list.
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    /// Base URL of an S3-compatible service such as MinIO, e.g.
+    /// `http://localhost:9000`, addressed path-style; AWS when `None`.
+    pub endpoint: Option<String>,
+    pub region: String,
+    pub bucket: String,
+    /// Only keys starting with this are listed.
+    pub prefix: String,
+    /// Requests are sent unsigned without them, which public buckets allow.
+    pub credentials: Option<Credentials>,
+}
+
+/// Whether `root` names a bucket (`s3://…`) rather than a local path.
+pub fn is_s3_url(root: &str) -> bool {
+    root.starts_with("s3://")
+}
+
+impl S3Config {
+    /// The bucket and prefix of `url` (`s3://bucket/prefix`), reached with
+    /// the region, endpoint and credentials of the usual `AWS_*`
+    /// environment variables.
+    pub fn from_url(url: &str) -> Result<Self, S3Error> {
+        let rest = url
+            .strip_prefix("s3://")
+            .ok_or_else(|| S3Error::InvalidUrl(url.to_string()))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(S3Error::InvalidUrl(url.to_string()));
+        }
+        // `s3://bucket/photos` means the folder, not every key starting so
+        let prefix = match prefix {
+            "" => String::new(),
+            p if p.ends_with('/') => p.to_string(),
+            p => format!("{p}/"),
+        };
+        let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let credentials = match (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY")) {
+            (Some(access_key), Some(secret_key)) => Some(Credentials {
+                access_key,
+                secret_key,
+                session_token: env("AWS_SESSION_TOKEN"),
+            }),
+            _ => None,
+        };
+        Ok(S3Config {
+            endpoint: env("AWS_ENDPOINT_URL_S3").or_else(|| env("AWS_ENDPOINT_URL")),
+            region: env("AWS_REGION")
+                .or_else(|| env("AWS_DEFAULT_REGION"))
+                .unwrap_or_else(|| "us-east-1".to_string()),
+            bucket: bucket.to_string(),
+            prefix,
+            credentials,
+        })
+    }
+
+    /// The `s3://` URL of the listed prefix, used as the tree's root path.
+    pub fn url(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.prefix.trim_end_matches('/'))
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    /// Host and path of bucket requests: path-style on a custom endpoint,
+    /// virtual-hosted on AWS.
+    fn location(&self) -> (String, String, String) {
+        match &self.endpoint {
+            Some(endpoint) => {
+                let endpoint = endpoint.trim_end_matches('/');
+                let (scheme, host) = endpoint.split_once("://").unwrap_or(("https", endpoint));
+                (
+                    scheme.to_string(),
+                    host.to_string(),
+                    format!("/{}", uri_encode(&self.bucket)),
+                )
+            }
+            None => (
+                "https".to_string(),
+                format!("{}.s3.{}.amazonaws.com", self.bucket, self.region),
+                "/".to_string(),
+            ),
+        }
+    }
+}
+
+/// An object found by [`list_objects`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Object {
+    pub key: String,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// Lists every object under the configured prefix, a page of up to 1000
+/// at a time, calling `on_page` with the running object count and bytes.
+pub fn list_objects(
+    config: &S3Config,
+    mut on_page: impl FnMut(u64, u128),
+) -> Result<Vec<Object>, S3Error> {
+    let mut objects = Vec::new();
+    let mut bytes = 0u128;
+    list_pages(config, |page| {
+        bytes += page.iter().map(|o| u128::from(o.size)).sum::<u128>();
+        objects.extend_from_slice(page);
+        on_page(objects.len() as u64, bytes);
+        true
+    })?;
+    Ok(objects)
+}
+
+/// Calls `on_page` with each page of objects under the configured prefix,
+/// until it returns false or there are no more.
+fn list_pages(
+    config: &S3Config,
+    mut on_page: impl FnMut(&[Object]) -> bool,
+) -> Result<(), S3Error> {
+    let agent = ureq::AgentBuilder::new().build();
+    let mut token = None;
+    loop {
+        let mut query = vec![("list-type", "2".to_string())];
+        if !config.prefix.is_empty() {
+            query.push(("prefix", config.prefix.clone()));
+        }
+        if let Some(token) = token.take() {
+            query.push(("continuation-token", token));
+        }
+        let body = get(&agent, config, &query)?;
+        let doc = roxmltree::Document::parse(&body)?;
+        let root = doc.root_element();
+        let text = |node: roxmltree::Node, name: &str| {
+            node.children()
+                .find(|c| c.has_tag_name(name))
+                .and_then(|c| c.text())
+                .map(str::to_string)
+        };
+        let mut page = Vec::new();
+        for entry in root.children().filter(|c| c.has_tag_name("Contents")) {
+            let Some(key) = text(entry, "Key") else {
+                continue;
+            };
+            let size = text(entry, "Size")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0);
+            let modified = text(entry, "LastModified")
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(&t).ok())
+                .map(SystemTime::from);
+            page.push(Object {
+                key,
+                size,
+                modified,
+            });
+        }
+        if !on_page(&page) {
+            return Ok(());
+        }
+        token = text(root, "NextContinuationToken");
+        if text(root, "IsTruncated").as_deref() != Some("true") || token.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+/// Lists the configured prefix into a tree: keys are split at `/` into
+/// folders, the way S3 consoles show them, and objects become files.
+pub fn scan(config: &S3Config, mut on_page: impl FnMut(u64, u128)) -> Result<Tree, S3Error> {
+    let scanner = Scanner::new();
+    // Nobody listens, so the sink's messages are dropped as they are sent
+    let (tx, _) = crossbeam_channel::unbounded();
+    let root = PathBuf::from(config.url());
+    let mut sink = scanner.sink(&root, &tx);
+    let (mut objects, mut bytes) = (0, 0u128);
+    config.list_into(&root, &mut sink, |page| {
+        objects += page.len() as u64;
+        bytes += page.iter().map(|o| u128::from(o.size)).sum::<u128>();
+        on_page(objects, bytes);
+    })?;
+    let (mut tree, _) = sink.finish();
+    tree.nodes[tree.root.0 as usize].name = config.url().into();
+    tree.meta.storage = StorageKind::Network;
+    tree.meta.scanned_at = Some(chrono::Local::now());
+    Ok(tree)
+}
+
+impl S3Config {
+    /// Reports the objects under the prefix to `sink`, placed below `root`.
+    fn list_into(
+        &self,
+        root: &Path,
+        sink: &mut ScanSink<'_>,
+        mut on_page: impl FnMut(&[Object]),
+    ) -> Result<(), S3Error> {
+        list_pages(self, |page| {
+            for object in page {
+                let relative = object.key.strip_prefix(&self.prefix).unwrap_or(&object.key);
+                // Empty components from `//` or a leading `/` would be unnamed folders
+                let path: PathBuf = root.join(
+                    relative
+                        .split('/')
+                        .filter(|p| !p.is_empty())
+                        .collect::<PathBuf>(),
+                );
+                if relative.ends_with('/') || path == root {
+                    // A folder marker
+                    sink.dir(path, Attributes::default());
+                } else {
+                    let file = FoundFile {
+                        size: object.size,
+                        modified: object.modified,
+                        ..FoundFile::default()
+                    };
+                    sink.file(path, file);
+                }
+            }
+            on_page(page);
+            !sink.is_cancelled()
+        })
+    }
+}
+
+/// Lists the bucket as a scan, with keys split into folders as by [`scan`].
+/// `root` is where the prefix goes in the tree, normally [`S3Config::url`].
+impl ScanBackend for S3Config {
+    fn walk(&self, root: &Path, sink: &mut ScanSink<'_>) {
+        if let Err(e) = self.list_into(root, sink, |_| {}) {
+            sink.error(
+                root.to_path_buf(),
+                ScanErrorKind::Io,
+                format!("could not list bucket: {e}"),
+            );
+        }
+    }
+
+    fn storage(&self, _root: &Path) -> StorageKind {
+        StorageKind::Network
+    }
+}
+
+/// Sends a GET for the bucket with `query`, signed with Signature Version 4
+/// when there are credentials, and returns the response body.
+fn get(
+    agent: &ureq::Agent,
+    config: &S3Config,
+    query: &[(&str, String)],
+) -> Result<String, S3Error> {
+    let (scheme, host, path) = config.location();
+    let mut pairs: Vec<(String, String)> = query
+        .iter()
+        .map(|(k, v)| (uri_encode(k), uri_encode(v)))
+        .collect();
+    pairs.sort();
+    let query = pairs
+        .iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join("&");
+    let mut request = agent.get(&format!("{scheme}://{host}{path}?{query}"));
+    if let Some(credentials) = &config.credentials {
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload = hex(&Sha256::digest(b""));
+        let mut headers = vec![
+            ("host", host.clone()),
+            ("x-amz-content-sha256", payload.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let signed = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+            .collect();
+        let canonical = format!("GET\n{path}\n{query}\n{canonical_headers}\n{signed}\n{payload}");
+        let scope = format!("{date}/{}/s3/aws4_request", config.region);
+        let to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical.as_bytes()))
+        );
+        let key = [config.region.as_str(), "s3", "aws4_request"].iter().fold(
+            hmac(
+                format!("AWS4{}", credentials.secret_key).as_bytes(),
+                date.as_bytes(),
+            ),
+            |key, part| hmac(&key, part.as_bytes()),
+        );
+        let signature = hex(&hmac(&key, to_sign.as_bytes()));
+        // ureq sets Host itself, from the same URL
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.set(name, value);
+        }
+        request = request.set(
+            "authorization",
+            &format!(
+                "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed}, Signature={signature}",
+                credentials.access_key
+            ),
+        );
+    }
+    match request.call() {
+        Ok(response) => Ok(response.into_string()?),
+        Err(ureq::Error::Status(status, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            let doc = roxmltree::Document::parse(&body).ok();
+            let field = |name: &str| {
+                doc.as_ref()
+                    .and_then(|d| d.descendants().find(|n| n.has_tag_name(name)))
+                    .and_then(|n| n.text())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            Err(S3Error::Service {
+                status,
+                code: field("Code"),
+                message: field("Message"),
+            })
+        }
+        Err(ureq::Error::Transport(transport)) => Err(Box::new(transport).into()),
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Percent-encodes all but the characters RFC 3986 leaves unreserved, as
+/// Signature Version 4 requires of query strings.
+fn uri_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
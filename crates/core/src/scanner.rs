@@ -1,240 +1,1144 @@
 use crossbeam_channel::Sender;
-use ignore::{WalkBuilder, WalkState};
-use std::collections::HashMap;
-use std::{
-    path::{Path, PathBuf},
-    sync::{
-        atomic::{AtomicBool, AtomicU64, Ordering},
-        Arc,
-    },
-};
+use ignore::overrides::{Override, OverrideBuilder};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use crate::attrs::Attributes;
+use crate::backend::{ScanBackend, ScanSink};
+use crate::checkpoint::Checkpoint;
+use crate::handle::{ScanControl, ScanHandle};
+use crate::local::LocalBackend;
+use crate::mapped::NodeTableWriter;
 use crate::model::*;
-use std::thread::sleep;
-use std::time::Duration;
+use crate::owner::OwnerId;
+use crate::progress::{Progress, ProgressEvent, ProgressHub, ProgressThrottle};
+use crate::tuning::{ScanTuning, StorageKind};
+use std::time::{Duration, SystemTime};
+
+/// Capacity of the channel returned by [`scan_channel`].
+pub const SCAN_CHANNEL_CAPACITY: usize = 1024;
+
+/// A bounded channel suitable for [`Scanner::scan`].
+pub fn scan_channel() -> (Sender<ScanMsg>, crossbeam_channel::Receiver<ScanMsg>) {
+    crossbeam_channel::bounded(SCAN_CHANNEL_CAPACITY)
+}
 
+/// Messages sent by [`Scanner::scan`].
+///
+/// # Backpressure
+///
+/// Use a bounded channel such as [`scan_channel`], so memory stays flat
+/// however far the receiver falls behind. `Progress`, `File` and
+/// `PartialTree` are best effort: they are dropped whenever the channel is
+/// full, which coalesces progress into whatever the receiver has room for
+/// (each `Progress` carries running totals, so nothing is lost by skipping
+/// some). A `PartialTree` holds everything found so far, so one is only
+/// sent once the receiver has emptied the channel. `Error`, `DirDone`,
+/// `Done` and `Cancelled` are always delivered and block the walk until
+/// the receiver makes room, so a stalled receiver pauses the scan rather
+/// than buffering it. A final `Progress` with the exact totals is sent just
+/// before `Done` or `Cancelled`. With an unbounded channel every message is
+/// delivered, and a receiver that stalls lets them pile up.
+///
+/// # Trees
+///
+/// Trees are sent behind an `Arc`, so cloning or forwarding a message never
+/// copies one. A receiver that keeps the tree can take it out with
+/// [`Arc::unwrap_or_clone`], which only copies it if the message was cloned.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum ScanMsg {
     Progress {
         scanned: u64,
         discovered: u64,
         bytes: u128,
+        /// Entries that disappeared before they could be examined.
+        vanished: u64,
+        /// Time since the scan started.
+        elapsed: Duration,
+        /// Average rates since the scan started; see [`Progress::files_per_sec`].
+        files_per_sec: f64,
+        bytes_per_sec: f64,
+        /// See [`Progress::eta`].
+        eta: Option<Duration>,
+        /// See [`Progress::current`].
+        current: Option<PathBuf>,
     },
+    /// Everything below `path` has been walked; sent when enabled with
+    /// [`Scanner::with_dir_done`]. `bytes`, `files` and `dirs` total the
+    /// whole subtree, not counting `path` itself. A directory always comes
+    /// after the directories inside it.
     DirDone {
         path: PathBuf,
         bytes: u128,
         files: u64,
         dirs: u64,
     },
+    /// A file was sized; sent when enabled with
+    /// [`Scanner::with_file_messages`].
     File {
         path: PathBuf,
         bytes: u64,
     },
-    Done(Tree),
+    Done(Arc<Tree>),
+    /// The scan was cancelled; sent instead of `Done` with everything
+    /// walked until then. Directories the walker hadn't finished are
+    /// missing entries, so their sizes are too small.
+    Cancelled(Arc<Tree>),
     Error(String),
+    /// Everything found so far, with provisional sizes; sent periodically
+    /// when enabled with [`Scanner::with_partial_trees`]. Node ids stay valid
+    /// in later partial trees and in the tree sent with `Done`.
+    PartialTree(Arc<Tree>),
+    /// A file was created or changed after the scan; sent by
+    /// [`crate::watch::watch`].
+    NodeUpdated {
+        path: PathBuf,
+        bytes: u64,
+        modified: Option<SystemTime>,
+    },
+    /// A file or directory disappeared after the scan; sent by
+    /// [`crate::watch::watch`].
+    NodeRemoved {
+        path: PathBuf,
+    },
+}
+
+/// Options controlling what the scanner walks.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ScanOptions {
+    /// Glob patterns (gitignore syntax) for entries that are never walked,
+    /// e.g. `node_modules`, `target/`, `**/.cache`.
+    pub excludes: Vec<String>,
+    /// Do not descend into directories on a different filesystem than the
+    /// root; they are recorded as empty [`NodeKind::MountPoint`] nodes instead.
+    pub one_file_system: bool,
+    /// Follow symbolic links that lead outside the scanned tree. Links into
+    /// the tree, and targets already visited, are kept as
+    /// [`NodeKind::Symlink`] nodes so nothing is counted twice.
+    pub follow_links: bool,
+    /// Parallelism and batching of the walk.
+    pub tuning: ScanTuning,
+    /// Storage type used to pick tuning defaults; detected from the root
+    /// when `None`.
+    pub storage: Option<StorageKind>,
+    /// Count the alternate data streams of NTFS files in their size, and
+    /// record them in [`ScanMeta::alternate_streams`].
+    /// Costs an extra system call per file; has no effect off Windows.
+    pub alternate_streams: bool,
+    /// Record who owns each file in [`TreeNode::owner`]. Costs an extra
+    /// system call per file on Windows.
+    pub owners: bool,
+    /// List the contents of zip, tar, tar.gz and 7z files as
+    /// [`NodeKind::ArchiveEntry`] nodes below them. Needs the `archives`
+    /// feature; ignored without it.
+    pub archives: bool,
+    /// Files smaller than this many bytes are left out of the tree, the
+    /// totals and the cache entirely; 0 keeps every file.
+    pub min_file_size: u64,
+    /// When not empty, only files with one of these extensions are kept,
+    /// e.g. `mp4` or `.iso`. Compared without regard to case.
+    pub include_extensions: Vec<String>,
+    /// Files with one of these extensions are left out, like files below
+    /// [`Self::min_file_size`].
+    pub exclude_extensions: Vec<String>,
+    /// Levels below the root to list, like `du --max-depth`; whatever lies
+    /// deeper is still walked and counted in the folder at that depth, but
+    /// gets no node of its own. `Some(0)` lists only the root. Unless a
+    /// cache, checkpoint or sample needs each file, the deeper files aren't
+    /// kept in memory either, so a shallow view of a huge share stays small;
+    /// a folded folder can be listed later with [`Scanner::scan_subtree`].
+    pub max_depth: Option<usize>,
+    /// Size only this many files in a thousand, picked at random, and
+    /// extrapolate folder sizes from them; see [`Estimate`]. The others are
+    /// counted but never stat'ed, and don't appear in the tree.
+    /// [`Self::min_file_size`] only applies to the sized files.
+    pub sample_per_mille: Option<u16>,
+    /// Write every node to a [`NodeTable`](crate::mapped::NodeTable) in
+    /// this folder as it is found, for scans too large to keep in memory.
+    /// The tree then lists folders only, each with the totals of everything
+    /// below it, and records where the table is in
+    /// [`ScanMeta::node_table`]; files are read from the table. With a
+    /// cache or checkpoint, which need every file, files are still held in
+    /// memory until the scan ends. [`Scanner::scan_many`] writes a table
+    /// per root, to numbered folders inside this one.
+    pub spill: Option<PathBuf>,
+}
+
+impl ScanOptions {
+    /// Hash identifying these options, for telling whether two scans of the
+    /// same root are interchangeable. Only stable within one build.
+    pub fn fingerprint(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Share of files sized, when sampling; see [`Self::sample_per_mille`].
+    fn sample_fraction(&self) -> Option<f64> {
+        self.sample_per_mille
+            .map(|per_mille| f64::from(per_mille.clamp(1, 1000)) / 1000.0)
+    }
+
+    /// Whether the file at `path` is sized rather than left to the sample.
+    /// Picked by a hash of the path, so a rescan samples the same files.
+    pub(crate) fn is_sampled(&self, path: &Path) -> bool {
+        use std::hash::{Hash, Hasher};
+        let Some(per_mille) = self.sample_per_mille else {
+            return true;
+        };
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        path.hash(&mut hasher);
+        hasher.finish() % 1000 < u64::from(per_mille.clamp(1, 1000))
+    }
+
+    /// Whether files named like `path` are kept by the extension filters.
+    pub(crate) fn keeps_extension(&self, path: &Path) -> bool {
+        if self.include_extensions.is_empty() && self.exclude_extensions.is_empty() {
+            return true;
+        }
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy())
+            .unwrap_or_default();
+        let matches = |wanted: &String| {
+            wanted
+                .strip_prefix('.')
+                .unwrap_or(wanted)
+                .eq_ignore_ascii_case(&ext)
+        };
+        (self.include_extensions.is_empty() || self.include_extensions.iter().any(matches))
+            && !self.exclude_extensions.iter().any(matches)
+    }
+}
+
+/// What the scanner learns about a file, and a [`DirSizeStore`] keeps.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct CachedFile {
+    pub(crate) size: u64,
+    pub(crate) modified: Option<SystemTime>,
+    pub(crate) online: bool,
+    pub(crate) owner: Option<OwnerId>,
+    pub(crate) attributes: Attributes,
+}
+
+/// Sizes of files from a previous scan, used so unchanged directories don't
+/// need each of their files stat'ed again.
+pub(crate) trait DirSizeStore: Sync {
+    /// File name to size and modification time for `dir`, if it was
+    /// recorded with this `mtime`.
+    fn lookup(&self, dir: &Path, mtime: SystemTime) -> Option<HashMap<String, CachedFile>>;
+    fn store(&self, dir: &Path, mtime: SystemTime, files: Vec<(String, CachedFile)>);
+    /// Called once the walk is complete with every directory it visited.
+    fn finish(&self, root: &Path, visited: &HashSet<PathBuf>);
 }
 
+#[derive(Default)]
 pub struct Scanner {
-    cancel: Arc<AtomicBool>,
-    paused: Arc<AtomicBool>,
+    control: ScanControl,
+    pub(crate) options: ScanOptions,
+    progress: ProgressHub,
+    pub(crate) throttle: ProgressThrottle,
+    pub(crate) partial_every: Option<Duration>,
+    pub(crate) dir_done: bool,
+    pub(crate) file_messages: bool,
+    /// Where to write checkpoints, and how often.
+    checkpoint: Option<(PathBuf, Duration)>,
 }
 
 impl Scanner {
-    pub fn new(cancel: Arc<AtomicBool>, paused: Arc<AtomicBool>) -> Self {
-        Self { cancel, paused }
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    pub fn scan(&self, root: PathBuf, tx: Sender<ScanMsg>) {
-        use parking_lot::Mutex;
+    /// Uses `control` instead of a token of its own, e.g. to cancel several
+    /// scans together.
+    pub fn with_control(mut self, control: ScanControl) -> Self {
+        self.control = control;
+        self
+    }
 
-        let cancel = self.cancel.clone();
-        let paused = self.paused.clone();
+    /// Token for cancelling or pausing scans run by this scanner.
+    pub fn control(&self) -> ScanControl {
+        self.control.clone()
+    }
 
-        // Shared progress counters
-        let discovered = Arc::new(AtomicU64::new(0));
-        let scanned = Arc::new(AtomicU64::new(0));
-        let bytes = Arc::new(Mutex::new(0u128));
+    /// Publishes progress to `hub` instead of a hub of its own, e.g. to
+    /// subscribe listeners before the scan starts.
+    pub fn with_progress(mut self, hub: ProgressHub) -> Self {
+        self.progress = hub;
+        self
+    }
 
-        // Collected files for final tree assembly
-        let files: Arc<Mutex<Vec<(PathBuf, u64)>>> = Arc::new(Mutex::new(Vec::with_capacity(4096)));
+    /// Hub that progress, errors and completion of scans run by this
+    /// scanner are published to.
+    pub fn progress(&self) -> ProgressHub {
+        self.progress.clone()
+    }
 
-        let mut builder = WalkBuilder::new(&root);
-        builder
-            .hidden(false)
-            .git_global(false)
-            .follow_links(false)
-            .threads(num_cpus::get());
-
-        let walker = builder.build_parallel();
-        walker.run(|| {
-            let paused_outer = paused.clone();
-            let cancel = cancel.clone();
-            let tx = tx.clone();
-            let discovered = discovered.clone();
-            let scanned = scanned.clone();
-            let bytes = bytes.clone();
-            let files = files.clone();
-            Box::new(move |entry| {
-                while paused_outer.load(Ordering::Relaxed) {
-                    if cancel.load(Ordering::Relaxed) {
-                        return WalkState::Quit;
-                    }
-                    sleep(Duration::from_millis(40));
-                }
-                if cancel.load(Ordering::Relaxed) {
-                    return WalkState::Quit;
-                }
-                match entry {
-                    Ok(ent) => {
-                        if ent.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
-                            discovered.fetch_add(1, Ordering::Relaxed);
-                            let path = ent.path().to_path_buf();
-                            match ent.metadata() {
-                                Ok(md) => {
-                                    let sz = md.len() as u64;
-                                    scanned.fetch_add(1, Ordering::Relaxed);
-                                    {
-                                        let mut b = bytes.lock();
-                                        *b = b.saturating_add(sz as u128);
-                                        let _ = tx.send(ScanMsg::Progress {
-                                            scanned: scanned.load(Ordering::Relaxed),
-                                            discovered: discovered.load(Ordering::Relaxed),
-                                            bytes: *b,
-                                        });
-                                    }
-                                    let _ = tx.send(ScanMsg::File {
-                                        path: path.clone(),
-                                        bytes: sz,
-                                    });
-                                    files.lock().push((path, sz));
-                                }
-                                Err(_) => {
-                                    // Still count as scanned, but no size
-                                    scanned.fetch_add(1, Ordering::Relaxed);
-                                    let b = *bytes.lock();
-                                    let _ = tx.send(ScanMsg::Progress {
-                                        scanned: scanned.load(Ordering::Relaxed),
-                                        discovered: discovered.load(Ordering::Relaxed),
-                                        bytes: b,
-                                    });
-                                }
-                            }
+    /// Reports running totals as often as `throttle` allows instead of
+    /// about every 50ms. Raise it when a scan of a fast disk spends
+    /// noticeable time reporting, or lower it for a livelier display.
+    pub fn with_progress_throttle(mut self, throttle: ProgressThrottle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Sends a [`ScanMsg::PartialTree`] about every `every` while scanning,
+    /// or less often when copying the tree takes a noticeable share of that.
+    pub fn with_partial_trees(mut self, every: Duration) -> Self {
+        self.partial_every = Some(every);
+        self
+    }
+
+    /// Sends a [`ScanMsg::DirDone`] for every directory as soon as all of
+    /// its subtree has been walked.
+    pub fn with_dir_done(mut self) -> Self {
+        self.dir_done = true;
+        self
+    }
+
+    /// Sends a [`ScanMsg::File`] for every file sized. Off by default, as
+    /// most receivers only need the totals and the tree, and a message per
+    /// file keeps the channel full on large scans.
+    pub fn with_file_messages(mut self) -> Self {
+        self.file_messages = true;
+        self
+    }
+
+    /// Writes a [`Checkpoint`] to `path` about every `every` while
+    /// scanning a single root, and once more if the scan is cancelled, so
+    /// a crashed or cancelled scan can be continued with
+    /// [`Scanner::resume`]. The file is removed when the scan completes.
+    ///
+    /// Files are published one at a time instead of in batches, so a
+    /// directory can be claimed as soon as it is complete; this is meant
+    /// for scans long enough to be worth it.
+    pub fn with_checkpoints(mut self, path: PathBuf, every: Duration) -> Self {
+        self.checkpoint = Some((path, every));
+        self
+    }
+
+    /// A scanner sharing this one's options and control but publishing
+    /// nowhere, for scans whose progress is reported differently.
+    fn unpublished(&self) -> Scanner {
+        Scanner {
+            control: self.control.clone(),
+            options: self.options.clone(),
+            progress: ProgressHub::default(),
+            throttle: self.throttle,
+            partial_every: self.partial_every,
+            dir_done: self.dir_done,
+            file_messages: self.file_messages,
+            checkpoint: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: ScanOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Walks `root` and reports on `tx`, ending with [`ScanMsg::Done`], or
+    /// [`ScanMsg::Cancelled`] if cancelled through [`Scanner::control`]; see
+    /// [`ScanMsg`] for how a full channel is handled.
+    pub fn scan(&self, root: PathBuf, tx: Sender<ScanMsg>) {
+        self.scan_inner(root, tx, None, None);
+    }
+
+    /// Continues the scan `checkpoint` was taken from, reporting as
+    /// [`Scanner::scan`] does. Directories the checkpoint completed are not
+    /// walked again; the rest of its root is. The checkpoint doesn't record
+    /// scan options, so use those of the interrupted scan.
+    pub fn resume(&self, checkpoint: Checkpoint, tx: Sender<ScanMsg>) {
+        let root = checkpoint.root.clone();
+        self.scan_inner(root, tx, None, Some(checkpoint));
+    }
+
+    /// Scans each of `roots` in turn and reports a single tree joining them
+    /// under a synthetic root (see [`Tree::combine`]). Progress is
+    /// cumulative across roots and `Done` is sent once, at the end. On
+    /// cancellation `Cancelled` carries the roots scanned so far. A single
+    /// root is scanned as by [`Scanner::scan`].
+    pub fn scan_many(&self, mut roots: Vec<PathBuf>, tx: Sender<ScanMsg>) {
+        if roots.len() == 1 {
+            return self.scan(roots.remove(0), tx);
+        }
+        let mut trees = Vec::with_capacity(roots.len());
+        let mut done = Progress::default();
+        let mut inner = self.unpublished();
+        for (n, root) in roots.into_iter().enumerate() {
+            if self.control.is_cancelled() {
+                break;
+            }
+            if let Some(dir) = &self.options.spill {
+                inner.options.spill = Some(dir.join(n.to_string()));
+            }
+            let (sub_tx, sub_rx) = scan_channel();
+            let mut last = Progress::default();
+            std::thread::scope(|s| {
+                s.spawn(|| inner.scan(root, sub_tx));
+                for msg in sub_rx {
+                    match msg {
+                        ScanMsg::Progress {
+                            scanned,
+                            discovered,
+                            bytes,
+                            vanished,
+                            elapsed,
+                            current,
+                            ..
+                        } => {
+                            last = Progress {
+                                files: scanned,
+                                discovered,
+                                bytes,
+                                vanished,
+                                elapsed,
+                                current,
+                            };
+                            self.report(&tx, done.plus(&last));
+                        }
+                        ScanMsg::Done(tree) | ScanMsg::Cancelled(tree) => {
+                            trees.push(Arc::unwrap_or_clone(tree))
+                        }
+                        ScanMsg::Error(e) => self.report_error(&tx, e),
+                        msg @ ScanMsg::DirDone { .. } => {
+                            let _ = tx.send(msg);
+                        }
+                        ScanMsg::PartialTree(partial) => send_partial(&tx, || {
+                            let mut parts = trees.clone();
+                            parts.push(Arc::unwrap_or_clone(partial));
+                            Tree::combine(parts)
+                        }),
+                        other => {
+                            let _ = tx.try_send(other);
                         }
-                        WalkState::Continue
-                    }
-                    Err(e) => {
-                        let _ = tx.send(ScanMsg::Error(e.to_string()));
-                        WalkState::Continue
                     }
                 }
+            });
+            done = done.plus(&last);
+        }
+        self.finish(&tx, done, Tree::combine(trees));
+    }
+
+    /// Sends running totals, best effort, to `tx` and the progress hub.
+    pub(crate) fn report(&self, tx: &Sender<ScanMsg>, progress: Progress) {
+        let _ = tx.try_send(progress_msg(&progress));
+        self.progress.publish(ProgressEvent::Progress(progress));
+    }
+
+    pub(crate) fn report_error(&self, tx: &Sender<ScanMsg>, message: String) {
+        let _ = tx.send(ScanMsg::Error(message.clone()));
+        self.progress.publish(ProgressEvent::Error(message));
+    }
+
+    /// Sends the exact final totals and the tree, as `Cancelled` if the
+    /// scan was cut short, then tells listeners the scan is over.
+    fn finish(&self, tx: &Sender<ScanMsg>, progress: Progress, tree: Tree) {
+        let cancelled = self.control.is_cancelled();
+        let _ = tx.send(progress_msg(&progress));
+        let _ = tx.send(if cancelled {
+            ScanMsg::Cancelled(Arc::new(tree))
+        } else {
+            ScanMsg::Done(Arc::new(tree))
+        });
+        self.progress.publish(ProgressEvent::Finished {
+            progress,
+            cancelled,
+        });
+    }
+
+    /// Scans `root` with `backend` instead of walking the local
+    /// filesystem, reporting as [`Scanner::scan`] does. Options only a
+    /// local walk can honour, such as exclude patterns and following links,
+    /// are left to the backend. Checkpoints are only written by
+    /// [`Scanner::scan`].
+    pub fn scan_with(&self, backend: &dyn ScanBackend, root: PathBuf, tx: Sender<ScanMsg>) {
+        self.run(backend, root, &tx, |_| {});
+    }
+
+    /// Feeds what `backend` finds below `root` to a sink set up by
+    /// `prepare`, and reports the tree it builds.
+    fn run(
+        &self,
+        backend: &dyn ScanBackend,
+        root: PathBuf,
+        tx: &Sender<ScanMsg>,
+        prepare: impl FnOnce(&mut ScanSink<'_>),
+    ) {
+        let mut sink = self.sink(&root, tx);
+        prepare(&mut sink);
+        backend.walk(&root, &mut sink);
+        let (mut tree, progress) = sink.finish();
+        if !self.control.is_cancelled() {
+            let before = tree.errors.len();
+            backend.finish(&root, &mut tree);
+            for error in &tree.errors[before..] {
+                self.report_error(tx, format!("{}: {}", error.path.display(), error.message));
+            }
+        }
+        tree.meta.storage = backend.storage(&root);
+        tree.meta.scanned_at = Some(chrono::Local::now());
+        self.finish(tx, progress, tree);
+    }
+
+    /// A sink feeding a tree of `root` built with this scanner's options.
+    pub(crate) fn sink<'a>(&'a self, root: &Path, tx: &'a Sender<ScanMsg>) -> ScanSink<'a> {
+        ScanSink::new(self, root, tx)
+    }
+
+    /// Runs [`Scanner::scan`] on a new thread.
+    pub fn spawn(self, root: PathBuf, tx: Sender<ScanMsg>) -> ScanHandle {
+        ScanHandle::spawn(self.control(), self.progress(), move || self.scan(root, tx))
+    }
+
+    /// Runs [`Scanner::resume`] on a new thread.
+    pub fn spawn_resume(self, checkpoint: Checkpoint, tx: Sender<ScanMsg>) -> ScanHandle {
+        ScanHandle::spawn(self.control(), self.progress(), move || {
+            self.resume(checkpoint, tx)
+        })
+    }
+
+    /// Runs [`Scanner::scan_with`] on a new thread.
+    pub fn spawn_with(
+        self,
+        backend: Box<dyn ScanBackend>,
+        root: PathBuf,
+        tx: Sender<ScanMsg>,
+    ) -> ScanHandle {
+        ScanHandle::spawn(self.control(), self.progress(), move || {
+            self.scan_with(backend.as_ref(), root, tx)
+        })
+    }
+
+    /// Runs [`Scanner::scan_many`] on a new thread.
+    pub fn spawn_many(self, roots: Vec<PathBuf>, tx: Sender<ScanMsg>) -> ScanHandle {
+        ScanHandle::spawn(self.control(), self.progress(), move || {
+            self.scan_many(roots, tx)
+        })
+    }
+
+    /// Runs [`Scanner::scan_with_cache`] on a new thread.
+    #[cfg(feature = "cache")]
+    pub fn spawn_with_cache(
+        self,
+        root: PathBuf,
+        cache_path: PathBuf,
+        tx: Sender<ScanMsg>,
+    ) -> ScanHandle {
+        ScanHandle::spawn(self.control(), self.progress(), move || {
+            self.scan_with_cache(root, &cache_path, tx)
+        })
+    }
+
+    /// Async counterpart of [`Scanner::scan`]: runs the walk on background
+    /// threads and yields its messages as a [`Stream`](tokio_stream::Stream).
+    #[cfg(feature = "async")]
+    pub fn scan_stream(self, root: PathBuf) -> crate::stream::ScanStream {
+        crate::stream::ScanStream::spawn(self, root)
+    }
+
+    /// Scans `dir` to completion and returns its tree, for replacing a stale
+    /// part of an existing tree with [`Tree::splice_subtree`]. Progress is
+    /// published to [`Scanner::progress`] as usual; the messages a scan
+    /// sends on its channel are discarded.
+    pub fn scan_subtree(&self, dir: PathBuf) -> Tree {
+        match self.scan_dir(dir, |_| {}) {
+            Some(ScanMsg::Done(tree) | ScanMsg::Cancelled(tree)) => Arc::unwrap_or_clone(tree),
+            _ => Tree::default(),
+        }
+    }
+
+    /// Re-walks the directory `id` of `tree` and puts the result in place of
+    /// its subtree, fixing the sizes and counts above it; see
+    /// [`Tree::splice_subtree`]. Progress and errors go to `tx` as for
+    /// [`Scanner::scan`], but the new subtree goes into `tree` instead of a
+    /// `Done` message. Returns how ids moved, or `None` if `id` is not a
+    /// directory or the rescan was cancelled, leaving `tree` as it was.
+    pub fn rescan_node(&self, tree: &mut Tree, id: NodeId, tx: Sender<ScanMsg>) -> Option<IdRemap> {
+        let node = tree.nodes.get(id.0 as usize)?;
+        if !matches!(node.kind, NodeKind::Dir) {
+            return None;
+        }
+        // Errors wait for room; progress may be dropped, as in a scan
+        let forward = |msg| match msg {
+            ScanMsg::Error(_) => {
+                let _ = tx.send(msg);
+            }
+            msg => {
+                let _ = tx.try_send(msg);
+            }
+        };
+        match self.scan_dir(tree.path(id), forward) {
+            Some(ScanMsg::Done(subtree)) => tree.splice_subtree(id, Arc::unwrap_or_clone(subtree)),
+            _ => None,
+        }
+    }
+
+    /// Scans `dir` without partial trees or per-directory messages, passing
+    /// everything else the scan sends to `forward`. Returns the closing
+    /// `Done` or `Cancelled` message.
+    fn scan_dir(&self, dir: PathBuf, mut forward: impl FnMut(ScanMsg)) -> Option<ScanMsg> {
+        let (tx, rx) = scan_channel();
+        let inner = Scanner {
+            partial_every: None,
+            dir_done: false,
+            progress: self.progress.clone(),
+            ..self.unpublished()
+        };
+        std::thread::scope(|s| {
+            s.spawn(|| inner.scan(dir, tx));
+            rx.iter().find_map(|msg| match msg {
+                ScanMsg::Done(_) | ScanMsg::Cancelled(_) => Some(msg),
+                msg => {
+                    forward(msg);
+                    None
+                }
             })
+        })
+    }
+
+    /// Like [`Scanner::scan`], but reuses file sizes recorded in the cache at
+    /// `cache_path` for directories whose mtime hasn't changed, and updates
+    /// the cache afterwards. Falls back to a plain scan if the cache can't be
+    /// opened.
+    #[cfg(feature = "cache")]
+    pub fn scan_with_cache(&self, root: PathBuf, cache_path: &Path, tx: Sender<ScanMsg>) {
+        match crate::cache::DirCache::open(cache_path) {
+            Ok(cache) => self.scan_inner(root, tx, Some(&cache), None),
+            Err(e) => {
+                self.report_error(
+                    &tx,
+                    format!("could not open cache {}: {e}", cache_path.display()),
+                );
+                self.scan_inner(root, tx, None, None);
+            }
+        }
+    }
+
+    fn scan_inner(
+        &self,
+        root: PathBuf,
+        tx: Sender<ScanMsg>,
+        store: Option<&dyn DirSizeStore>,
+        resumed: Option<Checkpoint>,
+    ) {
+        let backend = LocalBackend::new(&root, &self.options, store, resumed.as_ref());
+        self.run(&backend, root, &tx, |sink| {
+            if let Some((path, every)) = &self.checkpoint {
+                sink.checkpoint_to(path.clone(), *every);
+            }
+            // What it found in the directories it completed counts as
+            // found by this scan
+            if let Some(checkpoint) = resumed {
+                sink.restore(checkpoint);
+            }
         });
+    }
+}
 
-        // Assemble a tree from the collected file list
-        let files = Arc::try_unwrap(files)
-            .map(|m| m.into_inner())
-            .unwrap_or_else(|arc| arc.lock().clone());
-        let tree = build_tree(&root, files);
-        let _ = tx.send(ScanMsg::Done(tree));
+/// Matcher for the exclude `patterns` of a scan of `root`, or `None` when
+/// there are none; patterns that don't parse are passed to `invalid` and
+/// left out.
+pub(crate) fn exclude_override(
+    root: &Path,
+    patterns: &[String],
+    mut invalid: impl FnMut(String),
+) -> Option<Override> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let mut overrides = OverrideBuilder::new(root);
+    for pattern in patterns {
+        // Overrides are whitelists by default; a leading `!` turns them into ignores.
+        if let Err(e) = overrides.add(&format!("!{}", pattern)) {
+            invalid(format!("invalid exclude pattern {pattern:?}: {e}"));
+        }
     }
+    match overrides.build() {
+        Ok(ov) => Some(ov),
+        Err(e) => {
+            invalid(format!("invalid exclude patterns: {e}"));
+            None
+        }
+    }
+}
+
+/// Sends the partial tree `snapshot` makes, unless the receiver hasn't
+/// emptied the channel yet; each holds the whole tree so far, so they
+/// mustn't queue up behind a slow receiver.
+pub(crate) fn send_partial(tx: &Sender<ScanMsg>, snapshot: impl FnOnce() -> Tree) {
+    if tx.is_empty() {
+        let _ = tx.try_send(ScanMsg::PartialTree(Arc::new(snapshot())));
+    }
+}
+
+fn progress_msg(progress: &Progress) -> ScanMsg {
+    ScanMsg::Progress {
+        scanned: progress.files,
+        discovered: progress.discovered,
+        bytes: progress.bytes,
+        vanished: progress.vanished,
+        elapsed: progress.elapsed,
+        files_per_sec: progress.files_per_sec(),
+        bytes_per_sec: progress.bytes_per_sec(),
+        eta: progress.eta(),
+        current: progress.current.clone(),
+    }
+}
+
+pub(crate) type LinkEntry = (PathBuf, u64, Option<PathBuf>);
+pub(crate) type FileEntry = (PathBuf, CachedFile);
+pub(crate) type Failure = (PathBuf, ScanErrorKind, String);
+
+/// Identifier of the filesystem/volume `path` lives on.
+#[cfg(unix)]
+pub(crate) fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|md| md.dev())
+}
+
+#[cfg(windows)]
+pub(crate) fn device_id(path: &Path) -> Option<u64> {
+    let handle = winapi_util::Handle::from_path_any(path).ok()?;
+    winapi_util::file::information(&handle)
+        .ok()
+        .map(|info| info.volume_serial_number())
 }
 
-fn build_tree(root: &Path, files: Vec<(PathBuf, u64)>) -> Tree {
-    use crate::model::{NodeId, NodeKind, Tree, TreeNode};
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Assembles a [`Tree`] from walked entries as they arrive, so provisional
+/// snapshots can be taken mid-scan. Nodes are only ever appended, so ids in
+/// a snapshot stay valid in later snapshots and in the finished tree.
+///
+/// While building, each directory holds only what was counted in it
+/// directly; the totals of its subtree are summed in one pass when a
+/// snapshot is taken or the tree is finished, so adding a file costs the
+/// same however deep it lies.
+///
+/// When spilling to a node table, every node is also written to the table,
+/// and leaves are only counted in their directory, as below the depth
+/// limit.
+pub(crate) struct TreeBuilder {
+    root: PathBuf,
+    /// See [`ScanOptions::max_depth`].
+    max_depth: Option<usize>,
+    /// See [`ScanOptions::sample_per_mille`].
+    sample: Option<f64>,
+    /// Files sized while sampling.
+    sampled: u64,
+    /// Estimated variance of the size of each directory's own files, while
+    /// sampling.
+    variance: HashMap<NodeId, f64>,
+    nodes: Vec<TreeNode>,
+    names: Interner,
+    /// Paths the tree can't derive from names; see [`TreeNode::needs_anchor`].
+    anchors: HashMap<NodeId, PathBuf>,
+    id_by_path: HashMap<PathBuf, NodeId>,
+    /// The directory a leaf was last added to; files of a directory mostly
+    /// arrive together, so this spares most lookups.
+    last_dir: Option<(PathBuf, NodeId)>,
+    dir_attributes: HashMap<PathBuf, Attributes>,
+    /// See [`ScanOptions::spill`]; dropped if writing to it fails.
+    table: Option<NodeTableWriter>,
+    /// Id in the table of each node, while spilling.
+    table_ids: Vec<NodeId>,
+    /// Folder of the table, and why spilling stopped if it did.
+    spill: Option<(PathBuf, Option<String>)>,
+}
 
-    let root = root.to_path_buf();
-    let mut nodes: Vec<TreeNode> = Vec::with_capacity(1024);
-    let mut id_by_path: HashMap<PathBuf, NodeId> = HashMap::new();
+impl TreeBuilder {
+    pub(crate) fn new(root: &Path, options: &ScanOptions) -> Self {
+        let mut builder = Self {
+            root: root.to_path_buf(),
+            max_depth: options.max_depth,
+            sample: options.sample_fraction(),
+            sampled: 0,
+            variance: HashMap::new(),
+            nodes: Vec::with_capacity(1024),
+            names: Interner::new(),
+            anchors: HashMap::new(),
+            id_by_path: HashMap::new(),
+            last_dir: None,
+            dir_attributes: HashMap::new(),
+            table: None,
+            table_ids: Vec::new(),
+            spill: None,
+        };
+        if let Some(dir) = &options.spill {
+            match NodeTableWriter::create(dir, root) {
+                Ok(table) => {
+                    builder.table = Some(table);
+                    builder.spill = Some((dir.clone(), None));
+                }
+                Err(e) => builder.spill = Some((dir.clone(), Some(e.to_string()))),
+            }
+        }
+        builder.ensure_dir(root);
+        builder
+    }
 
-    // Helper to ensure a directory node exists (and link it to its parent)
-    fn ensure_dir(
-        path: &Path,
-        root: &Path,
-        nodes: &mut Vec<TreeNode>,
-        id_by_path: &mut HashMap<PathBuf, NodeId>,
-    ) -> NodeId {
-        if let Some(id) = id_by_path.get(path).cloned() {
+    /// The directory node at `path`, created (and linked to its parent)
+    /// if it doesn't exist yet.
+    fn ensure_dir(&mut self, path: &Path) -> NodeId {
+        if let Some(id) = self.id_by_path.get(path).cloned() {
             return id;
         }
-        let parent_id = if path == root {
+        let parent_id = if path == self.root {
             None
         } else {
-            let parent = path.parent().unwrap_or(root);
-            Some(ensure_dir(parent, root, nodes, id_by_path))
+            let parent = path.parent().unwrap_or(&self.root).to_path_buf();
+            Some(self.ensure_dir(&parent))
         };
-        let id = NodeId(nodes.len() as u64);
-        let name = path
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or_else(|| path.as_os_str().to_str().unwrap_or(""))
-            .to_string();
-        nodes.push(TreeNode {
+        let id = NodeId(self.nodes.len() as u32);
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        let name = self.names.intern(&name.to_string_lossy());
+        self.nodes.push(TreeNode {
             id,
             parent: parent_id,
-            path: path.to_path_buf(),
             name,
             kind: NodeKind::Dir,
             size: 0,
             file_count: 0,
             children: Vec::new(),
             modified: None,
+            link_target: None,
+            online_only: false,
+            owner: None,
+            attributes: Attributes::default(),
         });
-        id_by_path.insert(path.to_path_buf(), id);
+        self.anchor(id, path);
+        self.id_by_path.insert(path.to_path_buf(), id);
         if let Some(pid) = parent_id {
             // Link as child of parent
-            if let Some(p) = nodes.get_mut(pid.0 as usize) {
+            if let Some(p) = self.nodes.get_mut(pid.0 as usize) {
                 p.children.push(id);
             }
         }
+        if self.table.is_some() {
+            let node = self.nodes[id.0 as usize].clone();
+            if let Some(table_id) = self.spill_node(parent_id, &node) {
+                self.table_ids.push(table_id);
+            }
+        }
         id
     }
 
-    // create root dir node
-    let root_id = ensure_dir(&root, &root, &mut nodes, &mut id_by_path);
+    /// Writes `node` to the table below `parent`, as numbered in the tree,
+    /// and returns its id in the table; `None` when not spilling, or when
+    /// writing failed and spilling stopped.
+    fn spill_node(&mut self, parent: Option<NodeId>, node: &TreeNode) -> Option<NodeId> {
+        let table = self.table.as_mut()?;
+        let parent = parent.map(|p| self.table_ids[p.0 as usize]);
+        match table.push(parent, node) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                // The folders pushed so far are kept, but from here on the
+                // tree has to hold everything
+                self.table = None;
+                if let Some((_, error)) = &mut self.spill {
+                    *error = Some(e.to_string());
+                }
+                None
+            }
+        }
+    }
+
+    fn anchor(&mut self, id: NodeId, path: &Path) {
+        if self.nodes[id.0 as usize].needs_anchor(path) {
+            self.anchors.insert(id, path.to_path_buf());
+        }
+    }
+
+    /// The directory at [`Self::max_depth`] that `path` is counted in, if
+    /// it lies deeper than that.
+    fn cut_off(&self, path: &Path) -> Option<PathBuf> {
+        let max_depth = self.max_depth?;
+        let relative = path.strip_prefix(&self.root).ok()?;
+        (relative.components().count() > max_depth).then(|| {
+            let kept: PathBuf = relative.components().take(max_depth).collect();
+            self.root.join(kept)
+        })
+    }
+
+    /// Counts `size` and `files` in `dir` itself; see [`Self::summed`].
+    fn count_in(&mut self, dir: NodeId, size: u128, files: u64) {
+        let node = &mut self.nodes[dir.0 as usize];
+        node.size = node.size.saturating_add(size);
+        node.file_count = node.file_count.saturating_add(files);
+    }
 
-    // Add files and propagate sizes
-    for (path, sz) in files {
-        let parent_dir = path.parent().unwrap_or(&root);
-        let pid = ensure_dir(parent_dir, &root, &mut nodes, &mut id_by_path);
-        let id = NodeId(nodes.len() as u64);
-        let name = path
+    /// `nodes` with the size and file count of every node added to its
+    /// parent, so each directory holds the totals of its subtree.
+    fn summed(mut nodes: Vec<TreeNode>) -> Vec<TreeNode> {
+        // Children come after their parent, so they are summed first
+        for idx in (1..nodes.len()).rev() {
+            if let Some(parent) = nodes[idx].parent {
+                let (size, files) = (nodes[idx].size, nodes[idx].file_count);
+                let parent = &mut nodes[parent.0 as usize];
+                parent.size = parent.size.saturating_add(size);
+                parent.file_count = parent.file_count.saturating_add(files);
+            }
+        }
+        nodes
+    }
+
+    /// The directory node holding `path`.
+    fn parent_dir(&mut self, path: &Path) -> NodeId {
+        // Only the root has no parent, and it is always the first node
+        let Some(parent) = path.parent() else {
+            return NodeId(0);
+        };
+        if let Some((dir, id)) = &self.last_dir {
+            if dir == parent {
+                return *id;
+            }
+        }
+        let id = self.ensure_dir(parent);
+        self.last_dir = Some((parent.to_path_buf(), id));
+        id
+    }
+
+    /// Appends a leaf under its parent directory. Leaves below the depth
+    /// limit are only counted.
+    fn push_leaf(&mut self, path: &Path, mut leaf: TreeNode) {
+        if let Some(dir) = self.cut_off(path) {
+            let dir = self.ensure_dir(&dir);
+            self.count_in(dir, leaf.size, leaf.file_count);
+            return;
+        }
+        let pid = self.parent_dir(path);
+        let id = NodeId(self.nodes.len() as u32);
+        leaf.id = id;
+        leaf.parent = Some(pid);
+        leaf.name = path
             .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_string();
-        nodes.push(TreeNode {
-            id,
-            parent: Some(pid),
-            path: path.clone(),
-            name,
-            kind: NodeKind::File,
-            size: sz as u128,
-            file_count: 1,
+            .map(|s| self.names.intern(&s.to_string_lossy()))
+            .unwrap_or_default();
+        if self.spill_node(Some(pid), &leaf).is_some() {
+            self.count_in(pid, leaf.size, leaf.file_count);
+            return;
+        }
+        self.nodes.push(leaf);
+        self.nodes[pid.0 as usize].children.push(id);
+        self.anchor(id, path);
+    }
+
+    fn leaf(kind: NodeKind, size: u64) -> TreeNode {
+        TreeNode {
+            id: NodeId(0),
+            parent: None,
+            name: Name::default(),
+            kind,
+            size: size as u128,
+            file_count: 0,
             children: Vec::new(),
             modified: None,
-        });
-        if let Some(p) = nodes.get_mut(pid.0 as usize) {
-            p.children.push(id);
+            link_target: None,
+            online_only: false,
+            owner: None,
+            attributes: Attributes::default(),
         }
+    }
 
-        // Propagate to ancestors
-        let mut cur = Some(parent_dir.to_path_buf());
-        while let Some(dir) = cur {
-            if let Some(did) = id_by_path.get(&dir).cloned() {
-                if let Some(node) = nodes.get_mut(did.0 as usize) {
-                    node.size = node.size.saturating_add(sz as u128);
-                    node.file_count = node.file_count.saturating_add(1);
-                }
-            }
-            if dir == root {
-                break;
+    pub(crate) fn add_file(&mut self, (path, file): FileEntry) {
+        let dir = self
+            .sample
+            .map(|_| path.parent().unwrap_or(&self.root).to_path_buf());
+        let mut node = Self::leaf(NodeKind::File, file.size);
+        node.file_count = 1;
+        node.modified = file.modified;
+        node.online_only = file.online;
+        node.owner = file.owner;
+        node.attributes = file.attributes;
+        self.push_leaf(&path, node);
+        if let (Some(fraction), Some(dir)) = (self.sample, dir) {
+            // Stands in for the files of its directory that weren't sized,
+            // which are estimated to hold `size / fraction` in all
+            let dir = self.cut_off(&dir).unwrap_or(dir);
+            let dir = self.ensure_dir(&dir);
+            let size = file.size as f64;
+            self.count_in(dir, (size / fraction - size) as u128, 0);
+            *self.variance.entry(dir).or_default() +=
+                (1.0 - fraction) * size * size / (fraction * fraction);
+            self.sampled += 1;
+        }
+    }
+
+    /// Counts `files` in `dir` that were left out of the sample.
+    pub(crate) fn add_unsampled(&mut self, dir: &Path, files: u64) {
+        if !dir.starts_with(&self.root) {
+            return;
+        }
+        let dir = self.cut_off(dir).unwrap_or_else(|| dir.to_path_buf());
+        let dir = self.ensure_dir(&dir);
+        self.count_in(dir, 0, files);
+    }
+
+    /// How the sizes were extrapolated, when sampling.
+    fn estimate(&self) -> Option<Estimate> {
+        let fraction = self.sample?;
+        let mut variance = vec![0.0; self.nodes.len()];
+        for (id, own) in &self.variance {
+            variance[id.0 as usize] = *own;
+        }
+        // Children come after their parent, so they are summed first
+        for idx in (1..self.nodes.len()).rev() {
+            if let Some(parent) = self.nodes[idx].parent {
+                variance[parent.0 as usize] += variance[idx];
             }
-            cur = dir.parent().map(|p| p.to_path_buf());
+        }
+        let margins = self
+            .id_by_path
+            .iter()
+            .map(|(path, id)| {
+                (
+                    path.clone(),
+                    (1.96 * variance[id.0 as usize].sqrt()) as u128,
+                )
+            })
+            .collect();
+        Some(Estimate {
+            fraction,
+            sampled: self.sampled,
+            margins,
+        })
+    }
+
+    /// Every directory walked, with its attributes, added by
+    /// [`TreeBuilder::finish`] once the files are in the tree.
+    pub(crate) fn set_dir_attributes(&mut self, attributes: HashMap<PathBuf, Attributes>) {
+        self.dir_attributes = attributes;
+    }
+
+    /// Mount points stay as empty placeholders under their parent directory.
+    pub(crate) fn add_mount(&mut self, path: PathBuf) {
+        self.push_leaf(&path, Self::leaf(NodeKind::MountPoint, 0));
+    }
+
+    /// Sockets, pipes and devices are listed but take no space.
+    pub(crate) fn add_special(&mut self, path: PathBuf) {
+        self.push_leaf(&path, Self::leaf(NodeKind::Other, 0));
+    }
+
+    /// Symlinks are leaves carrying their own (small) size.
+    pub(crate) fn add_link(&mut self, (path, size, target): LinkEntry) {
+        let mut node = Self::leaf(NodeKind::Symlink, size);
+        node.link_target = target;
+        self.push_leaf(&path, node);
+    }
+
+    /// The tree as built so far.
+    pub(crate) fn snapshot(&self) -> Tree {
+        Tree {
+            root: NodeId(0),
+            nodes: Self::summed(self.nodes.clone()),
+            errors: Vec::new(),
+            meta: ScanMeta::default(),
+            anchors: self.anchors.clone(),
         }
     }
 
-    Tree {
-        root: root_id,
-        nodes,
+    pub(crate) fn finish(mut self, failures: Vec<Failure>) -> Tree {
+        // Errors go on the unreadable directory itself, or on a file's parent
+        let mut errors = Vec::with_capacity(failures.len());
+        for (path, kind, message) in failures {
+            if kind == ScanErrorKind::Vanished {
+                // Gone, so marked on the deepest directory left in the tree
+                let node = path
+                    .ancestors()
+                    .skip(1)
+                    .find_map(|dir| self.id_by_path.get(dir).copied())
+                    .unwrap_or(NodeId(0));
+                errors.push(ScanError {
+                    node,
+                    path,
+                    kind,
+                    message,
+                });
+                continue;
+            }
+            // Walked directories are known, so no need to look on disk
+            let is_dir =
+                self.dir_attributes.contains_key(&path) || self.id_by_path.contains_key(&path);
+            let dir = if is_dir {
+                path.as_path()
+            } else {
+                path.parent().unwrap_or(&self.root)
+            };
+            let dir = if dir.starts_with(&self.root) {
+                self.cut_off(dir).unwrap_or_else(|| dir.to_path_buf())
+            } else {
+                self.root.clone()
+            };
+            let node = self.ensure_dir(&dir);
+            errors.push(ScanError {
+                node,
+                path,
+                kind,
+                message,
+            });
+        }
+        // Every walked directory gets a node, even one without files in it
+        let mut dirs: Vec<_> = std::mem::take(&mut self.dir_attributes)
+            .into_iter()
+            .collect();
+        dirs.sort_by(|a, b| a.0.cmp(&b.0));
+        for (path, attributes) in dirs {
+            if !path.starts_with(&self.root) {
+                continue;
+            }
+            if let Some(dir) = self.cut_off(&path) {
+                self.ensure_dir(&dir);
+                continue;
+            }
+            let id = self.ensure_dir(&path);
+            self.nodes[id.0 as usize].attributes = attributes;
+        }
+        let estimate = self.estimate();
+        let nodes = Self::summed(self.nodes);
+        let mut node_table = None;
+        if let Some((dir, mut error)) = self.spill {
+            if let Some(table) = self.table {
+                let totals = nodes.iter().map(|node| {
+                    (
+                        self.table_ids[node.id.0 as usize],
+                        node.size,
+                        node.file_count,
+                    )
+                });
+                match table.finish(totals) {
+                    Ok(_) => node_table = Some(dir.clone()),
+                    Err(e) => error = Some(e.to_string()),
+                }
+            }
+            if let Some(message) = error {
+                errors.push(ScanError {
+                    node: NodeId(0),
+                    message: format!("could not write the node table: {message}"),
+                    path: dir,
+                    kind: ScanErrorKind::Io,
+                });
+            }
+        }
+        Tree {
+            root: NodeId(0),
+            nodes,
+            errors,
+            meta: ScanMeta {
+                max_depth: self.max_depth,
+                estimate,
+                node_table,
+                ..ScanMeta::default()
+            },
+            anchors: self.anchors,
+        }
     }
 }
@@ -0,0 +1,274 @@
+use crossbeam_channel::{Receiver, Sender};
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::handle::ScanControl;
+use crate::model::Tree;
+use crate::scanner::{scan_channel, ScanMsg, ScanOptions, Scanner};
+use crate::watchdog::{GrowthAlert, GrowthRate};
+
+/// Name of every snapshot a schedule saves, before the time it was taken.
+const SNAPSHOT_PREFIX: &str = "scan_";
+
+/// What to scan, how often, and where to keep the results.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    /// Scanned together as one tree, like [`Scanner::scan_many`].
+    pub roots: Vec<PathBuf>,
+    /// Time from the start of one scan to the start of the next. A scan
+    /// that takes longer delays the next one rather than overlapping it.
+    pub every: Duration,
+    /// Folder each scan is saved to, as `scan_YYYYmmdd_HHMMSS.tsz`.
+    pub dir: PathBuf,
+    /// Snapshots to keep in `dir`, deleting the oldest; 0 keeps them all.
+    pub keep: usize,
+    /// Raise [`ScheduleEvent::Grew`] for a root that grew faster than this
+    /// between two scans, averaged over the time between them.
+    pub growth_limit: Option<GrowthRate>,
+}
+
+/// What [`Scheduler`] listeners receive.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ScheduleEvent {
+    /// A scan finished and was saved to `path`.
+    Snapshot { path: PathBuf, tree: Arc<Tree> },
+    /// A root grew faster than [`Schedule::growth_limit`] since the scan
+    /// before; `grown` is the growth between the two.
+    Grew(GrowthAlert),
+    /// A scan couldn't be saved or old snapshots couldn't be removed. The
+    /// schedule carries on.
+    Error(String),
+}
+
+/// Runs the scans of a [`Schedule`] on a thread of its own and tells
+/// listeners about each new snapshot.
+#[derive(Debug)]
+pub struct Scheduler {
+    schedule: Schedule,
+    options: ScanOptions,
+    listeners: Arc<Mutex<Vec<Sender<ScheduleEvent>>>>,
+}
+
+impl Scheduler {
+    pub fn new(schedule: Schedule) -> Self {
+        Self {
+            schedule,
+            options: ScanOptions::default(),
+            listeners: Arc::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: ScanOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Adds a listener for events from now on. Events wait for a listener
+    /// to read them; dropping its receiver unsubscribes it.
+    pub fn subscribe(&self) -> Receiver<ScheduleEvent> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.listeners.lock().push(tx);
+        rx
+    }
+
+    /// Starts scanning right away, then every [`Schedule::every`] until the
+    /// returned handle is stopped. Growth is measured from the newest
+    /// snapshot already in [`Schedule::dir`], if any, so it carries on
+    /// across restarts.
+    pub fn spawn(self) -> ScheduleHandle {
+        let (stop_tx, stop_rx) = crossbeam_channel::bounded(1);
+        let control = Arc::new(Mutex::new(ScanControl::new()));
+        let current = control.clone();
+        let thread = std::thread::spawn(move || self.run(&current, &stop_rx));
+        ScheduleHandle {
+            stop: stop_tx,
+            control,
+            thread,
+        }
+    }
+
+    fn run(&self, control: &Mutex<ScanControl>, stop: &Receiver<()>) {
+        let mut previous = snapshots(&self.schedule.dir)
+            .ok()
+            .and_then(|paths| paths.last().and_then(|path| Tree::load(path).ok()))
+            .map(Arc::new);
+        loop {
+            let started = Instant::now();
+            let scan = ScanControl::new();
+            *control.lock() = scan.clone();
+            let Some(tree) = self.scan(scan) else {
+                return;
+            };
+            if let Some(previous) = &previous {
+                for alert in self.growth_alerts(previous, &tree) {
+                    self.publish(ScheduleEvent::Grew(alert));
+                }
+            }
+            let taken = tree.meta.scanned_at.unwrap_or_else(chrono::Local::now);
+            let path = self.schedule.dir.join(format!(
+                "{SNAPSHOT_PREFIX}{}.tsz",
+                taken.format("%Y%m%d_%H%M%S")
+            ));
+            let saved = std::fs::create_dir_all(&self.schedule.dir)
+                .map_err(|e| e.to_string())
+                .and_then(|_| tree.save(&path).map_err(|e| e.to_string()));
+            let tree = Arc::new(tree);
+            match saved {
+                Ok(()) => {
+                    self.prune();
+                    self.publish(ScheduleEvent::Snapshot {
+                        path,
+                        tree: tree.clone(),
+                    });
+                }
+                Err(e) => self.publish(ScheduleEvent::Error(format!(
+                    "could not save {}: {e}",
+                    path.display()
+                ))),
+            }
+            previous = Some(tree);
+            let wait = self.schedule.every.saturating_sub(started.elapsed());
+            if !matches!(
+                stop.recv_timeout(wait),
+                Err(crossbeam_channel::RecvTimeoutError::Timeout)
+            ) {
+                return;
+            }
+        }
+    }
+
+    /// Scans the roots to completion; `None` if stopped midway.
+    fn scan(&self, control: ScanControl) -> Option<Tree> {
+        let scanner = Scanner::new()
+            .with_options(self.options.clone())
+            .with_control(control);
+        let (tx, rx) = scan_channel();
+        let roots = self.schedule.roots.clone();
+        std::thread::scope(|s| {
+            s.spawn(|| scanner.scan_many(roots, tx));
+            rx.iter().find_map(|msg| match msg {
+                ScanMsg::Done(tree) => Some(Some(Arc::unwrap_or_clone(tree))),
+                ScanMsg::Cancelled(_) => Some(None),
+                _ => None,
+            })
+        })
+        .flatten()
+    }
+
+    /// Roots that grew by more than the limit allows for the time between
+    /// `previous` and `current`.
+    fn growth_alerts(&self, previous: &Tree, current: &Tree) -> Vec<GrowthAlert> {
+        let Some(limit) = self.schedule.growth_limit else {
+            return Vec::new();
+        };
+        let (Some(then), Some(now)) = (previous.meta.scanned_at, current.meta.scanned_at) else {
+            return Vec::new();
+        };
+        let between = (now - then).to_std().unwrap_or_default();
+        let allowed = limit.bytes as f64 * between.as_secs_f64() / limit.per.as_secs_f64();
+        let size =
+            |tree: &Tree, root: &Path| tree.find(root).map(|id| tree.nodes[id.0 as usize].size);
+        self.schedule
+            .roots
+            .iter()
+            .filter_map(|root| {
+                let grown = size(current, root)?.checked_sub(size(previous, root)?)?;
+                (grown as f64 > allowed).then(|| GrowthAlert {
+                    path: root.clone(),
+                    grown,
+                    limit,
+                })
+            })
+            .collect()
+    }
+
+    /// Deletes the oldest snapshots beyond [`Schedule::keep`].
+    fn prune(&self) {
+        if self.schedule.keep == 0 {
+            return;
+        }
+        let paths = match snapshots(&self.schedule.dir) {
+            Ok(paths) => paths,
+            Err(e) => {
+                self.publish(ScheduleEvent::Error(format!(
+                    "could not list {}: {e}",
+                    self.schedule.dir.display()
+                )));
+                return;
+            }
+        };
+        let surplus = paths.len().saturating_sub(self.schedule.keep);
+        for path in &paths[..surplus] {
+            if let Err(e) = std::fs::remove_file(path) {
+                self.publish(ScheduleEvent::Error(format!(
+                    "could not remove {}: {e}",
+                    path.display()
+                )));
+            }
+        }
+    }
+
+    fn publish(&self, event: ScheduleEvent) {
+        self.listeners
+            .lock()
+            .retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+/// A running [`Scheduler`]. Dropping the handle detaches the schedule
+/// rather than stopping it.
+#[derive(Debug)]
+pub struct ScheduleHandle {
+    stop: Sender<()>,
+    control: Arc<Mutex<ScanControl>>,
+    thread: JoinHandle<()>,
+}
+
+impl ScheduleHandle {
+    /// Cancels the scan in progress, if any, and runs no more. A cancelled
+    /// scan is not saved.
+    pub fn stop(&self) {
+        self.control.lock().cancel();
+        let _ = self.stop.try_send(());
+    }
+
+    /// Pauses the scan in progress; the schedule itself keeps time.
+    pub fn pause(&self) {
+        self.control.lock().pause();
+    }
+
+    pub fn resume(&self) {
+        self.control.lock().resume();
+    }
+
+    /// Whether the schedule has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Waits for the schedule to stop. Errors only if it panicked.
+    pub fn join(self) -> std::thread::Result<()> {
+        self.thread.join()
+    }
+}
+
+/// The snapshots a schedule saved in `dir`, oldest first.
+pub fn snapshots(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "tsz")
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(SNAPSHOT_PREFIX))
+        })
+        .collect();
+    // The timestamp in the name sorts chronologically
+    paths.sort();
+    Ok(paths)
+}
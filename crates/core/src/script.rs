@@ -0,0 +1,316 @@
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, AST, INT};
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::export::{self, ExportOptions};
+use crate::human::{human_bytes, parse_bytes};
+use crate::model::{NodeId, NodeKind, Tree};
+use crate::owner::usage_by_owner;
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("script does not compile: {0}")]
+    Compile(String),
+    #[error("script failed: {0}")]
+    Run(String),
+}
+
+/// What a script asked for beyond printing, for the caller to act on.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptOutcome {
+    /// Nodes passed to `tag(node, label)`, in the order they were tagged.
+    pub tags: Vec<(NodeId, String)>,
+    /// Messages passed to `notify(message)`.
+    pub notifications: Vec<String>,
+    /// Files written by `export_report(path)`.
+    pub exported: Vec<PathBuf>,
+}
+
+/// A [Rhai](https://rhai.rs) script run on a scanned tree, for policies
+/// that would otherwise need a change to the program, such as tagging
+/// every folder over 100 GB and mailing the list.
+///
+/// Scripts see the tree through these functions:
+///
+/// - `root()` and `find(path)` give a node; `find` also takes paths
+///   relative to the scanned folder and returns `()` for unknown ones.
+/// - `folders()` and `top_files(n)` give arrays of nodes.
+/// - Nodes have `name`, `path`, `kind`, `size`, `files`, `modified`
+///   (seconds since 1970, or `()`), `age_days`, `owner` (files scanned
+///   with owners only), `is_dir`, `parent`, `children` and `descendants`,
+///   and `owners`, an array of `#{name, bytes, files}` largest first.
+/// - `tag(node, label)`, `notify(message)` and `export_report(path)` are
+///   gathered into the [`ScriptOutcome`]; `export_report` writes the tree
+///   right away as JSON, CSV, PDF or folded stacks by the file's extension.
+/// - `bytes("100 GB")` and `human_bytes(n)` convert sizes.
+///
+/// Sizes above `i64::MAX` bytes are capped there. Scripts run within
+/// [`ScriptLimits`], so a runaway loop fails instead of hanging the report.
+#[derive(Debug, Clone)]
+pub struct Script {
+    ast: AST,
+    limits: ScriptLimits,
+}
+
+/// Bounds on what a script may do before it is stopped with
+/// [`ScriptError::Run`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptLimits {
+    /// Operations, roughly one per expression evaluated.
+    pub max_operations: u64,
+    /// Depth of nested function calls.
+    pub max_call_levels: usize,
+    /// Elements in one array.
+    pub max_array_size: usize,
+    /// Bytes in one string.
+    pub max_string_size: usize,
+}
+
+impl Default for ScriptLimits {
+    /// Ample for walking a tree of tens of millions of nodes a few times,
+    /// and reached in well under a minute by a loop that never ends.
+    fn default() -> Self {
+        Self {
+            max_operations: 1_000_000_000,
+            max_call_levels: 64,
+            max_array_size: 50_000_000,
+            max_string_size: 64 << 20,
+        }
+    }
+}
+
+/// A node of the tree a script runs on.
+#[derive(Clone)]
+struct ScriptNode {
+    tree: Arc<Tree>,
+    id: NodeId,
+}
+
+impl ScriptNode {
+    fn dynamic(tree: &Arc<Tree>, id: NodeId) -> Dynamic {
+        Dynamic::from(ScriptNode {
+            tree: Arc::clone(tree),
+            id,
+        })
+    }
+}
+
+impl Script {
+    /// Reads and compiles the script at `path`.
+    pub fn load(path: &Path) -> Result<Self, ScriptError> {
+        Self::compile(&fs::read_to_string(path)?)
+    }
+
+    pub fn compile(source: &str) -> Result<Self, ScriptError> {
+        let ast = Engine::new()
+            .compile(source)
+            .map_err(|e| ScriptError::Compile(e.to_string()))?;
+        Ok(Self {
+            ast,
+            limits: ScriptLimits::default(),
+        })
+    }
+
+    /// Runs the script within `limits` instead of the defaults.
+    pub fn with_limits(mut self, limits: ScriptLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Runs the script on `tree`. `print` writes to standard output and
+    /// `debug` to standard error.
+    pub fn run(&self, tree: Arc<Tree>) -> Result<ScriptOutcome, ScriptError> {
+        let outcome = Rc::new(RefCell::new(ScriptOutcome::default()));
+        let engine = engine(&tree, &outcome, &self.limits);
+        engine
+            .run_ast(&self.ast)
+            .map_err(|e| ScriptError::Run(e.to_string()))?;
+        drop(engine);
+        Ok(Rc::try_unwrap(outcome)
+            .map(RefCell::into_inner)
+            .unwrap_or_else(|shared| shared.borrow().clone()))
+    }
+}
+
+fn engine(tree: &Arc<Tree>, outcome: &Rc<RefCell<ScriptOutcome>>, limits: &ScriptLimits) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(limits.max_operations)
+        .set_max_call_levels(limits.max_call_levels)
+        .set_max_array_size(limits.max_array_size)
+        .set_max_string_size(limits.max_string_size);
+    engine
+        .register_type_with_name::<ScriptNode>("Node")
+        .register_get("name", |n: &mut ScriptNode| {
+            n.tree.node(n.id).name.to_string()
+        })
+        .register_get("path", |n: &mut ScriptNode| {
+            n.tree.path(n.id).display().to_string()
+        })
+        .register_get("kind", |n: &mut ScriptNode| n.tree.node(n.id).kind.as_str())
+        .register_get("size", |n: &mut ScriptNode| int(n.tree.node(n.id).size))
+        .register_get("files", |n: &mut ScriptNode| {
+            int(n.tree.node(n.id).file_count.into())
+        })
+        .register_get("modified", |n: &mut ScriptNode| {
+            n.tree
+                .node(n.id)
+                .modified
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .map_or(Dynamic::UNIT, |since| {
+                    Dynamic::from(int(since.as_secs().into()))
+                })
+        })
+        .register_get("age_days", |n: &mut ScriptNode| {
+            n.tree
+                .node(n.id)
+                .modified
+                .and_then(|m| SystemTime::now().duration_since(m).ok())
+                .map_or(Dynamic::UNIT, |age| {
+                    Dynamic::from(int((age.as_secs() / 86_400).into()))
+                })
+        })
+        .register_get("owner", |n: &mut ScriptNode| {
+            n.tree
+                .node(n.id)
+                .owner
+                .as_ref()
+                .map_or(Dynamic::UNIT, |owner| {
+                    Dynamic::from(crate::owner::resolve(owner).to_string())
+                })
+        })
+        .register_get("is_dir", |n: &mut ScriptNode| {
+            matches!(n.tree.node(n.id).kind, NodeKind::Dir)
+        })
+        .register_get("parent", |n: &mut ScriptNode| {
+            n.tree
+                .node(n.id)
+                .parent
+                .map_or(Dynamic::UNIT, |parent| ScriptNode::dynamic(&n.tree, parent))
+        })
+        .register_get("children", |n: &mut ScriptNode| -> Array {
+            let tree = &n.tree;
+            let children = &tree.node(n.id).children;
+            children
+                .iter()
+                .map(|&c| ScriptNode::dynamic(tree, c))
+                .collect()
+        })
+        .register_get("descendants", |n: &mut ScriptNode| -> Array {
+            let tree = &n.tree;
+            tree.descendants(n.id)
+                .map(|id| ScriptNode::dynamic(tree, id))
+                .collect()
+        })
+        .register_get("owners", |n: &mut ScriptNode| -> Array {
+            usage_by_owner(n.tree.as_ref(), n.id)
+                .into_iter()
+                .map(|usage| {
+                    let mut entry = Map::new();
+                    entry.insert("name".into(), usage.owner.to_string().into());
+                    entry.insert("bytes".into(), int(usage.bytes).into());
+                    entry.insert("files".into(), int(usage.files.into()).into());
+                    entry.into()
+                })
+                .collect()
+        })
+        .register_fn("to_string", |n: &mut ScriptNode| {
+            n.tree.path(n.id).display().to_string()
+        })
+        .register_fn("to_debug", |n: &mut ScriptNode| {
+            format!("Node({})", n.tree.path(n.id).display())
+        })
+        .register_fn("==", |a: ScriptNode, b: ScriptNode| {
+            Arc::ptr_eq(&a.tree, &b.tree) && a.id == b.id
+        })
+        .register_fn("!=", |a: ScriptNode, b: ScriptNode| {
+            !Arc::ptr_eq(&a.tree, &b.tree) || a.id != b.id
+        });
+
+    let shared = Arc::clone(tree);
+    engine.register_fn("root", move || ScriptNode::dynamic(&shared, shared.root));
+    let shared = Arc::clone(tree);
+    engine.register_fn("find", move |path: &str| {
+        shared
+            .find_by_path(Path::new(path))
+            .map_or(Dynamic::UNIT, |id| ScriptNode::dynamic(&shared, id))
+    });
+    let shared = Arc::clone(tree);
+    engine.register_fn("folders", move || -> Array {
+        shared
+            .nodes
+            .iter()
+            .filter(|node| matches!(node.kind, NodeKind::Dir))
+            .map(|node| ScriptNode::dynamic(&shared, node.id))
+            .collect()
+    });
+    let shared = Arc::clone(tree);
+    engine.register_fn("top_files", move |n: INT| -> Array {
+        shared
+            .top_files(usize::try_from(n).unwrap_or(0))
+            .into_iter()
+            .map(|id| ScriptNode::dynamic(&shared, id))
+            .collect()
+    });
+
+    let tags = Rc::clone(outcome);
+    engine.register_fn("tag", move |node: ScriptNode, label: &str| {
+        tags.borrow_mut().tags.push((node.id, label.to_string()));
+    });
+    let notifications = Rc::clone(outcome);
+    engine.register_fn("notify", move |message: &str| {
+        notifications
+            .borrow_mut()
+            .notifications
+            .push(message.to_string());
+    });
+    let (shared, exported) = (Arc::clone(tree), Rc::clone(outcome));
+    engine.register_fn(
+        "export_report",
+        move |path: &str| -> Result<(), Box<EvalAltResult>> {
+            let path = PathBuf::from(path);
+            export_to(&shared, &path).map_err(|e| format!("{}: {e}", path.display()))?;
+            exported.borrow_mut().exported.push(path);
+            Ok(())
+        },
+    );
+
+    engine
+        .register_fn("bytes", |size: &str| -> Result<INT, Box<EvalAltResult>> {
+            parse_bytes(size)
+                .map(int)
+                .ok_or_else(|| format!("not a size: {size}").into())
+        })
+        .register_fn("human_bytes", |n: INT| {
+            human_bytes(u128::try_from(n).unwrap_or(0))
+        });
+    engine
+}
+
+/// Writes `tree` to `path` in the format its extension names.
+fn export_to(tree: &Tree, path: &Path) -> Result<(), String> {
+    let options = ExportOptions::default();
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    let written = match extension.as_deref() {
+        Some("json") => export::export_json(tree, path, &options),
+        Some("csv") => export::export_csv(tree, path, &options),
+        Some("pdf") => export::export_pdf(tree, path, &options),
+        Some("folded") => export::export_folded(tree, path),
+        _ => return Err("export to .json, .csv, .pdf or .folded".to_string()),
+    };
+    written.map_err(|e| e.to_string())
+}
+
+/// `n` as a script integer, capped at its largest value.
+fn int(n: u128) -> INT {
+    INT::try_from(n).unwrap_or(INT::MAX)
+}
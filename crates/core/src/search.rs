@@ -1,7 +1,52 @@
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
 
 pub fn fuzzy_score(needle: &str, hay: &str) -> Option<i64> {
     let m = SkimMatcherV2::default();
     m.fuzzy_match(hay, needle)
 }
+
+/// How file names are compared when searching and grouping duplicates.
+/// The default compares them exactly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameMatching {
+    /// Treat names that only differ in Unicode composition as equal, e.g.
+    /// "é" stored precomposed (NFC) or as "e" plus an accent (NFD, as macOS
+    /// often does).
+    pub normalize: bool,
+    pub ignore_case: bool,
+}
+
+impl NameMatching {
+    /// Matching that mirrors how this platform's filesystems tell names
+    /// apart: ignoring case on Windows and macOS, whose default filesystems
+    /// are case-insensitive, and always normalizing.
+    pub fn native() -> Self {
+        Self {
+            normalize: true,
+            ignore_case: cfg!(any(windows, target_os = "macos")),
+        }
+    }
+
+    /// `name` in the form names are compared in; two names match exactly
+    /// when their keys are equal.
+    pub fn key<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        let mut key = Cow::Borrowed(name);
+        if self.normalize && is_nfc_quick(name.chars()) != IsNormalized::Yes {
+            key = Cow::Owned(name.nfc().collect());
+        }
+        if self.ignore_case && key.chars().any(char::is_uppercase) {
+            key = Cow::Owned(key.to_lowercase());
+        }
+        key
+    }
+
+    /// Whether `hay` contains `needle_key`, a needle already passed through
+    /// [`NameMatching::key`].
+    pub fn contains(&self, hay: &str, needle_key: &str) -> bool {
+        self.key(hay).contains(needle_key)
+    }
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::model::Tree;
+use crate::search::NameMatching;
+use crate::snapshot::{SnapshotError, FORMAT_VERSION, OLDEST_FORMAT_VERSION};
+
+/// Identifies a shared view; followed by the little-endian `u32`
+/// [`FORMAT_VERSION`] of the tree, the length of the view as a `u32`, the
+/// view as JSON and then the tree.
+const MAGIC: &[u8; 8] = b"TSZVIEW\0";
+
+/// What someone was looking at in a tree, so that another instance can
+/// show exactly the same. Entries are named by path, not [`NodeId`](crate::NodeId).
+///
+/// Stored as JSON, so fields can be added without breaking older files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SharedView {
+    /// Folder being looked at; the root if `None`.
+    pub current: Option<PathBuf>,
+    pub selected: Option<PathBuf>,
+    /// Folder percentages are shares of, if not the folder they are in.
+    pub baseline: Option<PathBuf>,
+    /// Search text the tree is filtered by; empty for none.
+    pub search: String,
+    pub matching: NameMatching,
+    /// Front-end settings such as the sort order, by name. A front end
+    /// ignores the ones it doesn't know.
+    pub settings: BTreeMap<String, String>,
+}
+
+/// A [`SharedView`] together with the tree it is of, in one file.
+#[derive(Debug, Clone)]
+pub struct ViewBundle {
+    pub view: SharedView,
+    pub tree: Tree,
+}
+
+impl ViewBundle {
+    /// Writes the bundle to `path` in the compact binary format of
+    /// [`Tree::save`].
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        let view = serde_json::to_vec(&self.view).map_err(std::io::Error::from)?;
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        out.write_all(&(view.len() as u32).to_le_bytes())?;
+        out.write_all(&view)?;
+        bincode::serialize_into(&mut out, &self.tree)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Reads a bundle written by [`ViewBundle::save`].
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let mut input = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        let mut version = [0u8; 4];
+        input
+            .read_exact(&mut magic)
+            .and_then(|_| input.read_exact(&mut version))
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => SnapshotError::NotASnapshot,
+                _ => SnapshotError::Io(e),
+            })?;
+        if &magic != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        match u32::from_le_bytes(version) {
+            OLDEST_FORMAT_VERSION..=FORMAT_VERSION => {}
+            other => return Err(SnapshotError::UnsupportedVersion(other)),
+        }
+        let mut length = [0u8; 4];
+        input.read_exact(&mut length)?;
+        let mut view = vec![0u8; u32::from_le_bytes(length) as usize];
+        input.read_exact(&mut view)?;
+        Ok(Self {
+            view: serde_json::from_slice(&view).map_err(std::io::Error::from)?,
+            tree: bincode::deserialize_from(input)?,
+        })
+    }
+}
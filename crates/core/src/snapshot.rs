@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::export::{import_json, ExportError};
+use crate::model::Tree;
+
+/// Identifies a saved tree; followed by a little-endian `u32` format version.
+const MAGIC: &[u8; 8] = b"TSZTREE\0";
+/// Bumped whenever the encoded layout of [`Tree`] changes.
+pub const FORMAT_VERSION: u32 = 9;
+/// The oldest format version still read. Trees of version 8 decode as they
+/// are: they only lack [`NodeKind::Archive`](crate::model::NodeKind::Archive)
+/// and [`NodeKind::Other`](crate::model::NodeKind::Other), which were added
+/// after the others.
+pub const OLDEST_FORMAT_VERSION: u32 = 8;
+/// Extension given to files written by [`Tree::save`].
+pub const SNAPSHOT_EXTENSION: &str = "tsz";
+
+/// Folder scans are saved to when no other is chosen, in the per-user
+/// data directory.
+pub fn default_dir() -> PathBuf {
+    let base = dirs::data_local_dir().unwrap_or_else(std::env::temp_dir);
+    base.join("treesize").join("scans")
+}
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("encoding error: {0}")]
+    Encode(#[from] bincode::Error),
+    #[error("not a saved scan")]
+    NotASnapshot,
+    #[error(
+        "saved with format version {0}, this build reads versions \
+         {OLDEST_FORMAT_VERSION} to {FORMAT_VERSION}"
+    )]
+    UnsupportedVersion(u32),
+    #[error(transparent)]
+    Export(#[from] ExportError),
+}
+
+impl Tree {
+    /// Writes the tree to `path` in a compact binary format that
+    /// [`Tree::load`] reads back.
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut out, self)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Reads either a saved scan or a JSON report from
+    /// [`export_json`](crate::export::export_json), telling them apart by
+    /// content.
+    pub fn open(path: &Path) -> Result<Tree, SnapshotError> {
+        match Tree::load(path) {
+            Err(SnapshotError::NotASnapshot) => Ok(import_json(path)?),
+            result => result,
+        }
+    }
+
+    /// Reads a tree written by [`Tree::save`].
+    pub fn load(path: &Path) -> Result<Tree, SnapshotError> {
+        let mut input = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        let mut version = [0u8; 4];
+        input
+            .read_exact(&mut magic)
+            .and_then(|_| input.read_exact(&mut version))
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => SnapshotError::NotASnapshot,
+                _ => SnapshotError::Io(e),
+            })?;
+        if &magic != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        match u32::from_le_bytes(version) {
+            OLDEST_FORMAT_VERSION..=FORMAT_VERSION => {
+                let mut tree: Tree = bincode::deserialize_from(input)?;
+                tree.intern_names();
+                Ok(tree)
+            }
+            other => Err(SnapshotError::UnsupportedVersion(other)),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Local};
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::model::Tree;
+
+/// A scan published to a [`SnapshotStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GenerationInfo {
+    /// Counts up from 1 with every scan published; never reused.
+    pub id: u64,
+    pub scanned_at: Option<DateTime<Local>>,
+    pub published_at: DateTime<Local>,
+    pub size_bytes: u128,
+    pub files: u64,
+}
+
+struct Generation {
+    info: GenerationInfo,
+    tree: Arc<Tree>,
+}
+
+/// Successive scans of the same roots, by generation, for a server to
+/// answer from while the next scan runs.
+///
+/// Readers get an `Arc` to a finished tree and never wait on a scan: a new
+/// generation is only added once its tree is complete, under a lock held
+/// just long enough to insert it. The oldest generations are dropped past
+/// the number kept, though a reader still holding one keeps it alive.
+pub struct SnapshotStore {
+    generations: RwLock<BTreeMap<u64, Generation>>,
+    keep: usize,
+}
+
+impl SnapshotStore {
+    /// Keeps the last `keep` generations, and at least one.
+    pub fn new(keep: usize) -> Self {
+        Self {
+            generations: RwLock::new(BTreeMap::new()),
+            keep: keep.max(1),
+        }
+    }
+
+    /// Adds `tree` as the newest generation and returns its id.
+    pub fn publish(&self, tree: Tree) -> u64 {
+        let root = &tree.nodes[tree.root.0 as usize];
+        let mut info = GenerationInfo {
+            id: 0,
+            scanned_at: tree.meta.scanned_at,
+            published_at: Local::now(),
+            size_bytes: root.size,
+            files: root.file_count,
+        };
+        let tree = Arc::new(tree);
+        let mut generations = self.generations.write();
+        info.id = generations.keys().next_back().map_or(1, |id| id + 1);
+        generations.insert(info.id, Generation { info, tree });
+        while generations.len() > self.keep {
+            generations.pop_first();
+        }
+        info.id
+    }
+
+    /// Every generation kept, newest first.
+    pub fn generations(&self) -> Vec<GenerationInfo> {
+        self.generations
+            .read()
+            .values()
+            .rev()
+            .map(|g| g.info)
+            .collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<(GenerationInfo, Arc<Tree>)> {
+        self.generations
+            .read()
+            .get(&id)
+            .map(|g| (g.info, Arc::clone(&g.tree)))
+    }
+
+    /// The newest generation; `None` until the first scan is published.
+    pub fn latest(&self) -> Option<(GenerationInfo, Arc<Tree>)> {
+        self.generations
+            .read()
+            .values()
+            .next_back()
+            .map(|g| (g.info, Arc::clone(&g.tree)))
+    }
+}
@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+
+use crate::handle::ScanControl;
+use crate::scanner::{scan_channel, ScanMsg, Scanner, SCAN_CHANNEL_CAPACITY};
+
+/// Item of a [`ScanStream`]; the same messages [`Scanner::scan`] sends.
+pub type ScanEvent = ScanMsg;
+
+/// Events of a scan running on background threads, returned by
+/// [`Scanner::scan_stream`]. Ends after [`ScanMsg::Done`] or
+/// [`ScanMsg::Cancelled`]. Dropping the stream cancels the scan.
+///
+/// Delivery follows [`ScanMsg`]: progress is coalesced while the consumer
+/// lags, `Error`, `Done` and `Cancelled` always arrive. Needs no
+/// particular runtime.
+pub struct ScanStream {
+    inner: ReceiverStream<ScanEvent>,
+    control: ScanControl,
+}
+
+impl ScanStream {
+    pub(crate) fn spawn(scanner: Scanner, root: PathBuf) -> Self {
+        let (tx, rx) = scan_channel();
+        let (async_tx, async_rx) = tokio::sync::mpsc::channel(SCAN_CHANNEL_CAPACITY);
+        let control = scanner.spawn(root, tx).control().clone();
+        std::thread::spawn({
+            let control = control.clone();
+            move || {
+                for msg in rx {
+                    if async_tx.blocking_send(msg).is_err() {
+                        // Stream dropped; stop the walk rather than finish it unseen
+                        control.cancel();
+                        break;
+                    }
+                }
+            }
+        });
+        Self {
+            inner: ReceiverStream::new(async_rx),
+            control,
+        }
+    }
+}
+
+impl Stream for ScanStream {
+    type Item = ScanEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ScanEvent>> {
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+}
+
+impl Drop for ScanStream {
+    fn drop(&mut self) {
+        self.control.cancel();
+    }
+}
@@ -0,0 +1,109 @@
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+
+/// A named data stream of a file besides its main content, as NTFS keeps
+/// for e.g. the mark on files downloaded from the internet. Its bytes take
+/// up disk space but aren't part of the file's reported length.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AltStream {
+    /// Name without the leading `:` and the `:$DATA` type, e.g.
+    /// `Zone.Identifier`.
+    pub name: String,
+    pub size: u64,
+}
+
+/// The alternate data streams of `path`. Empty on filesystems and
+/// platforms that don't have them.
+pub fn alternate_streams(path: &Path) -> io::Result<Vec<AltStream>> {
+    platform::streams(path)
+}
+
+/// Total bytes in the alternate data streams of `path`.
+pub fn alternate_stream_bytes(path: &Path) -> io::Result<u64> {
+    Ok(alternate_streams(path)?.iter().map(|s| s.size).sum())
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::AltStream;
+    use std::ffi::OsString;
+    use std::io;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{
+        ERROR_HANDLE_EOF, ERROR_INVALID_FUNCTION, INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{
+        FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard,
+        WIN32_FIND_STREAM_DATA,
+    };
+
+    pub(super) fn streams(path: &Path) -> io::Result<Vec<AltStream>> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        // SAFETY: a plain C struct, valid when zeroed
+        let mut data: WIN32_FIND_STREAM_DATA = unsafe { std::mem::zeroed() };
+        // SAFETY: `wide` is NUL-terminated and `data` is the struct the
+        // standard info level fills in
+        let handle = unsafe {
+            FindFirstStreamW(
+                wide.as_ptr(),
+                FindStreamInfoStandard,
+                std::ptr::addr_of_mut!(data).cast(),
+                0,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            let err = io::Error::last_os_error();
+            // No streams at all (e.g. some directories), or a filesystem
+            // without them such as FAT
+            return match err.raw_os_error().map(|code| code as u32) {
+                Some(ERROR_HANDLE_EOF | ERROR_INVALID_FUNCTION) => Ok(Vec::new()),
+                _ => Err(err),
+            };
+        }
+        let mut streams = Vec::new();
+        let result = loop {
+            let len = data
+                .cStreamName
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(data.cStreamName.len());
+            let name = OsString::from_wide(&data.cStreamName[..len]);
+            // `::$DATA`, the unnamed stream, is the file's content
+            if let Some(name) = name
+                .to_string_lossy()
+                .strip_prefix(':')
+                .and_then(|name| name.strip_suffix(":$DATA"))
+                .filter(|name| !name.is_empty())
+            {
+                streams.push(AltStream {
+                    name: name.to_string(),
+                    size: data.StreamSize as u64,
+                });
+            }
+            // SAFETY: `handle` is open and `data` is as above
+            if unsafe { FindNextStreamW(handle, std::ptr::addr_of_mut!(data).cast()) } == 0 {
+                let err = io::Error::last_os_error();
+                break match err.raw_os_error().map(|code| code as u32) {
+                    Some(ERROR_HANDLE_EOF) => Ok(streams),
+                    _ => Err(err),
+                };
+            }
+        };
+        // SAFETY: `handle` came from FindFirstStreamW and is closed once
+        unsafe { FindClose(handle) };
+        result
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use super::AltStream;
+    use std::io;
+    use std::path::Path;
+
+    pub(super) fn streams(_path: &Path) -> io::Result<Vec<AltStream>> {
+        Ok(Vec::new())
+    }
+}
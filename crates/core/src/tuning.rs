@@ -0,0 +1,555 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Kind of storage a scan root lives on, which decides how hard the walker
+/// can push it: SSDs handle many parallel reads, spinning disks seek between
+/// them, and network shares are latency bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    Ssd,
+    Hdd,
+    Network,
+    #[default]
+    Unknown,
+}
+
+impl StorageKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            StorageKind::Ssd => "SSD",
+            StorageKind::Hdd => "HDD",
+            StorageKind::Network => "Network",
+            StorageKind::Unknown => "Unknown",
+        }
+    }
+}
+
+impl std::str::FromStr for StorageKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ssd" => Ok(StorageKind::Ssd),
+            "hdd" => Ok(StorageKind::Hdd),
+            "network" => Ok(StorageKind::Network),
+            "unknown" => Ok(StorageKind::Unknown),
+            _ => Err(format!(
+                "unknown storage type {s:?} (expected ssd, hdd or network)"
+            )),
+        }
+    }
+}
+
+/// Best guess at the storage holding `path`. On Linux, network filesystems
+/// are recognised from the mount table and local disks from the kernel's
+/// rotational flag; on macOS from the mount flags and the disk's IOKit
+/// medium type; on Windows from the drive type and the volume's seek
+/// penalty. Anything else, or a disk that doesn't say, is
+/// [`StorageKind::Unknown`].
+pub fn detect_storage(path: &Path) -> StorageKind {
+    #[cfg(target_os = "linux")]
+    {
+        linux::detect(path).unwrap_or_default()
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos::detect(path).unwrap_or_default()
+    }
+    #[cfg(windows)]
+    {
+        windows::detect(path).unwrap_or_default()
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    {
+        let _ = path;
+        StorageKind::Unknown
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) mod linux {
+    use super::StorageKind;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+
+    pub(crate) const NETWORK_FS: &[&str] = &[
+        "nfs",
+        "nfs4",
+        "cifs",
+        "smb3",
+        "smbfs",
+        "9p",
+        "afs",
+        "ceph",
+        "glusterfs",
+        "lustre",
+        "davfs",
+        "fuse.sshfs",
+        "fuse.rclone",
+    ];
+
+    pub(super) fn detect(path: &Path) -> Option<StorageKind> {
+        let path = std::fs::canonicalize(path).ok()?;
+        if let Some(fs_type) = mount_fs_type(&path) {
+            if NETWORK_FS.contains(&fs_type.as_str()) {
+                return Some(StorageKind::Network);
+            }
+        }
+        let dev = std::fs::metadata(&path).ok()?.dev();
+        let (major, minor) = (
+            ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff),
+            (dev & 0xff) | ((dev >> 12) & !0xff),
+        );
+        let device = std::fs::canonicalize(format!("/sys/dev/block/{major}:{minor}")).ok()?;
+        // Partitions keep the flag on their parent disk
+        let flag = [
+            device.join("queue/rotational"),
+            device.join("../queue/rotational"),
+        ]
+        .iter()
+        .find_map(|p| std::fs::read_to_string(p).ok())?;
+        match flag.trim() {
+            "0" => Some(StorageKind::Ssd),
+            "1" => Some(StorageKind::Hdd),
+            _ => None,
+        }
+    }
+
+    /// Filesystem type of the innermost mount containing `path`.
+    fn mount_fs_type(path: &Path) -> Option<String> {
+        mount_of(path).map(|(fs_type, _)| fs_type)
+    }
+
+    /// Filesystem type and source, such as `/dev/sda1`, of the innermost
+    /// mount containing `path`.
+    pub(crate) fn mount_of(path: &Path) -> Option<(String, String)> {
+        let table = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+        table
+            .lines()
+            .filter_map(|line| {
+                let (left, right) = line.split_once(" - ")?;
+                let mount_point = PathBuf::from(unescape(left.split(' ').nth(4)?));
+                let mut right = right.split(' ');
+                let (fs_type, source) = (right.next()?, right.next()?);
+                path.starts_with(&mount_point).then(|| {
+                    (
+                        mount_point.as_os_str().len(),
+                        (fs_type.to_string(), unescape(source)),
+                    )
+                })
+            })
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, mount)| mount)
+    }
+
+    /// Undoes the octal escapes (`\040` for space, ...) used in mountinfo.
+    pub(crate) fn unescape(field: &str) -> String {
+        let bytes = field.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'\\' && i + 3 < bytes.len() {
+                if let Ok(v) = u8::from_str_radix(&field[i + 1..i + 4], 8) {
+                    out.push(v);
+                    i += 4;
+                    continue;
+                }
+            }
+            out.push(bytes[i]);
+            i += 1;
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::StorageKind;
+    use std::ffi::{c_char, c_void, CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    type CFTypeRef = *const c_void;
+    type IoObject = u32;
+
+    const MAIN_PORT_DEFAULT: u32 = 0;
+    const ITERATE_RECURSIVELY: u32 = 1;
+    const ITERATE_PARENTS: u32 = 2;
+    const UTF8: u32 = 0x0800_0100;
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOBSDNameMatching(main_port: u32, options: u32, bsd_name: *const c_char) -> CFTypeRef;
+        fn IOServiceGetMatchingService(main_port: u32, matching: CFTypeRef) -> IoObject;
+        fn IORegistryEntrySearchCFProperty(
+            entry: IoObject,
+            plane: *const c_char,
+            key: CFTypeRef,
+            allocator: CFTypeRef,
+            options: u32,
+        ) -> CFTypeRef;
+        fn IOObjectRelease(object: IoObject) -> i32;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringCreateWithCString(
+            allocator: CFTypeRef,
+            text: *const c_char,
+            encoding: u32,
+        ) -> CFTypeRef;
+        fn CFDictionaryGetValue(dict: CFTypeRef, key: CFTypeRef) -> CFTypeRef;
+        fn CFDictionaryGetTypeID() -> usize;
+        fn CFGetTypeID(value: CFTypeRef) -> usize;
+        fn CFEqual(a: CFTypeRef, b: CFTypeRef) -> u8;
+        fn CFRelease(value: CFTypeRef);
+    }
+
+    /// A Core Foundation string, released when dropped.
+    struct CfString(CFTypeRef);
+
+    impl CfString {
+        fn new(text: &CStr) -> Option<Self> {
+            // SAFETY: `text` is NUL-terminated; a null result is an error
+            let string =
+                unsafe { CFStringCreateWithCString(std::ptr::null(), text.as_ptr(), UTF8) };
+            (!string.is_null()).then_some(Self(string))
+        }
+    }
+
+    impl Drop for CfString {
+        fn drop(&mut self) {
+            // SAFETY: created above and released once
+            unsafe { CFRelease(self.0) };
+        }
+    }
+
+    pub(super) fn detect(path: &Path) -> Option<StorageKind> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        // SAFETY: a plain C struct, valid when zeroed
+        let mut fs: libc::statfs = unsafe { std::mem::zeroed() };
+        // SAFETY: `path` is NUL-terminated and `fs` is the struct it fills
+        if unsafe { libc::statfs(path.as_ptr(), &mut fs) } != 0 {
+            return None;
+        }
+        if fs.f_flags & libc::MNT_LOCAL as u32 == 0 {
+            return Some(StorageKind::Network);
+        }
+        // SAFETY: the kernel NUL-terminates this fixed-size buffer
+        let source = unsafe { CStr::from_ptr(fs.f_mntfromname.as_ptr()) };
+        let disk = CString::new(source.to_bytes().strip_prefix(b"/dev/")?).ok()?;
+        medium_type(&disk)
+    }
+
+    /// The `Medium Type` the disk holding BSD device `disk` (`disk3s1`,
+    /// ...) reports in its `Device Characteristics`. APFS volumes find it
+    /// through their container on the physical disk.
+    fn medium_type(disk: &CStr) -> Option<StorageKind> {
+        let characteristics = CfString::new(c"Device Characteristics")?;
+        let medium = CfString::new(c"Medium Type")?;
+        let solid = CfString::new(c"Solid State")?;
+        let rotational = CfString::new(c"Rotational")?;
+        // SAFETY: `disk` is NUL-terminated; the matching dictionary is
+        // consumed by IOServiceGetMatchingService
+        let service = unsafe {
+            let matching = IOBSDNameMatching(MAIN_PORT_DEFAULT, 0, disk.as_ptr());
+            if matching.is_null() {
+                return None;
+            }
+            IOServiceGetMatchingService(MAIN_PORT_DEFAULT, matching)
+        };
+        if service == 0 {
+            return None;
+        }
+        // SAFETY: `service` is a live registry entry and the key a valid
+        // string; the returned property is owned here
+        let found = unsafe {
+            IORegistryEntrySearchCFProperty(
+                service,
+                c"IOService".as_ptr(),
+                characteristics.0,
+                std::ptr::null(),
+                ITERATE_RECURSIVELY | ITERATE_PARENTS,
+            )
+        };
+        // SAFETY: `service` came from IOServiceGetMatchingService
+        unsafe { IOObjectRelease(service) };
+        if found.is_null() {
+            return None;
+        }
+        // SAFETY: `found` is checked to be a dictionary before it is read,
+        // and released once after
+        unsafe {
+            let kind = if CFGetTypeID(found) == CFDictionaryGetTypeID() {
+                let value = CFDictionaryGetValue(found, medium.0);
+                if value.is_null() {
+                    None
+                } else if CFEqual(value, solid.0) != 0 {
+                    Some(StorageKind::Ssd)
+                } else if CFEqual(value, rotational.0) != 0 {
+                    Some(StorageKind::Hdd)
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+            CFRelease(found);
+            kind
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::StorageKind;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        CreateFileW, GetDriveTypeW, GetVolumeNameForVolumeMountPointW, GetVolumePathNameW,
+        FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+    };
+    use windows_sys::Win32::System::Ioctl::{
+        PropertyStandardQuery, StorageDeviceSeekPenaltyProperty, DEVICE_SEEK_PENALTY_DESCRIPTOR,
+        IOCTL_STORAGE_QUERY_PROPERTY, STORAGE_PROPERTY_QUERY,
+    };
+    use windows_sys::Win32::System::WindowsProgramming::DRIVE_REMOTE;
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    pub(super) fn detect(path: &Path) -> Option<StorageKind> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut root = [0u16; 261];
+        // SAFETY: `wide` is NUL-terminated and `root` as long as passed
+        if unsafe { GetVolumePathNameW(wide.as_ptr(), root.as_mut_ptr(), root.len() as u32) } == 0 {
+            return None;
+        }
+        // SAFETY: `root` was NUL-terminated by GetVolumePathNameW
+        if unsafe { GetDriveTypeW(root.as_ptr()) } == DRIVE_REMOTE {
+            return Some(StorageKind::Network);
+        }
+        // `\\?\Volume{...}\`, which also covers volumes mounted in folders
+        let mut volume = [0u16; 50];
+        // SAFETY: as above, with `volume` as long as passed
+        if unsafe {
+            GetVolumeNameForVolumeMountPointW(
+                root.as_ptr(),
+                volume.as_mut_ptr(),
+                volume.len() as u32,
+            )
+        } == 0
+        {
+            return None;
+        }
+        // Without the trailing backslash the name opens the volume itself
+        let len = volume.iter().position(|&c| c == 0)?;
+        volume[len - 1] = 0;
+        seek_penalty(&volume).map(|penalty| {
+            if penalty {
+                StorageKind::Hdd
+            } else {
+                StorageKind::Ssd
+            }
+        })
+    }
+
+    /// Whether the disk under `volume` seeks, as reported by its driver.
+    /// Volumes spanning several disks don't answer.
+    fn seek_penalty(volume: &[u16]) -> Option<bool> {
+        // SAFETY: `volume` is NUL-terminated; no access rights are asked
+        // for, which is enough to query the device
+        let handle = unsafe {
+            CreateFileW(
+                volume.as_ptr(),
+                0,
+                FILE_SHARE_READ | FILE_SHARE_WRITE,
+                std::ptr::null(),
+                OPEN_EXISTING,
+                0,
+                0,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return None;
+        }
+        let query = STORAGE_PROPERTY_QUERY {
+            PropertyId: StorageDeviceSeekPenaltyProperty,
+            QueryType: PropertyStandardQuery,
+            AdditionalParameters: [0],
+        };
+        // SAFETY: a plain C struct, valid when zeroed
+        let mut descriptor: DEVICE_SEEK_PENALTY_DESCRIPTOR = unsafe { std::mem::zeroed() };
+        let mut returned = 0u32;
+        // SAFETY: `handle` is open and both buffers are the sizes passed
+        let ok = unsafe {
+            DeviceIoControl(
+                handle,
+                IOCTL_STORAGE_QUERY_PROPERTY,
+                std::ptr::addr_of!(query).cast(),
+                std::mem::size_of_val(&query) as u32,
+                std::ptr::addr_of_mut!(descriptor).cast(),
+                std::mem::size_of_val(&descriptor) as u32,
+                &mut returned,
+                std::ptr::null_mut(),
+            )
+        };
+        // SAFETY: `handle` came from CreateFileW and is closed once
+        unsafe { CloseHandle(handle) };
+        (ok != 0 && returned as usize >= std::mem::size_of_val(&descriptor))
+            .then_some(descriptor.IncursSeekPenalty != 0)
+    }
+}
+
+/// Walker tuning. Fields left as `None` are filled in from the preset for
+/// the storage being scanned; see [`ScanTuning::resolve`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ScanTuning {
+    /// Worker threads walking directories in parallel.
+    pub threads: Option<usize>,
+    /// Files each worker sizes before publishing them together with a
+    /// progress update. Larger batches mean less lock contention but
+    /// coarser progress.
+    pub batch_size: Option<usize>,
+    /// Most directories read at once. Each worker reads one directory at a
+    /// time, so this caps the thread count.
+    pub max_open_dirs: Option<usize>,
+    /// Pause each worker takes after every entry, to leave the disk to
+    /// other programs. No preset sets one.
+    pub entry_delay: Option<Duration>,
+    /// Run the workers at background priority, so other programs' disk
+    /// and CPU use comes first; see [`enter_background`].
+    pub background: bool,
+}
+
+impl ScanTuning {
+    /// Settings known to work well for `storage`.
+    pub fn preset(storage: StorageKind) -> Self {
+        let cpus = num_cpus::get();
+        let (threads, batch_size, max_open_dirs) = match storage {
+            StorageKind::Ssd => (cpus.max(4), 256, 256),
+            StorageKind::Hdd => (2, 128, 4),
+            StorageKind::Network => ((cpus * 4).clamp(8, 32), 32, 64),
+            StorageKind::Unknown => (cpus, 128, 128),
+        };
+        Self {
+            threads: Some(threads),
+            batch_size: Some(batch_size),
+            max_open_dirs: Some(max_open_dirs),
+            entry_delay: None,
+            background: false,
+        }
+    }
+
+    /// Fills unset fields from the preset for `storage`. The returned
+    /// tuning has every field but `entry_delay` set, with `threads` within
+    /// `max_open_dirs`.
+    pub fn resolve(&self, storage: StorageKind) -> Self {
+        let preset = Self::preset(storage);
+        let max_open_dirs = self
+            .max_open_dirs
+            .or(preset.max_open_dirs)
+            .unwrap_or(1)
+            .max(1);
+        let threads = self.threads.or(preset.threads).unwrap_or(1).max(1);
+        Self {
+            threads: Some(threads.min(max_open_dirs)),
+            batch_size: Some(self.batch_size.or(preset.batch_size).unwrap_or(1).max(1)),
+            max_open_dirs: Some(max_open_dirs),
+            entry_delay: self.entry_delay,
+            background: self.background,
+        }
+    }
+}
+
+/// Lowers the calling thread's disk and CPU priority for the rest of its
+/// life: the idle I/O class and lowest nice value on Linux, throttled I/O on
+/// macOS, and background mode (low I/O and memory priority) on Windows.
+/// Only the thread is affected, so an app's UI stays responsive.
+pub fn enter_background() -> io::Result<()> {
+    background::enter()
+}
+
+#[cfg(target_os = "linux")]
+mod background {
+    use std::io;
+
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+    pub(super) fn enter() -> io::Result<()> {
+        // SAFETY: plain syscalls on the calling thread (who = 0 / its tid)
+        unsafe {
+            let tid = libc::gettid();
+            if libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0,
+                IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            ) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, 19) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod background {
+    use std::io;
+
+    const IOPOL_TYPE_DISK: libc::c_int = 0;
+    const IOPOL_SCOPE_THREAD: libc::c_int = 1;
+    const IOPOL_THROTTLE: libc::c_int = 3;
+
+    extern "C" {
+        fn setiopolicy_np(
+            iotype: libc::c_int,
+            scope: libc::c_int,
+            policy: libc::c_int,
+        ) -> libc::c_int;
+    }
+
+    pub(super) fn enter() -> io::Result<()> {
+        // SAFETY: only changes the calling thread's I/O policy
+        if unsafe { setiopolicy_np(IOPOL_TYPE_DISK, IOPOL_SCOPE_THREAD, IOPOL_THROTTLE) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod background {
+    use std::io;
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_BEGIN,
+    };
+
+    pub(super) fn enter() -> io::Result<()> {
+        // SAFETY: the pseudo handle always refers to the calling thread
+        if unsafe { SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_BEGIN) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod background {
+    use std::io;
+
+    pub(super) fn enter() -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "background priority is not supported on this platform",
+        ))
+    }
+}
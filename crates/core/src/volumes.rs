@@ -0,0 +1,266 @@
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A mounted drive or volume and how full it is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Volume {
+    /// Where the volume is reachable, e.g. `C:\` or `/home`.
+    pub mount_point: PathBuf,
+    /// Volume label on Windows, device or share elsewhere; may be empty.
+    pub name: String,
+    pub fs_type: String,
+    pub total_bytes: u64,
+    /// Space the current user can still write, which may be less than what
+    /// is free on the volume when some of it is reserved.
+    pub free_bytes: u64,
+}
+
+impl Volume {
+    pub fn used_bytes(&self) -> u64 {
+        self.total_bytes.saturating_sub(self.free_bytes)
+    }
+
+    /// Used share of the volume, from 0.0 to 1.0.
+    pub fn used_fraction(&self) -> f32 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        (self.used_bytes() as f64 / self.total_bytes as f64) as f32
+    }
+}
+
+/// Mounted drives and volumes holding user data, sorted by mount point.
+/// Pseudo filesystems, empty drives and bind mounts of a volume already
+/// listed are left out.
+pub fn list_volumes() -> io::Result<Vec<Volume>> {
+    let mut volumes = platform::volumes()?;
+    volumes.retain(|v| v.total_bytes > 0);
+    volumes.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+    Ok(volumes)
+}
+
+/// Total and available bytes of the volume holding `path`.
+pub fn space(path: &Path) -> io::Result<(u64, u64)> {
+    platform::space(path)
+}
+
+#[cfg(unix)]
+// The statvfs field types vary between platforms
+#[allow(clippy::useless_conversion)]
+fn statvfs(path: &Path) -> io::Result<(u64, u64)> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stat` is only read on success
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        stat.assume_init()
+    };
+    let block = u64::from(stat.f_frsize);
+    Ok((
+        u64::from(stat.f_blocks) * block,
+        u64::from(stat.f_bavail) * block,
+    ))
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::Volume;
+    use crate::tuning::linux::{unescape, NETWORK_FS};
+    use std::collections::HashSet;
+    use std::io;
+    use std::path::PathBuf;
+
+    pub(super) use super::statvfs as space;
+
+    pub(super) fn volumes() -> io::Result<Vec<Volume>> {
+        let table = std::fs::read_to_string("/proc/self/mountinfo")?;
+        let mut devices = HashSet::new();
+        let mut volumes = Vec::new();
+        for line in table.lines() {
+            let Some((left, right)) = line.split_once(" - ") else {
+                continue;
+            };
+            let fields: Vec<&str> = left.split(' ').collect();
+            let mut right = right.split(' ');
+            let (Some(fs_type), Some(source)) = (right.next(), right.next()) else {
+                continue;
+            };
+            if fields.len() < 5 {
+                continue;
+            }
+            let (device, mounted_root) = (fields[2], fields[3]);
+            let is_disk = source.starts_with("/dev/") && !source.starts_with("/dev/loop");
+            if !(is_disk || NETWORK_FS.contains(&fs_type)) {
+                continue;
+            }
+            // Bind mounts show up again under the same device
+            if mounted_root != "/" || !devices.insert(device.to_string()) {
+                continue;
+            }
+            let mount_point = PathBuf::from(unescape(fields[4]));
+            let Ok((total_bytes, free_bytes)) = space(&mount_point) else {
+                continue;
+            };
+            volumes.push(Volume {
+                mount_point,
+                name: unescape(source),
+                fs_type: fs_type.to_string(),
+                total_bytes,
+                free_bytes,
+            });
+        }
+        Ok(volumes)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::Volume;
+    use std::ffi::CStr;
+    use std::io;
+    use std::path::PathBuf;
+
+    pub(super) use super::statvfs as space;
+
+    pub(super) fn volumes() -> io::Result<Vec<Volume>> {
+        let mut mounts: *mut libc::statfs = std::ptr::null_mut();
+        // SAFETY: getmntinfo points `mounts` at `count` entries it owns
+        let mounts = unsafe {
+            let count = libc::getmntinfo(&mut mounts, libc::MNT_NOWAIT);
+            if count <= 0 {
+                return Err(io::Error::last_os_error());
+            }
+            std::slice::from_raw_parts(mounts, count as usize)
+        };
+        let text = |field: &[libc::c_char]| {
+            // SAFETY: the kernel NUL-terminates these fixed-size buffers
+            unsafe { CStr::from_ptr(field.as_ptr()) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        Ok(mounts
+            .iter()
+            // System volumes hidden from Finder, devfs, autofs maps, ...
+            .filter(|fs| (fs.f_flags & libc::MNT_DONTBROWSE as u32) == 0)
+            .map(|fs| Volume {
+                mount_point: PathBuf::from(text(&fs.f_mntonname)),
+                name: text(&fs.f_mntfromname),
+                fs_type: text(&fs.f_fstypename),
+                total_bytes: fs.f_blocks * u64::from(fs.f_bsize),
+                free_bytes: fs.f_bavail * u64::from(fs.f_bsize),
+            })
+            .collect())
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+mod platform {
+    use super::Volume;
+    use std::io;
+    use std::path::PathBuf;
+
+    pub(super) use super::statvfs as space;
+
+    /// Mount tables differ between the BSDs; only the root is listed.
+    pub(super) fn volumes() -> io::Result<Vec<Volume>> {
+        let mount_point = PathBuf::from("/");
+        let (total_bytes, free_bytes) = space(&mount_point)?;
+        Ok(vec![Volume {
+            mount_point,
+            name: String::new(),
+            fs_type: String::new(),
+            total_bytes,
+            free_bytes,
+        }])
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::Volume;
+    use std::ffi::OsString;
+    use std::io;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetDiskFreeSpaceExW, GetLogicalDrives, GetVolumeInformationW,
+    };
+
+    fn wide(path: &Path) -> Vec<u16> {
+        path.as_os_str().encode_wide().chain(Some(0)).collect()
+    }
+
+    fn from_wide(buf: &[u16]) -> String {
+        let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        OsString::from_wide(&buf[..len])
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub(super) fn space(path: &Path) -> io::Result<(u64, u64)> {
+        let path = wide(path);
+        let (mut available, mut total) = (0u64, 0u64);
+        // SAFETY: `path` is NUL-terminated; the outputs are plain integers
+        let ok = unsafe {
+            GetDiskFreeSpaceExW(
+                path.as_ptr(),
+                &mut available,
+                &mut total,
+                std::ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((total, available))
+    }
+
+    pub(super) fn volumes() -> io::Result<Vec<Volume>> {
+        // SAFETY: no arguments; returns a bitmask of drive letters
+        let drives = unsafe { GetLogicalDrives() };
+        if drives == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut volumes = Vec::new();
+        for letter in (0..26u8).filter(|i| drives & (1 << i) != 0) {
+            let mount_point = PathBuf::from(format!("{}:\\", (b'A' + letter) as char));
+            // Card readers and optical drives without media fail here
+            let Ok((total_bytes, free_bytes)) = space(&mount_point) else {
+                continue;
+            };
+            let (mut label, mut fs_type) = ([0u16; 261], [0u16; 261]);
+            let root = wide(&mount_point);
+            // SAFETY: both buffers are as large as the sizes passed
+            let ok = unsafe {
+                GetVolumeInformationW(
+                    root.as_ptr(),
+                    label.as_mut_ptr(),
+                    label.len() as u32,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    fs_type.as_mut_ptr(),
+                    fs_type.len() as u32,
+                )
+            };
+            if ok == 0 {
+                label[0] = 0;
+                fs_type[0] = 0;
+            }
+            volumes.push(Volume {
+                mount_point,
+                name: from_wide(&label),
+                fs_type: from_wide(&fs_type),
+                total_bytes,
+                free_bytes,
+            });
+        }
+        Ok(volumes)
+    }
+}
@@ -0,0 +1,196 @@
+use crossbeam_channel::Sender;
+use ignore::overrides::Override;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::RwLock;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+use crate::scanner::{device_id, exclude_override, ScanMsg, ScanOptions};
+
+/// Keeps a recursive filesystem watch alive; dropping it stops the updates.
+pub struct TreeWatcher {
+    watcher: RecommendedWatcher,
+    filter: Arc<RwLock<WatchFilter>>,
+}
+
+impl TreeWatcher {
+    /// Also watches `root`, reporting to the same channel; for trees joining
+    /// several scan roots.
+    pub fn add(&mut self, root: &Path) -> notify::Result<()> {
+        self.filter.write().add(root);
+        self.watcher.watch(root, RecursiveMode::Recursive)
+    }
+}
+
+/// A watched root, with what the scan of it left out.
+struct WatchedRoot {
+    path: PathBuf,
+    excludes: Option<Override>,
+    /// Filesystem of the root, when the scan stayed on it.
+    device: Option<u64>,
+}
+
+/// Tells which changes belong in a tree scanned with the given options.
+struct WatchFilter {
+    options: ScanOptions,
+    roots: Vec<WatchedRoot>,
+}
+
+impl WatchFilter {
+    fn add(&mut self, root: &Path) {
+        // Invalid patterns were reported by the scan already
+        let excludes = exclude_override(root, &self.options.excludes, |_| {});
+        let device = if self.options.one_file_system {
+            device_id(root)
+        } else {
+            None
+        };
+        self.roots.push(WatchedRoot {
+            path: root.to_path_buf(),
+            excludes,
+            device,
+        });
+    }
+
+    /// Whether the scan would have walked `path`: it isn't excluded, nor
+    /// below an excluded directory or on another filesystem.
+    fn walks(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(root) = self
+            .roots
+            .iter()
+            .filter(|root| path.starts_with(&root.path))
+            .max_by_key(|root| root.path.components().count())
+        else {
+            return true;
+        };
+        let below_root = path
+            .ancestors()
+            .take_while(|dir| *dir != root.path.as_path());
+        for (n, dir) in below_root.enumerate() {
+            if root.excluded(dir, n > 0 || is_dir) {
+                return false;
+            }
+        }
+        match root.device {
+            // Removed entries are judged by the nearest folder still there
+            Some(device) => path
+                .ancestors()
+                .find_map(device_id)
+                .is_none_or(|found| found == device),
+            None => true,
+        }
+    }
+
+    /// Whether a file of `size` bytes at `path` passes the scan's filters.
+    fn keeps_file(&self, path: &Path, size: u64) -> bool {
+        size >= self.options.min_file_size && self.options.keeps_extension(path)
+    }
+}
+
+impl WatchedRoot {
+    fn excluded(&self, path: &Path, is_dir: bool) -> bool {
+        self.excludes
+            .as_ref()
+            .is_some_and(|excludes| excludes.matched(path, is_dir).is_ignore())
+    }
+}
+
+/// Watches `root` recursively and reports changes below it as
+/// [`ScanMsg::NodeUpdated`] and [`ScanMsg::NodeRemoved`], to be applied to a
+/// finished scan with [`crate::Tree::upsert_file`] and
+/// [`crate::Tree::remove_path`]. Each event is resolved by looking at the
+/// filesystem again, so renames arrive as a removal plus updates and a new
+/// directory is reported file by file.
+///
+/// `options` should be those the tree was scanned with: changes to entries
+/// the scan left out, such as excluded folders or files below the minimum
+/// size, are not reported, and a file that no longer passes the filters is
+/// reported as removed.
+pub fn watch(
+    root: &Path,
+    options: &ScanOptions,
+    tx: Sender<ScanMsg>,
+) -> notify::Result<TreeWatcher> {
+    let mut filter = WatchFilter {
+        options: options.clone(),
+        roots: Vec::new(),
+    };
+    filter.add(root);
+    let filter = Arc::new(RwLock::new(filter));
+    let shared = filter.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+        Ok(event) => {
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            let filter = shared.read();
+            for path in event.paths {
+                report(path, &event.kind, &filter, &tx);
+            }
+        }
+        Err(e) => {
+            let _ = tx.send(ScanMsg::Error(format!("watch error: {e}")));
+        }
+    })?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    Ok(TreeWatcher { watcher, filter })
+}
+
+fn report(path: PathBuf, kind: &EventKind, filter: &WatchFilter, tx: &Sender<ScanMsg>) {
+    let Ok(md) = std::fs::symlink_metadata(&path) else {
+        if filter.walks(&path, false) {
+            let _ = tx.send(ScanMsg::NodeRemoved { path });
+        }
+        return;
+    };
+    if !filter.walks(&path, md.is_dir()) {
+        return;
+    }
+    if md.is_file() {
+        update(path, &md, filter, tx);
+    } else if md.is_dir()
+        && matches!(
+            kind,
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+        )
+    {
+        // A directory created or moved in may already hold files the watch
+        // never saw
+        let root = filter
+            .roots
+            .iter()
+            .filter(|root| path.starts_with(&root.path))
+            .max_by_key(|root| root.path.components().count());
+        let entries = WalkDir::new(&path)
+            .same_file_system(filter.options.one_file_system)
+            .into_iter()
+            .filter_entry(|entry| {
+                root.is_none_or(|root| !root.excluded(entry.path(), entry.file_type().is_dir()))
+            });
+        for entry in entries.filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if let Ok(md) = entry.metadata() {
+                update(entry.into_path(), &md, filter, tx);
+            }
+        }
+    }
+}
+
+/// Reports the file at `path` as updated, or as removed if the scan's
+/// filters leave it out.
+fn update(path: PathBuf, md: &std::fs::Metadata, filter: &WatchFilter, tx: &Sender<ScanMsg>) {
+    let msg = if filter.keeps_file(&path, md.len()) {
+        ScanMsg::NodeUpdated {
+            path,
+            bytes: md.len(),
+            modified: md.modified().ok(),
+        }
+    } else {
+        ScanMsg::NodeRemoved { path }
+    };
+    let _ = tx.send(msg);
+}
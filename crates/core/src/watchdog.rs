@@ -0,0 +1,185 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::human::{human_bytes, parse_bytes};
+
+/// A growth limit such as `1 GB/hour`: at most `bytes` added within any
+/// `per`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GrowthRate {
+    pub bytes: u128,
+    pub per: Duration,
+}
+
+impl std::str::FromStr for GrowthRate {
+    type Err = String;
+
+    /// A size and a period: `1 GB/hour`, `500MB/10min`, `2 GB/day`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (size, period) = s
+            .split_once('/')
+            .ok_or_else(|| format!("expected SIZE/PERIOD such as 1GB/hour, got {s:?}"))?;
+        let bytes = parse_bytes(size).ok_or_else(|| format!("invalid size {size:?}"))?;
+        let period = period.trim();
+        let split = period
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(period.len());
+        let (count, unit) = period.split_at(split);
+        let count: u64 = if count.is_empty() {
+            1
+        } else {
+            count
+                .parse()
+                .map_err(|_| format!("invalid period {period:?}"))?
+        };
+        let unit_secs = match unit.trim().to_ascii_lowercase().as_str() {
+            "s" | "sec" | "second" | "seconds" => 1,
+            "m" | "min" | "minute" | "minutes" => 60,
+            "h" | "hour" | "hours" => 3600,
+            "d" | "day" | "days" => 86_400,
+            _ => return Err(format!("invalid period {period:?}")),
+        };
+        if count == 0 {
+            return Err(format!("invalid period {period:?}"));
+        }
+        Ok(GrowthRate {
+            bytes,
+            per: Duration::from_secs(count * unit_secs),
+        })
+    }
+}
+
+impl fmt::Display for GrowthRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.per.as_secs();
+        let per = match secs {
+            60 => "minute".to_string(),
+            3600 => "hour".to_string(),
+            86_400 => "day".to_string(),
+            _ if secs.is_multiple_of(86_400) => format!("{}d", secs / 86_400),
+            _ if secs.is_multiple_of(3600) => format!("{}h", secs / 3600),
+            _ if secs.is_multiple_of(60) => format!("{}min", secs / 60),
+            _ => format!("{secs}s"),
+        };
+        write!(f, "{}/{per}", human_bytes(self.bytes))
+    }
+}
+
+/// A directory that grew faster than a [`Watchdog`] allows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrowthAlert {
+    pub path: PathBuf,
+    /// Net bytes added below `path` within the last `limit.per`.
+    pub grown: u128,
+    pub limit: GrowthRate,
+}
+
+impl fmt::Display for GrowthAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} grew by {}, faster than {}",
+            self.path.display(),
+            human_bytes(self.grown),
+            self.limit
+        )
+    }
+}
+
+/// Net size changes below one directory within the trailing window.
+#[derive(Debug, Default)]
+struct Window {
+    changes: VecDeque<(Instant, i128)>,
+    sum: i128,
+}
+
+impl Window {
+    fn expire(&mut self, cutoff: Instant) {
+        while let Some(&(at, delta)) = self.changes.front() {
+            if at >= cutoff {
+                break;
+            }
+            self.sum -= delta;
+            self.changes.pop_front();
+        }
+    }
+}
+
+/// Raises an alarm when a directory grows faster than a [`GrowthRate`],
+/// fed with the size changes a watch reports so nothing is rescanned.
+#[derive(Debug)]
+pub struct Watchdog {
+    roots: Vec<PathBuf>,
+    limit: GrowthRate,
+    windows: HashMap<PathBuf, Window>,
+    last_sweep: Option<Instant>,
+}
+
+impl Watchdog {
+    /// Watches directories at and below `roots`.
+    pub fn new(roots: Vec<PathBuf>, limit: GrowthRate) -> Self {
+        Watchdog {
+            roots,
+            limit,
+            windows: HashMap::new(),
+            last_sweep: None,
+        }
+    }
+
+    pub fn limit(&self) -> GrowthRate {
+        self.limit
+    }
+
+    /// Records that the file at `path` grew by `delta` bytes (shrank if
+    /// negative) at `now`. Returns an alert for the deepest directory
+    /// above it that has now grown by more than the limit within the
+    /// limit's period. That directory and its parents then start counting
+    /// afresh, so a runaway directory is reported once per limit's worth
+    /// of growth rather than on every write.
+    pub fn record(&mut self, path: &Path, delta: i128, now: Instant) -> Option<GrowthAlert> {
+        let cutoff = now.checked_sub(self.limit.per).unwrap_or(now);
+        self.sweep(now, cutoff);
+        let dirs: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| self.roots.iter().any(|root| dir.starts_with(root)))
+            .collect();
+        let mut offender = None;
+        for dir in &dirs {
+            let window = self.windows.entry(dir.to_path_buf()).or_default();
+            window.expire(cutoff);
+            window.changes.push_back((now, delta));
+            window.sum += delta;
+            if offender.is_none() && window.sum > self.limit.bytes as i128 {
+                offender = Some((dir.to_path_buf(), window.sum as u128));
+            }
+        }
+        let (offender, grown) = offender?;
+        for dir in offender.ancestors() {
+            self.windows.remove(dir);
+        }
+        Some(GrowthAlert {
+            path: offender,
+            grown,
+            limit: self.limit,
+        })
+    }
+
+    /// Forgets directories with no changes left in the window, once per
+    /// period.
+    fn sweep(&mut self, now: Instant, cutoff: Instant) {
+        if self
+            .last_sweep
+            .is_some_and(|last| now.duration_since(last) < self.limit.per)
+        {
+            return;
+        }
+        self.last_sweep = Some(now);
+        self.windows.retain(|_, window| {
+            window.expire(cutoff);
+            !window.changes.is_empty()
+        });
+    }
+}
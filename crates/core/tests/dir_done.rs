@@ -0,0 +1,94 @@
+//! Every directory is reported done once, after everything below it, with
+//! the totals of its subtree.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use treesize_core::tuning::{ScanTuning, StorageKind};
+use treesize_core::{ScanMsg, ScanOptions, Scanner};
+
+fn fixture() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("treesize-dir-done-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    for (dir, files) in [("a", 3), ("a/b", 2), ("a/b/c", 1), ("d", 0), ("e", 4)] {
+        let dir = root.join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        for n in 0..files {
+            fs::write(dir.join(format!("{n}.bin")), vec![0u8; 10]).unwrap();
+        }
+    }
+    fs::write(root.join("top.bin"), vec![0u8; 10]).unwrap();
+    fs::create_dir_all(root.join("skipped")).unwrap();
+    fs::write(root.join("skipped").join("x.bin"), vec![0u8; 10]).unwrap();
+    root
+}
+
+#[test]
+fn every_directory_is_done_after_its_subtree() {
+    let root = fixture();
+    for threads in [1, 4] {
+        let options = ScanOptions {
+            excludes: vec!["skipped".into()],
+            storage: Some(StorageKind::Ssd),
+            tuning: ScanTuning {
+                threads: Some(threads),
+                ..ScanTuning::default()
+            },
+            ..ScanOptions::default()
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        Scanner::new()
+            .with_options(options)
+            .with_dir_done()
+            .scan(root.clone(), tx);
+
+        let mut done: HashMap<PathBuf, (u128, u64, u64)> = HashMap::new();
+        let mut order = Vec::new();
+        for msg in rx.try_iter() {
+            if let ScanMsg::DirDone {
+                path,
+                bytes,
+                files,
+                dirs,
+            } = msg
+            {
+                assert!(
+                    done.insert(path.clone(), (bytes, files, dirs)).is_none(),
+                    "{path:?} done twice"
+                );
+                order.push(path);
+            }
+        }
+        // Everything below a directory is done before it
+        for (at, path) in order.iter().enumerate() {
+            for below in order.iter().filter(|p| p.starts_with(path) && p != &path) {
+                assert!(
+                    order[..at].contains(below),
+                    "{path:?} done before {below:?}"
+                );
+            }
+        }
+
+        let rel = |dir: &str| {
+            if dir.is_empty() {
+                root.clone()
+            } else {
+                root.join(dir)
+            }
+        };
+        let expected = [
+            ("", (110, 11, 5)),
+            ("a", (60, 6, 2)),
+            ("a/b", (30, 3, 1)),
+            ("a/b/c", (10, 1, 0)),
+            ("d", (0, 0, 0)),
+            ("e", (40, 4, 0)),
+        ];
+        assert_eq!(done.len(), expected.len(), "{done:?}");
+        for (dir, totals) in expected {
+            assert_eq!(done.get(&rel(dir)), Some(&totals), "{dir}");
+        }
+    }
+    let _ = fs::remove_dir_all(&root);
+}
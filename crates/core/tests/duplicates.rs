@@ -0,0 +1,49 @@
+//! Files are proposed as duplicates only when their contents match, even
+//! when they differ past the start that is compared first.
+
+use std::fs;
+
+use treesize_core::advisor::{advise, AdvisorOptions, Reason};
+use treesize_core::Scanner;
+
+const LEN: usize = 200 * 1024;
+
+#[test]
+fn duplicates_match_in_full() {
+    let root = std::env::temp_dir().join(format!("treesize-duplicates-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let data = vec![7u8; LEN];
+    let mut late = data.clone();
+    late[LEN - 1] = 8;
+    for (dir, contents) in [("a", &data), ("b", &data), ("c", &late)] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+        fs::write(root.join(dir).join("big.bin"), contents).unwrap();
+        fs::write(root.join(dir).join("small.txt"), dir).unwrap();
+    }
+    let tree = Scanner::new().scan_subtree(root.clone());
+
+    let duplicates = |options: &AdvisorOptions| {
+        let mut found: Vec<_> = advise(&tree, u128::MAX, options)
+            .candidates
+            .into_iter()
+            .filter(|c| matches!(c.reason, Reason::Duplicate { .. }))
+            .map(|c| tree.path(c.id))
+            .collect();
+        found.sort();
+        found
+    };
+    let read = duplicates(&AdvisorOptions::default());
+    let unread = duplicates(&AdvisorOptions {
+        read_contents: false,
+        ..AdvisorOptions::default()
+    });
+    let _ = fs::remove_dir_all(&root);
+
+    // Only one of the two identical files; `c` differs at its last byte
+    // and the small files differ throughout
+    assert_eq!(read.len(), 1);
+    assert!(read[0].ends_with("big.bin"), "{read:?}");
+    assert!(!read[0].starts_with(root.join("c")), "{read:?}");
+    // Unread, name and size are all that count
+    assert_eq!(unread.len(), 4, "{unread:?}");
+}
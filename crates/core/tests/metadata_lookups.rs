@@ -0,0 +1,57 @@
+//! The scanner looks up each walked entry's metadata once, whether or not
+//! it stays on one file system.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use treesize_core::{scan_channel, ScanMsg, ScanOptions, Scanner, Tree};
+
+const DIRS: usize = 8;
+const FILES_PER_DIR: usize = 12;
+
+fn fixture() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("treesize-lookups-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    for d in 0..DIRS {
+        let dir = root.join(format!("d{d}")).join("nested");
+        fs::create_dir_all(&dir).unwrap();
+        for f in 0..FILES_PER_DIR {
+            fs::write(dir.join(format!("f{f}")), [0u8; 16]).unwrap();
+        }
+    }
+    root
+}
+
+fn scan(root: &Path, options: ScanOptions) -> Tree {
+    let (tx, rx) = scan_channel();
+    let handle = Scanner::new()
+        .with_options(options)
+        .spawn(root.to_path_buf(), tx);
+    let mut tree = None;
+    for msg in rx {
+        if let ScanMsg::Done(done) = msg {
+            tree = Some(Arc::unwrap_or_clone(done));
+        }
+    }
+    handle.join().expect("scan panicked");
+    tree.expect("scan finished")
+}
+
+#[test]
+fn one_lookup_per_entry() {
+    let root = fixture();
+    // The root, two folders per `d*` and their files
+    let entries = (1 + DIRS * (2 + FILES_PER_DIR)) as u64;
+    for one_file_system in [false, true] {
+        let options = ScanOptions {
+            one_file_system,
+            ..ScanOptions::default()
+        };
+        let tree = scan(&root, options);
+        assert_eq!(tree.nodes.len() as u64, entries);
+        let lookups = tree.meta.profile.map(|p| p.metadata_lookups);
+        assert_eq!(lookups, Some(entries), "one_file_system={one_file_system}");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
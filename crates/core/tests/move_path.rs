@@ -0,0 +1,56 @@
+//! Moving across filesystems removes only what was copied, so anything
+//! written into the source meanwhile survives.
+#![cfg(target_os = "linux")]
+
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use treesize_core::ops::{move_path, TransferOptions};
+
+#[test]
+fn files_added_during_a_copy_stay() {
+    let src = std::env::temp_dir().join(format!("treesize-move-src-{}", std::process::id()));
+    let dest = Path::new("/dev/shm").join(format!("treesize-move-dest-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&src);
+    let _ = fs::remove_dir_all(&dest);
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::write(src.join("a.bin"), [1u8; 16]).unwrap();
+    fs::write(src.join("sub").join("b.bin"), [2u8; 16]).unwrap();
+    let device = |p: &Path| fs::metadata(p).map(|m| m.dev());
+    if device(&src).ok() == device(Path::new("/dev/shm")).ok() {
+        // Both on one filesystem: the move is a rename
+        let _ = fs::remove_dir_all(&src);
+        return;
+    }
+
+    // The root is listed before the first file is copied
+    let late = src.join("late.bin");
+    let mut written = false;
+    move_path(&src, &dest, TransferOptions::default(), |_| {
+        if !written {
+            fs::write(&late, [3u8; 16]).unwrap();
+            written = true;
+        }
+    })
+    .unwrap();
+
+    let copied_late = dest.join("late.bin").exists();
+    let kept_late = late.exists();
+    let result = (
+        dest.join("a.bin").exists(),
+        dest.join("sub").join("b.bin").exists(),
+        src.join("a.bin").exists(),
+        src.join("sub").join("b.bin").exists(),
+    );
+    let _ = fs::remove_dir_all(&src);
+    let _ = fs::remove_dir_all(&dest);
+
+    assert_eq!(result, (true, true, false, false));
+    // Written after its folder was listed it isn't copied, so it stays,
+    // and so does the folder holding it; copied, it is moved like the rest
+    assert!(
+        copied_late != kept_late,
+        "copied {copied_late}, kept {kept_late}"
+    );
+}
@@ -0,0 +1,144 @@
+//! Node tables are checked when opened, and answer the same reports as the
+//! tree they were written from.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use treesize_core::extstats::ext_stats;
+use treesize_core::mapped::{NodeTable, NodeTableWriter, TableError};
+use treesize_core::model::Name;
+use treesize_core::owner::{usage_by_owner, OwnerId};
+use treesize_core::{NodeId, NodeKind, TreeNode};
+
+/// Bytes per record in `nodes.bin`.
+const RECORD: usize = 72;
+
+fn node(name: &str, kind: NodeKind, size: u128, owner: Option<OwnerId>) -> TreeNode {
+    TreeNode {
+        id: NodeId(0),
+        parent: None,
+        name: Name::from(name),
+        kind,
+        size,
+        file_count: 0,
+        children: Vec::new(),
+        modified: None,
+        link_target: None,
+        online_only: false,
+        owner,
+        attributes: Default::default(),
+    }
+}
+
+/// Writes a root holding `a.txt`, `sub/b.txt`, `sub/c.log` and an empty
+/// `empty` folder.
+fn write_table(dir: &Path) -> NodeTable {
+    let alice = Some(OwnerId::Unix {
+        uid: 1000,
+        gid: 1000,
+    });
+    let bob = Some(OwnerId::Unix {
+        uid: 1001,
+        gid: 1001,
+    });
+    let mut writer = NodeTableWriter::create(dir, Path::new("/scan")).unwrap();
+    let root = writer
+        .push(None, &node("scan", NodeKind::Dir, 0, None))
+        .unwrap();
+    let a = node("a.txt", NodeKind::File, 10, alice.clone());
+    writer.push(Some(root), &a).unwrap();
+    let sub = writer
+        .push(Some(root), &node("sub", NodeKind::Dir, 0, None))
+        .unwrap();
+    writer
+        .push(Some(root), &node("empty", NodeKind::Dir, 0, None))
+        .unwrap();
+    let b = node("b.txt", NodeKind::File, 20, bob);
+    writer.push(Some(sub), &b).unwrap();
+    let c = node("c.log", NodeKind::File, 0, alice);
+    writer.push(Some(sub), &c).unwrap();
+    writer.finish([(root, 30, 3), (sub, 20, 2)]).unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("treesize-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Overwrites `bytes` at `at` in the record of node `id`.
+fn patch(dir: &Path, id: usize, at: usize, bytes: &[u8]) {
+    let path = dir.join("nodes.bin");
+    let mut nodes = fs::read(&path).unwrap();
+    nodes[id * RECORD + at..id * RECORD + at + bytes.len()].copy_from_slice(bytes);
+    fs::write(&path, nodes).unwrap();
+}
+
+#[test]
+fn reports_read_the_table_like_a_tree() {
+    let dir = temp_dir("table-reports");
+    let table = write_table(&dir);
+    let tree = table.to_tree();
+
+    let exts = |stats: Vec<treesize_core::extstats::ExtStat>| {
+        stats
+            .into_iter()
+            .map(|s| (s.extension, s.bytes, s.files))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        exts(ext_stats(&table, table.root())),
+        exts(ext_stats(&tree, tree.root))
+    );
+    let owners = |usage: Vec<treesize_core::owner::OwnerUsage>| {
+        usage
+            .into_iter()
+            .map(|u| (u.owner.id, u.bytes, u.files))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        owners(usage_by_owner(&table, table.root())),
+        owners(usage_by_owner(&tree, tree.root))
+    );
+    assert_eq!(owners(usage_by_owner(&table, table.root())).len(), 2);
+    assert_eq!(table.top_files(1), tree.top_files(1));
+    assert_eq!(table.empty_files(), tree.empty_files());
+    assert_eq!(table.empty_dirs(&Default::default()), tree.empty_dirs());
+    assert_eq!(
+        table.path(table.empty_dirs(&Default::default())[0]),
+        Path::new("/scan/empty")
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn damaged_records_are_refused() {
+    let dir = temp_dir("table-damaged");
+    drop(write_table(&dir));
+    let intact = fs::read(dir.join("nodes.bin")).unwrap();
+    let damages: [(&str, usize, usize, &[u8]); 5] = [
+        // Parent of `a.txt` after it, then past the end
+        ("parent after child", 1, 0, &3u32.to_le_bytes()),
+        ("parent out of range", 1, 0, &100u32.to_le_bytes()),
+        // Children of `sub` past the end of the index
+        ("children out of range", 2, 56, &4u32.to_le_bytes()),
+        // Name of `b.txt` past the end of the names
+        ("name out of range", 4, 16, &1000u64.to_le_bytes()),
+        // Owner of `c.log` not in the manifest
+        ("owner out of range", 5, 64, &7u32.to_le_bytes()),
+    ];
+    for (what, id, at, bytes) in damages {
+        fs::write(dir.join("nodes.bin"), &intact).unwrap();
+        patch(&dir, id, at, bytes);
+        assert!(
+            matches!(NodeTable::open(&dir), Err(TableError::Damaged(_))),
+            "{what}"
+        );
+    }
+    fs::write(dir.join("nodes.bin"), &intact).unwrap();
+    let mut children = fs::read(dir.join("children.bin")).unwrap();
+    children[0..4].copy_from_slice(&50u32.to_le_bytes());
+    fs::write(dir.join("children.bin"), children).unwrap();
+    assert!(matches!(NodeTable::open(&dir), Err(TableError::Damaged(_))));
+    let _ = fs::remove_dir_all(&dir);
+}
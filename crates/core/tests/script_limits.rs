@@ -0,0 +1,30 @@
+//! Scripts that never finish are stopped by their limits.
+#![cfg(feature = "scripting")]
+
+use std::sync::Arc;
+
+use treesize_core::script::{Script, ScriptError, ScriptLimits};
+use treesize_core::Tree;
+
+fn run(source: &str) -> Result<(), ScriptError> {
+    let script = Script::compile(source).unwrap().with_limits(ScriptLimits {
+        max_operations: 100_000,
+        ..ScriptLimits::default()
+    });
+    let tree = Tree::combine(Vec::new());
+    script.run(Arc::new(tree)).map(drop)
+}
+
+#[test]
+fn runaway_scripts_fail() {
+    assert!(matches!(run("loop {}"), Err(ScriptError::Run(_))));
+    assert!(matches!(
+        run("fn down(n) { down(n + 1) } down(0)"),
+        Err(ScriptError::Run(_))
+    ));
+    assert!(matches!(
+        run("let s = \"x\"; loop { s += s; }"),
+        Err(ScriptError::Run(_))
+    ));
+    assert!(run("let n = 0; while n < 1000 { n += 1; }").is_ok());
+}
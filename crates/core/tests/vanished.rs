@@ -0,0 +1,143 @@
+//! Entries deleted or renamed while a scan is running are counted as
+//! vanished, not as errors.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use treesize_core::tuning::{ScanTuning, StorageKind};
+use treesize_core::{NodeKind, ScanErrorKind, ScanMsg, ScanOptions, Scanner, Tree};
+
+/// Enough victims that many are still queued when the first file is
+/// reported.
+const FILES: usize = 2000;
+const DIRS: usize = 6;
+
+fn fixture() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("treesize-vanished-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("keep")).unwrap();
+    fs::write(root.join("keep").join("k.bin"), [0u8; 64]).unwrap();
+    for n in 0..FILES {
+        fs::write(root.join(format!("v{n:02}.bin")), [0u8; 16]).unwrap();
+    }
+    for n in 0..DIRS {
+        let dir = root.join(format!("d{n}"));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("inner.bin"), [0u8; 32]).unwrap();
+        fs::write(dir.join("nested").join("n.bin"), [0u8; 8]).unwrap();
+    }
+    root
+}
+
+/// Deletes half of the victims and renames the other half, files and
+/// directories alike.
+fn mutate(root: &Path) {
+    for n in 0..FILES {
+        let path = root.join(format!("v{n:02}.bin"));
+        let _ = if n % 2 == 0 {
+            fs::remove_file(&path)
+        } else {
+            fs::rename(&path, root.join(format!("v{n:02}.moved")))
+        };
+    }
+    for n in 0..DIRS {
+        let dir = root.join(format!("d{n}"));
+        let _ = if n % 2 == 0 {
+            fs::remove_dir_all(&dir)
+        } else {
+            fs::rename(&dir, root.join(format!("d{n}.moved")))
+        };
+    }
+}
+
+/// The deepest ancestor of `path` that is still in `tree`.
+fn surviving_dir(tree: &Tree, path: &Path) -> Option<treesize_core::NodeId> {
+    path.ancestors().skip(1).find_map(|dir| tree.find(dir))
+}
+
+#[test]
+fn entries_removed_mid_scan_are_vanished() {
+    let root = fixture();
+    let options = ScanOptions {
+        storage: Some(StorageKind::Ssd),
+        tuning: ScanTuning {
+            threads: Some(1),
+            batch_size: Some(1),
+            ..ScanTuning::default()
+        },
+        ..ScanOptions::default()
+    };
+    let scanner = Scanner::new().with_options(options).with_file_messages();
+    let control = scanner.control();
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let handle = scanner.spawn(root.clone(), tx);
+
+    let mut mutated = false;
+    let mut progress = None;
+    let mut tree = None;
+    for msg in rx {
+        match msg {
+            ScanMsg::File { .. } if !mutated => {
+                // Any file being sized means the root has been listed
+                control.pause();
+                mutate(&root);
+                control.resume();
+                mutated = true;
+            }
+            ScanMsg::Progress {
+                scanned,
+                discovered,
+                vanished,
+                ..
+            } => progress = Some((scanned, discovered, vanished)),
+            ScanMsg::Done(done) => tree = Some(done),
+            _ => {}
+        }
+    }
+    handle.join().expect("scan panicked");
+    let tree = tree.expect("scan finished");
+    let (scanned, discovered, vanished) = progress.expect("final progress");
+    let _ = fs::remove_dir_all(&root);
+
+    assert!(mutated);
+    let gone: Vec<_> = tree
+        .errors
+        .iter()
+        .filter(|e| e.kind == ScanErrorKind::Vanished)
+        .collect();
+    assert!(
+        tree.errors
+            .iter()
+            .all(|e| !matches!(e.kind, ScanErrorKind::NotFound | ScanErrorKind::Io)),
+        "{:?}",
+        tree.errors
+    );
+    assert!(!gone.is_empty());
+    assert_eq!(vanished, gone.len() as u64);
+
+    for error in &gone {
+        assert!(tree.find(&error.path).is_none(), "{:?}", error.path);
+        assert_eq!(Some(error.node), surviving_dir(&tree, &error.path));
+    }
+    // Every victim was either sized before it went, or is vanished
+    for n in 0..FILES {
+        let path = root.join(format!("v{n:02}.bin"));
+        let sized = tree.find(&path).is_some();
+        let lost = gone.iter().any(|e| e.path == path);
+        assert!(sized != lost, "{path:?}: sized {sized}, vanished {lost}");
+    }
+    // Removed directories, or the entries queued inside them, vanish too
+    assert!(gone.iter().any(|e| {
+        let top = e.path.strip_prefix(&root).unwrap().components().next();
+        top.is_some_and(|c| c.as_os_str().to_string_lossy().starts_with('d'))
+    }));
+
+    let files = tree
+        .nodes
+        .iter()
+        .filter(|node| matches!(node.kind, NodeKind::File))
+        .count() as u64;
+    assert_eq!(discovered, files);
+    assert_eq!(scanned, files);
+    assert!(tree.find(&root.join("keep").join("k.bin")).is_some());
+}